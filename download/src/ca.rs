@@ -0,0 +1,43 @@
+//! Picks the bundle of CA certificates that downloads trust, for networks
+//! whose TLS is intercepted by a proxy with an internal CA.
+//!
+//! A bundle given in a download's [`Options`] takes precedence over the one in
+//! `CURL_CA_BUNDLE`, which takes precedence over the one in `SSL_CERT_FILE`,
+//! with an empty variable counting as unset. The bundle is a file of PEM
+//! certificates, which are then trusted instead of the system's, as curl and
+//! OpenSSL do with those variables.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::DownloadError;
+#[cfg(doc)]
+use crate::Options;
+
+/// The bundle that a download given `set` trusts, or `None` for the
+/// system's certificates.
+pub fn ca_bundle(set: Option<&Path>) -> Option<PathBuf> {
+    set.map(Path::to_owned).or_else(|| {
+        ["CURL_CA_BUNDLE", "SSL_CERT_FILE"]
+            .into_iter()
+            .find_map(|name| env::var_os(name).filter(|value| !value.is_empty()))
+            .map(PathBuf::from)
+    })
+}
+
+/// Reads the bundle at `path`, which must hold at least one PEM
+/// certificate, so that a wrong path fails here rather than in the TLS
+/// handshake.
+pub fn read_ca_bundle(path: &Path) -> Result<Vec<u8>, DownloadError> {
+    let error = |reason: String| DownloadError::CaBundle {
+        path: path.to_owned(),
+        reason,
+    };
+    let pem = fs::read(path).map_err(|e| error(e.to_string()))?;
+    let marker = b"-----BEGIN CERTIFICATE-----";
+    if !pem.windows(marker.len()).any(|window| window == marker) {
+        return Err(error("it contains no PEM certificates".to_owned()));
+    }
+    Ok(pem)
+}
@@ -6,6 +6,15 @@ pub enum DownloadError {
     HttpStatus(u32),
     #[error("file not found")]
     FileNotFound,
+    #[error("file not found on local mirror: '{}'", .0.display())]
+    LocalFileNotFound(std::path::PathBuf),
+    #[error("download was cancelled")]
+    Cancelled,
+    #[error("could not use the CA bundle '{}': {reason}", .path.display())]
+    CaBundle {
+        path: std::path::PathBuf,
+        reason: String,
+    },
     #[error("download backend '{0}' unavailable")]
     BackendUnavailable(&'static str),
     #[error("{0}")]
@@ -19,3 +28,96 @@ pub enum DownloadError {
     #[error(transparent)]
     CurlError(#[from] curl::Error),
 }
+
+/// Whether `err`, returned from a download, may go away if the download is
+/// tried again: a failure to connect, a timeout, a dropped connection, a
+/// transfer that stopped short, or a server-side error or rate limit.
+/// Missing files, other client errors, TLS and protocol failures, malformed
+/// URLs and failures of the caller's own callback are not transient.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    use std::io;
+
+    fn status_is_transient(code: u32) -> bool {
+        matches!(code, 408 | 429 | 500..=599)
+    }
+
+    fn io_is_transient(e: &io::Error) -> bool {
+        use io::ErrorKind::*;
+        if matches!(
+            e.kind(),
+            ConnectionReset
+                | ConnectionAborted
+                | BrokenPipe
+                | TimedOut
+                | UnexpectedEof
+                | Interrupted
+        ) {
+            return true;
+        }
+        // reqwest reports failures while reading the body as `io::Error`s
+        // wrapping its own error type.
+        #[cfg(feature = "reqwest-backend")]
+        if let Some(e) = e.get_ref().and_then(|inner| inner.downcast_ref()) {
+            return reqwest_is_transient(e);
+        }
+        false
+    }
+
+    #[cfg(feature = "reqwest-backend")]
+    fn reqwest_is_transient(e: &reqwest::Error) -> bool {
+        if e.is_connect() || e.is_timeout() || e.is_body() {
+            return true;
+        }
+        if let Some(status) = e.status() {
+            return status_is_transient(status.as_u16().into());
+        }
+        // A connection reset while sending the request is only visible as
+        // the `io::Error` that caused it.
+        let mut source = std::error::Error::source(e);
+        while let Some(cause) = source {
+            if let Some(e) = cause.downcast_ref::<io::Error>() {
+                return io_is_transient(e);
+            }
+            source = cause.source();
+        }
+        false
+    }
+
+    #[cfg(feature = "curl-backend")]
+    fn curl_is_transient(e: &curl::Error) -> bool {
+        e.is_couldnt_resolve_proxy()
+            || e.is_couldnt_resolve_host()
+            || e.is_couldnt_connect()
+            || e.is_operation_timedout()
+            || e.is_partial_file()
+            || e.is_got_nothing()
+            || e.is_send_error()
+            || e.is_recv_error()
+    }
+
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<DownloadError>() {
+            return match e {
+                DownloadError::HttpStatus(code) => status_is_transient(*code),
+                DownloadError::IoError(e) => io_is_transient(e),
+                #[cfg(feature = "reqwest-backend")]
+                DownloadError::Reqwest(e) => reqwest_is_transient(e),
+                #[cfg(feature = "curl-backend")]
+                DownloadError::CurlError(e) => curl_is_transient(e),
+                _ => false,
+            };
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            return io_is_transient(e);
+        }
+        #[cfg(feature = "reqwest-backend")]
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return reqwest_is_transient(e);
+        }
+        #[cfg(feature = "curl-backend")]
+        if let Some(e) = cause.downcast_ref::<curl::Error>() {
+            return curl_is_transient(e);
+        }
+    }
+    false
+}
@@ -7,8 +7,14 @@ use anyhow::Context;
 pub use anyhow::Result;
 use url::Url;
 
+mod ca;
+pub use crate::ca::{ca_bundle, read_ca_bundle};
 mod errors;
 pub use crate::errors::*;
+mod proxy;
+pub use crate::proxy::{proxy_for_url, NoProxy};
+mod rate;
+pub use crate::rate::RateLimiter;
 
 /// User agent header value for HTTP request.
 /// See: https://github.com/rust-lang/rustup/issues/2860.
@@ -20,12 +26,22 @@ pub enum Backend {
     Reqwest(TlsBackend),
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TlsBackend {
     Rustls,
     Default,
 }
 
+/// How a download is made, beyond where from.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Options<'a> {
+    /// Shared by all downloads that together must stay within a rate.
+    pub rate_limiter: Option<&'a RateLimiter>,
+    /// The bundle of CA certificates to trust instead of the one in the
+    /// environment, if any. See [`ca_bundle`].
+    pub ca_bundle: Option<&'a Path>,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Event<'a> {
     ResumingPartialDownload,
@@ -40,11 +56,88 @@ fn download_with_backend(
     url: &Url,
     resume_from: u64,
     callback: &dyn Fn(Event<'_>) -> Result<()>,
+    ca_bundle: Option<&Path>,
 ) -> Result<()> {
+    // Short-circuit the backends for the "file:" URL scheme
+    if download_from_file_url(url, resume_from, callback)? {
+        return Ok(());
+    }
+
+    let ca_bundle = crate::ca_bundle(ca_bundle);
+    let ca_bundle = ca_bundle.as_deref();
+    match backend {
+        Backend::Curl => curl::download(url, resume_from, callback, ca_bundle),
+        Backend::Reqwest(tls) => reqwest_be::download(url, resume_from, callback, tls, ca_bundle),
+    }
+}
+
+/// The length of what downloading `url` would receive, or `None` if the
+/// server does not say.
+pub fn content_length_with_backend(
+    backend: Backend,
+    url: &Url,
+    options: Options<'_>,
+) -> Result<Option<u64>> {
+    if url.scheme() == "file" {
+        let src = url
+            .to_file_path()
+            .map_err(|_| DownloadError::Message(format!("bogus file url: '{url}'")))?;
+        return match std::fs::metadata(&src) {
+            Ok(metadata) if metadata.is_file() => Ok(Some(metadata.len())),
+            _ => Err(DownloadError::LocalFileNotFound(src).into()),
+        };
+    }
+
+    let ca_bundle = crate::ca_bundle(options.ca_bundle);
+    let ca_bundle = ca_bundle.as_deref();
     match backend {
-        Backend::Curl => curl::download(url, resume_from, callback),
-        Backend::Reqwest(tls) => reqwest_be::download(url, resume_from, callback, tls),
+        Backend::Curl => curl::content_length(url, ca_bundle),
+        Backend::Reqwest(tls) => reqwest_be::content_length(url, tls, ca_bundle),
+    }
+}
+
+/// Reads `file:` URLs straight off disk, so that a dist server mirrored to
+/// a local directory behaves the same regardless of the backend in use.
+/// Returns `false` for any other scheme.
+fn download_from_file_url(
+    url: &Url,
+    resume_from: u64,
+    callback: &dyn Fn(Event<'_>) -> Result<()>,
+) -> Result<bool> {
+    use std::fs;
+    use std::io;
+
+    if url.scheme() != "file" {
+        return Ok(false);
     }
+
+    let src = url
+        .to_file_path()
+        .map_err(|_| DownloadError::Message(format!("bogus file url: '{url}'")))?;
+    // Mirrors commonly use relative symlinks (e.g. for the undated channel
+    // manifests), so resolve the path before looking at it.
+    let src = match fs::canonicalize(&src) {
+        Ok(src) if src.is_file() => src,
+        // Callers treat this like a 404 from the network, but the error
+        // names the missing file to make broken mirrors easy to diagnose.
+        _ => return Err(DownloadError::LocalFileNotFound(src).into()),
+    };
+
+    let mut f = fs::File::open(src).context("unable to open downloaded file")?;
+    let len = f.metadata()?.len();
+    callback(Event::DownloadContentLengthReceived(len))?;
+    io::Seek::seek(&mut f, io::SeekFrom::Start(resume_from))?;
+
+    let mut buffer = vec![0u8; 0x10000];
+    loop {
+        let bytes_read = io::Read::read(&mut f, &mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        callback(Event::DownloadDataReceived(&buffer[0..bytes_read]))?;
+    }
+
+    Ok(true)
 }
 
 type DownloadCallback<'a> = &'a dyn Fn(Event<'_>) -> Result<()>;
@@ -55,6 +148,7 @@ pub fn download_to_path_with_backend(
     path: &Path,
     resume_from_partial: bool,
     callback: Option<DownloadCallback<'_>>,
+    options: Options<'_>,
 ) -> Result<()> {
     use std::cell::RefCell;
     use std::fs::remove_file;
@@ -112,18 +206,24 @@ pub fn download_to_path_with_backend(
         };
 
         let file = RefCell::new(file);
+        // Reading a local file uses no bandwidth to limit
+        let rate_limiter = options.rate_limiter.filter(|_| url.scheme() != "file");
 
-        download_with_backend(backend, url, resume_from, &|event| {
+        let callback: &dyn Fn(Event<'_>) -> Result<()> = &|event| {
             if let Event::DownloadDataReceived(data) = event {
                 file.borrow_mut()
                     .write_all(data)
                     .context("unable to write download to disk")?;
+                if let Some(limiter) = rate_limiter {
+                    limiter.take(data.len());
+                }
             }
             match callback {
                 Some(cb) => cb(event),
                 None => Ok(()),
             }
-        })?;
+        };
+        download_with_backend(backend, url, resume_from, callback, options.ca_bundle)?;
 
         file.borrow_mut()
             .sync_data()
@@ -132,7 +232,11 @@ pub fn download_to_path_with_backend(
         Ok(())
     }()
     .map_err(|e| {
-        // TODO: We currently clear up the cached download on any error, should we restrict it to a subset?
+        // Keep what was received before a transient failure, so that the
+        // next attempt can pick up from there.
+        if resume_from_partial && is_transient(&e) {
+            return e;
+        }
         if let Err(file_err) = remove_file(path).context("cleaning up cached downloads") {
             file_err.context(e)
         } else {
@@ -141,6 +245,25 @@ pub fn download_to_path_with_backend(
     })
 }
 
+/// Downloads `url` without saving it anywhere, leaving `callback` to do
+/// whatever is to be done with the data.
+pub fn download_to_callback_with_backend(
+    backend: Backend,
+    url: &Url,
+    callback: DownloadCallback<'_>,
+    options: Options<'_>,
+) -> Result<()> {
+    // Reading a local file uses no bandwidth to limit
+    let rate_limiter = options.rate_limiter.filter(|_| url.scheme() != "file");
+    let callback: &dyn Fn(Event<'_>) -> Result<()> = &|event| {
+        if let (Event::DownloadDataReceived(data), Some(limiter)) = (event, rate_limiter) {
+            limiter.take(data.len());
+        }
+        callback(event)
+    };
+    download_with_backend(backend, url, 0, callback, options.ca_bundle)
+}
+
 #[cfg(all(not(feature = "reqwest-backend"), not(feature = "curl-backend")))]
 compile_error!("Must enable at least one backend");
 
@@ -149,7 +272,9 @@ compile_error!("Must enable at least one backend");
 #[cfg(feature = "curl-backend")]
 pub mod curl {
     use std::cell::RefCell;
+    use std::path::{Path, PathBuf};
     use std::str;
+    use std::sync::{Mutex, PoisonError};
     use std::time::Duration;
 
     use anyhow::{Context, Result};
@@ -159,103 +284,168 @@ pub mod curl {
     use super::Event;
     use crate::errors::*;
 
+    /// The libcurl handles of finished downloads, which keep their
+    /// connections open for the next download to the same host to reuse,
+    /// whichever thread it runs on. A handle per thread would close them
+    /// when the worker threads of concurrent downloads finish. Each is kept
+    /// with the CA bundle it was set to trust, which it can't be unset from.
+    static HANDLES: Mutex<Vec<(Option<PathBuf>, Easy)>> = Mutex::new(Vec::new());
+
     pub fn download(
         url: &Url,
         resume_from: u64,
         callback: &dyn Fn(Event<'_>) -> Result<()>,
+        ca_bundle: Option<&Path>,
     ) -> Result<()> {
-        // Fetch either a cached libcurl handle (which will preserve open
-        // connections) or create a new one if it isn't listed.
-        //
-        // Once we've acquired it, reset the lifetime from 'static to our local
-        // scope.
-        thread_local!(static EASY: RefCell<Easy> = RefCell::new(Easy::new()));
-        EASY.with(|handle| {
-            let mut handle = handle.borrow_mut();
-
-            handle.url(url.as_ref())?;
-            handle.follow_location(true)?;
-            handle.useragent(super::USER_AGENT)?;
-
-            if resume_from > 0 {
-                handle.resume_from(resume_from)?;
-            } else {
-                // an error here indicates that the range header isn't supported by underlying curl,
-                // so there's nothing to "clear" - safe to ignore this error.
-                let _ = handle.resume_from(0);
+        let handles = || HANDLES.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut handle = {
+            let mut handles = handles();
+            match handles.iter().position(|(b, _)| b.as_deref() == ca_bundle) {
+                Some(i) => handles.swap_remove(i).1,
+                None => Easy::new(),
             }
+        };
+        let result = download_with(&mut handle, url, resume_from, callback, ca_bundle);
+        // A failed transfer may have left its connection unusable
+        if result.is_ok() {
+            handles().push((ca_bundle.map(Path::to_owned), handle));
+        }
+        result
+    }
 
-            // Take at most 30s to connect
-            handle.connect_timeout(Duration::new(30, 0))?;
-
-            {
-                let cberr = RefCell::new(None);
-                let mut transfer = handle.transfer();
-
-                // Data callback for libcurl which is called with data that's
-                // downloaded. We just feed it into our hasher and also write it out
-                // to disk.
-                transfer.write_function(|data| {
-                    match callback(Event::DownloadDataReceived(data)) {
-                        Ok(()) => Ok(data.len()),
-                        Err(e) => {
-                            *cberr.borrow_mut() = Some(e);
-                            Ok(0)
-                        }
-                    }
-                })?;
-
-                // Listen for headers and parse out a `Content-Length` (case-insensitive) if it
-                // comes so we know how much we're downloading.
-                transfer.header_function(|header| {
-                    if let Ok(data) = str::from_utf8(header) {
-                        let prefix = "content-length: ";
-                        if data.to_ascii_lowercase().starts_with(prefix) {
-                            if let Ok(s) = data[prefix.len()..].trim().parse::<u64>() {
-                                let msg = Event::DownloadContentLengthReceived(s + resume_from);
-                                match callback(msg) {
-                                    Ok(()) => (),
-                                    Err(e) => {
-                                        *cberr.borrow_mut() = Some(e);
-                                        return false;
-                                    }
+    fn download_with(
+        handle: &mut Easy,
+        url: &Url,
+        resume_from: u64,
+        callback: &dyn Fn(Event<'_>) -> Result<()>,
+        ca_bundle: Option<&Path>,
+    ) -> Result<()> {
+        handle.url(url.as_ref())?;
+        handle.follow_location(true)?;
+        handle.useragent(super::USER_AGENT)?;
+
+        set_proxy(handle, url)?;
+        set_cainfo(handle, ca_bundle)?;
+
+        if resume_from > 0 {
+            handle.resume_from(resume_from)?;
+        } else {
+            // an error here indicates that the range header isn't supported by underlying curl,
+            // so there's nothing to "clear" - safe to ignore this error.
+            let _ = handle.resume_from(0);
+        }
+
+        // Take at most 30s to connect
+        handle.connect_timeout(Duration::new(30, 0))?;
+
+        {
+            let cberr = RefCell::new(None);
+            let mut transfer = handle.transfer();
+
+            // Data callback for libcurl which is called with data that's
+            // downloaded. We just feed it into our hasher and also write it out
+            // to disk.
+            transfer.write_function(|data| match callback(Event::DownloadDataReceived(data)) {
+                Ok(()) => Ok(data.len()),
+                Err(e) => {
+                    *cberr.borrow_mut() = Some(e);
+                    Ok(0)
+                }
+            })?;
+
+            // Listen for headers and parse out a `Content-Length` (case-insensitive) if it
+            // comes so we know how much we're downloading.
+            transfer.header_function(|header| {
+                if let Ok(data) = str::from_utf8(header) {
+                    let prefix = "content-length: ";
+                    if data.to_ascii_lowercase().starts_with(prefix) {
+                        if let Ok(s) = data[prefix.len()..].trim().parse::<u64>() {
+                            let msg = Event::DownloadContentLengthReceived(s + resume_from);
+                            match callback(msg) {
+                                Ok(()) => (),
+                                Err(e) => {
+                                    *cberr.borrow_mut() = Some(e);
+                                    return false;
                                 }
                             }
                         }
                     }
-                    true
-                })?;
-
-                // If an error happens check to see if we had a filesystem error up
-                // in `cberr`, but we always want to punt it up.
-                transfer.perform().or_else(|e| {
-                    // If the original error was generated by one of our
-                    // callbacks, return it.
-                    match cberr.borrow_mut().take() {
-                        Some(cberr) => Err(cberr),
-                        None => {
-                            // Otherwise, return the error from curl
-                            if e.is_file_couldnt_read_file() {
-                                Err(e).context(DownloadError::FileNotFound)
-                            } else {
-                                Err(e).context("error during download")?
-                            }
+                }
+                true
+            })?;
+
+            // If an error happens check to see if we had a filesystem error up
+            // in `cberr`, but we always want to punt it up.
+            transfer.perform().or_else(|e| {
+                // If the original error was generated by one of our
+                // callbacks, return it.
+                match cberr.borrow_mut().take() {
+                    Some(cberr) => Err(cberr),
+                    None => {
+                        // Otherwise, return the error from curl
+                        if e.is_file_couldnt_read_file() {
+                            Err(e).context(DownloadError::FileNotFound)
+                        } else {
+                            Err(e).context("error during download")?
                         }
                     }
-                })?;
+                }
+            })?;
+        }
+
+        // If we didn't get a 20x or 0 ("OK" for files) then return an error
+        let code = handle.response_code()?;
+        match code {
+            0 | 200..=299 => {}
+            _ => {
+                return Err(DownloadError::HttpStatus(code).into());
             }
+        };
 
-            // If we didn't get a 20x or 0 ("OK" for files) then return an error
-            let code = handle.response_code()?;
-            match code {
-                0 | 200..=299 => {}
-                _ => {
-                    return Err(DownloadError::HttpStatus(code).into());
-                }
-            };
+        Ok(())
+    }
 
-            Ok(())
-        })
+    pub fn content_length(url: &Url, ca_bundle: Option<&Path>) -> Result<Option<u64>> {
+        // A handle of its own, so that the cached one is never left
+        // making HEAD requests
+        let mut handle = Easy::new();
+        handle.url(url.as_ref())?;
+        handle.follow_location(true)?;
+        handle.useragent(super::USER_AGENT)?;
+        set_proxy(&mut handle, url)?;
+        set_cainfo(&mut handle, ca_bundle)?;
+        handle.connect_timeout(Duration::new(30, 0))?;
+        handle.nobody(true)?;
+        handle.perform().context("error during download")?;
+
+        let code = handle.response_code()?;
+        if !(200..=299).contains(&code) {
+            return Err(DownloadError::HttpStatus(code).into());
+        }
+        let len = handle.content_length_download()?;
+        Ok((len >= 0.0).then_some(len as u64))
+    }
+
+    /// Rather than leaving libcurl to read the environment, which it does
+    /// with different `no_proxy` rules, sets what was picked for `url`, as
+    /// a handle keeps it across downloads.
+    fn set_proxy(handle: &mut Easy, url: &Url) -> Result<(), curl::Error> {
+        match super::proxy_for_url(url) {
+            Some(proxy) => {
+                handle.proxy(proxy.as_str())?;
+                handle.noproxy("")
+            }
+            None => handle.noproxy("*"),
+        }
+    }
+
+    /// Sets the bundle of CA certificates to trust, if there is one.
+    fn set_cainfo(handle: &mut Easy, ca_bundle: Option<&Path>) -> Result<()> {
+        if let Some(bundle) = ca_bundle {
+            super::read_ca_bundle(bundle)?;
+            handle.cainfo(bundle)?;
+        }
+        Ok(())
     }
 }
 
@@ -268,13 +458,15 @@ pub mod reqwest_be {
     compile_error!("Must select a reqwest TLS backend");
 
     use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Mutex, PoisonError};
     use std::time::Duration;
 
     use anyhow::{anyhow, Context, Result};
     #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-default-tls"))]
     use once_cell::sync::Lazy;
     use reqwest::blocking::{Client, ClientBuilder, Response};
-    use reqwest::{header, Proxy};
+    use reqwest::{header, Certificate, Proxy};
     use url::Url;
 
     use super::Event;
@@ -286,13 +478,10 @@ pub mod reqwest_be {
         resume_from: u64,
         callback: &dyn Fn(Event<'_>) -> Result<()>,
         tls: TlsBackend,
+        ca_bundle: Option<&Path>,
     ) -> Result<()> {
-        // Short-circuit reqwest for the "file:" URL scheme
-        if download_from_file_url(url, resume_from, callback)? {
-            return Ok(());
-        }
-
-        let mut res = request(url, resume_from, tls).context("failed to make network request")?;
+        let mut res =
+            request(url, resume_from, tls, ca_bundle).context("failed to make network request")?;
 
         if !res.status().is_success() {
             let code: u16 = res.status().into();
@@ -319,11 +508,33 @@ pub mod reqwest_be {
         }
     }
 
+    pub fn content_length(
+        url: &Url,
+        tls: TlsBackend,
+        ca_bundle: Option<&Path>,
+    ) -> Result<Option<u64>> {
+        let res = client(tls, ca_bundle)?
+            .head(url.as_str())
+            .send()
+            .map_err(DownloadError::from)
+            .context("failed to make network request")?;
+
+        if !res.status().is_success() {
+            let code: u16 = res.status().into();
+            return Err(anyhow!(DownloadError::HttpStatus(u32::from(code))));
+        }
+
+        Ok(res
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse().ok()))
+    }
+
     fn client_generic() -> ClientBuilder {
         Client::builder()
             .gzip(false)
             .user_agent(super::USER_AGENT)
-            .proxy(Proxy::custom(env_proxy))
+            .proxy(Proxy::custom(super::proxy_for_url))
             .timeout(Duration::from_secs(30))
     }
 
@@ -353,80 +564,95 @@ pub mod reqwest_be {
         catcher().unwrap()
     });
 
-    fn env_proxy(url: &Url) -> Option<Url> {
-        env_proxy::for_url(url).to_url()
+    /// The clients that trust a CA bundle, built when the bundle is first
+    /// used, as reading it may fail.
+    static CA_BUNDLE_CLIENTS: Mutex<Vec<(TlsBackend, PathBuf, Client)>> = Mutex::new(Vec::new());
+
+    fn client(backend: TlsBackend, ca_bundle: Option<&Path>) -> Result<Client, DownloadError> {
+        match ca_bundle {
+            Some(bundle) => ca_bundle_client(backend, bundle),
+            None => default_client(backend).cloned(),
+        }
     }
 
-    fn request(
-        url: &Url,
-        resume_from: u64,
-        backend: TlsBackend,
-    ) -> Result<Response, DownloadError> {
-        let client: &Client = match backend {
+    fn ca_bundle_client(backend: TlsBackend, bundle: &Path) -> Result<Client, DownloadError> {
+        let mut clients = CA_BUNDLE_CLIENTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, _, client)) = clients
+            .iter()
+            .find(|(b, path, _)| *b == backend && *path == bundle)
+        {
+            return Ok(client.clone());
+        }
+
+        let certs = Certificate::from_pem_bundle(&super::read_ca_bundle(bundle)?).map_err(|e| {
+            DownloadError::CaBundle {
+                path: bundle.to_owned(),
+                reason: e.to_string(),
+            }
+        })?;
+        let mut builder = match backend {
             #[cfg(feature = "reqwest-rustls-tls")]
-            TlsBackend::Rustls => &CLIENT_RUSTLS_TLS,
+            TlsBackend::Rustls => client_generic().use_rustls_tls(),
             #[cfg(not(feature = "reqwest-rustls-tls"))]
             TlsBackend::Rustls => {
                 return Err(DownloadError::BackendUnavailable("reqwest rustls"));
             }
             #[cfg(feature = "reqwest-default-tls")]
-            TlsBackend::Default => &CLIENT_DEFAULT_TLS,
+            TlsBackend::Default => client_generic(),
             #[cfg(not(feature = "reqwest-default-tls"))]
             TlsBackend::Default => {
                 return Err(DownloadError::BackendUnavailable("reqwest default TLS"));
             }
-        };
-        let mut req = client.get(url.as_str());
-
-        if resume_from != 0 {
-            req = req.header(header::RANGE, format!("bytes={resume_from}-"));
         }
+        .tls_built_in_root_certs(false);
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+        let client = builder.build()?;
+        clients.push((backend, bundle.to_owned(), client.clone()));
+        Ok(client)
+    }
 
-        Ok(req.send()?)
+    fn default_client(backend: TlsBackend) -> Result<&'static Client, DownloadError> {
+        Ok(match backend {
+            #[cfg(feature = "reqwest-rustls-tls")]
+            TlsBackend::Rustls => &CLIENT_RUSTLS_TLS,
+            #[cfg(not(feature = "reqwest-rustls-tls"))]
+            TlsBackend::Rustls => {
+                return Err(DownloadError::BackendUnavailable("reqwest rustls"));
+            }
+            #[cfg(feature = "reqwest-default-tls")]
+            TlsBackend::Default => &CLIENT_DEFAULT_TLS,
+            #[cfg(not(feature = "reqwest-default-tls"))]
+            TlsBackend::Default => {
+                return Err(DownloadError::BackendUnavailable("reqwest default TLS"));
+            }
+        })
     }
 
-    fn download_from_file_url(
+    fn request(
         url: &Url,
         resume_from: u64,
-        callback: &dyn Fn(Event<'_>) -> Result<()>,
-    ) -> Result<bool> {
-        use std::fs;
-
-        // The file scheme is mostly for use by tests to mock the dist server
-        if url.scheme() == "file" {
-            let src = url
-                .to_file_path()
-                .map_err(|_| DownloadError::Message(format!("bogus file url: '{url}'")))?;
-            if !src.is_file() {
-                // Because some of rustup's logic depends on checking
-                // the error when a downloaded file doesn't exist, make
-                // the file case return the same error value as the
-                // network case.
-                return Err(anyhow!(DownloadError::FileNotFound));
-            }
-
-            let mut f = fs::File::open(src).context("unable to open downloaded file")?;
-            io::Seek::seek(&mut f, io::SeekFrom::Start(resume_from))?;
-
-            let mut buffer = vec![0u8; 0x10000];
-            loop {
-                let bytes_read = io::Read::read(&mut f, &mut buffer)?;
-                if bytes_read == 0 {
-                    break;
-                }
-                callback(Event::DownloadDataReceived(&buffer[0..bytes_read]))?;
-            }
+        backend: TlsBackend,
+        ca_bundle: Option<&Path>,
+    ) -> Result<Response, DownloadError> {
+        let mut req = client(backend, ca_bundle)?.get(url.as_str());
 
-            Ok(true)
-        } else {
-            Ok(false)
+        if resume_from != 0 {
+            req = req.header(header::RANGE, format!("bytes={resume_from}-"));
         }
+
+        Ok(req.send()?)
     }
 }
 
 #[cfg(not(feature = "curl-backend"))]
 pub mod curl {
 
+    use std::path::Path;
+
     use anyhow::{anyhow, Result};
 
     use super::Event;
@@ -437,14 +663,21 @@ pub mod curl {
         _url: &Url,
         _resume_from: u64,
         _callback: &dyn Fn(Event<'_>) -> Result<()>,
+        _ca_bundle: Option<&Path>,
     ) -> Result<()> {
         Err(anyhow!(DownloadError::BackendUnavailable("curl")))
     }
+
+    pub fn content_length(_url: &Url, _ca_bundle: Option<&Path>) -> Result<Option<u64>> {
+        Err(anyhow!(DownloadError::BackendUnavailable("curl")))
+    }
 }
 
 #[cfg(not(feature = "reqwest-backend"))]
 pub mod reqwest_be {
 
+    use std::path::Path;
+
     use anyhow::{anyhow, Result};
 
     use super::Event;
@@ -457,7 +690,16 @@ pub mod reqwest_be {
         _resume_from: u64,
         _callback: &dyn Fn(Event<'_>) -> Result<()>,
         _tls: TlsBackend,
+        _ca_bundle: Option<&Path>,
     ) -> Result<()> {
         Err(anyhow!(DownloadError::BackendUnavailable("reqwest")))
     }
+
+    pub fn content_length(
+        _url: &Url,
+        _tls: TlsBackend,
+        _ca_bundle: Option<&Path>,
+    ) -> Result<Option<u64>> {
+        Err(anyhow!(DownloadError::BackendUnavailable("reqwest")))
+    }
 }
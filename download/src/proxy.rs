@@ -0,0 +1,161 @@
+//! Picks the proxy for a download from the environment, following the
+//! convention of curl and most other tools.
+//!
+//! The proxy for `https:` URLs is taken from `https_proxy` or `HTTPS_PROXY`
+//! and the one for `http:` URLs from `http_proxy` alone, as the upper-case
+//! form can be set by a CGI request header. Either falls back to `all_proxy`
+//! or `ALL_PROXY`. The lower-case variable takes precedence if both are set,
+//! and an empty one counts as unset.
+//!
+//! Hosts matching `no_proxy` (or `NO_PROXY`) are connected to directly. It
+//! is a list separated by commas or spaces where each entry is one of:
+//!
+//! - `*`, matching every host;
+//! - an IP address, or a range of them in CIDR notation like `10.0.0.0/8`
+//!   or `fd00::/8`, matching hosts given as an address in it;
+//! - a domain name, matching that domain and every subdomain of it, with
+//!   any leading `.` or `*.` ignored, so that `example.com`, `.example.com`
+//!   and `*.example.com` all match both `example.com` and
+//!   `mirror.example.com`.
+//!
+//! A name or address may be followed by `:port` to only match that port,
+//! with an IPv6 address then written in brackets like `[::1]:8080`.
+
+use std::env;
+use std::net::IpAddr;
+
+use url::{Host, Url};
+
+/// The proxy to download `url` through, or `None` to connect directly.
+pub fn proxy_for_url(url: &Url) -> Option<Url> {
+    let proxy = match url.scheme() {
+        "https" => var_pair("https_proxy", "HTTPS_PROXY"),
+        "http" => var("http_proxy"),
+        _ => None,
+    }
+    .or_else(|| var_pair("all_proxy", "ALL_PROXY"))?;
+
+    if let Some(no_proxy) = var_pair("no_proxy", "NO_PROXY") {
+        if NoProxy::parse(&no_proxy).matches(url) {
+            return None;
+        }
+    }
+
+    if proxy.contains("://") {
+        Url::parse(&proxy).ok()
+    } else {
+        Url::parse(&format!("http://{proxy}")).ok()
+    }
+    .filter(|proxy| proxy.host_str().is_some())
+}
+
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn var_pair(lower: &str, upper: &str) -> Option<String> {
+    var(lower).or_else(|| var(upper))
+}
+
+/// The entries of a `no_proxy` list.
+#[derive(Debug, Default)]
+pub struct NoProxy {
+    entries: Vec<(Pattern, Option<u16>)>,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    All,
+    Net(IpAddr, u8),
+    Domain(String),
+}
+
+impl NoProxy {
+    /// Parses a `no_proxy` list, skipping entries that make no sense.
+    pub fn parse(list: &str) -> Self {
+        let entries = list
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(parse_entry)
+            .collect();
+        Self { entries }
+    }
+
+    /// Whether `url` is to be connected to directly.
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host() else {
+            return false;
+        };
+        let port = url.port_or_known_default();
+        self.entries.iter().any(|(pattern, entry_port)| {
+            if entry_port.is_some() && *entry_port != port {
+                return false;
+            }
+            match (pattern, &host) {
+                (Pattern::All, _) => true,
+                (Pattern::Net(net, bits), Host::Ipv4(addr)) => in_net(*net, *bits, (*addr).into()),
+                (Pattern::Net(net, bits), Host::Ipv6(addr)) => in_net(*net, *bits, (*addr).into()),
+                (Pattern::Domain(domain), Host::Domain(name)) => {
+                    let name = name.trim_end_matches('.').to_ascii_lowercase();
+                    match name.strip_suffix(domain.as_str()) {
+                        Some(rest) => rest.is_empty() || rest.ends_with('.'),
+                        None => false,
+                    }
+                }
+                _ => false,
+            }
+        })
+    }
+}
+
+fn parse_entry(entry: &str) -> Option<(Pattern, Option<u16>)> {
+    if entry.is_empty() {
+        return None;
+    }
+    if entry == "*" {
+        return Some((Pattern::All, None));
+    }
+
+    // An entry ending in a port, which for IPv6 needs the brackets to be
+    // told apart from the address
+    let (host, port) = if let Some(rest) = entry.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        match rest {
+            "" => (host, None),
+            _ => (host, Some(rest.strip_prefix(':')?.parse().ok()?)),
+        }
+    } else {
+        match entry.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => (host, Some(port.parse().ok()?)),
+            _ => (entry, None),
+        }
+    };
+
+    if let Some((addr, bits)) = host.split_once('/') {
+        let addr = addr.parse::<IpAddr>().ok()?;
+        let bits = bits.parse::<u8>().ok()?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        return (bits <= max).then_some((Pattern::Net(addr, bits), port));
+    }
+    if let Ok(addr) = host.parse::<IpAddr>() {
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        return Some((Pattern::Net(addr, bits), port));
+    }
+
+    let domain = host.strip_prefix('*').unwrap_or(host);
+    let domain = domain.trim_start_matches('.').trim_end_matches('.');
+    if domain.is_empty() {
+        return None;
+    }
+    Some((Pattern::Domain(domain.to_ascii_lowercase()), port))
+}
+
+/// Whether `addr` is in the network of the first `bits` bits of `net`.
+fn in_net(net: IpAddr, bits: u8, addr: IpAddr) -> bool {
+    let (net, addr, len) = match (net, addr) {
+        (IpAddr::V4(net), IpAddr::V4(addr)) => (u32::from(net).into(), u32::from(addr).into(), 32),
+        (IpAddr::V6(net), IpAddr::V6(addr)) => (u128::from(net), u128::from(addr), 128),
+        _ => return false,
+    };
+    let shift = len - u32::from(bits);
+    shift >= len || net >> shift == addr >> shift
+}
@@ -0,0 +1,60 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Caps the rate that downloads receive data at, as a token bucket holding
+/// up to a second's worth of bytes.
+///
+/// One limiter can be shared by downloads running at once. Each takes the
+/// bytes it has received out of the same bucket, and when that runs dry they
+/// queue up for it to refill, so that the rate is split evenly between them.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Below zero when data has been received that the bucket has yet to
+    /// refill for.
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    /// A limiter allowing `bytes_per_sec`, or `None` for no limit at all if
+    /// that is zero.
+    pub fn new(bytes_per_sec: u64) -> Option<Self> {
+        if bytes_per_sec == 0 {
+            return None;
+        }
+        Some(Self {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec as f64,
+                refilled: Instant::now(),
+            }),
+        })
+    }
+
+    /// Accounts for `len` bytes having been received, blocking the caller
+    /// until the data is within the limit.
+    pub fn take(&self, len: usize) {
+        let rate = self.bytes_per_sec as f64;
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(rate) - len as f64;
+            bucket.refilled = now;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            // Callers that find the bucket already in debt wait for their own
+            // share on top of everyone else's, which queues them in turn
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        };
+        thread::sleep(wait);
+    }
+}
@@ -0,0 +1,158 @@
+#![cfg(feature = "reqwest-backend")]
+
+use std::env::{remove_var, set_var};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use url::Url;
+
+use download::*;
+
+mod support;
+use crate::support::{serve_file, tmp_dir, write_file};
+
+static SERIALISE_TESTS: Mutex<()> = Mutex::new(());
+
+const CA_CERT: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBiDCCAS+gAwIBAgIURfXyDtosz1x0UfiBB32ymA0BdXgwCgYIKoZIzj0EAwIw
+GTEXMBUGA1UEAwwOcnVzdHVwIHRlc3QgQ0EwIBcNMjYxMDE0MTIzNjQ3WhgPMjEy
+NjA5MjAxMjM2NDdaMBkxFzAVBgNVBAMMDnJ1c3R1cCB0ZXN0IENBMFkwEwYHKoZI
+zj0CAQYIKoZIzj0DAQcDQgAEFmjX7PKKbVeo+Gz4TCHwI6zPjAqW1E10W/NTSy1v
+f2Vq8HHhkQcJfwOKrIpb/XIpycxLP0DEJDMfP8ZCGFOulKNTMFEwHQYDVR0OBBYE
+FC/XcOuhdWzFWcrwYX8XsGphrHoKMB8GA1UdIwQYMBaAFC/XcOuhdWzFWcrwYX8X
+sGphrHoKMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgX8fyfvd7
+WMm1S0m+7jC6GaUvG5bvGz7N8zxrE/wriTICIEz7jYqSd2mK5PLu8DfPSOgMg5g9
+2mQGLjt4g6ayEJ8R
+-----END CERTIFICATE-----
+";
+
+fn scrub_env() {
+    remove_var("CURL_CA_BUNDLE");
+    remove_var("SSL_CERT_FILE");
+}
+
+fn download(backend: Backend, url: &Url, ca_bundle: Option<&Path>) -> Result<()> {
+    let tmpdir = tmp_dir();
+    download_to_path_with_backend(
+        backend,
+        url,
+        &tmpdir.path().join("downloaded"),
+        false,
+        None,
+        Options {
+            ca_bundle,
+            ..Options::default()
+        },
+    )
+}
+
+fn backends() -> Vec<Backend> {
+    let mut backends = vec![
+        Backend::Reqwest(TlsBackend::Default),
+        Backend::Reqwest(TlsBackend::Rustls),
+    ];
+    if cfg!(feature = "curl-backend") {
+        backends.push(Backend::Curl);
+    }
+    backends
+}
+
+#[test]
+fn ca_bundle_precedence() {
+    let _guard = SERIALISE_TESTS
+        .lock()
+        .expect("Unable to lock the test guard");
+    scrub_env();
+    assert_eq!(ca_bundle(None), None);
+
+    set_var("SSL_CERT_FILE", "ssl.pem");
+    assert_eq!(ca_bundle(None), Some(PathBuf::from("ssl.pem")));
+    set_var("CURL_CA_BUNDLE", "curl.pem");
+    assert_eq!(ca_bundle(None), Some(PathBuf::from("curl.pem")));
+    set_var("CURL_CA_BUNDLE", "");
+    assert_eq!(ca_bundle(None), Some(PathBuf::from("ssl.pem")));
+    let set = Path::new("set.pem");
+    assert_eq!(ca_bundle(Some(set)), Some(PathBuf::from("set.pem")));
+    scrub_env();
+}
+
+#[test]
+fn downloads_with_ca_bundle() {
+    let _guard = SERIALISE_TESTS
+        .lock()
+        .expect("Unable to lock the test guard");
+    scrub_env();
+    let tmpdir = tmp_dir();
+    let bundle = tmpdir.path().join("bundle.pem");
+    write_file(&bundle, CA_CERT);
+
+    let addr = serve_file(b"12345".to_vec());
+    let url = format!("http://{addr}").parse().unwrap();
+    for backend in backends() {
+        download(backend, &url, Some(&bundle)).unwrap_or_else(|e| panic!("{backend:?}: {e:?}"));
+    }
+    scrub_env();
+}
+
+#[test]
+fn bad_ca_bundle_is_reported() {
+    let _guard = SERIALISE_TESTS
+        .lock()
+        .expect("Unable to lock the test guard");
+    scrub_env();
+    let tmpdir = tmp_dir();
+    let addr = serve_file(b"12345".to_vec());
+    let url = format!("http://{addr}").parse().unwrap();
+
+    let missing = tmpdir.path().join("missing.pem");
+    set_var("SSL_CERT_FILE", &missing);
+    let empty = tmpdir.path().join("empty.pem");
+    write_file(&empty, "not a certificate\n");
+    for backend in backends() {
+        let err = download(backend, &url, None).unwrap_err();
+        let msg = format!("{err:?}");
+        assert!(
+            msg.contains(&format!(
+                "could not use the CA bundle '{}'",
+                missing.display()
+            )),
+            "{backend:?}: {msg}"
+        );
+        assert!(!is_transient(&err));
+
+        let msg = format!("{:?}", download(backend, &url, Some(&empty)).unwrap_err());
+        assert!(
+            msg.contains("it contains no PEM certificates"),
+            "{backend:?}: {msg}"
+        );
+
+        // A download given no bundle is not affected by one given another
+        // bundle before it
+        let err = download(backend, &url, None).unwrap_err();
+        assert!(
+            format!("{err:?}").contains(&missing.display().to_string()),
+            "{backend:?}: {err:?}"
+        );
+    }
+
+    // What is wrong with the certificates themselves only shows once they
+    // are parsed
+    let malformed = tmpdir.path().join("malformed.pem");
+    write_file(
+        &malformed,
+        "-----BEGIN CERTIFICATE-----\nbm90IGEgY2VydGlmaWNhdGU=\n-----END CERTIFICATE-----\n",
+    );
+    let msg = format!(
+        "{:?}",
+        download(Backend::Reqwest(TlsBackend::Rustls), &url, Some(&malformed)).unwrap_err()
+    );
+    assert!(
+        msg.contains(&format!(
+            "could not use the CA bundle '{}'",
+            malformed.display()
+        )),
+        "{msg}"
+    );
+    scrub_env();
+}
@@ -20,8 +20,15 @@ fn partially_downloaded_file_gets_resumed_from_byte_offset() {
     write_file(&target_path, "123");
 
     let from_url = Url::from_file_path(&from_path).unwrap();
-    download_to_path_with_backend(Backend::Curl, &from_url, &target_path, true, None)
-        .expect("Test download failed");
+    download_to_path_with_backend(
+        Backend::Curl,
+        &from_url,
+        &target_path,
+        true,
+        None,
+        Options::default(),
+    )
+    .expect("Test download failed");
 
     assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
 }
@@ -65,6 +72,7 @@ fn callback_gets_all_data_as_if_the_download_happened_all_at_once() {
 
             Ok(())
         }),
+        Options::default(),
     )
     .expect("Test download failed");
 
@@ -74,3 +82,12 @@ fn callback_gets_all_data_as_if_the_download_happened_all_at_once() {
     assert_eq!(observed_bytes, vec![b'1', b'2', b'3', b'4', b'5']);
     assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
 }
+
+#[test]
+fn content_length_of_http_url() {
+    let addr = serve_file(b"12345".to_vec());
+    let from_url = format!("http://{addr}").parse().unwrap();
+    let len = content_length_with_backend(Backend::Curl, &from_url, Options::default())
+        .expect("Test request failed");
+    assert_eq!(len, Some(5));
+}
@@ -1,7 +1,10 @@
 #![cfg(feature = "reqwest-backend")]
 
+use std::io;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use url::Url;
 
@@ -26,12 +29,73 @@ fn resume_partial_from_file_url() {
         &target_path,
         true,
         None,
+        Options::default(),
     )
     .expect("Test download failed");
 
     assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
 }
 
+#[test]
+fn partial_file_is_kept_after_transient_error() {
+    let tmpdir = tmp_dir();
+    let from_path = tmpdir.path().join("download-source");
+    write_file(&from_path, "12345");
+
+    let target_path = tmpdir.path().join("downloaded");
+    let from_url = Url::from_file_path(&from_path).unwrap();
+    let err = download_to_path_with_backend(
+        Backend::Reqwest(TlsBackend::Default),
+        &from_url,
+        &target_path,
+        true,
+        Some(&|msg| match msg {
+            Event::DownloadDataReceived(_) => {
+                Err(io::Error::from(io::ErrorKind::ConnectionReset).into())
+            }
+            _ => Ok(()),
+        }),
+        Options::default(),
+    )
+    .unwrap_err();
+
+    assert!(is_transient(&err));
+    assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
+}
+
+#[test]
+fn partial_file_is_removed_after_other_errors() {
+    let tmpdir = tmp_dir();
+    let from_path = tmpdir.path().join("download-source");
+    write_file(&from_path, "12345");
+
+    let target_path = tmpdir.path().join("downloaded");
+    let from_url = Url::from_file_path(&from_path).unwrap();
+    let err = download_to_path_with_backend(
+        Backend::Reqwest(TlsBackend::Default),
+        &from_url,
+        &target_path,
+        true,
+        Some(&|msg| match msg {
+            Event::DownloadDataReceived(_) => Err(DownloadError::Cancelled.into()),
+            _ => Ok(()),
+        }),
+        Options::default(),
+    )
+    .unwrap_err();
+
+    assert!(!is_transient(&err));
+    assert!(!target_path.exists());
+}
+
+#[test]
+fn server_errors_are_transient() {
+    assert!(is_transient(&DownloadError::HttpStatus(503).into()));
+    assert!(is_transient(&DownloadError::HttpStatus(429).into()));
+    assert!(!is_transient(&DownloadError::HttpStatus(404).into()));
+    assert!(!is_transient(&DownloadError::FileNotFound.into()));
+}
+
 #[test]
 fn callback_gets_all_data_as_if_the_download_happened_all_at_once() {
     let tmpdir = tmp_dir();
@@ -71,6 +135,7 @@ fn callback_gets_all_data_as_if_the_download_happened_all_at_once() {
 
             Ok(())
         }),
+        Options::default(),
     )
     .expect("Test download failed");
 
@@ -80,3 +145,75 @@ fn callback_gets_all_data_as_if_the_download_happened_all_at_once() {
     assert_eq!(observed_bytes, vec![b'1', b'2', b'3', b'4', b'5']);
     assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
 }
+
+fn download_limited(url: &Url, name: &str, rate_limiter: &RateLimiter) {
+    let tmpdir = tmp_dir();
+    download_to_path_with_backend(
+        Backend::Reqwest(TlsBackend::Default),
+        url,
+        &tmpdir.path().join(name),
+        false,
+        None,
+        Options {
+            rate_limiter: Some(rate_limiter),
+            ..Options::default()
+        },
+    )
+    .expect("Test download failed");
+}
+
+#[test]
+fn download_is_limited_to_the_rate() {
+    let addr = serve_file(vec![0; 40_000]);
+    let url = format!("http://{addr}").parse().unwrap();
+    let rate_limiter = RateLimiter::new(20_000).unwrap();
+
+    // The first second's worth comes at once, and the rest takes a second
+    let start = Instant::now();
+    download_limited(&url, "downloaded", &rate_limiter);
+    assert!(start.elapsed() >= Duration::from_millis(900));
+}
+
+#[test]
+fn concurrent_downloads_share_the_rate() {
+    let addr = serve_file(vec![0; 20_000]);
+    let url = format!("http://{addr}").parse().unwrap();
+    let rate_limiter = RateLimiter::new(20_000).unwrap();
+
+    let start = Instant::now();
+    thread::scope(|s| {
+        s.spawn(|| download_limited(&url, "first", &rate_limiter));
+        s.spawn(|| download_limited(&url, "second", &rate_limiter));
+    });
+    assert!(start.elapsed() >= Duration::from_millis(900));
+}
+
+#[test]
+fn zero_rate_is_unlimited() {
+    assert!(RateLimiter::new(0).is_none());
+}
+
+#[test]
+fn content_length_of_http_and_file_urls() {
+    let addr = serve_file(b"12345".to_vec());
+    let from_url = format!("http://{addr}").parse().unwrap();
+    let len = content_length_with_backend(
+        Backend::Reqwest(TlsBackend::Default),
+        &from_url,
+        Options::default(),
+    )
+    .expect("Test request failed");
+    assert_eq!(len, Some(5));
+
+    let tmpdir = tmp_dir();
+    let from_path = tmpdir.path().join("download-source");
+    write_file(&from_path, "123");
+    let from_url = Url::from_file_path(&from_path).unwrap();
+    let len = content_length_with_backend(
+        Backend::Reqwest(TlsBackend::Default),
+        &from_url,
+        Options::default(),
+    )
+    .expect("Test request failed");
+    assert_eq!(len, Some(3));
+}
@@ -8,7 +8,7 @@ use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 
-use env_proxy::for_url;
+use download::{proxy_for_url, NoProxy};
 use reqwest::{blocking::Client, Proxy};
 use url::Url;
 
@@ -16,6 +16,7 @@ static SERIALISE_TESTS: Mutex<()> = Mutex::new(());
 
 fn scrub_env() {
     remove_var("http_proxy");
+    remove_var("HTTP_PROXY");
     remove_var("https_proxy");
     remove_var("HTTPS_PROXY");
     remove_var("ftp_proxy");
@@ -35,10 +36,73 @@ fn read_basic_proxy_params() {
     scrub_env();
     set_var("https_proxy", "http://proxy.example.com:8080");
     let u = Url::parse("https://www.example.org").ok().unwrap();
-    assert_eq!(
-        for_url(&u).host_port(),
-        Some(("proxy.example.com".to_string(), 8080))
+    let proxy = proxy_for_url(&u).unwrap();
+    assert_eq!(proxy.host_str(), Some("proxy.example.com"));
+    assert_eq!(proxy.port(), Some(8080));
+}
+
+#[test]
+fn proxy_env_precedence() {
+    let _guard = SERIALISE_TESTS
+        .lock()
+        .expect("Unable to lock the test guard");
+    scrub_env();
+    let https = Url::parse("https://www.example.org").unwrap();
+    let http = Url::parse("http://www.example.org").unwrap();
+    let host = |url| proxy_for_url(url).map(|p| p.host_str().unwrap().to_owned());
+
+    set_var("HTTPS_PROXY", "upper.example.com:8080");
+    assert_eq!(host(&https).as_deref(), Some("upper.example.com"));
+    set_var("https_proxy", "lower.example.com:8080");
+    assert_eq!(host(&https).as_deref(), Some("lower.example.com"));
+    set_var("https_proxy", "");
+    assert_eq!(host(&https).as_deref(), Some("upper.example.com"));
+
+    // Only the lower-case form is read for plain HTTP
+    set_var("HTTP_PROXY", "upper.example.com:8080");
+    assert_eq!(host(&http), None);
+    set_var("ALL_PROXY", "all.example.com:8080");
+    assert_eq!(host(&http).as_deref(), Some("all.example.com"));
+    set_var("http_proxy", "lower.example.com:8080");
+    assert_eq!(host(&http).as_deref(), Some("lower.example.com"));
+
+    set_var("NO_PROXY", "example.org");
+    assert_eq!(host(&https), None);
+    set_var("no_proxy", "example.com");
+    assert_eq!(host(&https).as_deref(), Some("upper.example.com"));
+}
+
+#[test]
+fn no_proxy_matching() {
+    let no_proxy = NoProxy::parse(
+        ".corp.example.com, *.mirror.example.org,static.example.net:8080 \
+         10.0.0.0/8,192.168.1.7 fd00::/8,[::1]:3128",
     );
+    let matches = |url: &str| no_proxy.matches(&Url::parse(url).unwrap());
+
+    assert!(matches("https://corp.example.com/"));
+    assert!(matches("https://dist.corp.example.com/"));
+    assert!(matches("https://DIST.Corp.Example.com./"));
+    assert!(!matches("https://notcorp.example.com/"));
+    assert!(!matches("https://example.com/"));
+    assert!(matches("https://mirror.example.org/"));
+    assert!(matches("https://a.b.mirror.example.org/"));
+
+    assert!(matches("http://static.example.net:8080/"));
+    assert!(!matches("http://static.example.net/"));
+
+    assert!(matches("https://10.1.2.3/"));
+    assert!(!matches("https://11.1.2.3/"));
+    assert!(matches("https://192.168.1.7/"));
+    assert!(!matches("https://192.168.1.8/"));
+    assert!(matches("https://[fd12::1]/"));
+    assert!(!matches("https://[fe80::1]/"));
+    assert!(matches("http://[::1]:3128/"));
+    assert!(!matches("http://[::1]/"));
+
+    assert!(NoProxy::parse("*").matches(&Url::parse("https://example.com").unwrap()));
+    assert!(NoProxy::parse("0.0.0.0/0").matches(&Url::parse("https://1.2.3.4").unwrap()));
+    assert!(!NoProxy::parse("").matches(&Url::parse("https://example.com").unwrap()));
 }
 
 // Tests to verify if socks feature is available and being used
@@ -60,11 +124,10 @@ fn socks_proxy_request() {
         }
     });
 
-    let env_proxy = |url: &Url| for_url(url).to_url();
     let url = Url::parse("http://192.168.0.1/").unwrap();
 
     let client = Client::builder()
-        .proxy(Proxy::custom(env_proxy))
+        .proxy(Proxy::custom(proxy_for_url))
         .timeout(Duration::from_secs(1))
         .build()
         .unwrap();
@@ -1,6 +1,7 @@
 /// The CLI specific code lives in the cli module and sub-modules.
 #[macro_use]
 pub mod log;
+mod cargo_config;
 pub mod common;
 mod download_tracker;
 pub mod errors;
@@ -0,0 +1,93 @@
+//! Finds the targets that cargo builds for by default in a workspace, so
+//! that removing one of them can be warned about.
+//!
+//! This is best-effort: cargo's own rules for merging its configuration are
+//! only followed as far as `CARGO_BUILD_TARGET` and the `build.target` of the
+//! nearest `.cargo/config.toml` (or `.cargo/config`) go, and files that
+//! cannot be read or parsed are skipped.
+
+use std::fs;
+use std::path::Path;
+
+use crate::currentprocess::{process, varsource::VarSource};
+
+/// A target cargo is configured to build for, along with where that was
+/// configured.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct BuildTarget {
+    pub target: String,
+    pub source: String,
+}
+
+/// The default build targets of the cargo workspace that `cwd` is in, or
+/// none if it is not in one.
+pub(crate) fn build_targets(cwd: &Path) -> Vec<BuildTarget> {
+    if !cwd.ancestors().any(|dir| dir.join("Cargo.toml").is_file()) {
+        return Vec::new();
+    }
+
+    if let Ok(target) = process().var("CARGO_BUILD_TARGET") {
+        let target = target.trim();
+        if !target.is_empty() {
+            return vec![BuildTarget {
+                target: target.to_owned(),
+                source: "`CARGO_BUILD_TARGET`".to_owned(),
+            }];
+        }
+    }
+
+    for dir in cwd.ancestors() {
+        for name in ["config.toml", "config"] {
+            let path = dir.join(".cargo").join(name);
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let targets = targets_in_config(&content);
+            if !targets.is_empty() {
+                return targets
+                    .into_iter()
+                    .map(|target| BuildTarget {
+                        target,
+                        source: format!("'{}'", path.display()),
+                    })
+                    .collect();
+            }
+        }
+    }
+    Vec::new()
+}
+
+/// The `build.target` of a cargo configuration file, which is either one
+/// target or a list of them.
+fn targets_in_config(content: &str) -> Vec<String> {
+    let Ok(config) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    match config.get("build").and_then(|build| build.get("target")) {
+        Some(toml::Value::String(target)) => vec![target.clone()],
+        Some(toml::Value::Array(targets)) => targets
+            .iter()
+            .filter_map(|target| target.as_str().map(str::to_owned))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_target_is_one_or_many() {
+        assert_eq!(
+            targets_in_config("[build]\ntarget = \"wasm32-unknown-unknown\"\n"),
+            ["wasm32-unknown-unknown"]
+        );
+        assert_eq!(
+            targets_in_config("build.target = [\"a-b-c\", \"d-e-f\"]\n"),
+            ["a-b-c", "d-e-f"]
+        );
+        assert!(targets_in_config("[build]\njobs = 4\n").is_empty());
+        assert!(targets_in_config("not toml").is_empty());
+    }
+}
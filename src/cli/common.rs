@@ -5,12 +5,16 @@ use std::fmt::Display;
 use std::fs;
 use std::io::{BufRead, ErrorKind, Write};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
 use std::{cmp, env};
 
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use git_testament::{git_testament, render_testament};
 use once_cell::sync::Lazy;
+use serde::Serialize;
 
 use super::self_update;
 use crate::cli::download_tracker::DownloadTracker;
@@ -21,15 +25,18 @@ use crate::currentprocess::{
     varsource::VarSource,
 };
 use crate::dist::dist::{TargetTriple, ToolchainDesc};
+use crate::dist::manifestation::DIST_MANIFEST;
 use crate::install::UpdateStatus;
 use crate::utils::notifications as util_notifications;
 use crate::utils::notify::NotificationLevel;
+use crate::utils::units::{Size, Unit, UnitMode};
 use crate::utils::utils;
 use crate::{
-    dist::notifications as dist_notifications, toolchain::distributable::DistributableToolchain,
+    dist::notifications as dist_notifications,
+    toolchain::{distributable::DistributableToolchain, names::ToolchainName},
 };
+use crate::{errors::RustupError, notifications::Notifier, Cfg, Notification};
 use crate::{process, toolchain::toolchain::Toolchain};
-use crate::{Cfg, Notification};
 
 pub(crate) const WARN_COMPLETE_PROFILE: &str = "downloading with complete profile isn't recommended unless you are a developer of the rust language";
 
@@ -116,6 +123,11 @@ pub(crate) fn question_bool(question: &str, default: bool) -> Result<bool> {
 }
 
 pub(crate) fn read_line() -> Result<String> {
+    read_stdin_line().context("unable to read from stdin for confirmation")
+}
+
+/// Reads the first line of stdin, without its line ending.
+pub(crate) fn read_stdin_line() -> Result<String> {
     let stdin = process().stdin();
     let stdin = stdin.lock();
     let mut lines = stdin.lines();
@@ -124,13 +136,13 @@ pub(crate) fn read_line() -> Result<String> {
         None => Err(anyhow!("no lines found from stdin")),
         Some(v) => Ok(v),
     }
-    .context("unable to read from stdin for confirmation")
 }
 
 #[derive(Default)]
 struct NotifyOnConsole {
     ram_notice_shown: bool,
     verbose: bool,
+    errors_only: bool,
 }
 
 impl NotifyOnConsole {
@@ -146,6 +158,9 @@ impl NotifyOnConsole {
             }
         };
         let level = n.level();
+        if self.errors_only && !matches!(level, NotificationLevel::Error) {
+            return;
+        }
         for n in format!("{n}").lines() {
             match level {
                 NotificationLevel::Verbose => {
@@ -170,20 +185,68 @@ impl NotifyOnConsole {
     }
 }
 
-#[cfg_attr(feature = "otel", tracing::instrument)]
-pub(crate) fn set_globals(verbose: bool, quiet: bool) -> Result<Cfg> {
-    let download_tracker = DownloadTracker::new_with_display_progress(!quiet);
-    let console_notifier = RefCell::new(NotifyOnConsole {
-        verbose,
-        ..Default::default()
-    });
+/// The command line's [`Notifier`]: download progress bars and log lines on
+/// the terminal.
+struct ConsoleNotifier {
+    download_tracker: Arc<Mutex<DownloadTracker>>,
+    console: RefCell<NotifyOnConsole>,
+}
 
-    Cfg::from_env(Arc::new(move |n: Notification<'_>| {
-        if download_tracker.lock().unwrap().handle_notification(&n) {
+impl Notifier for ConsoleNotifier {
+    fn handle(&self, n: Notification<'_>) {
+        if self
+            .download_tracker
+            .lock()
+            .unwrap()
+            .handle_notification(&n)
+        {
             return;
         }
-        console_notifier.borrow_mut().handle(n);
-    }))
+        self.console.borrow_mut().handle(n);
+    }
+}
+
+/// Sets up the configuration and how its notifications are shown: `quiet`
+/// hides download progress, and `errors_only` hides that and every
+/// notification that is not an error.
+#[cfg_attr(feature = "otel", tracing::instrument)]
+pub(crate) fn set_globals(verbose: bool, quiet: bool, errors_only: bool) -> Result<Cfg> {
+    let notifier = ConsoleNotifier {
+        download_tracker: DownloadTracker::new_with_display_progress(!quiet && !errors_only),
+        console: RefCell::new(NotifyOnConsole {
+            verbose,
+            errors_only,
+            ..Default::default()
+        }),
+    };
+
+    Cfg::from_env(Arc::new(move |n: Notification<'_>| notifier.handle(n)))
+}
+
+/// If `err` is that the settings file could not be parsed, offers to copy
+/// the backup of the settings over it, and says whether that was done.
+pub(crate) fn offer_to_restore_settings(err: &anyhow::Error) -> Result<bool> {
+    let Some(RustupError::CorruptSettings { path, backup }) = err.downcast_ref() else {
+        return Ok(false);
+    };
+    warn!("could not parse settings file '{}'", path.display());
+    let question = format!(
+        "Restore the settings from before they were last changed, kept in '{}'? (y/N)",
+        backup.display()
+    );
+    if !confirm(&question, false).unwrap_or(false) {
+        return Ok(false);
+    }
+    let settings = utils::read_file("settings backup", backup)?;
+    utils::write_file_atomic("settings", path, &settings)?;
+    info!("restored the settings from '{}'", backup.display());
+    Ok(true)
+}
+
+/// Whether `RUSTUP_QUIET` asks for rustup's own notifications to be hidden
+/// when running a command.
+pub(crate) fn quiet_from_env() -> bool {
+    matches!(process().var("RUSTUP_QUIET").as_deref(), Ok("1"))
 }
 
 pub(crate) fn show_channel_update(
@@ -208,7 +271,7 @@ impl Display for PackageUpdate {
     }
 }
 
-fn show_channel_updates(
+pub(crate) fn show_channel_updates(
     cfg: &Cfg,
     updates: Vec<(PackageUpdate, Result<UpdateStatus>)>,
 ) -> Result<()> {
@@ -287,8 +350,11 @@ pub(crate) fn update_all_channels(
     cfg: &Cfg,
     do_self_update: bool,
     force_update: bool,
+    allow_downgrade: bool,
+    keep_going: bool,
 ) -> Result<utils::ExitCode> {
-    let toolchains = cfg.update_all_channels(force_update)?;
+    let toolchains = cfg.update_all_channels(force_update, allow_downgrade)?;
+    let failed = toolchains.iter().any(|(_, result)| result.is_err());
 
     if toolchains.is_empty() {
         info!("no updatable toolchains installed");
@@ -307,11 +373,19 @@ pub(crate) fn update_all_channels(
         Ok(utils::ExitCode(0))
     };
 
-    if do_self_update {
-        self_update(show_channel_updates)
+    let exit_code = if do_self_update {
+        self_update(cfg, show_channel_updates)?
     } else {
-        show_channel_updates()
+        show_channel_updates()?
+    };
+    // Every toolchain is tried regardless, as it always has been, so that
+    // one broken channel cannot hold back the rest. Failures are shown as
+    // such above, and `--keep-going` only decides whether they fail the
+    // command.
+    if keep_going && failed {
+        return Ok(utils::ExitCode(1));
     }
+    Ok(exit_code)
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -348,7 +422,7 @@ pub(crate) fn self_update_permitted(explicit: bool) -> Result<SelfUpdatePermissi
     }
 }
 
-pub(crate) fn self_update<F>(before_restart: F) -> Result<utils::ExitCode>
+pub(crate) fn self_update<F>(cfg: &Cfg, before_restart: F) -> Result<utils::ExitCode>
 where
     F: FnOnce() -> Result<utils::ExitCode>,
 {
@@ -361,7 +435,7 @@ where
         SelfUpdatePermission::Permit => {}
     }
 
-    let setup_path = self_update::prepare_update()?;
+    let setup_path = self_update::prepare_update(None, false, cfg.download_ca_bundle.as_deref())?;
 
     before_restart()?;
 
@@ -375,26 +449,66 @@ where
     Ok(utils::ExitCode(0))
 }
 
-pub(crate) fn list_targets(distributable: DistributableToolchain<'_>) -> Result<utils::ExitCode> {
-    let mut t = process().stdout().terminal();
+#[derive(Serialize)]
+struct TargetEntry {
+    target: String,
+    installed: bool,
+}
+
+#[derive(Serialize)]
+struct ToolchainTargetsEntry {
+    toolchain: String,
+    targets: Vec<String>,
+    /// Why the targets could not be listed, in which case there are none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The standard library components of `distributable` that are installed
+/// or available, as their target and whether they are installed.
+fn target_statuses(
+    distributable: &DistributableToolchain<'_>,
+) -> Result<Vec<(TargetTriple, bool)>> {
     let manifestation = distributable.get_manifestation()?;
     let config = manifestation.read_config()?.unwrap_or_default();
     let manifest = distributable.get_manifest()?;
     let components = manifest.query_components(distributable.desc(), &config)?;
-    for component in components {
-        if component.component.short_name_in_manifest() == "rust-std" {
-            let target = component
-                .component
-                .target
-                .as_ref()
-                .expect("rust-std should have a target");
-            if component.installed {
-                let _ = t.attr(terminalsource::Attr::Bold);
-                let _ = writeln!(t.lock(), "{target} (installed)");
-                let _ = t.reset();
-            } else if component.available {
-                let _ = writeln!(t.lock(), "{target}");
-            }
+    Ok(components
+        .into_iter()
+        .filter(|c| c.component.short_name_in_manifest() == "rust-std")
+        .filter(|c| c.installed || c.available)
+        .map(|c| {
+            let target = c.component.target.expect("rust-std should have a target");
+            (target, c.installed)
+        })
+        .collect())
+}
+
+pub(crate) fn list_targets(
+    distributable: DistributableToolchain<'_>,
+    json: bool,
+) -> Result<utils::ExitCode> {
+    let targets = target_statuses(&distributable)?;
+    if json {
+        let entries: Vec<_> = targets
+            .iter()
+            .map(|(target, installed)| TargetEntry {
+                target: target.to_string(),
+                installed: *installed,
+            })
+            .collect();
+        write_json(&entries)?;
+        return Ok(utils::ExitCode(0));
+    }
+
+    let mut t = process().stdout().terminal();
+    for (target, installed) in targets {
+        if installed {
+            let _ = t.attr(terminalsource::Attr::Bold);
+            let _ = writeln!(t.lock(), "{target} (installed)");
+            let _ = t.reset();
+        } else {
+            let _ = writeln!(t.lock(), "{target}");
         }
     }
 
@@ -403,38 +517,100 @@ pub(crate) fn list_targets(distributable: DistributableToolchain<'_>) -> Result<
 
 pub(crate) fn list_installed_targets(
     distributable: DistributableToolchain<'_>,
+    json: bool,
 ) -> Result<utils::ExitCode> {
+    let mut targets = target_statuses(&distributable)?;
+    targets.retain(|(_, installed)| *installed);
+    if json {
+        let entries: Vec<_> = targets
+            .iter()
+            .map(|(target, _)| TargetEntry {
+                target: target.to_string(),
+                installed: true,
+            })
+            .collect();
+        write_json(&entries)?;
+        return Ok(utils::ExitCode(0));
+    }
+
     let t = process().stdout();
-    let manifestation = distributable.get_manifestation()?;
-    let config = manifestation.read_config()?.unwrap_or_default();
-    let manifest = distributable.get_manifest()?;
-    let components = manifest.query_components(distributable.desc(), &config)?;
-    for component in components {
-        if component.component.short_name_in_manifest() == "rust-std" {
-            let target = component
-                .component
-                .target
-                .as_ref()
-                .expect("rust-std should have a target");
-            if component.installed {
-                writeln!(t.lock(), "{target}")?;
+    for (target, _) in targets {
+        writeln!(t.lock(), "{target}")?;
+    }
+    Ok(utils::ExitCode(0))
+}
+
+/// Lists the targets installed in each toolchain that rustup installed,
+/// leaving out custom toolchains, which it can't tell the targets of. A
+/// toolchain whose targets can't be read is reported and the rest are still
+/// listed.
+pub(crate) fn list_all_installed_targets(cfg: &Cfg, json: bool) -> Result<utils::ExitCode> {
+    let mut entries = Vec::new();
+    for name in cfg.list_toolchains()? {
+        let ToolchainName::Official(desc) = &name else {
+            continue;
+        };
+        let targets = DistributableToolchain::new(cfg, desc.clone())
+            .map_err(anyhow::Error::from)
+            .and_then(|distributable| target_statuses(&distributable));
+        let entry = match targets {
+            Ok(targets) => ToolchainTargetsEntry {
+                toolchain: name.to_string(),
+                targets: targets
+                    .into_iter()
+                    .filter(|(_, installed)| *installed)
+                    .map(|(target, _)| target.to_string())
+                    .collect(),
+                error: None,
+            },
+            Err(e) if json => ToolchainTargetsEntry {
+                toolchain: name.to_string(),
+                targets: Vec::new(),
+                error: Some(format!("{e:#}")),
+            },
+            Err(e) => {
+                warn!("could not list the targets of '{name}': {e:#}");
+                continue;
+            }
+        };
+        entries.push(entry);
+    }
+
+    if json {
+        write_json(&entries)?;
+    } else {
+        let stdout = process().stdout();
+        let mut stdout = stdout.lock();
+        for entry in entries {
+            writeln!(stdout, "{}", entry.toolchain)?;
+            for target in entry.targets {
+                writeln!(stdout, "  {target}")?;
             }
         }
     }
     Ok(utils::ExitCode(0))
 }
 
+/// The components of `distributable`, leaving out those that are for other
+/// targets than `target` when one is given.
+fn component_statuses(
+    toolchain: &Toolchain<'_>,
+    target: Option<&str>,
+) -> Result<Vec<crate::config::ComponentStatus>> {
+    let mut components = toolchain.component_statuses()?;
+    if let Some(target) = target {
+        components.retain(|c| c.target.as_deref().map_or(true, |t| t == target));
+    }
+    Ok(components)
+}
+
 pub(crate) fn list_components(
-    distributable: DistributableToolchain<'_>,
+    toolchain: &Toolchain<'_>,
+    target: Option<&str>,
 ) -> Result<utils::ExitCode> {
     let mut t = process().stdout().terminal();
-
-    let manifestation = distributable.get_manifestation()?;
-    let config = manifestation.read_config()?.unwrap_or_default();
-    let manifest = distributable.get_manifest()?;
-    let components = manifest.query_components(distributable.desc(), &config)?;
-    for component in components {
-        let name = component.name;
+    for component in component_statuses(toolchain, target)? {
+        let name = component.full_name();
         if component.installed {
             t.attr(terminalsource::Attr::Bold)?;
             writeln!(t.lock(), "{name} (installed)")?;
@@ -447,22 +623,75 @@ pub(crate) fn list_components(
     Ok(utils::ExitCode(0))
 }
 
-pub(crate) fn list_installed_components(distributable: DistributableToolchain<'_>) -> Result<()> {
+/// Lists the components of `toolchain` that are installed, if `installed`,
+/// along with those that could still be added, if `available`.
+pub(crate) fn list_filtered_components(
+    toolchain: &Toolchain<'_>,
+    target: Option<&str>,
+    installed: bool,
+    available: bool,
+) -> Result<()> {
     let t = process().stdout();
-    for component in distributable.components()? {
-        if component.installed {
-            writeln!(t.lock(), "{}", component.name)?;
+    for component in component_statuses(toolchain, target)? {
+        let wanted = match component.installed {
+            true => installed,
+            false => available && component.available,
+        };
+        if wanted {
+            writeln!(t.lock(), "{}", component.full_name())?;
         }
     }
     Ok(())
 }
 
+/// When a toolchain was installed or last updated, and how much disk it uses,
+/// each unknown if it could not be read.
+struct ToolchainUsage {
+    updated: Option<SystemTime>,
+    size: Option<u64>,
+}
+
+impl ToolchainUsage {
+    /// Reads the usage of the toolchain in `path`. Its manifest is rewritten
+    /// on every install and update, so the time comes from there, or from
+    /// the directory itself for a toolchain without one.
+    fn read(path: &Path) -> Self {
+        let manifest = path.join("lib").join("rustlib").join(DIST_MANIFEST);
+        let updated = fs::metadata(manifest)
+            .or_else(|_| fs::metadata(path))
+            .and_then(|m| m.modified())
+            .ok();
+        let size = utils::dir_size(path).ok();
+        Self { updated, size }
+    }
+}
+
+impl Display for ToolchainUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.updated {
+            Some(updated) => write!(f, "{}", DateTime::<Utc>::from(updated).format("%Y-%m-%d"))?,
+            None => write!(f, "unknown")?,
+        }
+        match self.size {
+            Some(size) => write!(
+                f,
+                "\t{}",
+                Size::new(size as usize, Unit::B, UnitMode::Norm)
+                    .to_string()
+                    .trim_start()
+            ),
+            None => write!(f, "\tunknown"),
+        }
+    }
+}
+
 fn print_toolchain_path(
     cfg: &Cfg,
     toolchain: &str,
     if_default: &str,
     if_override: &str,
     verbose: bool,
+    usage: Option<&ToolchainUsage>,
 ) -> Result<()> {
     let toolchain_path = cfg.toolchains_dir.join(toolchain);
     let toolchain_meta = fs::symlink_metadata(&toolchain_path)?;
@@ -475,18 +704,24 @@ fn print_toolchain_path(
     } else {
         String::new()
     };
+    // After the path, so that scripts reading the fields of `--verbose`
+    // keep working
+    let usage = usage.map_or_else(String::new, |usage| format!("\t{usage}"));
     writeln!(
         process().stdout().lock(),
-        "{}{}{}{}",
+        "{}{}{}{}{}",
         &toolchain,
         if_default,
         if_override,
-        toolchain_path
+        toolchain_path,
+        usage
     )?;
     Ok(())
 }
 
-pub(crate) fn list_toolchains(cfg: &Cfg, verbose: bool) -> Result<utils::ExitCode> {
+/// Lists the installed toolchains, with `verbose` their paths, and with
+/// `usage` when they were installed or updated and their sizes.
+pub(crate) fn list_toolchains(cfg: &Cfg, verbose: bool, usage: bool) -> Result<utils::ExitCode> {
     // Work with LocalToolchainName to accommodate path based overrides
     let toolchains = cfg
         .list_toolchains()?
@@ -503,13 +738,37 @@ pub(crate) fn list_toolchains(cfg: &Cfg, verbose: bool) -> Result<utils::ExitCod
         } else {
             None
         };
-        for toolchain in toolchains {
-            let if_default = if def_toolchain_name.as_ref() == Some(&toolchain) {
+        // Adding up the sizes of whole toolchains is slow, so it is done for
+        // all of them at once
+        let usages = if usage {
+            thread::scope(|s| {
+                let handles: Vec<_> = toolchains
+                    .iter()
+                    .map(|toolchain| {
+                        let path = cfg.toolchain_path(toolchain);
+                        s.spawn(move || ToolchainUsage::read(&path))
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle.join().unwrap_or(ToolchainUsage {
+                            updated: None,
+                            size: None,
+                        })
+                    })
+                    .collect()
+            })
+        } else {
+            Vec::new()
+        };
+        for (i, toolchain) in toolchains.iter().enumerate() {
+            let if_default = if def_toolchain_name.as_ref() == Some(toolchain) {
                 " (default)"
             } else {
                 ""
             };
-            let if_override = if ovr_toolchain_name.as_ref() == Some(&toolchain) {
+            let if_override = if ovr_toolchain_name.as_ref() == Some(toolchain) {
                 " (override)"
             } else {
                 ""
@@ -521,6 +780,7 @@ pub(crate) fn list_toolchains(cfg: &Cfg, verbose: bool) -> Result<utils::ExitCod
                 if_default,
                 if_override,
                 verbose,
+                usages.get(i),
             )
             .context("Failed to list toolchains' directories")?;
         }
@@ -528,10 +788,36 @@ pub(crate) fn list_toolchains(cfg: &Cfg, verbose: bool) -> Result<utils::ExitCod
     Ok(utils::ExitCode(0))
 }
 
-pub(crate) fn list_overrides(cfg: &Cfg) -> Result<utils::ExitCode> {
+/// Write `value` to stdout as a single line of JSON, for `--json` output.
+pub(crate) fn write_json(value: &impl Serialize) -> Result<()> {
+    let stdout = process().stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer(&mut stdout, value)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct OverrideEntry<'a> {
+    path: String,
+    toolchain: &'a str,
+    exists: bool,
+}
+
+pub(crate) fn list_overrides(cfg: &Cfg, json: bool) -> Result<utils::ExitCode> {
     let overrides = cfg.settings_file.with(|s| Ok(s.overrides.clone()))?;
 
-    if overrides.is_empty() {
+    if json {
+        let entries: Vec<_> = overrides
+            .iter()
+            .map(|(path, toolchain)| OverrideEntry {
+                path: utils::format_path_for_display(path),
+                toolchain,
+                exists: Path::new(path).is_dir(),
+            })
+            .collect();
+        write_json(&entries)?;
+    } else if overrides.is_empty() {
         writeln!(process().stdout().lock(), "no overrides")?;
     } else {
         let mut any_not_exist = false;
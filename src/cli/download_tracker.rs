@@ -4,7 +4,9 @@ use std::io::Write;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-use crate::currentprocess::{filesource::StdoutSource, process, terminalsource};
+use crate::currentprocess::{
+    filesource::StdoutSource, process, terminalsource, varsource::VarSource,
+};
 use crate::dist::Notification as In;
 use crate::utils::units::{Size, Unit, UnitMode};
 use crate::utils::Notification as Un;
@@ -13,6 +15,39 @@ use crate::Notification;
 /// Keep track of this many past download amounts
 const DOWNLOAD_TRACK_COUNT: usize = 5;
 
+/// How far apart, in percent, the progress lines shown instead of a
+/// progress bar are.
+const LINE_STEP_PERCENT: usize = 10;
+
+/// Downloads of fewer bytes than this are quick enough that a progress bar
+/// would only flicker, so none is drawn for them.
+const MIN_PROGRESS_BYTES: usize = 1 << 20;
+
+/// How progress is shown, which `RUSTUP_TERM_PROGRESS_WHEN` picks like
+/// `RUSTUP_TERM_COLOR` picks colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProgressMode {
+    /// A bar redrawn in place, for a terminal.
+    Bar,
+    /// A line every [`LINE_STEP_PERCENT`], for a log.
+    Lines,
+    Off,
+}
+
+impl ProgressMode {
+    fn from_env(display_progress: bool) -> Self {
+        if !display_progress {
+            return Self::Off;
+        }
+        match process().var("RUSTUP_TERM_PROGRESS_WHEN").as_deref() {
+            Ok("never") => Self::Off,
+            Ok("always") => Self::Bar,
+            _ if process().stdout().is_a_tty() => Self::Bar,
+            _ => Self::Lines,
+        }
+    }
+}
+
 /// Tracks download progress and displays information about it to a terminal.
 ///
 /// *not* safe for tracking concurrent downloads yet - it is basically undefined
@@ -46,6 +81,13 @@ pub(crate) struct DownloadTracker {
     units: Vec<Unit>,
     /// Whether we display progress
     display_progress: bool,
+    mode: ProgressMode,
+    /// The percentage at which the next progress line is due.
+    next_line_percent: usize,
+    /// What the progress is of, such as the component being extracted.
+    label: Option<String>,
+    /// The components that are being downloaded, until they are extracted.
+    downloading: Vec<String>,
 }
 
 impl DownloadTracker {
@@ -62,6 +104,10 @@ impl DownloadTracker {
             displayed_charcount: None,
             units: vec![Unit::B],
             display_progress,
+            mode: ProgressMode::from_env(display_progress),
+            next_line_percent: LINE_STEP_PERCENT,
+            label: None,
+            downloading: Vec::new(),
         }))
     }
 
@@ -72,14 +118,17 @@ impl DownloadTracker {
 
                 true
             }
-            Notification::Install(In::Utils(Un::DownloadDataReceived(data))) => {
-                if process().stdout().is_a_tty() {
-                    self.data_received(data.len());
+            Notification::Install(In::Utils(Un::DownloadDataReceived(len))) => {
+                match self.mode {
+                    ProgressMode::Bar => self.data_received(len),
+                    ProgressMode::Lines => self.data_received_for_lines(len),
+                    ProgressMode::Off => {}
                 }
                 true
             }
             Notification::Install(In::Utils(Un::DownloadFinished)) => {
                 self.download_finished();
+                self.downloading.clear();
                 true
             }
             Notification::Install(In::Utils(Un::DownloadPushUnit(unit))) => {
@@ -90,6 +139,35 @@ impl DownloadTracker {
                 self.pop_unit();
                 true
             }
+            // Still shown as usual, but also label the progress that follows
+            Notification::Install(In::ExtractingComponent(component)) => {
+                // Unless the component is streamed, its download is over
+                let phase = if self.downloading == [component] {
+                    "downloading and extracting"
+                } else {
+                    "extracting"
+                };
+                self.label = Some(format!("{phase} '{component}'"));
+                self.downloading.clear();
+                false
+            }
+            Notification::Install(In::DownloadingComponent(component, ..)) => {
+                self.downloading.push(component.to_owned());
+                self.label = downloading_label(&self.downloading);
+                false
+            }
+            Notification::Install(In::FileAlreadyDownloaded) => {
+                self.downloading.pop();
+                self.label = downloading_label(&self.downloading);
+                false
+            }
+            // A download that is not of a component, such as of a manifest
+            Notification::Install(In::Utils(Un::DownloadingFile(..)))
+                if self.downloading.is_empty() =>
+            {
+                self.label = None;
+                false
+            }
 
             _ => false,
         }
@@ -104,6 +182,9 @@ impl DownloadTracker {
     pub(crate) fn data_received(&mut self, len: usize) {
         self.total_downloaded += len;
         self.downloaded_this_sec += len;
+        if self.is_small() {
+            return;
+        }
 
         let current_time = Instant::now();
 
@@ -126,6 +207,50 @@ impl DownloadTracker {
             }
         }
     }
+    /// Notifies self that data of size `len` has been received, when
+    /// progress is shown as lines. Like the bar, these are only shown for
+    /// what takes more than a second.
+    fn data_received_for_lines(&mut self, len: usize) {
+        self.total_downloaded += len;
+        if self.is_small() {
+            return;
+        }
+        let start = *self.last_sec.get_or_insert_with(Instant::now);
+        if Instant::now().saturating_duration_since(start) < Duration::from_secs(1) {
+            return;
+        }
+        let Some(content_len) = self.content_len.filter(|len| *len > 0) else {
+            return;
+        };
+        let percent = (self.total_downloaded * 100 / content_len).min(100);
+        if let Some(next) = next_line_percent(percent, self.next_line_percent) {
+            self.display_line(percent);
+            self.next_line_percent = next;
+        }
+    }
+
+    /// Whether the download is known to be too small for its progress to
+    /// be worth showing. Other units than bytes, such as the files written
+    /// while extracting, are always shown.
+    fn is_small(&self) -> bool {
+        matches!(self.units.last(), Some(Unit::B))
+            && self.content_len.is_some_and(|len| len < MIN_PROGRESS_BYTES)
+    }
+
+    fn display_line(&mut self, percent: usize) {
+        let unit = *self.units.last().unwrap();
+        let total_h = Size::new(self.total_downloaded, unit, UnitMode::Norm);
+        let content_len_h = Size::new(self.content_len.unwrap_or(0), unit, UnitMode::Norm);
+        let label = match &self.label {
+            Some(label) => format!("{label}: "),
+            None => String::new(),
+        };
+        let _ = writeln!(
+            self.term.lock(),
+            "{label}{total_h} / {content_len_h} ({percent:3} %)"
+        );
+    }
+
     /// Notifies self that the download has finished.
     pub(crate) fn download_finished(&mut self) {
         if self.displayed_charcount.is_some() {
@@ -133,6 +258,11 @@ impl DownloadTracker {
             self.display();
             let _ = writeln!(self.term.lock());
         }
+        // Lines that were started are seen to the end
+        if self.next_line_percent > LINE_STEP_PERCENT && self.next_line_percent <= 100 {
+            self.total_downloaded = self.content_len.unwrap_or(self.total_downloaded);
+            self.display_line(100);
+        }
         self.prepare_for_new_download();
     }
     /// Resets the state to be ready for a new download.
@@ -144,6 +274,7 @@ impl DownloadTracker {
         self.start_sec = Some(Instant::now());
         self.last_sec = None;
         self.displayed_charcount = None;
+        self.next_line_percent = LINE_STEP_PERCENT;
     }
     /// Display the tracked download information to the terminal.
     fn display(&mut self) {
@@ -175,6 +306,10 @@ impl DownloadTracker {
                     let _ = self.term.carriage_return();
                 }
 
+                let label = match &self.label {
+                    Some(label) => format!("{label}: "),
+                    None => String::new(),
+                };
                 let output = match self.content_len {
                     Some(content_len) => {
                         let content_len_h = Size::new(content_len, unit, UnitMode::Norm);
@@ -186,7 +321,8 @@ impl DownloadTracker {
                             (remaining / speed) as u64
                         });
                         format!(
-                            "{} / {} ({:3.0} %) {} in {} ETA: {}",
+                            "{}{} / {} ({:3.0} %) {} in {} ETA: {}",
+                            label,
                             total_h,
                             content_len_h,
                             percent,
@@ -196,7 +332,8 @@ impl DownloadTracker {
                         )
                     }
                     None => format!(
-                        "Total: {} Speed: {} Elapsed: {}",
+                        "{}Total: {} Speed: {} Elapsed: {}",
+                        label,
                         total_h,
                         speed_h,
                         elapsed_h.display()
@@ -250,6 +387,23 @@ impl fmt::Display for Display {
     }
 }
 
+/// The label of the progress of downloading `components`, which are named
+/// if there is just the one.
+fn downloading_label(components: &[String]) -> Option<String> {
+    match components {
+        [] => None,
+        [component] => Some(format!("downloading '{component}'")),
+        _ => Some(format!("downloading {} components", components.len())),
+    }
+}
+
+/// Whether a progress line is due at `percent` done when the next one is
+/// due at `next`, and if so when the one after it is due. Steps that were
+/// passed at once get the one line.
+fn next_line_percent(percent: usize, next: usize) -> Option<usize> {
+    (percent >= next).then(|| (percent / LINE_STEP_PERCENT + 1) * LINE_STEP_PERCENT)
+}
+
 // we're doing modular arithmetic, treat as integer
 fn format_dhms(sec: u64) -> (u64, u8, u8, u8) {
     let (mins, sec) = (sec / 60, (sec % 60) as u8);
@@ -262,7 +416,7 @@ fn format_dhms(sec: u64) -> (u64, u8, u8, u8) {
 mod tests {
     use rustup_macros::unit_test as test;
 
-    use super::format_dhms;
+    use super::{downloading_label, format_dhms, next_line_percent};
 
     #[test]
     fn download_tracker_format_dhms_test() {
@@ -278,4 +432,36 @@ mod tests {
 
         assert_eq!(format_dhms(222_292), (2, 13, 44, 52));
     }
+
+    #[test]
+    fn download_tracker_next_line_percent_test() {
+        assert_eq!(next_line_percent(5, 10), None);
+
+        assert_eq!(next_line_percent(10, 10), Some(20));
+
+        assert_eq!(next_line_percent(47, 20), Some(50));
+
+        assert_eq!(next_line_percent(100, 90), Some(110));
+    }
+
+    #[test]
+    fn download_tracker_downloading_label_test() {
+        assert_eq!(downloading_label(&[]), None);
+
+        let docs = ["rust-docs".to_owned()];
+        assert_eq!(
+            downloading_label(&docs).as_deref(),
+            Some("downloading 'rust-docs'")
+        );
+
+        let all = [
+            "rustc".to_owned(),
+            "cargo".to_owned(),
+            "rust-docs".to_owned(),
+        ];
+        assert_eq!(
+            downloading_label(&all).as_deref(),
+            Some("downloading 3 components")
+        );
+    }
 }
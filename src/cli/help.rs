@@ -15,7 +15,13 @@ pub(crate) static SHOW_HELP: &str = r"Discussion:
     compilation targets, then they are listed as well.
 
     If there are multiple toolchains installed then all installed
-    toolchains are listed as well.";
+    toolchains are listed as well.
+
+    For scripts, `--json` prints a single object with the default host,
+    `rustup_home` and `cargo_home`, the `active_toolchain` (null if there
+    is none) with its installed `targets`, and the `installed_toolchains`
+    with their installed `components`. Its `schema` field is increased
+    whenever a field is removed or changes meaning.";
 
 pub(crate) static SHOW_ACTIVE_TOOLCHAIN_HELP: &str = r"Discussion:
     Shows the name of the active toolchain.
@@ -32,7 +38,13 @@ pub(crate) static UPDATE_HELP: &str = r"Discussion:
     updates rustup itself.
 
     If given a toolchain argument then `update` updates that
-    toolchain, the same as `rustup toolchain install`.";
+    toolchain, the same as `rustup toolchain install`.
+
+    Updating every installed toolchain never stops at one that fails to
+    update, and `--keep-going` only makes the command fail at the end if
+    any of them did. Given toolchain arguments, `--keep-going` also
+    carries on past one that fails instead of stopping there, showing a
+    summary of every toolchain at the end.";
 
 pub(crate) static INSTALL_HELP: &str = r"Discussion:
     Installs a specific rust toolchain.
@@ -41,7 +53,18 @@ pub(crate) static INSTALL_HELP: &str = r"Discussion:
 
 pub(crate) static DEFAULT_HELP: &str = r"Discussion:
     Sets the default toolchain to the one specified. If the toolchain
-    is not already installed then it is installed first.";
+    is not already installed then it is installed first.
+
+    With `--unset`, or 'none' as the toolchain, there is no longer a
+    default, and running a proxy where no override is in effect fails.
+
+    With '-' as the toolchain, its name is read from the first line of
+    stdin, so that a script can pass it without quoting it.
+
+    With `--path <dir>` the toolchain is set for that directory and the
+    ones beneath it instead, just as `rustup override set --path <dir>`
+    does, and the global default is left alone. A `rust-toolchain.toml`
+    file in a directory nearer to where a proxy runs still wins over it.";
 
 pub(crate) static TOOLCHAIN_HELP: &str = r"Discussion:
     Many `rustup` commands deal with *toolchains*, a single
@@ -104,7 +127,35 @@ pub(crate) static TOOLCHAIN_LINK_HELP: &str = r"Discussion:
         $ rustup override set latest-stage1
 
     If you now compile a crate in the current directory, the custom
-    toolchain 'latest-stage1' will be used.";
+    toolchain 'latest-stage1' will be used.
+
+    If 'toolchain' is already linked, the link is replaced to point at
+    'path' instead. A toolchain that is installed rather than linked is
+    never replaced.";
+
+pub(crate) static TOOLCHAIN_GC_HELP: &str = r"Discussion:
+    Uninstalls every installed toolchain that is not referenced by the
+    default toolchain, a directory override, or a toolchain file found
+    beneath one of the `--root` directories. Custom toolchains are
+    never uninstalled.
+
+    To keep a few toolchains around for bisection, `--keep-last N`
+    retains the N most recently modified toolchains of each channel,
+    such as the latest dated nightlies:
+
+        $ rustup toolchain gc --root ~/src --keep-last 3 --dry-run";
+
+pub(crate) static TOOLCHAIN_VERIFY_HELP: &str = r"Discussion:
+    Checks that every file of the toolchain's installed components is
+    present and unchanged, and exits with an error if any is not.
+
+    The hashes of the files are recorded in the toolchain the first
+    time it is verified, and a file is only hashed again when its size
+    or modification time changes, so later checks are quick. The files
+    of a component that has been reinstalled or updated since are
+    recorded afresh.
+
+        $ rustup toolchain verify nightly";
 
 pub(crate) static OVERRIDE_HELP: &str = r"Discussion:
     Overrides configure Rustup to use a specific toolchain when
@@ -131,8 +182,14 @@ pub(crate) static OVERRIDE_UNSET_HELP: &str = r"Discussion:
     If `--path` argument is present, removes the override toolchain
     for the specified directory. If `--nonexistent` argument is
     present, removes the override toolchain for all nonexistent
-    directories. Otherwise, removes the override toolchain for the
-    current directory.";
+    directories, and with `--dry-run` as well only lists them.
+    Otherwise, removes the override toolchain for the current
+    directory.";
+
+pub(crate) static SET_DEFAULT_HOST_HELP: &str = r"Discussion:
+    The host must be one that `rustc` is shipped for, going by the
+    channel of the active toolchain and the hosts rustup knows of.
+    Pass `--force` to set a host that is newer than either.";
 
 pub(crate) static RUN_HELP: &str = r"Discussion:
     Configures an environment to use the given toolchain and then runs
@@ -140,6 +197,24 @@ pub(crate) static RUN_HELP: &str = r"Discussion:
     rustc or cargo. This can be used for testing arbitrary toolchains
     without setting an override.
 
+    A toolchain that is not installed is an error, unless `--install`
+    is given, in which case it is installed first, with the profile
+    named by `--profile` or else the configured one. Like the other
+    options, these go before the command, as anything after it is
+    passed on to the command:
+
+        $ rustup run --install --profile minimal nightly-2024-05-01 cargo test
+
+    Variables given with `--env KEY=VALUE` are set after rustup has
+    configured the environment, so the command sees them as given:
+
+        $ rustup run --env RUSTFLAGS=-Dwarnings nightly cargo build
+
+    With `--quiet`, or with `RUSTUP_QUIET=1` set, rustup only prints its
+    own error messages, such as a toolchain that cannot be found, and
+    none of its other notifications. The output and exit code of the
+    command are left as they are.
+
     Commands explicitly proxied by `rustup` (such as `rustc` and
     `cargo`) also have a shorthand for this available. The toolchain
     can be set by using `+toolchain` as the first argument. These are
@@ -153,8 +228,16 @@ pub(crate) static DOC_HELP: &str = r"Discussion:
     Opens the documentation for the currently active toolchain with
     the default browser.
 
+    The browser is the command given with `--open-in`, which is split
+    on whitespace into a program and its arguments, else the first of
+    the space-separated commands in the `BROWSER` environment variable
+    that can be started. If no browser can be launched, the path of
+    the documentation is printed instead.
+
     By default, it opens the documentation index. Use the various
-    flags to open specific pieces of documentation.";
+    flags to open specific pieces of documentation, and `--list` to
+    see which of them the toolchain has. With `--json`, the list
+    also gives the path of each page.";
 
 pub(crate) static COMPLETIONS_HELP: &str = r"Discussion:
     Enable tab completion for Bash, Fish, Zsh, or PowerShell
@@ -261,6 +344,15 @@ pub(crate) static COMPLETIONS_HELP: &str = r"Discussion:
 
         PS C:\> rustup completions powershell >> ${env:USERPROFILE}\Documents\WindowsPowerShell\Microsoft.PowerShell_profile.ps1
 
+    Dynamic completions:
+
+    With `--dynamic`, the Bash, Fish and Zsh scripts also complete the
+    names of installed toolchains, and the targets and components of the
+    active toolchain, by running `rustup` each time such a name is
+    completed:
+
+        $ rustup completions bash --dynamic > ~/.local/share/bash-completion/completions/rustup
+
     Cargo:
 
     Rustup can also generate a completion script for `cargo`. The script output
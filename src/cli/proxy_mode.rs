@@ -3,7 +3,10 @@ use std::ffi::OsString;
 use anyhow::Result;
 
 use crate::{
-    cli::{common::set_globals, job, self_update},
+    cli::{
+        common::{quiet_from_env, set_globals},
+        job, self_update,
+    },
     command::run_command_for_dir,
     currentprocess::argsource::ArgSource,
     toolchain::names::{LocalToolchainName, ResolvableLocalToolchainName},
@@ -35,7 +38,7 @@ pub fn main(arg0: &str) -> Result<ExitCode> {
             .skip(1 + toolchain.is_some() as usize)
             .collect();
 
-        let cfg = set_globals(false, true)?;
+        let cfg = set_globals(false, true, quiet_from_env())?;
         cfg.check_metadata_version()?;
         let toolchain = toolchain
             .map(|t| t.resolve(&cfg.get_default_host_triple()?))
@@ -1,18 +1,23 @@
+use std::collections::BTreeMap;
 use std::fmt;
+use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::SystemTime;
 
-use anyhow::{anyhow, Error, Result};
+use anyhow::{anyhow, Context, Error, Result};
 use clap::{
     builder::{EnumValueParser, PossibleValue, PossibleValuesParser},
     Arg, ArgAction, ArgGroup, ArgMatches, Command, ValueEnum,
 };
 use clap_complete::Shell;
 use itertools::Itertools;
+use serde::Serialize;
 
 use crate::{
     cli::{
+        cargo_config,
         common::{self, PackageUpdate},
         errors::CLIError,
         help::*,
@@ -25,9 +30,14 @@ use crate::{
         filesource::{StderrSource, StdoutSource},
     },
     dist::{
-        dist::{PartialToolchainDesc, Profile, TargetTriple},
-        manifest::{Component, ComponentStatus},
+        self,
+        component::integrity::Damage,
+        dist::{
+            InstallPlan, PartialToolchainDesc, Profile, TargetTriple, ToolchainDesc, KNOWN_HOSTS,
+        },
+        manifest::{Component, ComponentStatus, Package, PackageTargets},
     },
+    env_var,
     errors::RustupError,
     install::UpdateStatus,
     process,
@@ -37,12 +47,16 @@ use crate::{
         names::{
             custom_toolchain_name_parser, maybe_resolvable_toolchainame_parser,
             partial_toolchain_desc_parser, resolvable_local_toolchainame_parser,
-            resolvable_toolchainame_parser, CustomToolchainName, MaybeResolvableToolchainName,
-            ResolvableLocalToolchainName, ResolvableToolchainName, ToolchainName,
+            resolvable_toolchainame_parser, toolchain_sort, CustomToolchainName, InvalidName,
+            LocalToolchainName, MaybeResolvableToolchainName, ResolvableLocalToolchainName,
+            ResolvableToolchainName, ToolchainName,
         },
         toolchain::Toolchain,
     },
-    utils::utils,
+    utils::{
+        units::{Size, Unit, UnitMode},
+        utils,
+    },
     Cfg, Notification,
 };
 
@@ -101,7 +115,7 @@ pub fn main() -> Result<utils::ExitCode> {
 
             #[cfg_attr(feature = "otel", tracing::instrument)]
             fn rustc_version() -> std::result::Result<String, Box<dyn std::error::Error>> {
-                let cfg = &mut common::set_globals(false, true)?;
+                let cfg = &mut common::set_globals(false, true, false)?;
                 let cwd = std::env::current_dir()?;
 
                 if let Some(t) = process().args().find(|x| x.starts_with('+')) {
@@ -141,11 +155,24 @@ pub fn main() -> Result<utils::ExitCode> {
     }?;
     let verbose = matches.get_flag("verbose");
     let quiet = matches.get_flag("quiet");
-    let cfg = &mut common::set_globals(verbose, quiet)?;
+    // Only errors are shown next to the output of `rustup run --quiet`
+    let errors_only = match matches.subcommand() {
+        Some(("run", m)) => m.get_flag("quiet") || common::quiet_from_env(),
+        _ => false,
+    };
+    let cfg = &mut match common::set_globals(verbose, quiet, errors_only) {
+        Err(e) if common::offer_to_restore_settings(&e)? => {
+            common::set_globals(verbose, quiet, errors_only)?
+        }
+        cfg => cfg?,
+    };
 
     if let Some(t) = matches.get_one::<ResolvableToolchainName>("+toolchain") {
         cfg.set_toolchain_override(t);
     }
+    if matches.get_flag("offline") {
+        cfg.offline = true;
+    }
 
     if maybe_upgrade_data(cfg, &matches)? {
         return Ok(utils::ExitCode(0));
@@ -153,21 +180,28 @@ pub fn main() -> Result<utils::ExitCode> {
 
     cfg.check_metadata_version()?;
 
-    Ok(match matches.subcommand() {
+    let exit_code = match matches.subcommand() {
         Some(s) => match s {
             ("dump-testament", _) => common::dump_testament()?,
+            ("internal", c) => match c.subcommand() {
+                Some(("complete", m)) => handle_epipe(complete_values(cfg, m))?,
+                _ => unreachable!(),
+            },
             ("show", c) => match c.subcommand() {
                 Some(s) => match s {
                     ("active-toolchain", m) => handle_epipe(show_active_toolchain(cfg, m))?,
                     ("home", _) => handle_epipe(show_rustup_home(cfg))?,
-                    ("profile", _) => handle_epipe(show_profile(cfg))?,
+                    ("profile", m) => handle_epipe(show_profile(cfg, m.get_flag("json")))?,
+                    ("overrides", m) => {
+                        handle_epipe(common::list_overrides(cfg, m.get_flag("json")))?
+                    }
                     _ => handle_epipe(show(cfg, c))?,
                 },
                 None => handle_epipe(show(cfg, c))?,
             },
             ("install", m) => deprecated("toolchain install", cfg, m, update)?,
             ("update", m) => update(cfg, m)?,
-            ("check", _) => check_updates(cfg)?,
+            ("check", m) => check_updates(cfg, m.get_flag("json"))?,
             ("uninstall", m) => deprecated("toolchain uninstall", cfg, m, toolchain_remove)?,
             ("default", m) => default_(cfg, m)?,
             ("toolchain", c) => match c.subcommand() {
@@ -176,6 +210,8 @@ pub fn main() -> Result<utils::ExitCode> {
                     ("list", m) => handle_epipe(toolchain_list(cfg, m))?,
                     ("link", m) => toolchain_link(cfg, m)?,
                     ("uninstall", m) => toolchain_remove(cfg, m)?,
+                    ("gc", m) => toolchain_gc(cfg, m)?,
+                    ("verify", m) => toolchain_verify(cfg, m)?,
                     _ => unreachable!(),
                 },
                 None => unreachable!(),
@@ -200,7 +236,7 @@ pub fn main() -> Result<utils::ExitCode> {
             },
             ("override", c) => match c.subcommand() {
                 Some(s) => match s {
-                    ("list", _) => handle_epipe(common::list_overrides(cfg))?,
+                    ("list", m) => handle_epipe(common::list_overrides(cfg, m.get_flag("json")))?,
                     ("set", m) => override_add(cfg, m)?,
                     ("unset", m) => override_remove(cfg, m)?,
                     _ => unreachable!(),
@@ -214,8 +250,14 @@ pub fn main() -> Result<utils::ExitCode> {
             ("man", m) => man(cfg, m)?,
             ("self", c) => match c.subcommand() {
                 Some(s) => match s {
-                    ("update", _) => self_update::update(cfg)?,
+                    ("update", m) if m.get_flag("check") => self_update::check(cfg)?,
+                    ("update", m) => self_update::update(
+                        cfg,
+                        m.get_one::<String>("version").map(|v| v.as_str()),
+                        m.get_flag("allow-downgrade"),
+                    )?,
                     ("uninstall", m) => self_uninstall(m)?,
+                    ("check", _) => self_update::check_installation()?,
                     _ => unreachable!(),
                 },
                 None => unreachable!(),
@@ -225,6 +267,15 @@ pub fn main() -> Result<utils::ExitCode> {
                     ("default-host", m) => set_default_host_triple(cfg, m)?,
                     ("profile", m) => set_profile(cfg, m)?,
                     ("auto-self-update", m) => set_auto_self_update(cfg, m)?,
+                    ("disable-self-update", m) => set_disable_self_update(cfg, m)?,
+                    ("download-concurrency", m) => set_download_concurrency(cfg, m)?,
+                    ("download-retries", m) => set_download_retries(cfg, m)?,
+                    ("download-retry-backoff-ms", m) => set_download_retry_backoff(cfg, m)?,
+                    ("download-verify-signatures", m) => set_download_verify_signatures(cfg, m)?,
+                    ("download-max-rate", m) => set_download_max_rate(cfg, m)?,
+                    ("download-cache-size", m) => set_download_cache_size(cfg, m)?,
+                    ("download-ca-bundle", m) => set_download_ca_bundle(cfg, m)?,
+                    ("download-stream-extract", m) => set_download_stream_extract(cfg, m)?,
                     _ => unreachable!(),
                 },
                 None => unreachable!(),
@@ -236,6 +287,7 @@ pub fn main() -> Result<utils::ExitCode> {
                         c.get_one::<CompletionCommand>("command")
                             .copied()
                             .unwrap_or(CompletionCommand::Rustup),
+                        c.get_flag("dynamic"),
                     )?
                 } else {
                     unreachable!()
@@ -247,7 +299,19 @@ pub fn main() -> Result<utils::ExitCode> {
             eprintln!("{}", cli().render_long_help());
             utils::ExitCode(1)
         }
-    })
+    };
+
+    // `run` hands over to a toolchain command, like a proxy does, and the others
+    // already report on rustup updates
+    if !matches!(
+        matches.subcommand_name(),
+        None | Some("run" | "update" | "check" | "self" | "dump-testament" | "internal")
+    ) {
+        if let Err(e) = self_update::notify_available_update(cfg) {
+            debug!("could not check for a rustup update: {e}");
+        }
+    }
+    Ok(exit_code)
 }
 
 pub(crate) fn cli() -> Command {
@@ -266,6 +330,13 @@ pub(crate) fn cli() -> Command {
                 .help("Disable progress output")
                 .short('q')
                 .long("quiet")
+                .visible_alias("no-progress")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("offline")
+                .help("Fail instead of downloading anything")
+                .long("offline")
                 .action(ArgAction::SetTrue),
         )
         .arg(
@@ -285,6 +356,22 @@ pub(crate) fn cli() -> Command {
                 .about("Dump information about the build")
                 .hide(true), // Not for users, only CI
         )
+        .subcommand(
+            Command::new("internal")
+                .about("Commands used by rustup's own scripts")
+                .hide(true) // Not for users, only the generated completion scripts
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("complete")
+                        .about("List the candidate values for a completion context")
+                        .arg(
+                            Arg::new("context")
+                                .required(true)
+                                .value_parser(PossibleValuesParser::new(COMPLETION_CONTEXTS)),
+                        ),
+                ),
+        )
         .subcommand(
             Command::new("show")
                 .about("Show the active and installed toolchains or profiles")
@@ -292,6 +379,12 @@ pub(crate) fn cli() -> Command {
                 .arg(
                     verbose_arg("Enable verbose output with rustc information for all installed toolchains"),
                 )
+                .arg(
+                    Arg::new("json")
+                        .help("Print the homes, active toolchain and installed toolchains as JSON")
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                )
                 .subcommand(
                     Command::new("active-toolchain")
                         .about("Show the active toolchain")
@@ -304,7 +397,26 @@ pub(crate) fn cli() -> Command {
                     Command::new("home")
                         .about("Display the computed value of RUSTUP_HOME"),
                 )
-                .subcommand(Command::new("profile").about("Show the default profile used for the `rustup install` command"))
+                .subcommand(
+                    Command::new("profile")
+                        .about("Show the default profile used for the `rustup install` command")
+                        .arg(
+                            Arg::new("json")
+                                .help("Print the profile as JSON")
+                                .long("json")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("overrides")
+                        .about("Show the directory overrides, like `rustup override list`")
+                        .arg(
+                            Arg::new("json")
+                                .help("Print the overrides as JSON")
+                                .long("json")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
         )
         .subcommand(
             Command::new("install")
@@ -352,7 +464,8 @@ pub(crate) fn cli() -> Command {
                         .required(true)
                         .value_parser(resolvable_toolchainame_parser)
                         .num_args(1..)
-                ),
+                )
+                .arg(prune_overrides_arg()),
         )
         .subcommand(
             Command::new("update")
@@ -378,14 +491,35 @@ pub(crate) fn cli() -> Command {
                         .long("force")
                         .action(ArgAction::SetTrue)
                 )
+                .arg(
+                    Arg::new("allow-downgrade")
+                        .help("Allow rustup to downgrade a toolchain whose channel now resolves to an older build, or to satisfy your component choice")
+                        .long("allow-downgrade")
+                        .action(ArgAction::SetTrue)
+                )
                 .arg(
                     Arg::new("force-non-host")
                         .help("Install toolchains that require an emulator. See https://github.com/rust-lang/rustup/wiki/Non-host-toolchains")
                         .long("force-non-host")
                         .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("keep-going")
+                        .help("Carry on updating the other toolchains when one fails, and exit with an error at the end")
+                        .long("keep-going")
+                        .action(ArgAction::SetTrue)
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Check for updates to Rust toolchains and rustup")
+                .arg(
+                    Arg::new("json")
+                        .help("Print the installed and latest versions as JSON")
+                        .long("json")
+                        .action(ArgAction::SetTrue),
                 ),
         )
-        .subcommand(Command::new("check").about("Check for updates to Rust toolchains and rustup"))
         .subcommand(
             Command::new("default")
                 .about("Set the default toolchain")
@@ -394,7 +528,22 @@ pub(crate) fn cli() -> Command {
                     Arg::new("toolchain")
                         .help(MAYBE_RESOLVABLE_TOOLCHAIN_ARG_HELP)
                         .required(false)
-                        .value_parser(maybe_resolvable_toolchainame_parser)
+                        .value_parser(default_toolchain_parser)
+                )
+                .arg(
+                    Arg::new("unset")
+                        .help("Remove the default toolchain, as `rustup default none` does")
+                        .long("unset")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("toolchain"),
+                )
+                .arg(
+                    Arg::new("path")
+                        .help("Set the toolchain for this directory instead, as `rustup override set` does")
+                        .long("path")
+                        .num_args(1)
+                        .requires("toolchain")
+                        .conflicts_with("unset"),
                 ),
         )
         .subcommand(
@@ -408,6 +557,12 @@ pub(crate) fn cli() -> Command {
                         .about("List installed toolchains")
                         .arg(
                             verbose_arg("Enable verbose output with toolchain information"),
+                        )
+                        .arg(
+                            Arg::new("usage")
+                                .help("Also show when each toolchain was installed or updated, and its size")
+                                .long("usage")
+                                .action(ArgAction::SetTrue),
                         ),
                 )
                 .subcommand(
@@ -436,6 +591,20 @@ pub(crate) fn cli() -> Command {
                                 .use_value_delimiter(true)
                             .action(ArgAction::Append),
                         )
+                        .arg(
+                            Arg::new("only-components")
+                                .help("Install only the components given with `--component` (and what they need), instead of the profile")
+                                .long("only-components")
+                                .requires("components")
+                                .conflicts_with("profile")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("component-set")
+                                .help("Also install the components of this set from the `[component-sets]` of the settings")
+                                .long("component-set")
+                                .num_args(1),
+                        )
                         .arg(
                             Arg::new("targets")
                                 .help("Add specific targets on installation")
@@ -462,7 +631,7 @@ pub(crate) fn cli() -> Command {
                         )
                         .arg(
                             Arg::new("allow-downgrade")
-                                .help("Allow rustup to downgrade the toolchain to satisfy your component choice")
+                                .help("Allow rustup to downgrade a toolchain whose channel now resolves to an older build, or to satisfy your component choice")
                                 .long("allow-downgrade")
                                 .action(ArgAction::SetTrue)
                         )
@@ -471,6 +640,27 @@ pub(crate) fn cli() -> Command {
                                 .help("Install toolchains that require an emulator. See https://github.com/rust-lang/rustup/wiki/Non-host-toolchains")
                                 .long("force-non-host")
                                 .action(ArgAction::SetTrue)
+                        )
+                        .arg(
+                            Arg::new("component-tarballs")
+                                .help("Install from the manifest and component tarballs in this directory instead of the dist server")
+                                .long("component-tarballs")
+                                .value_name("DIR")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .num_args(1),
+                        )
+                        .arg(
+                            Arg::new("dry-run")
+                                .help("Print the components that would be downloaded and their sizes, without installing anything")
+                                .long("dry-run")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("download-only")
+                                .help("Download the components into the cache without installing them, so that they can be installed offline later")
+                                .long("download-only")
+                                .action(ArgAction::SetTrue)
+                                .conflicts_with("dry-run"),
                         ),
                 )
                 .subcommand(
@@ -483,7 +673,8 @@ pub(crate) fn cli() -> Command {
                                 .required(true)
                                 .value_parser(resolvable_toolchainame_parser)
                                 .num_args(1..)
-                        ),
+                        )
+                        .arg(prune_overrides_arg()),
                 )
                 .subcommand(
                     Command::new("link")
@@ -500,6 +691,50 @@ pub(crate) fn cli() -> Command {
                                 .help("Path to the directory")
                                 .required(true),
                         ),
+                )
+                .subcommand(
+                    Command::new("gc")
+                        .about("Uninstall toolchains that are no longer referenced")
+                        .after_help(TOOLCHAIN_GC_HELP)
+                        .arg(
+                            Arg::new("root")
+                                .help("Also keep toolchains named by toolchain files beneath this directory")
+                                .long("root")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .num_args(1)
+                                .action(ArgAction::Append),
+                        )
+                        .arg(
+                            Arg::new("keep-last")
+                                .help("Keep the N most recently modified toolchains of each channel")
+                                .long("keep-last")
+                                .value_name("N")
+                                .value_parser(clap::value_parser!(usize))
+                                .default_value("0"),
+                        )
+                        .arg(
+                            Arg::new("dry-run")
+                                .help("Only list the toolchains that would be uninstalled")
+                                .long("dry-run")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("no-prompt")
+                                .help("Uninstall the toolchains without asking for confirmation")
+                                .short('y')
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check that the files of a toolchain's components are intact")
+                        .after_help(TOOLCHAIN_VERIFY_HELP)
+                        .arg(
+                            Arg::new("toolchain")
+                                .help(RESOLVABLE_TOOLCHAIN_ARG_HELP)
+                                .required(true)
+                                .value_parser(resolvable_toolchainame_parser),
+                        ),
                 ),
         )
         .subcommand(
@@ -522,6 +757,19 @@ pub(crate) fn cli() -> Command {
                                 .long("installed")
                                 .help("List only installed targets")
                                 .action(ArgAction::SetTrue)
+                        )
+                        .arg(
+                            Arg::new("all-toolchains")
+                                .long("all-toolchains")
+                                .help("List the installed targets of every installed toolchain, by toolchain")
+                                .action(ArgAction::SetTrue)
+                                .conflicts_with("toolchain"),
+                        )
+                        .arg(
+                            Arg::new("json")
+                                .long("json")
+                                .help("Print the targets as JSON")
+                                .action(ArgAction::SetTrue),
                         ),
                 )
                 .subcommand(
@@ -530,7 +778,7 @@ pub(crate) fn cli() -> Command {
                         .alias("install")
                         .arg(
                             Arg::new("target")
-                            .required(true)
+                            .required_unless_present_any(["from-toolchain", "all"])
                             .num_args(1..)
                             .help(
                                 "List of targets to install; \
@@ -543,6 +791,29 @@ pub(crate) fn cli() -> Command {
                                 .long("toolchain")
                                 .num_args(1)
                                 .value_parser(partial_toolchain_desc_parser),
+                        )
+                        .arg(
+                            Arg::new("from-toolchain")
+                                .help("Install the targets that are installed in this toolchain")
+                                .long("from-toolchain")
+                                .num_args(1)
+                                .value_parser(partial_toolchain_desc_parser)
+                                .conflicts_with("target"),
+                        )
+                        .arg(
+                            Arg::new("all")
+                                .help("Install every available target, going on past those that fail")
+                                .long("all")
+                                .action(ArgAction::SetTrue)
+                                .conflicts_with_all(["target", "from-toolchain"]),
+                        )
+                        .arg(
+                            Arg::new("yes")
+                                .help("Don't ask before installing every available target")
+                                .short('y')
+                                .long("yes")
+                                .action(ArgAction::SetTrue)
+                                .requires("all"),
                         ),
                 )
                 .subcommand(
@@ -561,6 +832,12 @@ pub(crate) fn cli() -> Command {
                                 .long("toolchain")
                                 .num_args(1)
                                 .value_parser(partial_toolchain_desc_parser),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .help("Remove the target even if cargo is configured to build for it here")
+                                .long("force")
+                                .action(ArgAction::SetTrue),
                         ),
                 ),
         )
@@ -584,6 +861,18 @@ pub(crate) fn cli() -> Command {
                                 .long("installed")
                                 .help("List only installed components")
                                 .action(ArgAction::SetTrue)
+                        )
+                        .arg(
+                            Arg::new("available")
+                                .long("available")
+                                .help("List only components that are not installed but can be added")
+                                .action(ArgAction::SetTrue)
+                        )
+                        .arg(
+                            Arg::new("target")
+                                .long("target")
+                                .help("List only components for this target, besides those for every target")
+                                .num_args(1)
                         ),
                 )
                 .subcommand(
@@ -602,6 +891,21 @@ pub(crate) fn cli() -> Command {
                             Arg::new("target")
                             .long("target")
                             .num_args(1)
+                        )
+                        .arg(
+                            Arg::new("path")
+                                .help("Install from an unpacked rust-installer package in this directory")
+                                .long("path")
+                                .num_args(1)
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .conflicts_with("target"),
+                        )
+                        .arg(
+                            Arg::new("reinstall")
+                                .help("Remove and reinstall components that are already installed, to repair them")
+                                .long("reinstall")
+                                .action(ArgAction::SetTrue)
+                                .conflicts_with("path"),
                         ),
                 )
                 .subcommand(
@@ -620,6 +924,12 @@ pub(crate) fn cli() -> Command {
                             Arg::new("target")
                             .long("target")
                             .num_args(1)
+                        )
+                        .arg(
+                            Arg::new("purge-unused-deps")
+                                .help("Also remove the components installed only as dependencies that nothing needs any more")
+                                .long("purge-unused-deps")
+                                .action(ArgAction::SetTrue),
                         ),
                 ),
         )
@@ -630,7 +940,14 @@ pub(crate) fn cli() -> Command {
                 .subcommand_required(true)
                 .arg_required_else_help(true)
                 .subcommand(
-                    Command::new("list").about("List directory toolchain overrides"),
+                    Command::new("list")
+                        .about("List directory toolchain overrides")
+                        .arg(
+                            Arg::new("json")
+                                .help("Print the overrides as JSON")
+                                .long("json")
+                                .action(ArgAction::SetTrue),
+                        ),
                 )
                 .subcommand(
                     Command::new("set")
@@ -648,6 +965,12 @@ pub(crate) fn cli() -> Command {
                                 .long("path")
                                 .num_args(1)
                                 .help("Path to the directory"),
+                        )
+                        .arg(
+                            Arg::new("channel-freeze")
+                                .long("channel-freeze")
+                                .help("Keep `rustup update` from updating the channel past its current version")
+                                .action(ArgAction::SetTrue),
                         ),
                 )
                 .subcommand(
@@ -666,6 +989,13 @@ pub(crate) fn cli() -> Command {
                                 .long("nonexistent")
                                 .help("Remove override toolchain for all nonexistent directories")
                                 .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Only show the overrides that `--nonexistent` would remove")
+                                .requires("nonexistent")
+                                .action(ArgAction::SetTrue),
                         ),
                 ),
         )
@@ -692,6 +1022,30 @@ pub(crate) fn cli() -> Command {
                         .help("Install the requested toolchain if needed")
                         .long("install")
                         .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .help("The profile to install a missing toolchain with")
+                        .long("profile")
+                        .value_parser(PossibleValuesParser::new(Profile::names()))
+                        .num_args(1)
+                        .requires("install"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .help("Only show rustup's error messages, not its other notifications")
+                        .short('q')
+                        .long("quiet")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("env")
+                        .help("Set an environment variable for the command, other than one rustup sets such as PATH")
+                        .long("env")
+                        .value_name("KEY=VALUE")
+                        .num_args(1)
+                        .action(ArgAction::Append)
+                        .value_parser(env_var_parser),
                 ),
         )
         .subcommand(
@@ -704,6 +1058,20 @@ pub(crate) fn cli() -> Command {
                         .long("toolchain")
                         .num_args(1)
                         .value_parser(resolvable_toolchainame_parser),
+                )
+                .arg(
+                    Arg::new("all")
+                        .help("List the binary in every installed toolchain that provides it")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("toolchain"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .help("Look for a binary that the toolchain ships for this target, falling back to the host's and then `bin`")
+                        .long("target")
+                        .num_args(1)
+                        .conflicts_with("all"),
                 ),
         )
         .subcommand(
@@ -717,6 +1085,28 @@ pub(crate) fn cli() -> Command {
                         .help("Only print the path to the documentation")
                         .action(ArgAction::SetTrue),
                 )
+                .arg(
+                    Arg::new("open-in")
+                        .long("open-in")
+                        .value_name("browser")
+                        .help("The command to open the documentation with")
+                        .num_args(1)
+                        .conflicts_with("path"),
+                )
+                .arg(
+                    Arg::new("list")
+                        .long("list")
+                        .help("List the documentation pages that the toolchain has")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["path", "topic", "page"]),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print the list of pages as JSON")
+                        .action(ArgAction::SetTrue)
+                        .requires("list"),
+                )
                 .arg(
                     Arg::new("toolchain")
                         .help(OFFICIAL_TOOLCHAIN_ARG_HELP)
@@ -762,7 +1152,30 @@ pub(crate) fn cli() -> Command {
                 .about("Modify the rustup installation")
                 .subcommand_required(true)
                 .arg_required_else_help(true)
-                .subcommand(Command::new("update").about("Download and install updates to rustup"))
+                .subcommand(
+                    Command::new("update")
+                        .about("Download and install updates to rustup")
+                        .arg(
+                            Arg::new("check")
+                                .help("Only report whether an update is available, exiting with 100 if it is")
+                                .long("check")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("version")
+                                .help("Install this rustup release, such as 1.27.0, instead of the latest")
+                                .long("version")
+                                .num_args(1)
+                                .conflicts_with("check"),
+                        )
+                        .arg(
+                            Arg::new("allow-downgrade")
+                                .help("Allow the release given with --version to be older than this one")
+                                .long("allow-downgrade")
+                                .action(ArgAction::SetTrue)
+                                .requires("version"),
+                        ),
+                )
                 .subcommand(
                     Command::new("uninstall")
                         .about("Uninstall rustup.")
@@ -770,6 +1183,10 @@ pub(crate) fn cli() -> Command {
                 )
                 .subcommand(
                     Command::new("upgrade-data").about("Upgrade the internal data format."),
+                )
+                .subcommand(
+                    Command::new("check")
+                        .about("Check that the proxies, PATH and registry entries rustup installed are intact"),
                 ),
         )
         .subcommand(
@@ -780,16 +1197,29 @@ pub(crate) fn cli() -> Command {
                 .subcommand(
                     Command::new("default-host")
                         .about("The triple used to identify toolchains when not specified")
-                        .arg(Arg::new("host_triple").required(true)),
+                        .after_help(SET_DEFAULT_HOST_HELP)
+                        .arg(Arg::new("host_triple").required(true))
+                        .arg(
+                            Arg::new("force")
+                                .help("Set the host even if it is not a known host triple")
+                                .long("force")
+                                .action(ArgAction::SetTrue),
+                        ),
                 )
                 .subcommand(
                     Command::new("profile")
                         .about("The default components installed with a toolchain")
                         .arg(
                             Arg::new("profile-name")
-                                .required(true)
+                                .required_unless_present("list")
                                 .value_parser(PossibleValuesParser::new(Profile::names()))
                                 .default_value(Profile::default_name()),
+                        )
+                        .arg(
+                            Arg::new("list")
+                                .help("List the profiles, with their components if the active toolchain defines them")
+                                .long("list")
+                                .action(ArgAction::SetTrue),
                         ),
                 )
                 .subcommand(
@@ -801,6 +1231,97 @@ pub(crate) fn cli() -> Command {
                                 .value_parser(PossibleValuesParser::new(SelfUpdateMode::modes()))
                                 .default_value(SelfUpdateMode::default_mode()),
                         ),
+                )
+                .subcommand(
+                    Command::new("disable-self-update")
+                        .about("Whether to refuse every self-update, even an explicit one")
+                        .arg(
+                            Arg::new("disabled")
+                                .required(true)
+                                .value_parser(clap::value_parser!(bool)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-concurrency")
+                        .about("The number of component downloads to run at once")
+                        .arg(
+                            Arg::new("concurrency")
+                                .required(true)
+                                .value_parser(clap::value_parser!(u16).range(1..))
+                                .default_value("4"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-retries")
+                        .about("The number of times to retry a download that failed transiently")
+                        .arg(
+                            Arg::new("retries")
+                                .required(true)
+                                .value_parser(clap::value_parser!(u16))
+                                .default_value("3"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-retry-backoff-ms")
+                        .about(
+                            "The delay before retrying a download, doubled for each further retry",
+                        )
+                        .arg(
+                            Arg::new("milliseconds")
+                                .required(true)
+                                .value_parser(clap::value_parser!(u64))
+                                .default_value("500"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-verify-signatures")
+                        .about("Whether channel manifests must be signed by a trusted key")
+                        .arg(
+                            Arg::new("enabled")
+                                .required(true)
+                                .value_parser(clap::value_parser!(bool)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-max-rate")
+                        .about("The most bytes per second to download at, such as 500K or 2M")
+                        .arg(
+                            Arg::new("rate")
+                                .help("The rate, or 0 for no limit")
+                                .required(true)
+                                .value_parser(utils::parse_download_rate),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-cache-size")
+                        .about(
+                            "The most space that the download cache may take up, such as 4G",
+                        )
+                        .arg(
+                            Arg::new("size")
+                                .help("The size, or 0 for no limit")
+                                .required(true)
+                                .value_parser(utils::parse_download_cache_size),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-ca-bundle")
+                        .about("The file of PEM certificates that downloads trust instead of the system's")
+                        .arg(
+                            Arg::new("path")
+                                .help("The file, or 'none' to go back to CURL_CA_BUNDLE or SSL_CERT_FILE")
+                                .required(true)
+                                .value_parser(clap::value_parser!(PathBuf)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("download-stream-extract")
+                        .about("Whether components are unpacked as they download, without caching them")
+                        .arg(
+                            Arg::new("enabled")
+                                .required(true)
+                                .value_parser(clap::value_parser!(bool)),
+                        ),
                 ),
         );
 
@@ -814,6 +1335,12 @@ pub(crate) fn cli() -> Command {
                 Arg::new("command")
                     .value_parser(EnumValueParser::<CompletionCommand>::new())
                     .default_missing_value("rustup"),
+            )
+            .arg(
+                Arg::new("dynamic")
+                    .help("Complete toolchain, target and component names by asking rustup at completion time")
+                    .long("dynamic")
+                    .action(ArgAction::SetTrue),
             ),
     )
 }
@@ -826,6 +1353,13 @@ fn verbose_arg(help: &'static str) -> Arg {
         .action(ArgAction::SetTrue)
 }
 
+fn prune_overrides_arg() -> Arg {
+    Arg::new("prune-overrides")
+        .help("Also remove the directory overrides that point at the toolchain")
+        .long("prune-overrides")
+        .action(ArgAction::SetTrue)
+}
+
 fn maybe_upgrade_data(cfg: &Cfg, m: &ArgMatches) -> Result<bool> {
     match m.subcommand() {
         Some(("self", c)) => match c.subcommand() {
@@ -839,11 +1373,59 @@ fn maybe_upgrade_data(cfg: &Cfg, m: &ArgMatches) -> Result<bool> {
     }
 }
 
+/// The toolchain given to `rustup default`, where `-` stands for one whose
+/// name is read from stdin.
+#[derive(Clone)]
+enum DefaultToolchain {
+    Name(MaybeResolvableToolchainName),
+    Stdin,
+}
+
+fn default_toolchain_parser(value: &str) -> Result<DefaultToolchain, InvalidName> {
+    match value {
+        "-" => Ok(DefaultToolchain::Stdin),
+        _ => maybe_resolvable_toolchainame_parser(value).map(DefaultToolchain::Name),
+    }
+}
+
+fn read_toolchain_from_stdin() -> Result<MaybeResolvableToolchainName> {
+    let line = common::read_stdin_line().context("unable to read a toolchain name from stdin")?;
+    let name = line.trim();
+    if name.is_empty() {
+        return Err(anyhow!("no toolchain name was given on stdin"));
+    }
+    Ok(maybe_resolvable_toolchainame_parser(name)?)
+}
+
 fn default_(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     common::warn_if_host_is_emulated();
 
-    if let Some(toolchain) = m.get_one::<MaybeResolvableToolchainName>("toolchain") {
-        match toolchain.to_owned() {
+    let toolchain = if m.get_flag("unset") {
+        Some(MaybeResolvableToolchainName::None)
+    } else {
+        match m.get_one::<DefaultToolchain>("toolchain") {
+            Some(DefaultToolchain::Name(name)) => Some(name.clone()),
+            Some(DefaultToolchain::Stdin) => Some(read_toolchain_from_stdin()?),
+            None => None,
+        }
+    };
+    if let Some(path) = m.get_one::<String>("path") {
+        let toolchain_name = match toolchain {
+            Some(MaybeResolvableToolchainName::Some(name)) => {
+                name.resolve(&cfg.get_default_host_triple()?)?
+            }
+            _ => {
+                return Err(anyhow!(
+                    "a directory cannot be given no toolchain\n\
+                     help: use `rustup override unset --path {path}` to remove its override"
+                ));
+            }
+        };
+        return set_override(cfg, &toolchain_name, Path::new(path), false);
+    }
+
+    if let Some(toolchain) = toolchain {
+        match toolchain {
             MaybeResolvableToolchainName::None => {
                 cfg.set_default(None)?;
             }
@@ -877,14 +1459,47 @@ fn default_(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     Ok(utils::ExitCode(0))
 }
 
-fn check_updates(cfg: &Cfg) -> Result<utils::ExitCode> {
+/// The version of the layout of `rustup check --json`. It goes up when a
+/// field is removed or changes meaning, but not when one is added.
+const CHECK_JSON_SCHEMA: u32 = 1;
+
+#[derive(Serialize)]
+struct CheckJson {
+    schema: u32,
+    toolchains: Vec<CheckUpdateJson>,
+    /// `None` if the latest rustup could not be found, as said by `error`
+    rustup: Option<CheckUpdateJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The versions of a toolchain or of rustup, either of which is `None` if
+/// it could not be identified.
+#[derive(Serialize)]
+struct CheckUpdateJson {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    current_version: Option<String>,
+    latest_version: Option<String>,
+    update_available: bool,
+    /// For a toolchain, whether `latest_version` comes from a manifest
+    /// downloaded earlier, because the current one could not be fetched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stale: Option<bool>,
+}
+
+fn check_updates(cfg: &Cfg, json: bool) -> Result<utils::ExitCode> {
+    if json {
+        return check_updates_json(cfg);
+    }
+
     let mut t = process().stdout().terminal();
     let channels = cfg.list_channels()?;
 
     for channel in channels {
         let (name, distributable) = channel;
         let current_version = distributable.show_version()?;
-        let dist_version = distributable.show_dist_version()?;
+        let (dist_version, stale) = distributable.show_dist_version()?;
         let _ = t.attr(terminalsource::Attr::Bold);
         write!(t.lock(), "{name} - ")?;
         match (current_version, dist_version) {
@@ -898,6 +1513,12 @@ fn check_updates(cfg: &Cfg) -> Result<utils::ExitCode> {
                 let _ = t.reset();
                 writeln!(t.lock(), " : {cv}")?;
             }
+            (Some(cv), Some(dv)) if !is_newer_toolchain_version(&cv, &dv) => {
+                let _ = t.fg(terminalsource::Color::Green);
+                write!(t.lock(), "Up to date")?;
+                let _ = t.reset();
+                writeln!(t.lock(), " : {cv}")?;
+            }
             (Some(cv), Some(dv)) => {
                 let _ = t.fg(terminalsource::Color::Yellow);
                 write!(t.lock(), "Update available")?;
@@ -911,33 +1532,135 @@ fn check_updates(cfg: &Cfg) -> Result<utils::ExitCode> {
                 writeln!(t.lock(), " : (Unknown version) -> {dv}")?;
             }
         }
+        if stale {
+            writeln!(
+                t.lock(),
+                "  (as of the manifest downloaded earlier, as the latest could not be fetched)"
+            )?;
+        }
     }
 
-    check_rustup_update()?;
+    check_rustup_update(cfg)?;
+
+    Ok(utils::ExitCode(0))
+}
+
+/// Whether `latest` is a later toolchain version than `current`, both as
+/// given by the `rust` package, such as `1.78.0 (9b00956e5 2024-04-29)`.
+/// Nightlies share a version number, so their dates decide between them.
+/// Versions that can't be told apart count as later, as their manifests
+/// differ.
+fn is_newer_toolchain_version(current: &str, latest: &str) -> bool {
+    fn parse(version: &str) -> Option<(semver::Version, Option<&str>)> {
+        let (number, rest) = version.split_once(' ').unwrap_or((version, ""));
+        let date = rest
+            .trim_end_matches(')')
+            .rsplit(' ')
+            .next()
+            .filter(|d| d.len() == 10 && d.chars().all(|c| c.is_ascii_digit() || c == '-'));
+        Some((semver::Version::parse(number).ok()?, date))
+    }
+
+    match (parse(current), parse(latest)) {
+        (Some((cv, _)), Some((lv, _))) if cv != lv => lv > cv,
+        (Some((_, Some(cd))), Some((_, Some(ld)))) => ld > cd,
+        _ => true,
+    }
+}
+
+fn check_updates_json(cfg: &Cfg) -> Result<utils::ExitCode> {
+    let mut toolchains = Vec::new();
+    for (name, distributable) in cfg.list_channels()? {
+        let current_version = distributable.show_version()?;
+        let (dist_version, stale) = distributable.show_dist_version()?;
+        // No newer manifest means that the installed version is the latest
+        let (latest_version, update_available) = match (&current_version, dist_version) {
+            (Some(cv), Some(dv)) => {
+                let newer = is_newer_toolchain_version(cv, &dv);
+                (Some(dv), newer)
+            }
+            (None, Some(dv)) => (Some(dv), true),
+            (_, None) => (current_version.clone(), false),
+        };
+        toolchains.push(CheckUpdateJson {
+            name: Some(name.to_string()),
+            current_version,
+            latest_version,
+            update_available,
+            stale: Some(stale),
+        });
+    }
 
+    // The toolchains are still worth reporting if rustup's server can't be
+    // reached
+    let current_version = env!("CARGO_PKG_VERSION");
+    let latest = self_update::get_available_rustup_version(cfg.download_ca_bundle.as_deref())
+        .and_then(|latest| {
+            Ok((
+                self_update::is_newer_version(current_version, &latest)?,
+                latest,
+            ))
+        });
+    let (rustup, error) = match latest {
+        Ok((update_available, latest_version)) => {
+            let rustup = CheckUpdateJson {
+                name: None,
+                update_available,
+                current_version: Some(current_version.to_owned()),
+                latest_version: Some(latest_version),
+                stale: None,
+            };
+            (Some(rustup), None)
+        }
+        Err(e) => (None, Some(format!("{e:#}"))),
+    };
+    common::write_json(&CheckJson {
+        schema: CHECK_JSON_SCHEMA,
+        toolchains,
+        rustup,
+        error,
+    })?;
     Ok(utils::ExitCode(0))
 }
 
 fn update(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     common::warn_if_host_is_emulated();
     let self_update_mode = cfg.get_self_update_mode()?;
-    // Priority: no-self-update feature > self_update_mode > no-self-update args.
+    let self_update_disabled = cfg.self_update_disabled()?;
+    // Priority: no-self-update feature > policy > offline mode > self_update_mode > no-self-update args.
     // Update only if rustup does **not** have the no-self-update feature,
+    // is **not** disabled by policy, is **not** offline,
     // and auto-self-update is configured to **enable**
     // and has **no** no-self-update parameter.
     let self_update = !self_update::NEVER_SELF_UPDATE
+        && !self_update_disabled
+        && !cfg.offline
         && self_update_mode == SelfUpdateMode::Enable
         && !m.get_flag("no-self-update");
     let forced = m.get_flag("force-non-host");
+    let keep_going = matches!(m.try_get_one::<bool>("keep-going"), Ok(Some(true)));
+    let dry_run = matches!(m.try_get_one::<bool>("dry-run"), Ok(Some(true)));
+    let download_only = matches!(m.try_get_one::<bool>("download-only"), Ok(Some(true)));
     if let Ok(Some(p)) = m.try_get_one::<String>("profile") {
         let p = Profile::from_str(p)?;
         cfg.set_profile_override(p);
     }
+    if let Ok(Some(dir)) = m.try_get_one::<PathBuf>("component-tarballs") {
+        cfg.local_dist_dir = Some(dir.to_owned());
+    }
     let cfg = &cfg;
     if cfg.get_profile()? == Profile::Complete {
         warn!("{}", common::WARN_COMPLETE_PROFILE);
     }
+    let component_set = match m.try_get_one::<String>("component-set") {
+        Ok(Some(name)) => cfg.get_component_set(name)?,
+        _ => Vec::new(),
+    };
+    let mut exit_code = utils::ExitCode(0);
     if let Ok(Some(names)) = m.try_get_many::<PartialToolchainDesc>("toolchain") {
+        // With `--keep-going`, the results are only shown once all the
+        // toolchains have been tried
+        let mut results = Vec::new();
         for name in names.map(|n| n.to_owned()) {
             // This needs another pass to fix it all up
             if name.has_triple() {
@@ -955,11 +1678,15 @@ fn update(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
             }
             let desc = name.resolve(&cfg.get_default_host_triple()?)?;
 
-            let components: Vec<_> = m
+            let mut components: Vec<_> = m
                 .try_get_many::<String>("components")
                 .ok()
                 .flatten()
                 .map_or_else(Vec::new, |v| v.map(|s| &**s).collect());
+            // Components add to the profile, unless only they were asked for
+            let replaces_profile =
+                matches!(m.try_get_one::<bool>("only-components"), Ok(Some(true)));
+            components.extend(component_set.iter().map(|s| &**s));
             let targets: Vec<_> = m
                 .try_get_many::<String>("targets")
                 .ok()
@@ -970,12 +1697,49 @@ fn update(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
             let allow_downgrade =
                 matches!(m.try_get_one::<bool>("allow-downgrade"), Ok(Some(true)));
             let profile = cfg.get_profile()?;
-            let status = match crate::toolchain::distributable::DistributableToolchain::new(
+            let install_profile = match replaces_profile {
+                true => None,
+                false => Some(profile),
+            };
+            if dry_run {
+                let plan = DistributableToolchain::plan(
+                    cfg,
+                    &desc,
+                    &components,
+                    &targets,
+                    install_profile,
+                    force,
+                )?;
+                show_install_plan(&desc, &plan)?;
+                continue;
+            }
+            if download_only {
+                let downloaded = DistributableToolchain::download_only(
+                    cfg,
+                    &desc,
+                    &components,
+                    &targets,
+                    install_profile,
+                    force,
+                )?;
+                match downloaded {
+                    0 => info!("toolchain '{desc}' has nothing to download"),
+                    n => info!(
+                        "downloaded {n} component{} of '{desc}'; install it with `rustup --offline toolchain install {desc}`",
+                        if n == 1 { "" } else { "s" }
+                    ),
+                }
+                continue;
+            }
+            let result = match crate::toolchain::distributable::DistributableToolchain::new(
                 cfg,
                 desc.clone(),
             ) {
                 Ok(mut d) => {
-                    d.update_extra(&components, &targets, profile, force, allow_downgrade)?
+                    if cfg.skip_frozen_toolchain(&desc, force)? {
+                        continue;
+                    }
+                    d.update_extra(&components, &targets, profile, force, allow_downgrade)
                 }
                 Err(RustupError::ToolchainNotInstalled(_)) => {
                     crate::toolchain::distributable::DistributableToolchain::install(
@@ -983,36 +1747,65 @@ fn update(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
                         &desc,
                         &components,
                         &targets,
-                        profile,
+                        install_profile,
                         force,
-                    )?
-                    .0
+                    )
+                    .map(|(status, _)| status)
                 }
-                Err(e) => Err(e)?,
+                Err(e) => Err(e.into()),
+            };
+            let status = match result {
+                Ok(status) => status,
+                Err(e) if keep_going => {
+                    err!("{e:#}");
+                    exit_code = utils::ExitCode(1);
+                    results.push((PackageUpdate::Toolchain(desc), Err(e)));
+                    continue;
+                }
+                Err(e) => return Err(e),
             };
 
-            writeln!(process().stdout().lock())?;
-            common::show_channel_update(
-                cfg,
-                PackageUpdate::Toolchain(desc.clone()),
-                Ok(status.clone()),
-            )?;
+            if keep_going {
+                results.push((PackageUpdate::Toolchain(desc.clone()), Ok(status.clone())));
+            } else {
+                writeln!(process().stdout().lock())?;
+                common::show_channel_update(
+                    cfg,
+                    PackageUpdate::Toolchain(desc.clone()),
+                    Ok(status.clone()),
+                )?;
+            }
             if cfg.get_default()?.is_none() && matches!(status, UpdateStatus::Installed) {
                 cfg.set_default(Some(&desc.into()))?;
             }
         }
-        if self_update {
-            common::self_update(|| Ok(utils::ExitCode(0)))?;
+        if !results.is_empty() {
+            writeln!(process().stdout().lock())?;
+            common::show_channel_updates(cfg, results)?;
+        }
+        if self_update && !dry_run && !download_only {
+            common::self_update(cfg, || Ok(utils::ExitCode(0)))?;
         }
     } else {
-        common::update_all_channels(cfg, self_update, m.get_flag("force"))?;
+        let allow_downgrade = matches!(m.try_get_one::<bool>("allow-downgrade"), Ok(Some(true)));
+        exit_code = common::update_all_channels(
+            cfg,
+            self_update,
+            m.get_flag("force"),
+            allow_downgrade,
+            keep_going,
+        )?;
         info!("cleaning up downloads & tmp directories");
         utils::delete_dir_contents_following_links(&cfg.download_dir);
         cfg.tmp_cx.clean();
     }
 
-    if !self_update::NEVER_SELF_UPDATE && self_update_mode == SelfUpdateMode::CheckOnly {
-        check_rustup_update()?;
+    if !self_update::NEVER_SELF_UPDATE
+        && !self_update_disabled
+        && !cfg.offline
+        && self_update_mode == SelfUpdateMode::CheckOnly
+    {
+        check_rustup_update(cfg)?;
     }
 
     if self_update::NEVER_SELF_UPDATE {
@@ -1020,41 +1813,114 @@ fn update(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         info!("any updates to rustup will need to be fetched with your system package manager")
     }
 
-    Ok(utils::ExitCode(0))
+    Ok(exit_code)
 }
 
-fn run(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+fn run(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    if let Some(p) = m.get_one::<String>("profile") {
+        cfg.set_profile_override(Profile::from_str(p)?);
+    }
     let toolchain = m
         .get_one::<ResolvableLocalToolchainName>("toolchain")
         .unwrap();
     let args = m.get_many::<String>("command").unwrap();
     let args: Vec<_> = args.collect();
     let toolchain = toolchain.resolve(&cfg.get_default_host_triple()?)?;
-    let cmd = cfg.create_command_for_toolchain(&toolchain, m.get_flag("install"), args[0])?;
+    let mut cmd = cfg.create_command_for_toolchain(&toolchain, m.get_flag("install"), args[0])?;
+    for (key, value) in m.get_many::<(String, String)>("env").into_iter().flatten() {
+        cmd.env(key, value);
+    }
 
     let code = command::run_command_for_dir(cmd, args[0], &args[1..])?;
     Ok(code)
 }
 
+/// Parses a `--env` argument. As with the `[env]` of a toolchain file, the
+/// variables that rustup sets to run the toolchain can't be overridden.
+fn env_var_parser(value: &str) -> Result<(String, String)> {
+    match value.split_once('=') {
+        Some((key, _)) if env_var::RUSTUP_MANAGED_VARS.contains(&key) => Err(anyhow!(
+            "environment variable '{key}' is set by rustup and cannot be overridden with `--env`"
+        )),
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(anyhow!(
+            "invalid environment variable '{value}': expected KEY=VALUE"
+        )),
+    }
+}
+
 fn which(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let binary = m.get_one::<String>("command").unwrap();
-    let binary_path = if let Some(toolchain) = m.get_one::<ResolvableToolchainName>("toolchain") {
+    if m.get_flag("all") {
+        return which_all(cfg, binary);
+    }
+    let toolchain = if let Some(toolchain) = m.get_one::<ResolvableToolchainName>("toolchain") {
         let desc = toolchain.resolve(&cfg.get_default_host_triple()?)?;
-        Toolchain::new(cfg, desc.into())?.binary_file(binary)
+        Toolchain::new(cfg, desc.into())?
     } else {
-        cfg.which_binary(&utils::current_dir()?, binary)?
+        cfg.find_or_install_override_toolchain_or_default(&utils::current_dir()?)?
+            .0
     };
 
+    let target = m.get_one::<String>("target").map(|t| TargetTriple::new(t));
+    let binary_path = toolchain.find_binary_file(target.as_ref(), binary)?;
+
     utils::assert_is_file(&binary_path)?;
 
     writeln!(process().stdout().lock(), "{}", binary_path.display())?;
     Ok(utils::ExitCode(0))
 }
 
+/// Print `binary` in each installed toolchain that has it, marking the one
+/// that would run in the current directory.
+fn which_all(cfg: &Cfg, binary: &str) -> Result<utils::ExitCode> {
+    let active = cfg
+        .active_toolchain(&utils::current_dir()?)
+        .ok()
+        .map(|(toolchain, _reason)| toolchain);
+    let mut toolchains: Vec<LocalToolchainName> =
+        cfg.list_toolchains()?.into_iter().map(Into::into).collect();
+    // A path toolchain can be active without being installed under a name
+    if let Some(active) = active.as_ref().filter(|t| !toolchains.contains(t)) {
+        toolchains.insert(0, active.clone());
+    }
+
+    let mut found = false;
+    for name in toolchains {
+        let Ok(toolchain) = Toolchain::new(cfg, name.clone()) else {
+            continue;
+        };
+        let binary_path = toolchain.binary_file(binary);
+        if !utils::is_file(&binary_path) {
+            continue;
+        }
+        found = true;
+        let if_active = if active.as_ref() == Some(&name) {
+            " (active)"
+        } else {
+            ""
+        };
+        writeln!(
+            process().stdout().lock(),
+            "{} ({name}){if_active}",
+            binary_path.display()
+        )?;
+    }
+
+    if !found {
+        return Err(RustupError::BinaryNotInAnyToolchain(binary.to_owned()).into());
+    }
+    Ok(utils::ExitCode(0))
+}
+
 #[cfg_attr(feature = "otel", tracing::instrument(skip_all))]
 fn show(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     common::warn_if_host_is_emulated();
 
+    if m.get_flag("json") {
+        return show_json(cfg);
+    }
+
     let verbose = m.get_flag("verbose");
 
     // Print host triple
@@ -1179,16 +2045,10 @@ fn show(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         }
 
         match active_toolchain {
-            Ok(atc) => match atc {
-                (ref toolchain, Some(ref reason)) => {
-                    writeln!(t.lock(), "{} ({})", toolchain.name(), reason)?;
-                    writeln!(t.lock(), "{}", toolchain.rustc_version())?;
-                }
-                (ref toolchain, None) => {
-                    writeln!(t.lock(), "{} (default)", toolchain.name())?;
-                    writeln!(t.lock(), "{}", toolchain.rustc_version())?;
-                }
-            },
+            Ok((ref toolchain, ref reason)) => {
+                writeln!(t.lock(), "{} ({})", toolchain.name(), reason)?;
+                writeln!(t.lock(), "{}", toolchain.rustc_version())?;
+            }
             Err(err) => {
                 let root_cause = err.root_cause();
                 if let Some(RustupError::ToolchainNotSelected) =
@@ -1223,6 +2083,97 @@ fn show(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     Ok(utils::ExitCode(0))
 }
 
+/// The version of the layout of `rustup show --json`. It goes up when a field
+/// is removed or changes meaning, but not when one is added.
+const SHOW_JSON_SCHEMA: u32 = 1;
+
+#[derive(Serialize)]
+struct ShowJson {
+    schema: u32,
+    default_host: String,
+    rustup_home: PathBuf,
+    cargo_home: PathBuf,
+    active_toolchain: Option<ActiveToolchainJson>,
+    installed_toolchains: Vec<InstalledToolchainJson>,
+}
+
+#[derive(Serialize)]
+struct ActiveToolchainJson {
+    name: String,
+    reason: String,
+    /// Whether the toolchain is installed; `show` never installs it, so none
+    /// of its targets are listed if not
+    installed: bool,
+    targets: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct InstalledToolchainJson {
+    name: String,
+    default: bool,
+    components: Vec<String>,
+}
+
+fn show_json(cfg: &Cfg) -> Result<utils::ExitCode> {
+    let cwd = utils::current_dir()?;
+    let active_toolchain = match cfg.active_toolchain(&cwd) {
+        Ok((name, reason)) => {
+            let toolchain = match Toolchain::exists(cfg, &name)? {
+                true => Some(Toolchain::new(cfg, name.clone())?),
+                false => None,
+            };
+            Some(ActiveToolchainJson {
+                name: name.to_string(),
+                reason: reason.to_string(),
+                installed: toolchain.is_some(),
+                targets: toolchain
+                    .map(|toolchain| installed_components(&toolchain))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|c| c.name == "rust-std")
+                    .filter_map(|c| c.target)
+                    .collect(),
+            })
+        }
+        Err(e) => match e.root_cause().downcast_ref::<RustupError>() {
+            Some(RustupError::ToolchainNotSelected) => None,
+            _ => return Err(e),
+        },
+    };
+
+    let default = cfg.get_default()?;
+    let mut installed_toolchains = Vec::new();
+    for name in cfg.list_toolchains()? {
+        let toolchain = Toolchain::new(cfg, (&name).into())?;
+        installed_toolchains.push(InstalledToolchainJson {
+            default: default.as_ref() == Some(&name),
+            components: installed_components(&toolchain)
+                .iter()
+                .map(|c| c.full_name())
+                .collect(),
+            name: name.to_string(),
+        });
+    }
+
+    common::write_json(&ShowJson {
+        schema: SHOW_JSON_SCHEMA,
+        default_host: cfg.get_default_host_triple()?.to_string(),
+        rustup_home: cfg.rustup_dir.clone(),
+        cargo_home: utils::cargo_home()?,
+        active_toolchain,
+        installed_toolchains,
+    })?;
+    Ok(utils::ExitCode(0))
+}
+
+/// The components installed in `toolchain`, which are none for a custom
+/// toolchain, or one whose manifest can't be read.
+fn installed_components(toolchain: &Toolchain<'_>) -> Vec<crate::config::ComponentStatus> {
+    let mut components = toolchain.component_statuses().unwrap_or_default();
+    components.retain(|c| c.installed);
+    components
+}
+
 #[cfg_attr(feature = "otel", tracing::instrument(skip_all))]
 fn show_active_toolchain(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let verbose = m.get_flag("verbose");
@@ -1238,16 +2189,12 @@ fn show_active_toolchain(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
             }
         }
         Ok((toolchain, reason)) => {
-            if let Some(reason) = reason {
-                writeln!(
-                    process().stdout().lock(),
-                    "{} ({})",
-                    toolchain.name(),
-                    reason
-                )?;
-            } else {
-                writeln!(process().stdout().lock(), "{} (default)", toolchain.name())?;
-            }
+            writeln!(
+                process().stdout().lock(),
+                "{} ({})",
+                toolchain.name(),
+                reason
+            )?;
             if verbose {
                 writeln!(process().stdout().lock(), "{}", toolchain.rustc_version())?;
             }
@@ -1263,14 +2210,19 @@ fn show_rustup_home(cfg: &Cfg) -> Result<utils::ExitCode> {
 }
 
 fn target_list(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    let json = m.get_flag("json");
+    if m.get_flag("all-toolchains") {
+        return common::list_all_installed_targets(cfg, json);
+    }
+
     let toolchain = explicit_desc_or_dir_toolchain(cfg, m)?;
     // downcasting required because the toolchain files can name any toolchain
     let distributable = (&toolchain).try_into()?;
 
     if m.get_flag("installed") {
-        common::list_installed_targets(distributable)
+        common::list_installed_targets(distributable, json)
     } else {
-        common::list_targets(distributable)
+        common::list_targets(distributable, json)
     }
 }
 
@@ -1287,12 +2239,44 @@ fn target_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let manifest = distributable.get_manifest()?;
     let components = manifest.query_components(distributable.desc(), &config)?;
 
+    if m.get_flag("all") {
+        return target_add_all(&distributable, &components, m.get_flag("yes"));
+    }
+
     let mut targets: Vec<_> = m
         .get_many::<String>("target")
-        .unwrap()
+        .unwrap_or_default()
         .map(ToOwned::to_owned)
         .collect();
 
+    if let Some(source) = m.get_one::<PartialToolchainDesc>("from-toolchain") {
+        let source = explicit_or_dir_toolchain2(cfg, Some(source.into()))?;
+        let source = DistributableToolchain::try_from(&source)?;
+        let source_config = source
+            .get_manifestation()?
+            .read_config()?
+            .unwrap_or_default();
+        let source_targets = source_config
+            .components
+            .iter()
+            .filter(|c| c.short_name_in_manifest() == "rust-std")
+            .filter_map(|c| c.target.as_ref());
+        for target in source_targets {
+            let status = components.iter().find(|c| {
+                c.component.short_name_in_manifest() == "rust-std"
+                    && c.component.target.as_ref() == Some(target)
+            });
+            match status {
+                Some(status) if status.installed => {}
+                Some(status) if status.available => targets.push(target.to_string()),
+                _ => warn!(
+                    "skipping target '{target}', which is not available for '{}'",
+                    distributable.desc()
+                ),
+            }
+        }
+    }
+
     if targets.contains(&"all".to_string()) {
         if targets.len() != 1 {
             return Err(anyhow!(
@@ -1302,7 +2286,7 @@ fn target_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         }
 
         targets.clear();
-        for component in components {
+        for component in &components {
             if component.component.short_name_in_manifest() == "rust-std"
                 && component.available
                 && !component.installed
@@ -1317,21 +2301,119 @@ fn target_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         }
     }
 
+    let host = &distributable.desc().target;
     for target in targets {
-        let new_component = Component::new(
-            "rust-std".to_string(),
-            Some(TargetTriple::new(&target)),
-            false,
+        let target = if host.is_named_by(&target) {
+            (cfg.notify_handler)(dist::Notification::TargetIsHost(&target, host).into());
+            let host_std = components.iter().any(|c| {
+                c.installed
+                    && c.component.short_name_in_manifest() == "rust-std"
+                    && c.component.target.as_ref() == Some(host)
+            });
+            if host_std {
+                continue;
+            }
+            host.clone()
+        } else {
+            TargetTriple::new(&target)
+        };
+        let new_component = Component::new("rust-std".to_string(), Some(target), false);
+        distributable.add_component(new_component, false)?;
+    }
+
+    Ok(utils::ExitCode(0))
+}
+
+/// Installs every target that `components` has available, after asking
+/// unless `yes` is set, since that is a lot to download. A target that fails
+/// is reported without stopping the others.
+fn target_add_all(
+    distributable: &DistributableToolchain<'_>,
+    components: &[ComponentStatus],
+    yes: bool,
+) -> Result<utils::ExitCode> {
+    let targets: Vec<_> = components
+        .iter()
+        .filter(|c| {
+            c.component.short_name_in_manifest() == "rust-std" && c.available && !c.installed
+        })
+        .filter_map(|c| c.component.target.clone())
+        .collect();
+    if targets.is_empty() {
+        info!(
+            "every available target is installed for '{}' already",
+            distributable.desc()
         );
-        distributable.add_component(new_component)?;
+        return Ok(utils::ExitCode(0));
+    }
+
+    if !yes {
+        let downloads = distributable.plan_components(
+            targets
+                .iter()
+                .map(|t| Component::new("rust-std".to_string(), Some(t.clone()), false))
+                .collect(),
+        )?;
+        let total: u64 = downloads.iter().filter_map(|(_, len)| *len).sum();
+        let total = Size::new(total as usize, Unit::B, UnitMode::Norm).to_string();
+        let at_least = match downloads.iter().any(|(_, len)| len.is_none()) {
+            true => "at least ",
+            false => "",
+        };
+        writeln!(
+            process().stdout().lock(),
+            "{} targets will be installed for '{}', downloading {at_least}{}",
+            targets.len(),
+            distributable.desc(),
+            total.trim_start(),
+        )?;
+        if !common::confirm("\nContinue? (y/N)", false)? {
+            info!("aborting target add");
+            return Ok(utils::ExitCode(0));
+        }
     }
 
+    let mut failed = Vec::new();
+    for target in targets {
+        let component = Component::new("rust-std".to_string(), Some(target.clone()), false);
+        if let Err(e) = distributable.add_component(component, false) {
+            err!("{e:#}");
+            failed.push(target.to_string());
+        }
+    }
+    if !failed.is_empty() {
+        err!(
+            "these targets could not be installed: {}",
+            failed.join(", ")
+        );
+        return Ok(utils::ExitCode(1));
+    }
     Ok(utils::ExitCode(0))
 }
 
 fn target_remove(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let toolchain = explicit_desc_or_dir_toolchain(cfg, m)?;
     let distributable = DistributableToolchain::try_from(&toolchain)?;
+    // Checked for every target first, so that none are removed if one
+    // of them is still in use
+    let build_targets = cargo_config::build_targets(&utils::current_dir()?);
+    let in_use = m
+        .get_many::<String>("target")
+        .unwrap()
+        .filter_map(|target| build_targets.iter().find(|t| t.target == *target));
+    for build_target in in_use {
+        if !m.get_flag("force") {
+            return Err(RustupError::TargetInUse {
+                target: build_target.target.clone(),
+                configured_by: build_target.source.clone(),
+            }
+            .into());
+        }
+        warn!(
+            "cargo is configured to build for '{}' here by {}, which will fail without it",
+            build_target.target, build_target.source
+        );
+    }
 
     for target in m.get_many::<String>("target").unwrap() {
         let target = TargetTriple::new(target);
@@ -1354,21 +2436,27 @@ fn target_remove(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
             warn!("after removing the last target, no build targets will be available");
         }
         let new_component = Component::new("rust-std".to_string(), Some(target), false);
-        distributable.remove_component(new_component)?;
+        distributable.remove_component(new_component, false)?;
     }
 
     Ok(utils::ExitCode(0))
 }
 
 fn component_list(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    // The toolchain files can name any toolchain, and those that aren't
+    // from a dist server have their components found by their files
     let toolchain = explicit_desc_or_dir_toolchain(cfg, m)?;
-    // downcasting required because the toolchain files can name any toolchain
-    let distributable = (&toolchain).try_into()?;
 
-    if m.get_flag("installed") {
-        common::list_installed_components(distributable)?;
-    } else {
-        common::list_components(distributable)?;
+    let target = m.get_one::<String>("target").map(String::as_str);
+
+    // Asking for both installed and available components lists either kind
+    match (m.get_flag("installed"), m.get_flag("available")) {
+        (false, false) => {
+            common::list_components(&toolchain, target)?;
+        }
+        (installed, available) => {
+            common::list_filtered_components(&toolchain, target, installed, available)?
+        }
     }
     Ok(utils::ExitCode(0))
 }
@@ -1379,9 +2467,13 @@ fn component_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let target = get_target(m, &distributable);
 
     for component in m.get_many::<String>("component").unwrap() {
+        if let Some(path) = m.get_one::<PathBuf>("path") {
+            distributable.add_local_component(component, path)?;
+            continue;
+        }
         let new_component = Component::new_with_target(component, false)
             .unwrap_or_else(|| Component::new(component.to_string(), target.clone(), true));
-        distributable.add_component(new_component)?;
+        distributable.add_component(new_component, m.get_flag("reinstall"))?;
     }
 
     Ok(utils::ExitCode(0))
@@ -1400,9 +2492,12 @@ fn component_remove(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let target = get_target(m, &distributable);
 
     for component in m.get_many::<String>("component").unwrap() {
+        if distributable.remove_local_component(component)? {
+            continue;
+        }
         let new_component = Component::new_with_target(component, false)
             .unwrap_or_else(|| Component::new(component.to_string(), target.clone(), true));
-        distributable.remove_component(new_component)?;
+        distributable.remove_component(new_component, m.get_flag("purge-unused-deps"))?;
     }
 
     Ok(utils::ExitCode(0))
@@ -1436,12 +2531,18 @@ fn explicit_or_dir_toolchain2(
 }
 
 fn toolchain_list(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
-    common::list_toolchains(cfg, m.get_flag("verbose"))
+    common::list_toolchains(cfg, m.get_flag("verbose"), m.get_flag("usage"))
 }
 
 fn toolchain_link(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let toolchain = m.get_one::<CustomToolchainName>("toolchain").unwrap();
     let path = m.get_one::<String>("path").unwrap();
+    let dest = cfg.toolchain_path(&toolchain.into());
+    // `symlink_metadata` rather than `exists`, which is false for a link to
+    // a directory that has since gone
+    if fs::symlink_metadata(&dest).is_ok() && !utils::is_link(&dest) {
+        return Err(RustupError::ToolchainNotALink(toolchain.clone()).into());
+    }
     cfg.ensure_toolchains_dir()?;
     crate::toolchain::custom::CustomToolchain::install_from_dir(
         cfg,
@@ -1456,10 +2557,169 @@ fn toolchain_remove(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     for toolchain_name in m.get_many::<ResolvableToolchainName>("toolchain").unwrap() {
         let toolchain_name = toolchain_name.resolve(&cfg.get_default_host_triple()?)?;
         Toolchain::ensure_removed(cfg, (&toolchain_name).into())?;
+
+        // Overrides left pointing at the toolchain would otherwise only show
+        // up as "not installed" errors from the proxies
+        let paths = cfg.overrides_of(&toolchain_name)?;
+        if m.get_flag("prune-overrides") {
+            for path in paths {
+                cfg.settings_file.with_mut(|s| {
+                    Ok(s.remove_override(Path::new(&path), cfg.notify_handler.as_ref()))
+                })?;
+                info!("override toolchain for '{path}' removed");
+            }
+        } else if !paths.is_empty() {
+            for path in &paths {
+                warn!("the override for '{path}' is still '{toolchain_name}', which is no longer installed");
+            }
+            info!("pass `--prune-overrides` to remove such overrides along with the toolchain");
+        }
+    }
+    Ok(utils::ExitCode(0))
+}
+
+/// Prints what installing `desc` would download, for `--dry-run`.
+fn show_install_plan(desc: &ToolchainDesc, plan: &InstallPlan) -> Result<()> {
+    let t = process().stdout();
+    if plan.downloads.is_empty() {
+        writeln!(t.lock(), "toolchain '{desc}' ({}) is up to date", plan.date)?;
+        return Ok(());
+    }
+
+    let size = |len: u64| Size::new(len as usize, Unit::B, UnitMode::Norm).to_string();
+    let width = plan.downloads.iter().map(|(name, _)| name.len()).max();
+    writeln!(
+        t.lock(),
+        "toolchain '{desc}' ({}) would download:",
+        plan.date
+    )?;
+    for (name, len) in &plan.downloads {
+        let len = len.map_or_else(|| "unknown".to_owned(), size);
+        writeln!(
+            t.lock(),
+            "  {name:width$}  {len}",
+            width = width.unwrap_or(0)
+        )?;
+    }
+    let total = plan.downloads.iter().filter_map(|(_, len)| *len).sum();
+    if plan.downloads.iter().any(|(_, len)| len.is_none()) {
+        writeln!(t.lock(), "total: at least {}", size(total).trim_start())?;
+    } else {
+        writeln!(t.lock(), "total: {}", size(total).trim_start())?;
+    }
+    Ok(())
+}
+
+fn toolchain_gc(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    let roots: Vec<PathBuf> = m
+        .get_many::<PathBuf>("root")
+        .map(|roots| roots.cloned().collect())
+        .unwrap_or_default();
+    let keep_last = *m.get_one::<usize>("keep-last").unwrap();
+    let referenced = cfg.referenced_toolchains(&roots)?;
+
+    // Group the unreferenced official toolchains by channel so that the most
+    // recently modified ones can be kept. Custom toolchains are never
+    // collected: rustup cannot reinstall them.
+    let mut by_channel: BTreeMap<(String, TargetTriple), Vec<(SystemTime, ToolchainName)>> =
+        BTreeMap::new();
+    for name in cfg.list_toolchains()? {
+        let path = cfg.toolchain_path(&(&name).into());
+        let modified = fs::symlink_metadata(&path)
+            .and_then(|m| m.modified())
+            .with_context(|| format!("could not stat toolchain directory '{}'", path.display()))?;
+        if referenced.contains(&name) {
+            continue;
+        }
+        if let ToolchainName::Official(desc) = &name {
+            by_channel
+                .entry((desc.channel.clone(), desc.target.clone()))
+                .or_default()
+                .push((modified, name));
+        }
+    }
+
+    let mut unreferenced = Vec::new();
+    for mut toolchains in by_channel.into_values() {
+        toolchains.sort_by(|a, b| b.0.cmp(&a.0));
+        unreferenced.extend(toolchains.into_iter().skip(keep_last).map(|(_, name)| name));
+    }
+    toolchain_sort(&mut unreferenced);
+
+    if unreferenced.is_empty() {
+        info!("no unreferenced toolchains to uninstall");
+        return Ok(utils::ExitCode(0));
+    }
+
+    if m.get_flag("dry-run") {
+        for name in unreferenced {
+            writeln!(process().stdout().lock(), "{name}")?;
+        }
+        return Ok(utils::ExitCode(0));
+    }
+
+    if !m.get_flag("no-prompt") {
+        writeln!(
+            process().stdout().lock(),
+            "The following toolchains will be uninstalled:"
+        )?;
+        for name in &unreferenced {
+            writeln!(process().stdout().lock(), "  {name}")?;
+        }
+        if !common::confirm("\nContinue? (y/N)", false)? {
+            info!("aborting toolchain gc");
+            return Ok(utils::ExitCode(0));
+        }
+    }
+
+    for name in unreferenced {
+        Toolchain::ensure_removed(cfg, (&name).into())?;
     }
     Ok(utils::ExitCode(0))
 }
 
+fn toolchain_verify(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    let toolchain_name = m.get_one::<ResolvableToolchainName>("toolchain").unwrap();
+    let toolchain_name = toolchain_name.resolve(&cfg.get_default_host_triple()?)?;
+    let toolchain = Toolchain::new(cfg, (&toolchain_name).into())?;
+    let distributable = DistributableToolchain::try_from(&toolchain)?;
+    let report = distributable.verify()?;
+
+    for component in &report.unknown {
+        warn!(
+            "component '{component}' cannot be checked, as no hashes were recorded when it was installed"
+        );
+    }
+    if !report.unknown.is_empty() {
+        info!(
+            "to check them, run `rustup toolchain uninstall {toolchain_name}` and install it again"
+        );
+    }
+    for file in &report.damaged {
+        let damage = match file.damage {
+            Damage::Missing => "is missing",
+            Damage::Modified => "has been modified",
+        };
+        warn!(
+            "'{}' of component '{}' {damage}",
+            file.path.display(),
+            file.component
+        );
+    }
+    if !report.damaged.is_empty() {
+        return Err(RustupError::ToolchainDamaged {
+            toolchain: toolchain_name.to_string(),
+            count: report.damaged.len(),
+        }
+        .into());
+    }
+    info!(
+        "checked {} files of {} components, {} of which had to be hashed",
+        report.files, report.components, report.hashed
+    );
+    Ok(utils::ExitCode(0))
+}
+
 fn override_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let toolchain_name = m.get_one::<ResolvableToolchainName>("toolchain").unwrap();
     let toolchain_name = toolchain_name.resolve(&cfg.get_default_host_triple()?)?;
@@ -1470,9 +2730,28 @@ fn override_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         utils::current_dir()?
     };
 
-    match Toolchain::new(cfg, (&toolchain_name).into()) {
+    set_override(cfg, &toolchain_name, &path, m.get_flag("channel-freeze"))
+}
+
+/// Overrides the toolchain for `path`, installing it first if needed, for
+/// both `rustup override set` and `rustup default --path`.
+fn set_override(
+    cfg: &Cfg,
+    toolchain_name: &ToolchainName,
+    path: &Path,
+    freeze: bool,
+) -> Result<utils::ExitCode> {
+    let frozen = match toolchain_name {
+        _ if !freeze => None,
+        ToolchainName::Official(desc) if desc.is_tracking() => Some(desc),
+        _ => Err(RustupError::ToolchainNotFreezable(
+            toolchain_name.to_string(),
+        ))?,
+    };
+
+    match Toolchain::new(cfg, toolchain_name.into()) {
         Ok(_) => {}
-        Err(e @ RustupError::ToolchainNotInstalled(_)) => match &toolchain_name {
+        Err(e @ RustupError::ToolchainNotInstalled(_)) => match toolchain_name {
             ToolchainName::Custom(_) => Err(e)?,
             ToolchainName::Official(desc) => {
                 let status = DistributableToolchain::install(
@@ -1480,7 +2759,7 @@ fn override_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
                     desc,
                     &[],
                     &[],
-                    cfg.get_profile()?,
+                    Some(cfg.get_profile()?),
                     false,
                 )?
                 .0;
@@ -1495,7 +2774,16 @@ fn override_add(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         Err(e) => Err(e)?,
     }
 
-    cfg.make_override(&path, &toolchain_name)?;
+    cfg.make_override(path, toolchain_name)?;
+    if let Some(desc) = frozen {
+        let date = DistributableToolchain::new(cfg, desc.clone())?
+            .get_manifest()?
+            .date;
+        cfg.settings_file.with_mut(|s| {
+            s.freeze_override(path, date, cfg.notify_handler.as_ref());
+            Ok(())
+        })?;
+    }
     Ok(utils::ExitCode(0))
 }
 
@@ -1516,6 +2804,12 @@ fn override_remove(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         if list.is_empty() {
             info!("no nonexistent paths detected");
         }
+        if m.get_flag("dry-run") {
+            for path in &list {
+                info!("would remove override toolchain for '{path}'");
+            }
+            return Ok(utils::ExitCode(0));
+        }
         list
     } else if let Some(path) = m.get_one::<String>("path") {
         vec![path.to_owned()]
@@ -1565,6 +2859,10 @@ const DOCS_DATA: &[(&str, &str, &str)] = &[
 fn doc(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     let toolchain = explicit_desc_or_dir_toolchain(cfg, m)?;
 
+    if m.get_flag("list") {
+        return handle_epipe(list_docs(&toolchain, m.get_flag("json")));
+    }
+
     if let Ok(distributable) = DistributableToolchain::try_from(&toolchain) {
         let manifestation = distributable.get_manifestation()?;
         let config = manifestation.read_config()?.unwrap_or_default();
@@ -1619,11 +2917,47 @@ fn doc(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
         } else {
             writeln!(process().stderr().lock(), "Opening docs in your browser")?;
         }
-        toolchain.open_docs(doc_url)?;
+        let browser = m.get_one::<String>("open-in").map(|s| s.as_str());
+        if let Err(e) = toolchain.open_docs(doc_url, browser) {
+            // Still of use on a machine without a browser that works
+            warn!("{e:#}");
+            warn!("the docs can be opened from this path instead:");
+            let doc_path = toolchain.doc_path(doc_url)?;
+            writeln!(process().stdout().lock(), "{}", doc_path.display())?;
+        }
         Ok(utils::ExitCode(0))
     }
 }
 
+#[derive(Serialize)]
+struct DocEntry<'a> {
+    name: &'a str,
+    title: &'a str,
+    path: PathBuf,
+}
+
+/// Lists the pages of [`DOCS_DATA`] that are present in `toolchain`.
+fn list_docs(toolchain: &Toolchain<'_>, json: bool) -> Result<utils::ExitCode> {
+    let mut entries = Vec::new();
+    for &(name, title, path) in DOCS_DATA {
+        let path = toolchain.doc_path(path)?;
+        if utils::is_file(&path) {
+            entries.push(DocEntry { name, title, path });
+        }
+    }
+
+    if json {
+        common::write_json(&entries)?;
+    } else {
+        let stdout = process().stdout();
+        let mut stdout = stdout.lock();
+        for entry in entries {
+            writeln!(stdout, "{:<20}\t{}", entry.name, entry.title)?;
+        }
+    }
+    Ok(utils::ExitCode(0))
+}
+
 #[cfg(not(windows))]
 fn man(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     use crate::currentprocess::varsource::VarSource;
@@ -1656,15 +2990,81 @@ fn self_uninstall(m: &ArgMatches) -> Result<utils::ExitCode> {
 }
 
 fn set_default_host_triple(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
-    cfg.set_default_host_triple(m.get_one::<String>("host_triple").unwrap())?;
+    let host_triple = m.get_one::<String>("host_triple").unwrap();
+    let known_hosts = (!m.get_flag("force")).then(|| known_host_triples(cfg));
+    cfg.set_default_host_triple(host_triple, known_hosts.as_deref())?;
     Ok(utils::ExitCode(0))
 }
 
+/// The hosts the active toolchain's channel ships `rustc` for, along with
+/// the built-in ones and those of this machine.
+fn known_host_triples(cfg: &Cfg) -> Vec<TargetTriple> {
+    let mut hosts: Vec<_> = KNOWN_HOSTS.iter().map(|h| TargetTriple::new(h)).collect();
+    if let Some((_, manifest)) = active_manifest(cfg) {
+        if let Ok(Package {
+            targets: PackageTargets::Targeted(targets),
+            ..
+        }) = manifest.get_package("rustc")
+        {
+            hosts.extend(targets.keys().cloned());
+        }
+    }
+    hosts.push(TargetTriple::from_host_or_build());
+    if let Ok(host) = cfg.get_default_host_triple() {
+        hosts.push(host);
+    }
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
+
 fn set_profile(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
-    cfg.set_profile(m.get_one::<String>("profile-name").unwrap())?;
+    // The profiles that toolchains are actually shipped with, as far as the
+    // active one tells
+    let active = active_manifest(cfg);
+    let profiles = active
+        .as_ref()
+        .map(|(_, manifest)| manifest.profiles())
+        .unwrap_or_default();
+
+    if m.get_flag("list") {
+        let t = process().stdout();
+        if profiles.is_empty() {
+            for name in Profile::names() {
+                writeln!(t.lock(), "{name}")?;
+            }
+        } else {
+            for (profile, components) in &profiles {
+                writeln!(t.lock(), "{profile}: {}", components.join(", "))?;
+            }
+        }
+        return Ok(utils::ExitCode(0));
+    }
+
+    let name = m.get_one::<String>("profile-name").unwrap();
+    cfg.set_profile(name)?;
+    if let Some((desc, _)) = &active {
+        let profile = Profile::from_str(name)?;
+        if !profiles.is_empty() && !profiles.iter().any(|(p, _)| *p == profile) {
+            warn!(
+                "the active toolchain '{desc}' does not define the '{name}' profile; valid profiles for it are: {}",
+                profiles.iter().map(|(p, _)| format!("'{p}'")).join(", ")
+            );
+        }
+    }
     Ok(utils::ExitCode(0))
 }
 
+/// The manifest of the active toolchain, if it is an installed toolchain
+/// from a dist server.
+fn active_manifest(cfg: &Cfg) -> Option<(ToolchainDesc, crate::dist::manifest::Manifest)> {
+    let (name, _) = cfg.active_toolchain(&utils::current_dir().ok()?).ok()?;
+    let toolchain = Toolchain::new(cfg, name).ok()?;
+    let distributable = DistributableToolchain::try_from(&toolchain).ok()?;
+    let manifest = distributable.get_manifest().ok()?;
+    Some((distributable.desc().clone(), manifest))
+}
+
 fn set_auto_self_update(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
     if self_update::NEVER_SELF_UPDATE {
         let mut args = crate::process().args_os();
@@ -1679,9 +3079,67 @@ fn set_auto_self_update(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode
     Ok(utils::ExitCode(0))
 }
 
+fn set_disable_self_update(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_disable_self_update(*m.get_one::<bool>("disabled").unwrap())?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_concurrency(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_download_concurrency(usize::from(*m.get_one::<u16>("concurrency").unwrap()))?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_retries(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_download_retries(usize::from(*m.get_one::<u16>("retries").unwrap()))?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_retry_backoff(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_download_retry_backoff(*m.get_one::<u64>("milliseconds").unwrap())?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_verify_signatures(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_download_verify_signatures(*m.get_one::<bool>("enabled").unwrap())?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_max_rate(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_download_max_rate(*m.get_one::<u64>("rate").unwrap())?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_cache_size(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_download_cache_size(*m.get_one::<u64>("size").unwrap())?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_ca_bundle(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    let path = m.get_one::<PathBuf>("path").unwrap();
+    cfg.set_download_ca_bundle(Some(path.as_path()).filter(|p| *p != Path::new("none")))?;
+    Ok(utils::ExitCode(0))
+}
+
+fn set_download_stream_extract(cfg: &mut Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    cfg.set_download_stream_extract(*m.get_one::<bool>("enabled").unwrap())?;
+    Ok(utils::ExitCode(0))
+}
+
+#[derive(Serialize)]
+struct ProfileJson {
+    profile: String,
+}
+
 #[cfg_attr(feature = "otel", tracing::instrument(skip_all))]
-fn show_profile(cfg: &Cfg) -> Result<utils::ExitCode> {
-    writeln!(process().stdout().lock(), "{}", cfg.get_profile()?)?;
+fn show_profile(cfg: &Cfg, json: bool) -> Result<utils::ExitCode> {
+    let profile = cfg.get_profile()?;
+    if json {
+        common::write_json(&ProfileJson {
+            profile: profile.to_string(),
+        })?;
+        return Ok(utils::ExitCode(0));
+    }
+    writeln!(process().stdout().lock(), "{profile}")?;
     Ok(utils::ExitCode(0))
 }
 
@@ -1713,11 +3171,44 @@ impl fmt::Display for CompletionCommand {
     }
 }
 
-fn output_completion_script(shell: Shell, command: CompletionCommand) -> Result<utils::ExitCode> {
+fn output_completion_script(
+    shell: Shell,
+    command: CompletionCommand,
+    dynamic: bool,
+) -> Result<utils::ExitCode> {
     match command {
+        CompletionCommand::Rustup if dynamic => {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut cli(), "rustup", &mut script);
+            let script = String::from_utf8(script)?;
+            let script = match shell {
+                Shell::Bash => script + BASH_DYNAMIC_COMPLETIONS,
+                Shell::Fish => script + FISH_DYNAMIC_COMPLETIONS,
+                // The generated function may be autoloaded, so rather than
+                // registering another function after it, hand over to ours in
+                // its place
+                Shell::Zsh if script.contains(ZSH_COMPLETIONS_DISPATCH) => {
+                    script.replace(ZSH_COMPLETIONS_DISPATCH, ZSH_DYNAMIC_COMPLETIONS)
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "{} does not currently support dynamic completions for {}",
+                        command,
+                        shell
+                    ))
+                }
+            };
+            write!(process().stdout().lock(), "{script}")?;
+        }
         CompletionCommand::Rustup => {
             clap_complete::generate(shell, &mut cli(), "rustup", &mut process().stdout().lock());
         }
+        CompletionCommand::Cargo if dynamic => {
+            return Err(anyhow!(
+                "{} does not currently support dynamic completions",
+                command
+            ))
+        }
         CompletionCommand::Cargo => {
             if let Shell::Zsh = shell {
                 writeln!(process().stdout().lock(), "#compdef cargo")?;
@@ -1746,3 +3237,155 @@ fn output_completion_script(shell: Shell, command: CompletionCommand) -> Result<
 
     Ok(utils::ExitCode(0))
 }
+
+/// The kinds of values `rustup internal complete` can list.
+static COMPLETION_CONTEXTS: &[&str] = &["toolchain", "target", "component"];
+
+/// Print the candidates for `context` one per line, for the dynamic completion
+/// scripts. Targets and components are those of the active toolchain.
+fn complete_values(cfg: &Cfg, m: &ArgMatches) -> Result<utils::ExitCode> {
+    let candidates: Vec<String> = match m.get_one::<String>("context").map(String::as_str) {
+        Some("toolchain") => cfg
+            .list_toolchains()?
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        Some("target") => cfg
+            .list_components(&utils::current_dir()?)?
+            .into_iter()
+            .filter(|c| c.name == "rust-std" && c.available)
+            .filter_map(|c| c.target)
+            .collect(),
+        Some("component") => cfg
+            .list_components(&utils::current_dir()?)?
+            .iter()
+            .filter(|c| c.available)
+            .map(|c| c.full_name())
+            .collect(),
+        _ => unreachable!(),
+    };
+
+    let stdout = process().stdout();
+    let mut stdout = stdout.lock();
+    for candidate in candidates {
+        writeln!(stdout, "{candidate}")?;
+    }
+    Ok(utils::ExitCode(0))
+}
+
+// The dynamic completion scripts below work out which kind of value is being
+// completed from the words typed so far, and fall back to the generated
+// completions for anything else.
+
+static BASH_DYNAMIC_COMPLETIONS: &str = r#"
+_rustup_dynamic() {
+    local context=""
+    case "${COMP_WORDS[COMP_CWORD-1]}" in
+        --toolchain) context="toolchain" ;;
+        --target) context="target" ;;
+        *)
+            if [[ ${COMP_CWORD} -eq 2 ]]; then
+                case "${COMP_WORDS[1]}" in
+                    default|run) context="toolchain" ;;
+                esac
+            elif [[ ${COMP_CWORD} -ge 3 ]]; then
+                case "${COMP_WORDS[1]} ${COMP_WORDS[2]}" in
+                    "toolchain uninstall"|"override set") context="toolchain" ;;
+                    "target add"|"target remove") context="target" ;;
+                    "component add"|"component remove") context="component" ;;
+                esac
+            fi
+            ;;
+    esac
+
+    if [[ -z "${context}" ]]; then
+        _rustup "$@"
+        return
+    fi
+    local IFS=$'\n'
+    COMPREPLY=( $(compgen -W "$(rustup internal complete "${context}" 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}") )
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _rustup_dynamic -o nosort -o bashdefault -o default rustup
+else
+    complete -F _rustup_dynamic -o bashdefault -o default rustup
+fi
+"#;
+
+static FISH_DYNAMIC_COMPLETIONS: &str = r#"
+function __fish_rustup_completing
+    set -l words (commandline -opc)
+    set -l context
+    switch "$words[-1]"
+        case --toolchain
+            set context toolchain
+        case --target
+            set context target
+        case '*'
+            if test (count $words) -eq 2
+                switch "$words[2]"
+                    case default run
+                        set context toolchain
+                end
+            else if test (count $words) -ge 3
+                switch "$words[2] $words[3]"
+                    case "toolchain uninstall" "override set"
+                        set context toolchain
+                    case "target add" "target remove"
+                        set context target
+                    case "component add" "component remove"
+                        set context component
+                end
+            end
+    end
+    test "$context" = "$argv[1]"
+end
+
+complete -c rustup -n "__fish_rustup_completing toolchain" -f -a "(rustup internal complete toolchain 2>/dev/null)"
+complete -c rustup -n "__fish_rustup_completing target" -f -a "(rustup internal complete target 2>/dev/null)"
+complete -c rustup -n "__fish_rustup_completing component" -f -a "(rustup internal complete component 2>/dev/null)"
+"#;
+
+/// How the script `clap_complete` generates for zsh ends.
+static ZSH_COMPLETIONS_DISPATCH: &str = r#"if [ "$funcstack[1]" = "_rustup" ]; then
+    _rustup "$@"
+else
+    compdef _rustup rustup
+fi
+"#;
+
+static ZSH_DYNAMIC_COMPLETIONS: &str = r#"_rustup_dynamic() {
+    local context=""
+    case "${words[CURRENT-1]}" in
+        --toolchain) context="toolchain" ;;
+        --target) context="target" ;;
+        *)
+            if (( CURRENT == 3 )); then
+                case "${words[2]}" in
+                    default|run) context="toolchain" ;;
+                esac
+            elif (( CURRENT >= 4 )); then
+                case "${words[2]} ${words[3]}" in
+                    "toolchain uninstall"|"override set") context="toolchain" ;;
+                    "target add"|"target remove") context="target" ;;
+                    "component add"|"component remove") context="component" ;;
+                esac
+            fi
+            ;;
+    esac
+
+    if [[ -z "$context" ]]; then
+        _rustup "$@"
+        return
+    fi
+    local -a candidates
+    candidates=("${(@f)$(rustup internal complete "$context" 2>/dev/null)}")
+    compadd -a candidates
+}
+
+compdef _rustup_dynamic rustup
+if [ "$funcstack[1]" = "_rustup" ]; then
+    _rustup_dynamic "$@"
+fi
+"#;
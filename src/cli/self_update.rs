@@ -53,12 +53,14 @@ use std::io::Write;
 use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
 use std::process::Command;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, Context, Result};
 use cfg_if::cfg_if;
 use same_file::Handle;
+use sha2::{Digest, Sha256};
 
-use crate::currentprocess::terminalsource;
+use crate::currentprocess::{self, terminalsource};
 use crate::{
     cli::{
         common::{self, ignorable_error, report_error, Confirm, PackageUpdate},
@@ -67,12 +69,12 @@ use crate::{
     },
     currentprocess::{filesource::StdoutSource, varsource::VarSource},
     dist::dist::{self, PartialToolchainDesc, Profile, TargetTriple, ToolchainDesc},
+    errors::RustupError,
     install::UpdateStatus,
     process,
     toolchain::{
         distributable::DistributableToolchain,
         names::{MaybeOfficialToolchainName, ResolvableToolchainName, ToolchainName},
-        toolchain::Toolchain,
     },
     utils::{utils, Notification},
     Cfg, DUP_TOOLS, TOOLS,
@@ -88,6 +90,8 @@ pub(crate) struct InstallOpts<'a> {
     pub default_toolchain: Option<MaybeOfficialToolchainName>,
     pub profile: String,
     pub no_modify_path: bool,
+    #[cfg(windows)]
+    pub path_position: Option<PathPosition>,
     pub no_update_toolchain: bool,
     pub components: &'a [&'a str],
     pub targets: &'a [&'a str],
@@ -142,6 +146,47 @@ impl std::fmt::Display for SelfUpdateMode {
     }
 }
 
+/// Where the installer puts `CARGO_HOME/bin` in the Windows user `PATH`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PathPosition {
+    /// Before existing entries, so rustup's proxies take precedence.
+    #[default]
+    Prepend,
+    /// After existing entries, so other toolchains on the `PATH` take precedence.
+    Append,
+}
+
+impl PathPosition {
+    pub(crate) fn names() -> &'static [&'static str] {
+        &["prepend", "append"]
+    }
+}
+
+impl FromStr for PathPosition {
+    type Err = anyhow::Error;
+
+    fn from_str(position: &str) -> Result<Self> {
+        match position {
+            "prepend" => Ok(Self::Prepend),
+            "append" => Ok(Self::Append),
+            _ => Err(anyhow!(format!(
+                "unknown path position: '{}'; valid positions are {}",
+                position,
+                Self::names().join(", "),
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for PathPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PathPosition::Prepend => "prepend",
+            PathPosition::Append => "append",
+        })
+    }
+}
+
 // The big installation messages. These are macros because the first
 // argument of format! needs to be a literal.
 
@@ -466,6 +511,12 @@ pub(crate) fn install(
 
         if !opts.no_modify_path {
             do_add_to_programs()?;
+            #[cfg(windows)]
+            do_add_to_path(match opts.path_position {
+                Some(position) => position,
+                None => saved_path_position()?.unwrap_or_default(),
+            })?;
+            #[cfg(not(windows))]
             do_add_to_path()?;
         }
         utils::create_rustup_home()?;
@@ -479,6 +530,12 @@ pub(crate) fn install(
             verbose,
             quiet,
         )?;
+        #[cfg(windows)]
+        if !opts.no_modify_path {
+            if let Some(position) = opts.path_position {
+                save_path_position(position)?;
+            }
+        }
 
         Ok(utils::ExitCode(0))
     })();
@@ -645,7 +702,7 @@ fn do_pre_install_options_sanity_checks(opts: &InstallOpts<'_>) -> Result<()> {
 fn pre_install_msg(no_modify_path: bool) -> Result<String> {
     let cargo_home = utils::cargo_home()?;
     let cargo_home_bin = cargo_home.join("bin");
-    let rustup_home = home::rustup_home()?;
+    let rustup_home = utils::rustup_home()?;
 
     if !no_modify_path {
         // Brittle code warning: some duplication in unix::do_add_to_path
@@ -839,6 +896,81 @@ pub(crate) fn install_proxies() -> Result<()> {
     Ok(())
 }
 
+/// Checks that the installation of rustup in `CARGO_HOME/bin` is whole: that
+/// every proxy there points at rustup, that the directory is in `PATH` and,
+/// on Windows, that the registry entries the installer made are present.
+/// Reports each problem with a way to repair it, and exits with 1 if there
+/// were any.
+pub(crate) fn check_installation() -> Result<utils::ExitCode> {
+    let bin_path = utils::cargo_home()?.join("bin");
+    let rustup_path = bin_path.join(format!("rustup{EXE_SUFFIX}"));
+    let Ok(rustup) = Handle::from_path(rustup_path) else {
+        err!("rustup is not installed in '{}'", bin_path.display());
+        info!("run `rustup-init` to install it");
+        return Ok(utils::ExitCode(1));
+    };
+
+    let mut broken = false;
+    let mut broken_proxies = false;
+    for tool in TOOLS.iter().chain(DUP_TOOLS) {
+        let tool_path = bin_path.join(format!("{tool}{EXE_SUFFIX}"));
+        match Handle::from_path(&tool_path) {
+            Ok(handle) if handle == rustup => {}
+            // Left alone by `install_proxies`, as it was installed separately
+            Ok(_) if DUP_TOOLS.contains(tool) => {
+                warn!(
+                    "`{tool}` in '{}' is not managed by rustup",
+                    bin_path.display()
+                )
+            }
+            Ok(_) => {
+                err!("'{}' is not a proxy for rustup", tool_path.display());
+                broken_proxies = true;
+            }
+            Err(_) => {
+                err!("the proxy '{}' is missing", tool_path.display());
+                broken_proxies = true;
+            }
+        }
+    }
+    if broken_proxies {
+        info!("run `rustup self update` to restore the proxies");
+        broken = true;
+    } else {
+        info!(
+            "the proxies in '{}' all point at rustup",
+            bin_path.display()
+        );
+    }
+
+    let on_path = process().var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|p| same_file::is_same_file(p, &bin_path).unwrap_or(false))
+    });
+    if on_path {
+        info!("'{}' is in PATH", bin_path.display());
+    } else {
+        err!("'{}' is not in PATH", bin_path.display());
+        info!("run `rustup-init` again to add it, then restart your shell");
+        broken = true;
+    }
+
+    #[cfg(windows)]
+    {
+        let problems = windows::check_registry()?;
+        for problem in &problems {
+            err!("{problem}");
+        }
+        if problems.is_empty() {
+            info!("the registry entries for rustup are present");
+        } else {
+            info!("run `rustup-init` again to restore the registry entries");
+            broken = true;
+        }
+    }
+
+    Ok(utils::ExitCode(if broken { 1 } else { 0 }))
+}
+
 fn maybe_install_rust(
     toolchain: Option<MaybeOfficialToolchainName>,
     profile_str: &str,
@@ -849,7 +981,7 @@ fn maybe_install_rust(
     verbose: bool,
     quiet: bool,
 ) -> Result<()> {
-    let mut cfg = common::set_globals(verbose, quiet)?;
+    let mut cfg = common::set_globals(verbose, quiet, false)?;
 
     let toolchain = _install_selection(
         &mut cfg,
@@ -861,7 +993,7 @@ fn maybe_install_rust(
         targets,
     )?;
     if let Some(ref desc) = toolchain {
-        let status = if Toolchain::exists(&cfg, &desc.into())? {
+        let status = if cfg.toolchain_installed(&desc.into())? {
             warn!("Updating existing toolchain, profile choice will be ignored");
             // If we have a partial install we might not be able to read content here. We could:
             // - fail and folk have to delete the partially present toolchain to recover
@@ -876,7 +1008,7 @@ fn maybe_install_rust(
                 desc,
                 components,
                 targets,
-                cfg.get_profile()?,
+                Some(cfg.get_profile()?),
                 true,
             )?
             .0
@@ -903,7 +1035,7 @@ fn _install_selection(
     if let Some(default_host_triple) = default_host_triple {
         // Set host triple now as it will affect resolution of toolchain_str
         info!("setting default host triple to {}", default_host_triple);
-        cfg.set_default_host_triple(default_host_triple)?;
+        cfg.set_default_host_triple(default_host_triple, None)?;
     } else {
         info!("default host triple is {}", cfg.get_default_host_triple()?);
     }
@@ -993,11 +1125,15 @@ pub(crate) fn uninstall(no_prompt: bool) -> Result<utils::ExitCode> {
 
     info!("removing rustup home");
 
-    // Delete RUSTUP_HOME
-    let rustup_dir = home::rustup_home()?;
+    // Delete RUSTUP_HOME, and the settings of the XDG layout
+    let xdg_dirs = utils::xdg_dirs();
+    let rustup_dir = utils::rustup_home()?;
     if rustup_dir.exists() {
         utils::remove_dir("rustup_home", &rustup_dir, &|_: Notification<'_>| {})?;
     }
+    if let Some(config_dir) = xdg_dirs.map(|d| d.config).filter(|d| d.exists()) {
+        utils::remove_dir("rustup config", &config_dir, &|_: Notification<'_>| {})?;
+    }
 
     info!("removing cargo home");
 
@@ -1081,7 +1217,21 @@ pub(crate) fn uninstall(no_prompt: bool) -> Result<utils::ExitCode> {
 /// (and on windows this process will not be running to do it),
 /// rustup-init is stored in `CARGO_HOME`/bin, and then deleted next
 /// time rustup runs.
-pub(crate) fn update(cfg: &Cfg) -> Result<utils::ExitCode> {
+///
+/// With `version`, that release is installed instead of the latest one,
+/// which unless `allow_downgrade` must not be older than this one.
+pub(crate) fn update(
+    cfg: &Cfg,
+    version: Option<&str>,
+    allow_downgrade: bool,
+) -> Result<utils::ExitCode> {
+    if cfg.self_update_disabled()? {
+        err!("self-update is disabled by policy");
+        return Ok(utils::ExitCode(1));
+    }
+    if cfg.offline {
+        return Err(RustupError::OfflineSelfUpdate.into());
+    }
     common::warn_if_host_is_emulated();
 
     use common::SelfUpdatePermission::*;
@@ -1104,7 +1254,7 @@ pub(crate) fn update(cfg: &Cfg) -> Result<utils::ExitCode> {
         Permit => {}
     }
 
-    match prepare_update()? {
+    match prepare_update(version, allow_downgrade, cfg.download_ca_bundle.as_deref())? {
         Some(setup_path) => {
             let version = match get_new_rustup_version(&setup_path) {
                 Some(new_version) => parse_new_rustup_version(new_version),
@@ -1159,7 +1309,15 @@ fn parse_new_rustup_version(version: String) -> String {
     String::from(matched_version)
 }
 
-pub(crate) fn prepare_update() -> Result<Option<PathBuf>> {
+/// Downloads rustup-init for `version`, or for the latest release if none
+/// is given, into `CARGO_HOME`/bin, returning `None` if that is the running
+/// version. A given version is checked against the hash published with it.
+/// Downloads trust `ca_bundle`, if given.
+pub(crate) fn prepare_update(
+    version: Option<&str>,
+    allow_downgrade: bool,
+    ca_bundle: Option<&Path>,
+) -> Result<Option<PathBuf>> {
     let cargo_home = utils::cargo_home()?;
     let rustup_path = cargo_home.join(format!("bin{MAIN_SEPARATOR}rustup{EXE_SUFFIX}"));
     let setup_path = cargo_home.join(format!("bin{MAIN_SEPARATOR}rustup-init{EXE_SUFFIX}"));
@@ -1191,8 +1349,16 @@ pub(crate) fn prepare_update() -> Result<Option<PathBuf>> {
     let current_version = env!("CARGO_PKG_VERSION");
 
     // Get available version
-    info!("checking for self-update");
-    let available_version = get_available_rustup_version()?;
+    let available_version = match version {
+        Some(version) => {
+            check_pinned_version(version, current_version, allow_downgrade)?;
+            version.to_owned()
+        }
+        None => {
+            info!("checking for self-update");
+            get_available_rustup_version(ca_bundle)?
+        }
+    };
 
     // If up-to-date
     if available_version == current_version {
@@ -1205,9 +1371,40 @@ pub(crate) fn prepare_update() -> Result<Option<PathBuf>> {
     // Get download path
     let download_url = utils::parse_url(&url)?;
 
+    // The hash is only looked up for a pinned version, whose absence it
+    // also reveals
+    let expected_hash = match version {
+        Some(version) => Some(get_rustup_init_hash(
+            &url,
+            version,
+            &update_root,
+            ca_bundle,
+        )?),
+        None => None,
+    };
+
     // Download new version
     info!("downloading self-update");
-    utils::download_file(&download_url, &setup_path, None, &|_| ())?;
+    let mut hasher = Sha256::new();
+    utils::download_file(
+        &download_url,
+        &setup_path,
+        Some(&mut hasher),
+        ca_bundle,
+        &|_| (),
+    )?;
+    if let Some(expected) = expected_hash {
+        let calculated = format!("{:x}", hasher.finalize());
+        if expected != calculated {
+            utils::remove_file("setup", &setup_path)?;
+            return Err(RustupError::ChecksumFailed {
+                url,
+                expected,
+                calculated,
+            }
+            .into());
+        }
+    }
 
     // Mark as executable
     utils::make_executable(&setup_path)?;
@@ -1215,7 +1412,65 @@ pub(crate) fn prepare_update() -> Result<Option<PathBuf>> {
     Ok(Some(setup_path))
 }
 
-pub(crate) fn get_available_rustup_version() -> Result<String> {
+/// Whether `available` is a later rustup version than `current`, so that a
+/// local or development build newer than the latest release is not told to
+/// update.
+pub(crate) fn is_newer_version(current: &str, available: &str) -> Result<bool> {
+    let Ok(available_version) = semver::Version::parse(available) else {
+        return Err(RustupError::InvalidRustupVersion(available.to_owned()).into());
+    };
+    Ok(available_version > semver::Version::parse(current)?)
+}
+
+/// Checks that `version` is a rustup version to update to, refusing one
+/// older than `current_version` unless `allow_downgrade`.
+fn check_pinned_version(version: &str, current_version: &str, allow_downgrade: bool) -> Result<()> {
+    let Ok(pinned) = semver::Version::parse(version) else {
+        return Err(RustupError::InvalidRustupVersion(version.to_owned()).into());
+    };
+    let current = semver::Version::parse(current_version)?;
+    if pinned < current && !allow_downgrade {
+        return Err(RustupError::RustupDowngrade {
+            version: version.to_owned(),
+            current: current_version.to_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// The SHA-256 hash published next to the rustup-init at `url`, which is
+/// missing if there is no such release as `version`.
+fn get_rustup_init_hash(
+    url: &str,
+    version: &str,
+    update_root: &str,
+    ca_bundle: Option<&Path>,
+) -> Result<String> {
+    let tempdir = tempfile::Builder::new()
+        .prefix("rustup-update")
+        .tempdir()
+        .context("error creating temp directory")?;
+    let hash_url = utils::parse_url(&format!("{url}.sha256"))?;
+    let hash_file = tempdir.path().join("rustup-init.sha256");
+    if let Err(e) = utils::download_file(&hash_url, &hash_file, None, ca_bundle, &|_| ()) {
+        return Err(match e.downcast_ref::<RustupError>() {
+            Some(RustupError::DownloadNotExists { .. }) => RustupError::UnknownRustupVersion {
+                version: version.to_owned(),
+                update_root: update_root.to_owned(),
+            }
+            .into(),
+            _ => e,
+        });
+    }
+    let hash = utils::read_file("rustup-init hash", &hash_file)?;
+    hash.split_whitespace()
+        .next()
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("empty hash file for rustup {version}"))
+}
+
+pub(crate) fn get_available_rustup_version(ca_bundle: Option<&Path>) -> Result<String> {
     let update_root = update_root();
     let tempdir = tempfile::Builder::new()
         .prefix("rustup-update")
@@ -1226,7 +1481,7 @@ pub(crate) fn get_available_rustup_version() -> Result<String> {
     let release_file_url = format!("{update_root}/release-stable.toml");
     let release_file_url = utils::parse_url(&release_file_url)?;
     let release_file = tempdir.path().join("release-stable.toml");
-    utils::download_file(&release_file_url, &release_file, None, &|_| ())?;
+    utils::download_file(&release_file_url, &release_file, None, ca_bundle, &|_| ())?;
     let release_toml_str = utils::read_file("rustup release", &release_file)?;
     let release_toml: toml::Value =
         toml::from_str(&release_toml_str).context("unable to parse rustup release file")?;
@@ -1253,18 +1508,113 @@ pub(crate) fn get_available_rustup_version() -> Result<String> {
     Ok(String::from(available_version))
 }
 
-pub(crate) fn check_rustup_update() -> Result<()> {
+/// The exit status of `rustup self update --check` when an update is
+/// available, kept apart from the 1 that any error exits with.
+pub(crate) const UPDATE_AVAILABLE_EXIT_CODE: i32 = 100;
+
+/// Reports whether a newer rustup is available without downloading or
+/// installing it, for `rustup self update --check`.
+///
+/// Exits with [`UPDATE_AVAILABLE_EXIT_CODE`] if an update is available and 0
+/// if rustup is up to date.
+pub(crate) fn check(cfg: &Cfg) -> Result<utils::ExitCode> {
+    if cfg.self_update_disabled()? {
+        info!("self-update is disabled by policy");
+        return Ok(utils::ExitCode(0));
+    }
+    if cfg.offline {
+        return Err(RustupError::OfflineSelfUpdate.into());
+    }
+    if NEVER_SELF_UPDATE {
+        info!("self-update is disabled for this build of rustup");
+        info!("any updates to rustup will need to be fetched with your system package manager");
+        return Ok(utils::ExitCode(0));
+    }
+
+    let update_available = check_rustup_update(cfg)?;
+    Ok(utils::ExitCode(match update_available {
+        true => UPDATE_AVAILABLE_EXIT_CODE,
+        false => 0,
+    }))
+}
+
+/// How long `check-only` self-update mode waits between looking for a new
+/// rustup while running other commands.
+const SELF_UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long `check-only` self-update mode waits for the release file before
+/// giving up, so that a slow network does not hold up the command it runs
+/// after.
+const SELF_UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// In `check-only` self-update mode, prints a notice if a newer rustup is
+/// available. Only looks when stdout is a terminal, and then at most once
+/// per [`SELF_UPDATE_CHECK_INTERVAL`], keeping the time of the last attempt
+/// in the settings file, and gives up after [`SELF_UPDATE_CHECK_TIMEOUT`].
+pub(crate) fn notify_available_update(cfg: &Cfg) -> Result<()> {
+    if NEVER_SELF_UPDATE
+        || cfg.self_update_disabled()?
+        || cfg.offline
+        || !process().stdout().is_a_tty()
+        || cfg.get_self_update_mode()? != SelfUpdateMode::CheckOnly
+    {
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    let last_check = cfg.settings_file.with(|s| Ok(s.last_self_update_check))?;
+    if last_check.is_some_and(|t| now.saturating_sub(t) < SELF_UPDATE_CHECK_INTERVAL.as_secs()) {
+        return Ok(());
+    }
+    // Recorded whatever comes of it, so that a check that fails or times
+    // out, as it does offline, does not hold up every command after it
+    cfg.settings_file.with_mut(|s| {
+        s.last_self_update_check = Some(now);
+        Ok(())
+    })?;
+
+    // The download runs on its own thread so that it can be abandoned
+    // once the timeout is up
+    let (tx, rx) = std::sync::mpsc::channel();
+    let process = process();
+    let ca_bundle = cfg.download_ca_bundle.clone();
+    std::thread::spawn(move || {
+        let available = currentprocess::with(process, || {
+            get_available_rustup_version(ca_bundle.as_deref())
+        });
+        let _ = tx.send(available);
+    });
+    let available_version = rx
+        .recv_timeout(SELF_UPDATE_CHECK_TIMEOUT)
+        .map_err(|_| anyhow!("timed out looking for a rustup update"))??;
+
+    let current_version = env!("CARGO_PKG_VERSION");
+    if is_newer_version(current_version, &available_version)? {
+        info!(
+            "rustup {available_version} is available (you have {current_version}); \
+             run `rustup self update` to install it"
+        );
+    }
+    Ok(())
+}
+
+/// Prints the current and available rustup versions, returning whether the
+/// available one is newer.
+pub(crate) fn check_rustup_update(cfg: &Cfg) -> Result<bool> {
     let mut t = process().stdout().terminal();
     // Get current rustup version
     let current_version = env!("CARGO_PKG_VERSION");
 
     // Get available rustup version
-    let available_version = get_available_rustup_version()?;
+    let available_version = get_available_rustup_version(cfg.download_ca_bundle.as_deref())?;
 
     let _ = t.attr(terminalsource::Attr::Bold);
     write!(t.lock(), "rustup - ")?;
 
-    if current_version != available_version {
+    let update_available = is_newer_version(current_version, &available_version)?;
+    if update_available {
         let _ = t.fg(terminalsource::Color::Yellow);
         write!(t.lock(), "Update available")?;
         let _ = t.reset();
@@ -1276,7 +1626,7 @@ pub(crate) fn check_rustup_update() -> Result<()> {
         writeln!(t.lock(), " : {current_version}")?;
     }
 
-    Ok(())
+    Ok(update_available)
 }
 
 #[cfg_attr(feature = "otel", tracing::instrument)]
@@ -1324,7 +1674,7 @@ mod tests {
             currentprocess::with(tp.clone().into(), || -> Result<()> {
                 // TODO: we could pass in a custom cfg to get notification
                 // callbacks rather than output to the tp sink.
-                let mut cfg = common::set_globals(false, false).unwrap();
+                let mut cfg = common::set_globals(false, false, false).unwrap();
                 assert_eq!(
                     "stable"
                         .parse::<PartialToolchainDesc>()
@@ -1358,6 +1708,15 @@ info: default host triple is {0}
         .unwrap();
     }
 
+    #[test]
+    fn newer_versions_are_compared_as_versions() {
+        assert!(super::is_newer_version("1.27.0", "1.27.1").unwrap());
+        assert!(super::is_newer_version("1.9.0", "1.10.0").unwrap());
+        assert!(!super::is_newer_version("1.27.0", "1.27.0").unwrap());
+        assert!(!super::is_newer_version("1.28.0-dev", "1.27.1").unwrap());
+        assert!(super::is_newer_version("1.27.1", "garbage").is_err());
+    }
+
     #[test]
     fn install_bins_creates_cargo_home() {
         let root_dir = test_dir().unwrap();
@@ -10,11 +10,12 @@ use anyhow::{anyhow, Context, Result};
 
 use super::super::errors::*;
 use super::common;
-use super::{install_bins, InstallOpts};
+use super::{install_bins, InstallOpts, PathPosition};
 use crate::cli::download_tracker::DownloadTracker;
 use crate::currentprocess::{filesource::StdoutSource, varsource::VarSource};
 use crate::dist::dist::TargetTriple;
 use crate::process;
+use crate::settings::SettingsFile;
 use crate::utils::utils;
 use crate::utils::Notification;
 
@@ -184,7 +185,7 @@ pub(crate) fn try_install_msvc(opts: &InstallOpts<'_>) -> Result<ContinueInstall
     download_tracker.lock().unwrap().download_finished();
 
     info!("downloading Visual Studio installer");
-    utils::download_file(&visual_studio_url, &visual_studio, None, &move |n| {
+    utils::download_file(&visual_studio_url, &visual_studio, None, None, &move |n| {
         download_tracker
             .lock()
             .unwrap()
@@ -364,11 +365,29 @@ pub(crate) fn wait_for_parent() -> Result<()> {
     Ok(())
 }
 
-pub(crate) fn do_add_to_path() -> Result<()> {
-    let new_path = _with_path_cargo_home_bin(_add_to_path)?;
+pub(crate) fn do_add_to_path(position: PathPosition) -> Result<()> {
+    let new_path =
+        _with_path_cargo_home_bin(|old_path, path_str| _add_to_path(old_path, path_str, position))?;
     _apply_new_path(new_path)
 }
 
+// The `PATH` position recorded by an earlier `rustup-init --path-position`,
+// if any. Doesn't create the settings file when there isn't one yet.
+pub(crate) fn saved_path_position() -> Result<Option<PathPosition>> {
+    let settings_path = utils::rustup_home()?.join("settings.toml");
+    if !utils::is_file(&settings_path) {
+        return Ok(None);
+    }
+    SettingsFile::new(settings_path).with(|s| Ok(s.path_position))
+}
+
+pub(crate) fn save_path_position(position: PathPosition) -> Result<()> {
+    SettingsFile::new(utils::rustup_home()?.join("settings.toml")).with_mut(|s| {
+        s.path_position = Some(position);
+        Ok(())
+    })
+}
+
 fn _apply_new_path(new_path: Option<Vec<u16>>) -> Result<()> {
     use std::ptr;
     use winapi::shared::minwindef::*;
@@ -440,46 +459,64 @@ fn get_windows_path_var() -> Result<Option<Vec<u16>>> {
     }
 }
 
+// Splits a PATH value into its `;` separated entries.
+fn _path_entries(path: &[u16]) -> Vec<&[u16]> {
+    if path.is_empty() {
+        Vec::new()
+    } else {
+        path.split(|c| *c == b';' as u16).collect()
+    }
+}
+
+fn _join_path_entries(entries: &[&[u16]]) -> Vec<u16> {
+    entries.join(&(b';' as u16))
+}
+
 // Returns None if the existing old_path does not need changing, otherwise
-// prepends the path_str to old_path, handling empty old_path appropriately.
-fn _add_to_path(old_path: Vec<u16>, path_str: Vec<u16>) -> Option<Vec<u16>> {
-    if old_path.is_empty() {
-        Some(path_str)
-    } else if old_path
-        .windows(path_str.len())
-        .any(|path| path == path_str)
-    {
+// puts path_str at the given position of old_path, dropping any other
+// occurrences of it so that it appears exactly once.
+fn _add_to_path(
+    old_path: Vec<u16>,
+    path_str: Vec<u16>,
+    position: PathPosition,
+) -> Option<Vec<u16>> {
+    let mut entries: Vec<&[u16]> = _path_entries(&old_path)
+        .into_iter()
+        .filter(|entry| *entry != path_str.as_slice())
+        .collect();
+    match position {
+        PathPosition::Prepend => entries.insert(0, &path_str),
+        PathPosition::Append => {
+            // Don't leave an empty entry in front of ours.
+            while entries.last().map_or(false, |entry| entry.is_empty()) {
+                entries.pop();
+            }
+            entries.push(&path_str);
+        }
+    }
+
+    let new_path = _join_path_entries(&entries);
+    if new_path == old_path {
         None
     } else {
-        let mut new_path = path_str;
-        new_path.push(b';' as u16);
-        new_path.extend_from_slice(&old_path);
         Some(new_path)
     }
 }
 
-// Returns None if the existing old_path does not need changing
+// Returns None if the existing old_path does not need changing, otherwise
+// removes every entry that is exactly path_str, wherever it is.
 fn _remove_from_path(old_path: Vec<u16>, path_str: Vec<u16>) -> Option<Vec<u16>> {
-    let idx = old_path
-        .windows(path_str.len())
-        .position(|path| path == path_str)?;
-    // If there's a trailing semicolon (likely, since we probably added one
-    // during install), include that in the substring to remove. We don't search
-    // for that to find the string, because if it's the last string in the path,
-    // there may not be.
-    let mut len = path_str.len();
-    if old_path.get(idx + path_str.len()) == Some(&(b';' as u16)) {
-        len += 1;
-    }
-
-    let mut new_path = old_path[..idx].to_owned();
-    new_path.extend_from_slice(&old_path[idx + len..]);
-    // Don't leave a trailing ; though, we don't want an empty string in the
-    // path.
-    if new_path.last() == Some(&(b';' as u16)) {
-        new_path.pop();
+    let entries = _path_entries(&old_path);
+    let remaining: Vec<&[u16]> = entries
+        .iter()
+        .copied()
+        .filter(|entry| *entry != path_str.as_slice())
+        .collect();
+    if remaining.len() == entries.len() {
+        None
+    } else {
+        Some(_join_path_entries(&remaining))
     }
-    Some(new_path)
 }
 
 fn _with_path_cargo_home_bin<F>(f: F) -> Result<Option<Vec<u16>>>
@@ -543,6 +580,35 @@ pub(crate) fn do_add_to_programs() -> Result<()> {
     Ok(())
 }
 
+/// What is wrong with the registry entries that installing rustup makes:
+/// `CARGO_HOME/bin` in the user's `PATH`, and rustup in the installed
+/// programs. Empty if they are both present.
+pub(crate) fn check_registry() -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    let bin_path = utils::cargo_home()?.join("bin");
+    let bin: Vec<u16> = OsString::from(&bin_path).encode_wide().collect();
+    let in_path =
+        get_windows_path_var()?.is_some_and(|path| _path_entries(&path).contains(&bin.as_slice()));
+    if !in_path {
+        problems.push(format!(
+            "'{}' is not in the PATH of HKEY_CURRENT_USER\\Environment",
+            bin_path.display()
+        ));
+    }
+
+    let uninstall = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(RUSTUP_UNINSTALL_ENTRY)
+        .and_then(|key| key.get_raw_value("UninstallString"));
+    if uninstall.is_err() {
+        problems.push(format!(
+            "rustup is missing from the installed programs, at HKEY_CURRENT_USER\\{RUSTUP_UNINSTALL_ENTRY}"
+        ));
+    }
+
+    Ok(problems)
+}
+
 pub(crate) fn do_remove_from_programs() -> Result<()> {
     match RegKey::predef(HKEY_CURRENT_USER).delete_subkey_all(RUSTUP_UNINSTALL_ENTRY) {
         Ok(()) => Ok(()),
@@ -713,6 +779,7 @@ mod tests {
 
     use rustup_macros::unit_test as test;
 
+    use super::PathPosition;
     use crate::currentprocess;
     use crate::test::with_saved_path;
 
@@ -726,8 +793,52 @@ mod tests {
             None,
             super::_add_to_path(
                 wide(r"c:\users\example\.cargo\bin;foo"),
-                wide(r"c:\users\example\.cargo\bin")
+                wide(r"c:\users\example\.cargo\bin"),
+                PathPosition::Prepend,
+            )
+        );
+    }
+
+    #[test]
+    fn windows_install_appends_to_path() {
+        assert_eq!(
+            wide(r"foo;bar;c:\users\example\.cargo\bin"),
+            super::_add_to_path(
+                wide("foo;bar"),
+                wide(r"c:\users\example\.cargo\bin"),
+                PathPosition::Append,
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            None,
+            super::_add_to_path(
+                wide(r"foo;c:\users\example\.cargo\bin"),
+                wide(r"c:\users\example\.cargo\bin"),
+                PathPosition::Append,
+            )
+        );
+    }
+
+    #[test]
+    fn windows_install_moves_existing_path_entry() {
+        assert_eq!(
+            wide(r"foo;bar;c:\users\example\.cargo\bin"),
+            super::_add_to_path(
+                wide(r"c:\users\example\.cargo\bin;foo;c:\users\example\.cargo\bin;bar"),
+                wide(r"c:\users\example\.cargo\bin"),
+                PathPosition::Append,
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            wide(r"c:\users\example\.cargo\bin;foo"),
+            super::_add_to_path(
+                wide(r"foo;c:\users\example\.cargo\bin"),
+                wide(r"c:\users\example\.cargo\bin"),
+                PathPosition::Prepend,
             )
+            .unwrap()
         );
     }
 
@@ -743,7 +854,12 @@ mod tests {
 
         assert_eq!(
             &final_path,
-            &super::_add_to_path(initial_path.clone(), cargo_home.clone(),).unwrap()
+            &super::_add_to_path(
+                initial_path.clone(),
+                cargo_home.clone(),
+                PathPosition::Prepend,
+            )
+            .unwrap()
         );
         assert_eq!(
             &initial_path,
@@ -891,4 +1007,23 @@ mod tests {
             .unwrap()
         )
     }
+
+    #[test]
+    fn windows_uninstall_removes_only_whole_entries() {
+        assert_eq!(
+            wide(r"c:\users\example\.cargo\bin2;foo"),
+            super::_remove_from_path(
+                wide(r"c:\users\example\.cargo\bin2;c:\users\example\.cargo\bin;foo"),
+                wide(r"c:\users\example\.cargo\bin"),
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            None,
+            super::_remove_from_path(
+                wide(r"c:\users\example\.cargo\bin2;foo"),
+                wide(r"c:\users\example\.cargo\bin"),
+            )
+        );
+    }
 }
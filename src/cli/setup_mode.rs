@@ -106,6 +106,14 @@ pub fn main() -> Result<utils::ExitCode> {
                 .help("Don't configure the PATH environment variable")
                 .action(ArgAction::SetTrue),
         );
+    #[cfg(windows)]
+    let cli = cli.arg(
+        Arg::new("path-position")
+            .long("path-position")
+            .help("Whether to put rustup's bin directory before or after the existing PATH entries")
+            .conflicts_with("no-modify-path")
+            .value_parser(PossibleValuesParser::new(self_update::PathPosition::names())),
+    );
 
     let matches = match cli.try_get_matches_from(process().args_os()) {
         Ok(matches) => matches,
@@ -128,6 +136,11 @@ pub fn main() -> Result<utils::ExitCode> {
         .get_one::<String>("profile")
         .expect("Unreachable: Clap should supply a default");
     let no_modify_path = matches.get_flag("no-modify-path");
+    #[cfg(windows)]
+    let path_position = matches
+        .get_one::<String>("path-position")
+        .map(|p| p.parse::<self_update::PathPosition>())
+        .transpose()?;
     let no_update_toolchain = matches.get_flag("no-update-default-toolchain");
 
     let components: Vec<_> = matches
@@ -145,6 +158,8 @@ pub fn main() -> Result<utils::ExitCode> {
         default_toolchain,
         profile: profile.to_owned(),
         no_modify_path,
+        #[cfg(windows)]
+        path_position,
         no_update_toolchain,
         components: &components,
         targets: &targets,
@@ -31,6 +31,12 @@ pub(crate) fn run_command_for_dir<S: AsRef<OsStr> + Debug>(
     #[cfg(unix)]
     fn exec(cmd: &mut Command) -> io::Result<ExitCode> {
         use std::os::unix::prelude::*;
+
+        use crate::currentprocess::varsource::VarSource;
+
+        if let Ok(true) = crate::process().var("RUSTUP_NO_EXEC").map(|s| s == "1") {
+            return spawn_and_wait(cmd);
+        }
         Err(cmd.exec())
     }
 
@@ -55,3 +61,81 @@ pub(crate) fn run_command_for_dir<S: AsRef<OsStr> + Debug>(
         Ok(ExitCode(status.code().unwrap()))
     }
 }
+
+/// The child that [`spawn_and_wait`] is waiting for, for its signal handler
+/// to forward signals to, or zero before there is one.
+#[cfg(unix)]
+static CHILD: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+/// Runs `cmd` as a child instead of becoming it, for `RUSTUP_NO_EXEC`, and
+/// exits as it does.
+///
+/// The child shares the proxy's process group, so a Ctrl-C in the terminal
+/// already reaches it. SIGINT and SIGTERM sent by another process to the
+/// proxy alone are passed on to the child, rather than ending the proxy and
+/// leaving the child behind.
+#[cfg(unix)]
+fn spawn_and_wait(cmd: &mut Command) -> io::Result<ExitCode> {
+    use std::os::unix::process::ExitStatusExt;
+    use std::sync::atomic::Ordering;
+
+    extern "C" fn forward(signal: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+        if !sent_by_process(info) {
+            return;
+        }
+        let child = CHILD.load(Ordering::SeqCst);
+        if child > 0 {
+            unsafe { libc::kill(child, signal) };
+        }
+    }
+
+    // Installed before the child exists, so that no signal can end the proxy
+    // in between; the child gets the default handling back when it execs
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = forward as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+
+    let mut child = cmd.spawn()?;
+    CHILD.store(child.id() as i32, Ordering::SeqCst);
+    let status = child.wait()?;
+    // As a shell reports a child that a signal ended
+    let code = status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1);
+    Ok(ExitCode(code))
+}
+
+/// Whether the signal was sent by a process with `kill`, rather than by the
+/// kernel, as the terminal's are.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_vendor = "apple"
+))]
+fn sent_by_process(info: *mut libc::siginfo_t) -> bool {
+    unsafe { (*info).si_pid() != 0 }
+}
+
+/// Where the sender can't be told, every signal is passed on.
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_vendor = "apple"
+    ))
+))]
+fn sent_by_process(_: *mut libc::siginfo_t) -> bool {
+    true
+}
@@ -1,24 +1,30 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::env::consts::EXE_SUFFIX;
 use std::fmt::{self, Debug, Display};
 use std::io;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{anyhow, bail, Context, Result};
+use download::RateLimiter;
 use serde::Deserialize;
 use thiserror::Error as ThisError;
 
 use crate::{
     cli::self_update::SelfUpdateMode,
-    currentprocess::varsource::VarSource,
+    currentprocess::{varsource::VarSource, Process},
     dist::{
         dist::{self, PartialToolchainDesc, Profile, ToolchainDesc},
-        download::DownloadCfg,
+        download::{DownloadCfg, DEFAULT_CONCURRENCY, DEFAULT_RETRIES, DEFAULT_RETRY_BACKOFF_MS},
+        signature::{self, PublicKey},
         temp,
     },
-    errors::RustupError,
+    env_var,
+    errors::{ActiveToolchainError, InstallError, RustupError},
     fallback_settings::FallbackSettings,
     install::UpdateStatus,
     notifications::*,
@@ -43,11 +49,15 @@ enum OverrideFileConfigError {
     Invalid,
     #[error("error parsing override file")]
     Parsing,
+    #[error("environment variable '{0}' is set by rustup and cannot be overridden in a toolchain override file")]
+    ReservedEnvVar(String),
 }
 
 #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
 struct OverrideFile {
     toolchain: ToolchainSection,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
 }
 
 impl OverrideFile {
@@ -83,6 +93,7 @@ impl<T: Into<String>> From<T> for OverrideFile {
                     path: Some(PathBuf::from(override_)),
                     ..Default::default()
                 },
+                ..Default::default()
             }
         } else {
             Self {
@@ -90,22 +101,31 @@ impl<T: Into<String>> From<T> for OverrideFile {
                     channel: Some(override_),
                     ..Default::default()
                 },
+                ..Default::default()
             }
         }
     }
 }
 
-#[derive(Debug)]
-pub(crate) enum OverrideReason {
+/// Why a toolchain is the active one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ActiveReason {
+    /// The default toolchain, as set by `rustup default`.
+    Default,
+    /// The `RUSTUP_TOOLCHAIN` environment variable.
     Environment,
+    /// A `+toolchain` argument on the command line.
     CommandLine,
+    /// A `rustup override` for the given directory.
     OverrideDB(PathBuf),
+    /// The given `rust-toolchain` or `rust-toolchain.toml` file.
     ToolchainFile(PathBuf),
 }
 
-impl Display for OverrideReason {
+impl Display for ActiveReason {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
         match self {
+            Self::Default => write!(f, "default"),
             Self::Environment => write!(f, "environment override by RUSTUP_TOOLCHAIN"),
             Self::CommandLine => write!(f, "overridden by +toolchain on the command line"),
             Self::OverrideDB(path) => write!(f, "directory override for '{}'", path.display()),
@@ -114,12 +134,37 @@ impl Display for OverrideReason {
     }
 }
 
+/// A component of a toolchain and its state, as `rustup component list`
+/// shows it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentStatus {
+    /// The name of the component without its target, such as `rust-std`.
+    pub name: String,
+    /// The target the component is for, if it is target specific.
+    pub target: Option<String>,
+    pub installed: bool,
+    /// Whether the toolchain's manifest offers the component. Components
+    /// added with `rustup component add --path` are never available.
+    pub available: bool,
+}
+
+impl ComponentStatus {
+    /// The name including the target, as used on the command line.
+    pub fn full_name(&self) -> String {
+        match &self.target {
+            Some(target) => format!("{}-{target}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 struct OverrideCfg {
     toolchain: Option<LocalToolchainName>,
     components: Vec<String>,
     targets: Vec<String>,
     profile: Option<dist::Profile>,
+    env: BTreeMap<String, String>,
 }
 
 impl OverrideCfg {
@@ -165,42 +210,124 @@ impl OverrideCfg {
                 .as_deref()
                 .map(dist::Profile::from_str)
                 .transpose()?,
+            env: file.env,
         })
     }
 }
 
 pub(crate) const UNIX_FALLBACK_SETTINGS: &str = "/etc/rustup/settings.toml";
 
-pub(crate) struct Cfg {
+/// Rustup's configuration: where its files are, and the settings and
+/// environment variables that change what it does.
+pub struct Cfg {
     profile_override: Option<dist::Profile>,
-    pub rustup_dir: PathBuf,
-    pub settings_file: SettingsFile,
-    pub fallback_settings: Option<FallbackSettings>,
-    pub toolchains_dir: PathBuf,
-    pub update_hash_dir: PathBuf,
-    pub download_dir: PathBuf,
-    pub tmp_cx: temp::Context,
-    pub toolchain_override: Option<ResolvableToolchainName>,
-    pub env_override: Option<LocalToolchainName>,
-    pub dist_root_url: String,
-    pub notify_handler: Arc<dyn Fn(Notification<'_>)>,
+    pub(crate) rustup_dir: PathBuf,
+    pub(crate) settings_file: SettingsFile,
+    pub(crate) fallback_settings: Option<FallbackSettings>,
+    pub(crate) toolchains_dir: PathBuf,
+    pub(crate) update_hash_dir: PathBuf,
+    pub(crate) download_dir: PathBuf,
+    pub(crate) tmp_cx: temp::Context,
+    pub(crate) toolchain_override: Option<ResolvableToolchainName>,
+    pub(crate) env_override: Option<LocalToolchainName>,
+    pub(crate) dist_root_url: String,
+    pub(crate) download_concurrency: usize,
+    pub(crate) download_retries: usize,
+    pub(crate) download_retry_backoff: Duration,
+    pub(crate) local_dist_dir: Option<PathBuf>,
+    /// The keys that channel manifests must be signed with, when
+    /// `download.verify-signatures` is set.
+    pub(crate) signature_keys: Option<Vec<PublicKey>>,
+    pub(crate) allow_unsigned_local: bool,
+    /// Limits downloads to `download.max-rate`, if that is set.
+    pub(crate) rate_limiter: Option<RateLimiter>,
+    /// The `download.ca-bundle` that downloads trust, if that is set.
+    pub(crate) download_ca_bundle: Option<PathBuf>,
+    /// The `download.cache-size` that the download directory is trimmed to,
+    /// if that is set.
+    pub(crate) download_cache_size: Option<u64>,
+    /// Whether component packages are unpacked as they download, set by
+    /// `download.stream-extract`.
+    pub(crate) download_stream_extract: bool,
+    /// Whether network downloads are refused, set by `RUSTUP_OFFLINE` or
+    /// the `--offline` flag.
+    pub(crate) offline: bool,
+    /// `None` if it could not be found.
+    pub(crate) cargo_home: Option<PathBuf>,
+    /// The process whose environment variables configure this, which is
+    /// the current one unless the `Cfg` was made with [`Cfg::open`].
+    process: Process,
+    pub(crate) notify_handler: Arc<dyn Fn(Notification<'_>)>,
 }
 
 impl Cfg {
-    pub(crate) fn from_env(notify_handler: Arc<dyn Fn(Notification<'_>)>) -> Result<Self> {
-        // Set up the rustup home directory
-        let rustup_dir = utils::rustup_home()?;
+    /// Finds the rustup and cargo homes from the environment of the current
+    /// process, the way rustup itself does, and opens the configuration there.
+    ///
+    /// This must be called within [`currentprocess::with`].
+    ///
+    /// [`currentprocess::with`]: crate::currentprocess::with
+    pub fn from_env(notify_handler: Arc<dyn Fn(Notification<'_>)>) -> Result<Self> {
+        // Only the commands run from a toolchain need it, so a missing one is
+        // left for them to cope with
+        let cargo_home = utils::cargo_home().ok();
+        Self::open(utils::rustup_home()?, cargo_home, process(), notify_handler)
+    }
 
-        utils::ensure_dir_exists("home", &rustup_dir, notify_handler.as_ref())?;
+    /// Opens the configuration in `rustup_home` for the cargo home
+    /// `cargo_home`, if there is one.
+    ///
+    /// The settings file is kept in `rustup_home` too, unless that is the
+    /// XDG data directory, in which case it is in the XDG config directory.
+    /// Settings that come from environment variables, such as
+    /// `RUSTUP_DIST_SERVER` or `RUSTUP_TOOLCHAIN`, are read from `process`
+    /// rather than from the current process, so that a `Cfg` can be made for
+    /// a temporary directory without touching the environment.
+    pub fn open(
+        rustup_home: PathBuf,
+        cargo_home: Option<PathBuf>,
+        process: Process,
+        notify_handler: Arc<dyn Fn(Notification<'_>)>,
+    ) -> Result<Self> {
+        let settings_path = match utils::xdg_dirs_of(&process) {
+            Some(dirs) if dirs.data == rustup_home => {
+                utils::ensure_dir_exists("config", &dirs.config, notify_handler.as_ref())?;
+                let settings_path = dirs.config.join("settings.toml");
+                let legacy =
+                    home::env::home_dir_with_env(&process).map(|h| h.join(".rustup/settings.toml"));
+                if let Some(legacy) = legacy {
+                    migrate_legacy_settings(&legacy, &settings_path, notify_handler.as_ref())?;
+                }
+                settings_path
+            }
+            _ => rustup_home.join("settings.toml"),
+        };
+        Self::open_with_settings(
+            rustup_home,
+            settings_path,
+            cargo_home,
+            process,
+            notify_handler,
+        )
+    }
 
-        let settings_file = SettingsFile::new(rustup_dir.join("settings.toml"));
+    fn open_with_settings(
+        rustup_dir: PathBuf,
+        settings_path: PathBuf,
+        cargo_home: Option<PathBuf>,
+        process: Process,
+        notify_handler: Arc<dyn Fn(Notification<'_>)>,
+    ) -> Result<Self> {
+        // Set up the rustup home directory
+        utils::ensure_dir_exists("home", &rustup_dir, notify_handler.as_ref())?;
+        let settings_file = SettingsFile::new(settings_path);
 
         // Centralised file for multi-user systems to provide admin/distributor set initial values.
         let fallback_settings = if cfg!(not(windows)) {
             // If present, use the RUSTUP_OVERRIDE_UNIX_FALLBACK_SETTINGS environment
             // variable as settings path, or UNIX_FALLBACK_SETTINGS otherwise
             FallbackSettings::new(
-                match process().var("RUSTUP_OVERRIDE_UNIX_FALLBACK_SETTINGS") {
+                match process.var("RUSTUP_OVERRIDE_UNIX_FALLBACK_SETTINGS") {
                     Ok(s) => PathBuf::from(s),
                     Err(_) => PathBuf::from(UNIX_FALLBACK_SETTINGS),
                 },
@@ -214,9 +341,50 @@ impl Cfg {
         let download_dir = rustup_dir.join("downloads");
 
         // Figure out get_default_host_triple before Config is populated
-        let default_host_triple = settings_file.with(|s| Ok(get_default_host_triple(s)))?;
+        let default_host_triple =
+            settings_file.with(|s| Ok(get_default_host_triple(s, &process)))?;
+        let download_concurrency =
+            settings_file.with(|s| Ok(s.download_concurrency.unwrap_or(DEFAULT_CONCURRENCY)))?;
+        // RUSTUP_MAX_RETRIES predates the setting, and still takes precedence
+        let download_retries = match process
+            .var("RUSTUP_MAX_RETRIES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(retries) => retries,
+            None => settings_file.with(|s| Ok(s.download_retries.unwrap_or(DEFAULT_RETRIES)))?,
+        };
+        let download_retry_backoff = Duration::from_millis(settings_file.with(|s| {
+            Ok(s.download_retry_backoff_ms
+                .unwrap_or(DEFAULT_RETRY_BACKOFF_MS))
+        })?);
+        let (verify_signatures, allow_unsigned_local) = settings_file.with(|s| {
+            Ok((
+                s.download_verify_signatures.unwrap_or(false),
+                s.download_allow_unsigned_local.unwrap_or(false),
+            ))
+        })?;
+        let signature_keys = if verify_signatures {
+            Some(settings_file.with(|s| signature_keys(s.pgp_keys.as_deref(), &process))?)
+        } else {
+            None
+        };
+        let rate_limiter = settings_file
+            .with(|s| Ok(s.download_max_rate))?
+            .and_then(RateLimiter::new);
+        let download_cache_size = settings_file
+            .with(|s| Ok(s.download_cache_size))?
+            .filter(|size| *size > 0);
+        let download_stream_extract =
+            settings_file.with(|s| Ok(s.download_stream_extract.unwrap_or(false)))?;
+        let download_ca_bundle = settings_file
+            .with(|s| Ok(s.download_ca_bundle.clone()))?
+            .map(PathBuf::from);
+        let offline = process
+            .var("RUSTUP_OFFLINE")
+            .is_ok_and(|v| !v.is_empty() && v != "0");
         // Environment override
-        let env_override = process()
+        let env_override = process
             .var("RUSTUP_TOOLCHAIN")
             .ok()
             .and_then(utils::if_not_empty)
@@ -225,14 +393,14 @@ impl Cfg {
             .map(|t| t.resolve(&default_host_triple))
             .transpose()?;
 
-        let dist_root_server = match process().var("RUSTUP_DIST_SERVER") {
+        let dist_root_server = match process.var("RUSTUP_DIST_SERVER") {
             Ok(s) if !s.is_empty() => {
                 debug!("`RUSTUP_DIST_SERVER` has been set to `{s}`");
                 s
             }
             _ => {
                 // For backward compatibility
-                process()
+                process
                     .var("RUSTUP_DIST_ROOT")
                     .ok()
                     .and_then(utils::if_not_empty)
@@ -265,6 +433,19 @@ impl Cfg {
             toolchain_override: None,
             env_override,
             dist_root_url: dist_root,
+            download_concurrency,
+            download_retries,
+            download_retry_backoff,
+            local_dist_dir: None,
+            signature_keys,
+            allow_unsigned_local,
+            rate_limiter,
+            download_ca_bundle,
+            download_cache_size,
+            download_stream_extract,
+            offline,
+            cargo_home,
+            process,
         };
 
         // Run some basic checks against the constructed configuration
@@ -287,6 +468,17 @@ impl Cfg {
             dist_root: &self.dist_root_url,
             tmp_cx: &self.tmp_cx,
             download_dir: &self.download_dir,
+            concurrency: self.download_concurrency,
+            retries: self.download_retries,
+            retry_backoff: self.download_retry_backoff,
+            local_dist_dir: self.local_dist_dir.as_deref(),
+            signature_keys: self.signature_keys.as_deref(),
+            allow_unsigned_local: self.allow_unsigned_local,
+            rate_limiter: self.rate_limiter.as_ref(),
+            ca_bundle: self.download_ca_bundle.as_deref(),
+            cache_size: self.download_cache_size,
+            stream_extract: self.download_stream_extract,
+            offline: self.offline,
             notify_handler,
         }
     }
@@ -296,11 +488,12 @@ impl Cfg {
     }
 
     pub(crate) fn set_default(&self, toolchain: Option<&ToolchainName>) -> Result<()> {
+        let toolchain = toolchain.map(|t| t.to_string());
         self.settings_file.with_mut(|s| {
-            s.default_toolchain = toolchain.map(|t| t.to_string());
+            s.default_toolchain = toolchain.clone();
             Ok(())
         })?;
-        (self.notify_handler)(Notification::SetDefaultToolchain(toolchain));
+        (self.notify_handler)(Notification::SetDefaultToolchain(toolchain.as_deref()));
         Ok(())
     }
 
@@ -333,6 +526,97 @@ impl Cfg {
         }
     }
 
+    pub(crate) fn set_download_concurrency(&mut self, concurrency: usize) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.download_concurrency = Some(concurrency);
+            Ok(())
+        })?;
+        self.download_concurrency = concurrency;
+        (self.notify_handler)(Notification::SetDownloadConcurrency(concurrency));
+        Ok(())
+    }
+
+    pub(crate) fn set_download_retries(&mut self, retries: usize) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.download_retries = Some(retries);
+            Ok(())
+        })?;
+        self.download_retries = retries;
+        (self.notify_handler)(Notification::SetDownloadRetries(retries));
+        Ok(())
+    }
+
+    pub(crate) fn set_download_verify_signatures(&mut self, enabled: bool) -> Result<()> {
+        let pgp_keys = self.settings_file.with_mut(|s| {
+            s.download_verify_signatures = Some(enabled);
+            Ok(s.pgp_keys.clone())
+        })?;
+        self.signature_keys = if enabled {
+            Some(signature_keys(pgp_keys.as_deref(), &self.process)?)
+        } else {
+            None
+        };
+        (self.notify_handler)(Notification::SetDownloadVerifySignatures(enabled));
+        Ok(())
+    }
+
+    pub(crate) fn set_download_retry_backoff(&mut self, backoff_ms: u64) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.download_retry_backoff_ms = Some(backoff_ms);
+            Ok(())
+        })?;
+        self.download_retry_backoff = Duration::from_millis(backoff_ms);
+        (self.notify_handler)(Notification::SetDownloadRetryBackoff(backoff_ms));
+        Ok(())
+    }
+
+    pub(crate) fn set_download_max_rate(&mut self, bytes_per_sec: u64) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.download_max_rate = Some(bytes_per_sec);
+            Ok(())
+        })?;
+        self.rate_limiter = RateLimiter::new(bytes_per_sec);
+        (self.notify_handler)(Notification::SetDownloadMaxRate(bytes_per_sec));
+        Ok(())
+    }
+
+    pub(crate) fn set_download_cache_size(&mut self, size: u64) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.download_cache_size = Some(size);
+            Ok(())
+        })?;
+        self.download_cache_size = Some(size).filter(|size| *size > 0);
+        (self.notify_handler)(Notification::SetDownloadCacheSize(size));
+        Ok(())
+    }
+
+    pub(crate) fn set_download_stream_extract(&mut self, enabled: bool) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.download_stream_extract = Some(enabled);
+            Ok(())
+        })?;
+        self.download_stream_extract = enabled;
+        (self.notify_handler)(Notification::SetDownloadStreamExtract(enabled));
+        Ok(())
+    }
+
+    /// Sets the file of PEM certificates that downloads trust, or `None` to
+    /// go back to `CURL_CA_BUNDLE` or `SSL_CERT_FILE` if they are set. The
+    /// file is read now, so that a wrong path is reported straight away.
+    pub(crate) fn set_download_ca_bundle(&mut self, path: Option<&Path>) -> Result<()> {
+        let path = path.map(utils::to_absolute).transpose()?;
+        if let Some(path) = &path {
+            download::read_ca_bundle(path)?;
+        }
+        self.settings_file.with_mut(|s| {
+            s.download_ca_bundle = path.as_ref().map(|p| p.to_string_lossy().into_owned());
+            Ok(())
+        })?;
+        (self.notify_handler)(Notification::SetDownloadCaBundle(path.as_deref()));
+        self.download_ca_bundle = path;
+        Ok(())
+    }
+
     pub(crate) fn set_toolchain_override(&mut self, toolchain_override: &ResolvableToolchainName) {
         self.toolchain_override = Some(toolchain_override.to_owned());
     }
@@ -357,6 +641,31 @@ impl Cfg {
         })
     }
 
+    /// The components of the set called `name` in the `[component-sets]`
+    /// table of the settings.
+    pub(crate) fn get_component_set(&self, name: &str) -> Result<Vec<String>> {
+        self.settings_file
+            .with(|s| match s.component_sets.get(name) {
+                Some(components) => Ok(components.clone()),
+                None => Err(RustupError::ComponentSetNotFound {
+                    name: name.to_owned(),
+                    defined: s.component_sets.keys().cloned().collect(),
+                }
+                .into()),
+            })
+    }
+
+    /// The `post-install` command of the `[hooks]` table of the settings,
+    /// and whether it failing fails the install.
+    pub(crate) fn get_post_install_hook(&self) -> Result<Option<(String, bool)>> {
+        self.settings_file.with(|s| {
+            Ok(s.hooks_post_install
+                .clone()
+                .filter(|command| !command.trim().is_empty())
+                .map(|command| (command, s.hooks_post_install_fatal.unwrap_or(true))))
+        })
+    }
+
     pub(crate) fn get_self_update_mode(&self) -> Result<SelfUpdateMode> {
         self.settings_file.with(|s| {
             let mode = match &s.auto_self_update {
@@ -367,6 +676,30 @@ impl Cfg {
         })
     }
 
+    pub(crate) fn set_disable_self_update(&self, disabled: bool) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.disable_self_update = Some(disabled);
+            Ok(())
+        })?;
+        (self.notify_handler)(Notification::SetDisableSelfUpdate(disabled));
+        Ok(())
+    }
+
+    /// Whether self-update is disabled by policy, through the
+    /// `disable_self_update` setting or `RUSTUP_DISABLE_SELF_UPDATE`. The
+    /// variable can only disable it, so that it cannot lift the setting.
+    pub(crate) fn self_update_disabled(&self) -> Result<bool> {
+        if self
+            .process
+            .var("RUSTUP_DISABLE_SELF_UPDATE")
+            .is_ok_and(|v| !v.is_empty() && v != "0")
+        {
+            return Ok(true);
+        }
+        self.settings_file
+            .with(|s| Ok(s.disable_self_update.unwrap_or(false)))
+    }
+
     pub(crate) fn ensure_toolchains_dir(&self) -> Result<(), anyhow::Error> {
         utils::ensure_dir_exists("toolchains", &self.toolchains_dir, &|n| {
             (self.notify_handler)(n)
@@ -390,18 +723,27 @@ impl Cfg {
         Ok(self.update_hash_dir.join(toolchain.to_string()))
     }
 
-    pub(crate) fn which_binary(&self, path: &Path, binary: &str) -> Result<PathBuf> {
-        let (toolchain, _) = self.find_or_install_override_toolchain_or_default(path)?;
-        Ok(toolchain.binary_file(binary))
-    }
-
+    /// Runs every migration of the data in the rustup home that is still
+    /// pending, reporting each change it makes. Running it again does
+    /// nothing.
     #[cfg_attr(feature = "otel", tracing::instrument(skip_all))]
     pub(crate) fn upgrade_data(&self) -> Result<()> {
+        let mut changed = self.upgrade_metadata_version()?;
+        changed |= self.normalize_overrides()?;
+        changed |= self.remove_orphaned_update_hashes()?;
+        if !changed {
+            (self.notify_handler)(Notification::MetadataUpgradeNotNeeded(
+                DEFAULT_METADATA_VERSION,
+            ));
+        }
+        Ok(())
+    }
+
+    fn upgrade_metadata_version(&self) -> Result<bool> {
         let current_version = self.settings_file.with(|s| Ok(s.version.clone()))?;
 
         if current_version == DEFAULT_METADATA_VERSION {
-            (self.notify_handler)(Notification::MetadataUpgradeNotNeeded(&current_version));
-            return Ok(());
+            return Ok(false);
         }
 
         (self.notify_handler)(Notification::UpgradingMetadata(
@@ -429,13 +771,49 @@ impl Cfg {
 
                 self.settings_file.with_mut(|s| {
                     DEFAULT_METADATA_VERSION.clone_into(&mut s.version);
-                    Ok(())
+                    Ok(true)
                 })
             }
             _ => Err(RustupError::UnknownMetadataVersion(current_version).into()),
         }
     }
 
+    /// Rekeys the overrides under their canonical paths. The settings are
+    /// only written back if that changes anything.
+    fn normalize_overrides(&self) -> Result<bool> {
+        let notify = self.notify_handler.as_ref();
+        let pending = self
+            .settings_file
+            .with(|s| Ok(s.clone().normalize_overrides(&|_| {})))?;
+        if !pending {
+            return Ok(false);
+        }
+        self.settings_file
+            .with_mut(|s| Ok(s.normalize_overrides(notify)))
+    }
+
+    /// Removes the update hashes of the toolchains that were removed without
+    /// them, such as by deleting their directory by hand.
+    fn remove_orphaned_update_hashes(&self) -> Result<bool> {
+        if !utils::is_directory(&self.update_hash_dir) {
+            return Ok(false);
+        }
+        let mut changed = false;
+        for file in utils::read_dir("update hashes", &self.update_hash_dir)? {
+            let file = file.context("IO Error reading update hashes")?;
+            let name = file.file_name();
+            if self.toolchains_dir.join(&name).exists() {
+                continue;
+            }
+            utils::remove_file("update hash", &file.path())?;
+            (self.notify_handler)(Notification::RemovedOrphanedUpdateHash(
+                &name.to_string_lossy(),
+            ));
+            changed = true;
+        }
+        Ok(changed)
+    }
+
     pub(crate) fn find_default(&self) -> Result<Option<Toolchain<'_>>> {
         Ok(self
             .get_default()?
@@ -446,18 +824,46 @@ impl Cfg {
     pub(crate) fn find_override(
         &self,
         path: &Path,
-    ) -> Result<Option<(LocalToolchainName, OverrideReason)>> {
+    ) -> Result<Option<(LocalToolchainName, ActiveReason)>> {
         Ok(self
             .find_override_config(path)?
             .and_then(|(override_cfg, reason)| override_cfg.toolchain.map(|t| (t, reason))))
     }
 
-    fn find_override_config(&self, path: &Path) -> Result<Option<(OverrideCfg, OverrideReason)>> {
+    /// Resolve the toolchain that is active in `path` and the reason for it,
+    /// without installing anything.
+    ///
+    /// Fails with [`RustupError::ToolchainNotSelected`] if there is neither an
+    /// override nor a default toolchain.
+    pub(crate) fn active_toolchain(
+        &self,
+        path: &Path,
+    ) -> Result<(LocalToolchainName, ActiveReason)> {
+        let (toolchain, reason) = match self.find_override_config(path)? {
+            Some((override_cfg, reason)) => (override_cfg.toolchain, reason),
+            None => (None, ActiveReason::Default),
+        };
+        match toolchain {
+            Some(toolchain) => Ok((toolchain, reason)),
+            None => match self.get_default()? {
+                Some(toolchain) => Ok(((&toolchain).into(), reason)),
+                None => Err(RustupError::ToolchainNotSelected.into()),
+            },
+        }
+    }
+
+    /// The components of the toolchain that is active in `path`.
+    pub(crate) fn list_components(&self, path: &Path) -> Result<Vec<ComponentStatus>> {
+        let (toolchain, _) = self.active_toolchain(path)?;
+        Toolchain::new(self, toolchain)?.component_statuses()
+    }
+
+    fn find_override_config(&self, path: &Path) -> Result<Option<(OverrideCfg, ActiveReason)>> {
         let mut override_ = None;
 
         // First check toolchain override from command
         if let Some(ref name) = self.toolchain_override {
-            override_ = Some((name.to_string().into(), OverrideReason::CommandLine));
+            override_ = Some((name.to_string().into(), ActiveReason::CommandLine));
         }
 
         // Check RUSTUP_TOOLCHAIN
@@ -466,7 +872,7 @@ impl Cfg {
             // custom, distributable, and absolute path toolchains otherwise
             // rustup's export of a RUSTUP_TOOLCHAIN when running a process will
             // error when a nested rustup invocation occurs
-            override_ = Some((name.to_string().into(), OverrideReason::Environment));
+            override_ = Some((name.to_string().into(), ActiveReason::Environment));
         }
 
         // Then walk up the directory tree from 'path' looking for either the
@@ -485,19 +891,22 @@ impl Cfg {
             // on a line after the proximate error.
 
             let reason_err = match reason {
-                OverrideReason::Environment => {
+                ActiveReason::Default => {
+                    "the default toolchain does not describe an installed toolchain".to_string()
+                }
+                ActiveReason::Environment => {
                     "the RUSTUP_TOOLCHAIN environment variable specifies an uninstalled toolchain"
                         .to_string()
                 }
-                OverrideReason::CommandLine => {
+                ActiveReason::CommandLine => {
                     "the +toolchain on the command line specifies an uninstalled toolchain"
                         .to_string()
                 }
-                OverrideReason::OverrideDB(ref path) => format!(
+                ActiveReason::OverrideDB(ref path) => format!(
                     "the directory override for '{}' specifies an uninstalled toolchain",
                     utils::canonicalize_path(path, self.notify_handler.as_ref()).display(),
                 ),
-                OverrideReason::ToolchainFile(ref path) => format!(
+                ActiveReason::ToolchainFile(ref path) => format!(
                     "the toolchain file at '{}' specifies an uninstalled toolchain",
                     utils::canonicalize_path(path, self.notify_handler.as_ref()).display(),
                 ),
@@ -534,14 +943,14 @@ impl Cfg {
         &self,
         dir: &Path,
         settings: &Settings,
-    ) -> Result<Option<(OverrideFile, OverrideReason)>> {
+    ) -> Result<Option<(OverrideFile, ActiveReason)>> {
         let notify = self.notify_handler.as_ref();
         let mut dir = Some(dir);
 
         while let Some(d) = dir {
             // First check the override database
             if let Some(name) = settings.dir_override(d, notify) {
-                let reason = OverrideReason::OverrideDB(d.to_owned());
+                let reason = ActiveReason::OverrideDB(d.to_owned());
                 return Ok(Some((name.into(), reason)));
             }
 
@@ -581,7 +990,7 @@ impl Cfg {
                     .with_context(add_file_context)?;
                 if let Some(toolchain_name_str) = &override_file.toolchain.channel {
                     let toolchain_name = ResolvableToolchainName::try_from(toolchain_name_str)?;
-                    let default_host_triple = get_default_host_triple(settings);
+                    let default_host_triple = get_default_host_triple(settings, &self.process);
                     // Do not permit architecture/os selection in channels as
                     // these are host specific and toolchain files are portable.
                     if let ResolvableToolchainName::Official(ref name) = toolchain_name {
@@ -614,7 +1023,7 @@ impl Cfg {
                     }
                 }
 
-                let reason = OverrideReason::ToolchainFile(toolchain_file);
+                let reason = ActiveReason::ToolchainFile(toolchain_file);
                 return Ok(Some((override_file, reason)));
             }
 
@@ -646,10 +1055,18 @@ impl Cfg {
                     .context(OverrideFileConfigError::Parsing)?;
 
                 if override_file.is_empty() {
-                    Err(anyhow!(OverrideFileConfigError::Invalid))
-                } else {
-                    Ok(override_file)
+                    return Err(anyhow!(OverrideFileConfigError::Invalid));
+                }
+                if let Some(name) = override_file
+                    .env
+                    .keys()
+                    .find(|name| env_var::RUSTUP_MANAGED_VARS.contains(&name.as_str()))
+                {
+                    return Err(anyhow!(OverrideFileConfigError::ReservedEnvVar(
+                        name.clone()
+                    )));
                 }
+                Ok(override_file)
             }
         }
     }
@@ -658,8 +1075,24 @@ impl Cfg {
     pub(crate) fn find_or_install_override_toolchain_or_default(
         &self,
         path: &Path,
-    ) -> Result<(Toolchain<'_>, Option<OverrideReason>)> {
-        let (toolchain, components, targets, reason, profile) =
+    ) -> Result<(Toolchain<'_>, ActiveReason)> {
+        let (toolchain, reason, _, _) = self.find_or_install_override_toolchain_with_env(path)?;
+        Ok((toolchain, reason))
+    }
+
+    /// As [`Cfg::find_or_install_override_toolchain_or_default`], but also
+    /// returns the components and the `[env]` section of the toolchain file
+    /// in effect, if any.
+    fn find_or_install_override_toolchain_with_env(
+        &self,
+        path: &Path,
+    ) -> Result<(
+        Toolchain<'_>,
+        ActiveReason,
+        Vec<String>,
+        BTreeMap<String, String>,
+    )> {
+        let (toolchain, components, targets, reason, profile, env) =
             match self.find_override_config(path)? {
                 Some((
                     OverrideCfg {
@@ -667,10 +1100,18 @@ impl Cfg {
                         components,
                         targets,
                         profile,
+                        env,
                     },
                     reason,
-                )) => (toolchain, components, targets, Some(reason), profile),
-                None => (None, vec![], vec![], None, None),
+                )) => (toolchain, components, targets, reason, profile, env),
+                None => (
+                    None,
+                    vec![],
+                    vec![],
+                    ActiveReason::Default,
+                    None,
+                    BTreeMap::new(),
+                ),
             };
         let toolchain = match toolchain {
             t @ Some(_) => t,
@@ -681,27 +1122,27 @@ impl Cfg {
             None => Err(RustupError::ToolchainNotSelected.into()),
             Some(toolchain @ LocalToolchainName::Named(ToolchainName::Custom(_)))
             | Some(toolchain @ LocalToolchainName::Path(_)) => {
-                Ok((Toolchain::new(self, toolchain)?, reason))
+                Ok((Toolchain::new(self, toolchain)?, reason, components, env))
             }
             Some(LocalToolchainName::Named(ToolchainName::Official(desc))) => {
-                let components: Vec<_> = components.iter().map(AsRef::as_ref).collect();
+                let required: Vec<_> = components.iter().map(AsRef::as_ref).collect();
                 let targets: Vec<_> = targets.iter().map(AsRef::as_ref).collect();
                 let toolchain = match DistributableToolchain::new(self, desc.clone()) {
                     Err(RustupError::ToolchainNotInstalled(_)) => {
                         DistributableToolchain::install(
                             self,
                             &desc,
-                            &components,
+                            &required,
                             &targets,
-                            profile.unwrap_or(Profile::Default),
+                            Some(profile.unwrap_or(Profile::Default)),
                             false,
                         )?
                         .1
                     }
                     Ok(mut distributable) => {
-                        if !distributable.components_exist(&components, &targets)? {
+                        if !distributable.components_exist(&required, &targets)? {
                             distributable.update(
-                                &components,
+                                &required,
                                 &targets,
                                 profile.unwrap_or(Profile::Default),
                             )?;
@@ -711,7 +1152,7 @@ impl Cfg {
                     Err(e) => return Err(e.into()),
                 }
                 .into();
-                Ok((toolchain, reason))
+                Ok((toolchain, reason, components, env))
             }
         }
     }
@@ -736,6 +1177,14 @@ impl Cfg {
             .transpose()
     }
 
+    /// Whether `name` is installed. Only the toolchains directory is looked
+    /// at, never the dist server, so this is cheap and works offline; a
+    /// custom toolchain counts as installed if its link still leads to a
+    /// directory.
+    pub fn toolchain_installed(&self, name: &ToolchainName) -> Result<bool> {
+        Ok(Toolchain::exists(self, &name.into())?)
+    }
+
     /// List all the installed toolchains: that is paths in the toolchain dir
     /// that are:
     /// - not files
@@ -781,6 +1230,81 @@ impl Cfg {
             .collect::<Result<Vec<_>>>()
     }
 
+    /// List the toolchains that are still referenced from somewhere: the
+    /// default toolchain, the directory override database, and any
+    /// `rust-toolchain`/`rust-toolchain.toml` file found beneath `roots`.
+    pub(crate) fn referenced_toolchains(&self, roots: &[PathBuf]) -> Result<Vec<ToolchainName>> {
+        let default_host_triple = self.get_default_host_triple()?;
+        let mut names = Vec::new();
+
+        if let Some(default) = self.get_default()? {
+            names.push(default);
+        }
+
+        let overrides = self.settings_file.with(|s| Ok(s.overrides.clone()))?;
+        for name in overrides.into_values() {
+            // Path based overrides never refer to a toolchain in the toolchains
+            // dir, and unparseable entries are reported by `override list`.
+            if let Ok(name) = ResolvableToolchainName::try_from(&name) {
+                names.push(name.resolve(&default_host_triple)?);
+            }
+        }
+
+        // Anything beneath the roots that cannot be read may refer to a
+        // toolchain, so it fails the search, but only once everything else
+        // has been looked at, so that all of it is reported at once
+        let notify = self.notify_handler.as_ref();
+        let mut toolchain_files = Vec::new();
+        let mut unreadable = 0;
+        for root in roots {
+            find_toolchain_files(root, &mut toolchain_files, &mut unreadable, notify)?;
+        }
+        for (path, parse_mode) in toolchain_files {
+            let channel = utils::read_file("toolchain file", &path)
+                .and_then(|contents| Cfg::parse_override_file(contents, parse_mode))
+                .and_then(|file| {
+                    file.toolchain
+                        .channel
+                        .map(|channel| {
+                            ResolvableToolchainName::try_from(channel)?
+                                .resolve(&default_host_triple)
+                        })
+                        .transpose()
+                });
+            match channel {
+                Ok(Some(name)) => names.push(name),
+                Ok(None) => {}
+                Err(e) => {
+                    notify(Notification::UnreadableToolchainFile(&path, &e));
+                    unreadable += 1;
+                }
+            }
+        }
+        if unreadable > 0 {
+            return Err(RustupError::UnreadableToolchainFiles(unreadable).into());
+        }
+
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// The directories whose override in the settings is `toolchain`.
+    pub(crate) fn overrides_of(&self, toolchain: &ToolchainName) -> Result<Vec<String>> {
+        let default_host_triple = self.get_default_host_triple()?;
+        let overrides = self.settings_file.with(|s| Ok(s.overrides.clone()))?;
+        Ok(overrides
+            .into_iter()
+            .filter(|(_, name)| {
+                ResolvableToolchainName::try_from(name)
+                    .ok()
+                    .and_then(|name| name.resolve(&default_host_triple).ok())
+                    .is_some_and(|name| name == *toolchain)
+            })
+            .map(|(path, _)| path)
+            .collect())
+    }
+
     /// Create an override for a toolchain
     pub(crate) fn make_override(&self, path: &Path, toolchain: &ToolchainName) -> Result<()> {
         self.settings_file.with_mut(|s| {
@@ -789,17 +1313,46 @@ impl Cfg {
         })
     }
 
+    /// Returns whether `toolchain` must be left as it is because an override
+    /// froze it, warning about it if so. Only `force_update` lifts that.
+    pub(crate) fn skip_frozen_toolchain(
+        &self,
+        toolchain: &ToolchainDesc,
+        force_update: bool,
+    ) -> Result<bool> {
+        if force_update {
+            return Ok(false);
+        }
+        self.settings_file.with(|s| {
+            let Some((path, date)) = s.frozen_override(&toolchain.to_string()) else {
+                return Ok(false);
+            };
+            (self.notify_handler)(Notification::SkippingFrozenToolchain(
+                toolchain,
+                date,
+                Path::new(path),
+            ));
+            Ok(true)
+        })
+    }
+
     pub(crate) fn update_all_channels(
         &self,
         force_update: bool,
+        allow_downgrade: bool,
     ) -> Result<Vec<(ToolchainDesc, Result<UpdateStatus>)>> {
-        let channels = self.list_channels()?;
+        let mut channels = Vec::new();
+        for (desc, distributable) in self.list_channels()? {
+            if !self.skip_frozen_toolchain(&desc, force_update)? {
+                channels.push((desc, distributable));
+            }
+        }
         let channels = channels.into_iter();
         let profile = self.get_profile()?;
 
         // Update toolchains and collect the results
         let channels = channels.map(|(desc, mut distributable)| {
-            let st = distributable.update_extra(&[], &[], profile, force_update, false);
+            let st = distributable.update_extra(&[], &[], profile, force_update, allow_downgrade);
 
             if let Err(ref e) = st {
                 (self.notify_handler)(Notification::NonFatalError(e));
@@ -827,8 +1380,14 @@ impl Cfg {
     }
 
     pub(crate) fn create_command_for_dir(&self, path: &Path, binary: &str) -> Result<Command> {
-        let (toolchain, _) = self.find_or_install_override_toolchain_or_default(path)?;
-        self.create_command_for_toolchain_(toolchain, binary)
+        let (toolchain, reason, components, env) =
+            self.find_or_install_override_toolchain_with_env(path)?;
+        if let ActiveReason::ToolchainFile(file) = &reason {
+            check_required_component(&toolchain, &components, file, binary)?;
+        }
+        let mut cmd = self.create_command_for_toolchain_(toolchain, binary)?;
+        cmd.envs(env);
+        Ok(cmd)
     }
 
     pub(crate) fn create_command_for_toolchain(
@@ -847,7 +1406,7 @@ impl Cfg {
                                 desc,
                                 &[],
                                 &[],
-                                self.get_profile()?,
+                                Some(self.get_profile()?),
                                 true,
                             )?;
                         }
@@ -876,6 +1435,12 @@ impl Cfg {
         toolchain: Toolchain<'_>,
         binary: &str,
     ) -> Result<Command> {
+        if let Some(path) = self.tool_override(&toolchain, binary)? {
+            let mut cmd = Command::new(path);
+            toolchain.set_env(&mut cmd);
+            return Ok(cmd);
+        }
+
         // Should push the cargo fallback into a custom toolchain type? And then
         // perhaps a trait that create command layers on?
         if !matches!(
@@ -890,6 +1455,38 @@ impl Cfg {
         toolchain.create_command(binary)
     }
 
+    /// The binary that the `[tool-overrides]` of the settings run in place of
+    /// `binary` for `toolchain`, whose key is either the toolchain's name or
+    /// a name resolving to it, such as `stable`. A binary that does not exist
+    /// is an error, rather than quietly running the toolchain's own.
+    fn tool_override(&self, toolchain: &Toolchain<'_>, binary: &str) -> Result<Option<PathBuf>> {
+        let tool = binary.strip_suffix(EXE_SUFFIX).unwrap_or(binary);
+        let name = toolchain.name().to_string();
+        let tool_overrides = self.settings_file.with(|s| Ok(s.tool_overrides.clone()))?;
+        if tool_overrides.is_empty() {
+            return Ok(None);
+        }
+        let host = self.get_default_host_triple()?;
+        let path = tool_overrides.iter().find_map(|(key, overrides)| {
+            let matches = *key == name
+                || ResolvableToolchainName::try_from(&**key)
+                    .ok()
+                    .and_then(|key| key.resolve(&host).ok())
+                    .is_some_and(|key| key.to_string() == name);
+            matches.then(|| overrides.get(tool)).flatten()
+        });
+        let path = path.map(PathBuf::from);
+        match path {
+            Some(path) if !utils::is_file(&path) => Err(RustupError::ToolOverrideNotFound {
+                tool: tool.to_owned(),
+                toolchain: name,
+                path,
+            }
+            .into()),
+            path => Ok(path),
+        }
+    }
+
     // Custom toolchains don't have cargo, so here we detect that situation and
     // try to find a different cargo.
     fn maybe_do_cargo_fallback(
@@ -929,12 +1526,38 @@ impl Cfg {
         Ok(None)
     }
 
-    pub(crate) fn set_default_host_triple(&self, host_triple: &str) -> Result<()> {
+    /// Sets the default host, which must be one of `known_hosts` if they
+    /// are given.
+    pub(crate) fn set_default_host_triple(
+        &self,
+        host_triple: &str,
+        known_hosts: Option<&[dist::TargetTriple]>,
+    ) -> Result<()> {
         // Ensure that the provided host_triple is capable of resolving
         // against the 'stable' toolchain.  This provides early errors
         // if the supplied triple is insufficient / bad.
         dist::PartialToolchainDesc::from_str("stable")?
             .resolve(&dist::TargetTriple::new(host_triple))?;
+        if let Some(known_hosts) = known_hosts {
+            if !known_hosts.iter().any(|h| **h == *host_triple) {
+                use strsim::damerau_levenshtein;
+
+                // Suggest only for very small differences, as for components
+                const MAX_DISTANCE: usize = 3;
+
+                let suggestion = known_hosts
+                    .iter()
+                    .map(|h| (damerau_levenshtein(h, host_triple), h))
+                    .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+                    .min()
+                    .map(|(_, h)| h.to_string());
+                return Err(RustupError::UnknownHostTriple {
+                    triple: host_triple.to_owned(),
+                    suggestion,
+                }
+                .into());
+            }
+        }
         self.settings_file.with_mut(|s| {
             s.default_host_triple = Some(host_triple.to_owned());
             Ok(())
@@ -943,7 +1566,8 @@ impl Cfg {
 
     #[cfg_attr(feature = "otel", tracing::instrument(skip_all))]
     pub(crate) fn get_default_host_triple(&self) -> Result<dist::TargetTriple> {
-        self.settings_file.with(|s| Ok(get_default_host_triple(s)))
+        self.settings_file
+            .with(|s| Ok(get_default_host_triple(s, &self.process)))
     }
 
     /// The path on disk of any concrete toolchain
@@ -969,6 +1593,19 @@ impl Debug for Cfg {
             toolchain_override,
             env_override,
             dist_root_url,
+            download_concurrency,
+            download_retries,
+            download_retry_backoff,
+            local_dist_dir,
+            signature_keys,
+            allow_unsigned_local,
+            rate_limiter,
+            download_ca_bundle,
+            download_cache_size,
+            download_stream_extract,
+            offline,
+            cargo_home,
+            process,
             notify_handler: _,
         } = self;
 
@@ -984,15 +1621,153 @@ impl Debug for Cfg {
             .field("toolchain_override", toolchain_override)
             .field("env_override", env_override)
             .field("dist_root_url", dist_root_url)
+            .field("download_concurrency", download_concurrency)
+            .field("download_retries", download_retries)
+            .field("download_retry_backoff", download_retry_backoff)
+            .field("local_dist_dir", local_dist_dir)
+            .field("signature_keys", signature_keys)
+            .field("allow_unsigned_local", allow_unsigned_local)
+            .field("rate_limiter", rate_limiter)
+            .field("download_ca_bundle", download_ca_bundle)
+            .field("download_cache_size", download_cache_size)
+            .field("download_stream_extract", download_stream_extract)
+            .field("offline", offline)
+            .field("cargo_home", cargo_home)
+            .field("process", process)
             .finish()
     }
 }
 
-fn get_default_host_triple(s: &Settings) -> dist::TargetTriple {
+/// The keys that channel manifests may be signed with: rustup's own, and those
+/// in the key files that `RUSTUP_PGP_KEY` and the `pgp_keys` setting name.
+fn signature_keys(pgp_keys: Option<&str>, process: &Process) -> Result<Vec<PublicKey>> {
+    let env_key = process.var_os("RUSTUP_PGP_KEY").filter(|p| !p.is_empty());
+    let mut extra = Vec::new();
+    for path in env_key.iter().map(Path::new).chain(pgp_keys.map(Path::new)) {
+        let key = utils::read_file("pgp key", path)?;
+        extra.push(key);
+    }
+    signature::trusted_keys(&extra).context("could not load the keys to verify signatures with")
+}
+
+/// Moves the settings file of a `~/.rustup` home to where the XDG layout keeps
+/// it, unless there is one there already, in which case the old one is ignored.
+fn migrate_legacy_settings(
+    legacy: &Path,
+    settings_path: &Path,
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> Result<()> {
+    if !utils::is_file(legacy) {
+        return Ok(());
+    }
+    if utils::is_file(settings_path) {
+        notify_handler(Notification::IgnoringLegacySettings(legacy, settings_path));
+    } else {
+        utils::rename_file("settings", legacy, settings_path, notify_handler)?;
+        notify_handler(Notification::MovedLegacySettings(legacy, settings_path));
+    }
+    Ok(())
+}
+
+/// Resolve the toolchain that is active in `path` and the reason for it, as
+/// `rustup show active-toolchain` would, but without installing anything.
+/// Fails with [`ActiveToolchainError::NotSelected`] if there is neither an
+/// override nor a default toolchain.
+///
+/// This reads `RUSTUP_HOME`, `RUSTUP_TOOLCHAIN` and friends from the current
+/// process, so it must be called within [`currentprocess::with`].
+///
+/// [`currentprocess::with`]: crate::currentprocess::with
+pub fn active_toolchain(
+    path: &Path,
+) -> std::result::Result<(LocalToolchainName, ActiveReason), ActiveToolchainError> {
+    let cfg = Cfg::from_env(Arc::new(|_| {}))?;
+    Ok(cfg.active_toolchain(path)?)
+}
+
+/// List the components of the toolchain that is active in `path`, as `rustup
+/// component list` would.
+///
+/// Like [`active_toolchain`], this must be called within
+/// [`currentprocess::with`].
+///
+/// [`currentprocess::with`]: crate::currentprocess::with
+pub fn list_components(path: &Path) -> Result<Vec<ComponentStatus>> {
+    Cfg::from_env(Arc::new(|_| {}))?.list_components(path)
+}
+
+/// Install the toolchain `name`, such as `stable` or `nightly-2024-01-01`, with
+/// the configured profile, or update it if it is already installed.
+///
+/// Progress is reported to `notifier` instead of the terminal. Like
+/// [`active_toolchain`], this must be called within [`currentprocess::with`].
+///
+/// [`currentprocess::with`]: crate::currentprocess::with
+pub fn install_toolchain(
+    name: &str,
+    notifier: Arc<dyn Notifier>,
+) -> std::result::Result<(), InstallError> {
+    install_toolchain_(name, notifier).map_err(InstallError::from)
+}
+
+fn install_toolchain_(name: &str, notifier: Arc<dyn Notifier>) -> Result<()> {
+    let cfg = Cfg::from_env(Arc::new(move |n: Notification<'_>| notifier.handle(n)))?;
+    let desc = name
+        .parse::<PartialToolchainDesc>()?
+        .resolve(&cfg.get_default_host_triple()?)?;
+    let profile = cfg.get_profile()?;
+    match DistributableToolchain::new(&cfg, desc.clone()) {
+        Err(RustupError::ToolchainNotInstalled(_)) => {
+            DistributableToolchain::install(&cfg, &desc, &[], &[], Some(profile), false)?;
+        }
+        Ok(mut distributable) => {
+            distributable.update(&[], &[], profile)?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+/// Recursively collect the toolchain files beneath `dir`, without following
+/// symlinked directories. Only `dir` itself has to be readable: entries
+/// beneath it that are not are warned about and counted in `unreadable`.
+fn find_toolchain_files(
+    dir: &Path,
+    found: &mut Vec<(PathBuf, ParseMode)>,
+    unreadable: &mut usize,
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> Result<()> {
+    for entry in utils::read_dir("toolchain file root", dir)? {
+        let entry = match entry.and_then(|entry| Ok((entry.file_type()?, entry))) {
+            Ok(entry) => entry,
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                notify_handler(Notification::UnreadableToolchainFile(dir, &e));
+                *unreadable += 1;
+                continue;
+            }
+        };
+        let (file_type, entry) = entry;
+        let path = entry.path();
+        if file_type.is_dir() {
+            if let Err(e) = find_toolchain_files(&path, found, unreadable, notify_handler) {
+                notify_handler(Notification::UnreadableToolchainFile(&path, &e));
+                *unreadable += 1;
+            }
+        } else if entry.file_name() == "rust-toolchain" {
+            found.push((path, ParseMode::Both));
+        } else if entry.file_name() == "rust-toolchain.toml" {
+            found.push((path, ParseMode::OnlyToml));
+        }
+    }
+    Ok(())
+}
+
+fn get_default_host_triple(s: &Settings, process: &Process) -> dist::TargetTriple {
     s.default_host_triple
         .as_ref()
         .map(|s| dist::TargetTriple::new(s))
-        .unwrap_or_else(dist::TargetTriple::from_host_or_build)
+        .unwrap_or_else(|| dist::TargetTriple::from_host_or_build_in(process))
 }
 
 /// Specifies how a `rust-toolchain`/`rust-toolchain.toml` configuration file should be parsed.
@@ -1009,11 +1784,168 @@ enum ParseMode {
     Both,
 }
 
+/// Fails with an explanation of what to do about it if `binary` comes from
+/// one of the `components` that the toolchain file at `file` requires, but
+/// that the toolchain does not have.
+fn check_required_component(
+    toolchain: &Toolchain<'_>,
+    components: &[String],
+    file: &Path,
+    binary: &str,
+) -> Result<()> {
+    // Other settings than the toolchain have no effect alongside a `path`
+    if matches!(toolchain.name(), LocalToolchainName::Path(_)) {
+        return Ok(());
+    }
+    let Some(component) = crate::component_for_bin(binary) else {
+        return Ok(());
+    };
+    let required = components
+        .iter()
+        .any(|c| c == component || c.strip_suffix("-preview") == Some(component));
+    if !required || toolchain.binary_file(binary).exists() {
+        return Ok(());
+    }
+
+    let mut available = false;
+    match DistributableToolchain::try_from(toolchain) {
+        Ok(distributable) => {
+            let target = distributable.desc().target.to_string();
+            let status = distributable
+                .component_statuses()?
+                .into_iter()
+                .find(|s| s.name == component && s.target.as_ref().map_or(true, |t| *t == target));
+            match status {
+                // Whatever the binary is missing for, it isn't the component
+                Some(status) if status.installed => return Ok(()),
+                Some(status) => available = status.available,
+                None => {}
+            }
+        }
+        // Custom toolchains are given a cargo from elsewhere when they
+        // have none of their own
+        Err(_) if component == "cargo" => return Ok(()),
+        Err(_) => {}
+    }
+
+    let component = component.to_owned();
+    let file = file.to_owned();
+    let toolchain = toolchain.name().to_string();
+    Err(if available {
+        RustupError::RequiredComponentMissing {
+            component,
+            file,
+            toolchain,
+        }
+    } else {
+        RustupError::RequiredComponentUnavailable {
+            component,
+            file,
+            toolchain,
+        }
+    }
+    .into())
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use rustup_macros::unit_test as test;
 
     use super::*;
+    use crate::currentprocess::{self, TestProcess};
+
+    fn with_rustup_home<R>(vars: &[(&str, &str)], f: impl FnOnce(&Path) -> R) -> R {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        let mut vars: HashMap<_, _> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        vars.insert(
+            "RUSTUP_HOME".to_string(),
+            home.path().to_string_lossy().into_owned(),
+        );
+        let tp = TestProcess::new(project.path(), &["rustup"], vars, "");
+        currentprocess::with(tp.into(), || f(project.path()))
+    }
+
+    #[test]
+    fn open_reads_the_given_process() {
+        let rustup_home = tempfile::tempdir().unwrap();
+        let cargo_home = tempfile::tempdir().unwrap();
+        let vars = [
+            ("RUSTUP_DIST_SERVER", "https://mirror.example.com"),
+            ("RUSTUP_OVERRIDE_HOST_TRIPLE", "aarch64-unknown-linux-gnu"),
+            ("RUSTUP_OFFLINE", "1"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let tp = TestProcess::new(rustup_home.path(), &["rustup"], vars, "");
+
+        // The current process, which only gets the log output, has none of
+        // the variables set
+        currentprocess::with(TestProcess::default().into(), || {
+            let mut cfg = Cfg::open(
+                rustup_home.path().to_owned(),
+                Some(cargo_home.path().to_owned()),
+                tp.into(),
+                Arc::new(|_| {}),
+            )
+            .unwrap();
+            assert_eq!(cfg.rustup_dir, rustup_home.path());
+            assert_eq!(cfg.cargo_home.as_deref(), Some(cargo_home.path()));
+            assert_eq!(cfg.dist_root_url, "https://mirror.example.com/dist");
+            assert_eq!(
+                cfg.get_default_host_triple().unwrap().to_string(),
+                "aarch64-unknown-linux-gnu"
+            );
+            assert!(cfg.offline);
+
+            cfg.set_profile("minimal").unwrap();
+            assert!(rustup_home.path().join("settings.toml").is_file());
+        });
+    }
+
+    #[test]
+    fn active_toolchain_from_toolchain_file() {
+        with_rustup_home(&[], |project| {
+            let toolchain_file = project.join("rust-toolchain.toml");
+            std::fs::write(
+                &toolchain_file,
+                "[toolchain]\nchannel = \"nightly-2020-07-10\"\n",
+            )
+            .unwrap();
+
+            let (name, reason) = active_toolchain(project).unwrap();
+            assert!(
+                name.to_string().starts_with("nightly-2020-07-10-"),
+                "{name}"
+            );
+            assert_eq!(reason, ActiveReason::ToolchainFile(toolchain_file));
+        });
+    }
+
+    #[test]
+    fn active_toolchain_from_environment() {
+        with_rustup_home(&[("RUSTUP_TOOLCHAIN", "stable")], |project| {
+            let (name, reason) = active_toolchain(project).unwrap();
+            assert!(name.to_string().starts_with("stable-"), "{name}");
+            assert_eq!(reason, ActiveReason::Environment);
+        });
+    }
+
+    #[test]
+    fn active_toolchain_not_selected() {
+        with_rustup_home(&[], |project| {
+            assert!(matches!(
+                active_toolchain(project),
+                Err(ActiveToolchainError::NotSelected(_))
+            ));
+        });
+    }
 
     #[test]
     fn parse_legacy_toolchain_file() {
@@ -1029,7 +1961,8 @@ mod tests {
                     components: None,
                     targets: None,
                     profile: None,
-                }
+                },
+                env: BTreeMap::new(),
             }
         );
     }
@@ -1056,7 +1989,8 @@ profile = "default"
                         "thumbv2-none-eabi".into()
                     ]),
                     profile: Some("default".into()),
-                }
+                },
+                env: BTreeMap::new(),
             }
         );
     }
@@ -1077,7 +2011,8 @@ channel = "nightly-2020-07-10"
                     components: None,
                     targets: None,
                     profile: None,
-                }
+                },
+                env: BTreeMap::new(),
             }
         );
     }
@@ -1098,7 +2033,8 @@ path = "foobar"
                     components: None,
                     targets: None,
                     profile: None,
-                }
+                },
+                env: BTreeMap::new(),
             }
         );
     }
@@ -1120,7 +2056,8 @@ components = []
                     components: Some(vec![]),
                     targets: None,
                     profile: None,
-                }
+                },
+                env: BTreeMap::new(),
             }
         );
     }
@@ -1142,7 +2079,8 @@ targets = []
                     components: None,
                     targets: Some(vec![]),
                     profile: None,
-                }
+                },
+                env: BTreeMap::new(),
             }
         );
     }
@@ -1163,11 +2101,57 @@ components = [ "rustfmt" ]
                     components: Some(vec!["rustfmt".into()]),
                     targets: None,
                     profile: None,
-                }
+                },
+                env: BTreeMap::new(),
             }
         );
     }
 
+    #[test]
+    fn parse_toml_toolchain_file_env() {
+        let contents = r#"[toolchain]
+channel = "nightly-2020-07-10"
+
+[env]
+RUSTFLAGS = "-C target-cpu=native"
+MY_PROJECT_SETTING = "1"
+"#;
+
+        let result = Cfg::parse_override_file(contents, ParseMode::Both);
+        assert_eq!(
+            result.unwrap(),
+            OverrideFile {
+                toolchain: ToolchainSection {
+                    channel: Some("nightly-2020-07-10".into()),
+                    path: None,
+                    components: None,
+                    targets: None,
+                    profile: None,
+                },
+                env: BTreeMap::from([
+                    ("MY_PROJECT_SETTING".into(), "1".into()),
+                    ("RUSTFLAGS".into(), "-C target-cpu=native".into()),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_toml_toolchain_file_reserved_env() {
+        let contents = r#"[toolchain]
+channel = "nightly-2020-07-10"
+
+[env]
+RUSTUP_TOOLCHAIN = "stable"
+"#;
+
+        let result = Cfg::parse_override_file(contents, ParseMode::Both);
+        assert!(matches!(
+            result.unwrap_err().downcast::<OverrideFileConfigError>(),
+            Ok(OverrideFileConfigError::ReservedEnvVar(name)) if name == "RUSTUP_TOOLCHAIN"
+        ));
+    }
+
     #[test]
     fn parse_empty_toml_toolchain_file() {
         let contents = r#"
@@ -259,8 +259,7 @@ impl<'a> Executor for Threaded<'a> {
         // the units to files, show our progress, and set our units back
         // afterwards. The largest archives today - rust docs - have ~20k
         // items, and the download tracker's progress is confounded with
-        // actual handling of data today, we synthesis a data buffer and
-        // pretend to have bytes to deliver.
+        // actual handling of data today, we pretend to have bytes to deliver.
         let mut prev_files = self.n_files.load(Ordering::Relaxed);
         if let Some(handler) = self.notify_handler {
             handler(Notification::DownloadFinished);
@@ -272,7 +271,6 @@ impl<'a> Executor for Threaded<'a> {
         if prev_files > 50 {
             eprintln!("{prev_files} deferred IO operations");
         }
-        let buf: Vec<u8> = vec![0; prev_files];
         // Cheap wrap-around correctness check - we have 20k files, more than
         // 32K means we subtracted from 0 somewhere.
         assert!(32767 > prev_files);
@@ -284,7 +282,7 @@ impl<'a> Executor for Threaded<'a> {
             current_files = self.n_files.load(Ordering::Relaxed);
             let step_count = prev_files - current_files;
             if let Some(handler) = self.notify_handler {
-                handler(Notification::DownloadDataReceived(&buf[0..step_count]));
+                handler(Notification::DownloadDataReceived(step_count));
             }
         }
         self.pool.join();
@@ -2,11 +2,13 @@
 //! `Components` and `DirectoryPackage` are the two sides of the
 //! installation / uninstallation process.
 
-use std::io::BufWriter;
+use std::fs;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 
+use crate::dist::component::integrity;
 use crate::dist::component::package::{INSTALLER_VERSION, VERSION_FILE};
 use crate::dist::component::transaction::Transaction;
 use crate::dist::prefix::InstallPrefix;
@@ -18,11 +20,17 @@ const COMPONENTS_FILE: &str = "components";
 #[derive(Clone, Debug)]
 pub struct Components {
     prefix: InstallPrefix,
+    /// Whether components added replace files that are in their way, rather
+    /// than failing with a conflict.
+    overwrite: bool,
 }
 
 impl Components {
     pub fn open(prefix: InstallPrefix) -> Result<Self> {
-        let c = Self { prefix };
+        let c = Self {
+            prefix,
+            overwrite: false,
+        };
 
         // Validate that the metadata uses a format we know
         if let Some(v) = c.read_version()? {
@@ -89,6 +97,15 @@ impl Components {
     pub(crate) fn prefix(&self) -> InstallPrefix {
         self.prefix.clone()
     }
+    /// These components, but adding to them replaces the files that are in
+    /// the way. This is for reinstalling a component whose record of its
+    /// files has been lost, so the old files cannot be removed first.
+    pub(crate) fn overwriting(&self) -> Self {
+        Self {
+            overwrite: true,
+            ..self.clone()
+        }
+    }
 }
 
 pub(crate) struct ComponentBuilder<'a> {
@@ -102,33 +119,63 @@ impl<'a> ComponentBuilder<'a> {
     pub(crate) fn copy_file(&mut self, path: PathBuf, src: &Path) -> Result<()> {
         self.parts
             .push(ComponentPart("file".to_owned(), path.clone()));
+        self.make_room(&path)?;
         self.tx.copy_file(&self.name, path, src)
     }
     pub(crate) fn copy_dir(&mut self, path: PathBuf, src: &Path) -> Result<()> {
         self.parts
             .push(ComponentPart("dir".to_owned(), path.clone()));
+        self.make_room(&path)?;
         self.tx.copy_dir(&self.name, path, src)
     }
     pub(crate) fn move_file(&mut self, path: PathBuf, src: &Path) -> Result<()> {
         self.parts
             .push(ComponentPart("file".to_owned(), path.clone()));
+        self.make_room(&path)?;
         self.tx.move_file(&self.name, path, src)
     }
     pub(crate) fn move_dir(&mut self, path: PathBuf, src: &Path) -> Result<()> {
         self.parts
             .push(ComponentPart("dir".to_owned(), path.clone()));
+        self.make_room(&path)?;
         self.tx.move_dir(&self.name, path, src)
     }
+    /// If overwriting, remove what is already at `path`.
+    fn make_room(&mut self, path: &Path) -> Result<()> {
+        if !self.components.overwrite {
+            return Ok(());
+        }
+        match fs::symlink_metadata(self.components.prefix.abs_path(path)) {
+            Ok(m) if m.is_dir() => self.tx.remove_dir(&self.name, path.to_owned()),
+            Ok(_) => self.tx.remove_file(&self.name, path.to_owned()),
+            Err(_) => Ok(()),
+        }
+    }
     pub(crate) fn finish(mut self) -> Result<Transaction<'a>> {
         // Write component manifest
         let path = self.components.rel_component_manifest(&self.name);
         let abs_path = self.components.prefix.abs_path(&path);
-        let mut file = BufWriter::new(self.tx.add_file(&self.name, path)?);
-        for part in self.parts {
+        self.make_room(&path)?;
+        let mut file = BufWriter::new(self.tx.add_file(&self.name, path.clone())?);
+        for part in &self.parts {
             // FIXME: This writes relative paths to the component manifest,
             // but rust-installer writes absolute paths.
             utils::write_line("component", &mut file, &abs_path, &part.encode())?;
         }
+        file.flush().with_context(|| RustupError::WritingFile {
+            name: "component",
+            path: abs_path.clone(),
+        })?;
+        drop(file);
+
+        // Record the hashes of its files, to check them against later
+        integrity::record_component(
+            &self.components,
+            &self.name,
+            &path,
+            &self.parts,
+            &mut self.tx,
+        )?;
 
         // Add component to components file
         let path = self.components.rel_components_file();
@@ -187,14 +234,41 @@ impl Component {
         }
         Ok(result)
     }
-    pub fn uninstall<'a>(&self, mut tx: Transaction<'a>) -> Result<Transaction<'a>> {
-        // Update components file
+    pub fn uninstall<'a>(&self, tx: Transaction<'a>) -> Result<Transaction<'a>> {
+        self.uninstall_parts(tx, false)
+    }
+    /// Uninstall a damaged component so that it can be reinstalled. Files
+    /// that are already gone are passed over. If the component manifest has
+    /// gone too, the component is only struck from the list of components,
+    /// and its files are left for the reinstall to overwrite. Returns whether
+    /// the component manifest was there.
+    pub(crate) fn uninstall_damaged<'a>(
+        &self,
+        tx: Transaction<'a>,
+    ) -> Result<(Transaction<'a>, bool)> {
+        if utils::is_file(self.manifest_file()) {
+            Ok((self.uninstall_parts(tx, true)?, true))
+        } else {
+            Ok((self.unlist(tx)?, false))
+        }
+    }
+    /// Remove the component from the components file.
+    fn unlist<'a>(&self, mut tx: Transaction<'a>) -> Result<Transaction<'a>> {
         let path = self.components.rel_components_file();
         let abs_path = self.components.prefix.abs_path(&path);
         let temp = tx.temp().new_file()?;
         utils::filter_file("components", &abs_path, &temp, |l| l != self.name)?;
         tx.modify_file(path)?;
         utils::rename_file("components", &temp, &abs_path, tx.notify_handler())?;
+        Ok(tx)
+    }
+    fn uninstall_parts<'a>(
+        &self,
+        tx: Transaction<'a>,
+        skip_missing: bool,
+    ) -> Result<Transaction<'a>> {
+        // Update components file
+        let mut tx = self.unlist(tx)?;
 
         // TODO: If this is the last component remove the components file
         // and the version file.
@@ -300,6 +374,10 @@ impl Component {
             prefix: self.components.prefix.abs_path(""),
         };
         for part in self.parts()?.into_iter().rev() {
+            if skip_missing && !utils::path_exists(self.components.prefix.abs_path(&part.1)) {
+                pset.seen(part.1);
+                continue;
+            }
             match &*part.0 {
                 "file" => tx.remove_file(&self.name, part.1.clone())?,
                 "dir" => tx.remove_dir(&self.name, part.1.clone())?,
@@ -0,0 +1,312 @@
+//! An index of the hashes of the files of the installed components, kept in
+//! `lib/rustlib/integrity`, so that checking an installation only has to
+//! hash the files that changed since it was last checked. The files of a
+//! component are recorded as it is installed, in the same transaction.
+//!
+//! Each line of the index is a tab-separated record of a kind, a SHA-256
+//! hash, a length, a modification time in nanoseconds and a name. A
+//! `component` record is for the component manifest of the component of
+//! that name and is followed by a `file` record for each of its files,
+//! named by their path relative to the toolchain.
+//!
+//! A file whose recorded length and modification time still match is not
+//! hashed again. A component that has no records, because it was installed
+//! before they were kept or the index has gone, or whose manifest no longer
+//! matches its record, cannot be checked and is reported as unknown.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::dist::component::components::{ComponentPart, Components};
+use crate::dist::component::transaction::Transaction;
+use crate::dist::notifications::Notification;
+use crate::errors::RustupError;
+use crate::utils::utils;
+
+const INTEGRITY_FILE: &str = "integrity";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Record {
+    hash: String,
+    len: u64,
+    mtime: u128,
+}
+
+#[derive(Debug)]
+struct ComponentRecords {
+    manifest: Record,
+    files: Vec<(PathBuf, Record)>,
+}
+
+/// What is wrong with a file of an installed component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Damage {
+    Missing,
+    Modified,
+}
+
+#[derive(Debug)]
+pub(crate) struct DamagedFile {
+    pub component: String,
+    pub path: PathBuf,
+    pub damage: Damage,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    pub components: usize,
+    pub files: usize,
+    /// How many of `files` had to be hashed.
+    pub hashed: usize,
+    pub damaged: Vec<DamagedFile>,
+    /// The components that have no records to check their files against.
+    pub unknown: Vec<String>,
+}
+
+/// Records the hashes of the files of the component `name` in the
+/// integrity index as part of `tx`, as it is installed with the manifest at
+/// `manifest` and the given `parts`.
+pub(crate) fn record_component(
+    components: &Components,
+    name: &str,
+    manifest: &Path,
+    parts: &[ComponentPart],
+    tx: &mut Transaction<'_>,
+) -> Result<()> {
+    tx.notify_handler()(Notification::HashingComponentFiles(name));
+    let prefix = components.prefix();
+    let mut records = ComponentRecords {
+        manifest: record_of(&prefix.abs_path(manifest), None)?
+            .ok_or_else(|| RustupError::CorruptComponent(name.to_owned()))?,
+        files: Vec::new(),
+    };
+    for path in files_of(components, parts)? {
+        if let Some(record) = record_of(&prefix.abs_path(&path), None)? {
+            records.files.push((path, record));
+        }
+    }
+
+    let index_path = prefix.manifest_file(INTEGRITY_FILE);
+    let mut index = read_index(&index_path);
+    index.retain(|(other, _)| other != name);
+    index.push((name.to_owned(), records));
+    tx.modify_file(prefix.rel_manifest_file(INTEGRITY_FILE))?;
+    write_index(&index_path, &index)
+}
+
+/// Checks the files of every installed component against the integrity
+/// index, then writes the index back with what was found.
+///
+/// Components without records are reported as unknown rather than being
+/// recorded as they are now, as that would take any damage for the original.
+pub(crate) fn verify(components: &Components) -> Result<Report> {
+    let prefix = components.prefix();
+    let index_path = prefix.manifest_file(INTEGRITY_FILE);
+    let mut index = read_index(&index_path)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let mut report = Report::default();
+    let mut checked = Vec::new();
+    for component in components.list()? {
+        let name = component.name().to_owned();
+        let Some(old) = index.remove(&name) else {
+            report.unknown.push(name);
+            continue;
+        };
+        let manifest = record_of(&component.manifest_file(), Some(&old.manifest))?
+            .ok_or_else(|| RustupError::CorruptComponent(name.clone()))?;
+        if manifest.hash != old.manifest.hash {
+            report.unknown.push(name.clone());
+            // Kept as it was, as it is all there is to go by
+            checked.push((name, old));
+            continue;
+        }
+        let old = old.files.into_iter().collect::<HashMap<_, _>>();
+
+        let mut records = ComponentRecords {
+            manifest,
+            files: Vec::new(),
+        };
+        for path in files_of(components, &component.parts()?)? {
+            // A file that was not there when the component was installed
+            // has nothing to be checked against
+            let Some(known) = old.get(&path) else {
+                continue;
+            };
+            report.files += 1;
+            let Some(record) = record_of(&prefix.abs_path(&path), Some(known))? else {
+                report.damaged.push(DamagedFile {
+                    component: name.clone(),
+                    path,
+                    damage: Damage::Missing,
+                });
+                continue;
+            };
+            if *known != record {
+                report.hashed += 1;
+            }
+            if known.hash != record.hash {
+                // Keep what was recorded, so that the damage is reported
+                // until the component is reinstalled
+                records.files.push((path.clone(), known.clone()));
+                report.damaged.push(DamagedFile {
+                    component: name.clone(),
+                    path,
+                    damage: Damage::Modified,
+                });
+            } else {
+                records.files.push((path, record));
+            }
+        }
+        report.components += 1;
+        checked.push((name, records));
+    }
+
+    write_index(&index_path, &checked)?;
+    Ok(report)
+}
+
+/// The files of a component with the given `parts`, relative to the
+/// toolchain, with its directories listed file by file.
+fn files_of(components: &Components, parts: &[ComponentPart]) -> Result<Vec<PathBuf>> {
+    let root = components.prefix().abs_path("");
+    let mut files = Vec::new();
+    for part in parts {
+        match &*part.0 {
+            "dir" => list_files(&root, &part.1, &mut files)?,
+            _ => files.push(part.1.clone()),
+        }
+    }
+    Ok(files)
+}
+
+/// The record of the file at `path`, or `None` if there is no such file.
+/// The hash of `known` is reused if the file's length and modification
+/// time say it is unchanged.
+fn record_of(path: &Path, known: Option<&Record>) -> Result<Option<Record>> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("could not stat '{}'", path.display()));
+        }
+    };
+    let len = metadata.len();
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    let hash = match known {
+        Some(known) if known.len == len && known.mtime == mtime => known.hash.clone(),
+        _ => hash_file(path)?,
+    };
+    Ok(Some(Record { hash, len, mtime }))
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("could not open '{}'", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("could not read '{}'", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Appends the files beneath `dir`, relative to `root`, to `files`.
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let abs = root.join(dir);
+    let entries = match fs::read_dir(&abs) {
+        Ok(entries) => entries,
+        // Reported as a missing file, as there is nothing else to check
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            files.push(dir.to_owned());
+            return Ok(());
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("could not read '{}'", abs.display()));
+        }
+    };
+    let mut children = entries
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<Vec<_>>>()
+        .with_context(|| format!("could not read '{}'", abs.display()))?;
+    children.sort();
+    for child in children {
+        let path = dir.join(child);
+        if root.join(&path).is_dir() {
+            list_files(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The index at `path`, in the order it was written, or an empty one if it
+/// is missing or unreadable, as then there is nothing to check against.
+fn read_index(path: &Path) -> Vec<(String, ComponentRecords)> {
+    let mut index = Vec::new();
+    let Ok(content) = fs::read_to_string(path) else {
+        return index;
+    };
+    for line in content.lines() {
+        let mut fields = line.splitn(5, '\t');
+        let (Some(kind), Some(hash), Some(len), Some(mtime), Some(name)) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            return Vec::new();
+        };
+        let (Ok(len), Ok(mtime)) = (len.parse(), mtime.parse()) else {
+            return Vec::new();
+        };
+        let record = Record {
+            hash: hash.to_owned(),
+            len,
+            mtime,
+        };
+        match kind {
+            "component" => index.push((
+                name.to_owned(),
+                ComponentRecords {
+                    manifest: record,
+                    files: Vec::new(),
+                },
+            )),
+            "file" => match index.last_mut() {
+                Some((_, records)) => records.files.push((PathBuf::from(name), record)),
+                None => return Vec::new(),
+            },
+            _ => return Vec::new(),
+        }
+    }
+    index
+}
+
+fn write_index(path: &Path, index: &[(String, ComponentRecords)]) -> Result<()> {
+    let mut content = String::new();
+    let mut push = |kind: &str, record: &Record, name: &str| {
+        content.push_str(&format!(
+            "{kind}\t{}\t{}\t{}\t{name}\n",
+            record.hash, record.len, record.mtime
+        ));
+    };
+    for (name, records) in index {
+        push("component", &records.manifest, name);
+        for (path, record) in &records.files {
+            push("file", record, &path.to_string_lossy());
+        }
+    }
+    utils::write_file(INTEGRITY_FILE, path, &content)
+}
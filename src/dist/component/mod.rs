@@ -11,6 +11,8 @@ mod transaction;
 mod package;
 // The representation of *installed* components, and uninstallation
 mod components;
+// Checking the files of installed components against their recorded hashes
+pub(crate) mod integrity;
 
 #[cfg(test)]
 mod tests;
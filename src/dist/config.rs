@@ -11,6 +11,9 @@ pub(crate) const DEFAULT_CONFIG_VERSION: &str = "1";
 pub struct Config {
     pub config_version: String,
     pub components: Vec<Component>,
+    /// Those of `components` that were only installed because others need
+    /// them.
+    pub dependencies: Vec<Component>,
 }
 
 impl Config {
@@ -23,10 +26,14 @@ impl Config {
         let components = get_array(&mut table, "components", path)?;
         let components =
             Self::toml_to_components(components, &format!("{}{}.", path, "components"))?;
+        let dependencies = get_array(&mut table, "dependencies", path)?;
+        let dependencies =
+            Self::toml_to_components(dependencies, &format!("{}{}.", path, "dependencies"))?;
 
         Ok(Self {
             config_version,
             components,
+            dependencies,
         })
     }
     pub(crate) fn into_toml(self) -> toml::value::Table {
@@ -39,6 +46,10 @@ impl Config {
         if !components.is_empty() {
             result.insert("components".to_owned(), toml::Value::Array(components));
         }
+        let dependencies = Self::components_to_toml(self.dependencies);
+        if !dependencies.is_empty() {
+            result.insert("dependencies".to_owned(), toml::Value::Array(dependencies));
+        }
         result
     }
 
@@ -82,6 +93,7 @@ impl Default for Config {
         Self {
             config_version: DEFAULT_CONFIG_VERSION.to_owned(),
             components: Vec::new(),
+            dependencies: Vec::new(),
         }
     }
 }
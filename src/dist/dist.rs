@@ -14,7 +14,7 @@ use thiserror::Error as ThisError;
 
 pub(crate) use crate::dist::triple::*;
 use crate::{
-    currentprocess::varsource::VarSource,
+    currentprocess::{varsource::VarSource, Process},
     dist::{
         download::DownloadCfg,
         manifest::{Component, Manifest as ManifestV2},
@@ -24,7 +24,7 @@ use crate::{
     },
     errors::RustupError,
     process,
-    toolchain::names::ToolchainName,
+    toolchain::names::{InvalidName, ToolchainName},
     utils::utils,
 };
 pub static DEFAULT_DIST_SERVER: &str = "https://static.rust-lang.org";
@@ -102,8 +102,56 @@ pub(crate) enum DistError {
     ToolchainComponentsMissing(Vec<Component>, Box<ManifestV2>, String),
     #[error("no release found for '{0}'")]
     MissingReleaseForToolchain(String),
-    #[error("invalid toolchain name: '{0}'")]
-    InvalidOfficialName(String),
+    #[error("toolchain '{toolchain}' would be downgraded from the build of {installed} to the older one of {resolved}\nhelp: pass `--allow-downgrade` to install the older build anyway")]
+    Downgrade {
+        toolchain: String,
+        installed: String,
+        resolved: String,
+    },
+    #[error("toolchain '{toolchain}' does not contain component '{component}'{}", suggest_components(.suggestions))]
+    UnknownComponent {
+        toolchain: String,
+        component: String,
+        suggestions: Vec<String>,
+    },
+}
+
+fn suggest_components(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [s] => format!("; did you mean '{s}'?"),
+        [rest @ .., last] => format!(
+            "; did you mean one of {} or '{last}'?",
+            rest.iter()
+                .map(|s| format!("'{s}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// The closest matches for an unknown component name, best first.
+fn component_suggestions(name: &str, components: &[Component]) -> Vec<String> {
+    use strsim::damerau_levenshtein;
+
+    // Suggest only for very small differences, as in `rustup component add`
+    const MAX_DISTANCE: usize = 3;
+    const MAX_SUGGESTIONS: usize = 3;
+
+    let mut candidates: Vec<_> = components
+        .iter()
+        .map(|c| c.short_name_in_manifest())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|c| (damerau_levenshtein(c, name), c.clone()))
+        .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c)
+        .collect()
 }
 
 #[derive(Debug, PartialEq)]
@@ -140,6 +188,35 @@ pub struct ToolchainDesc {
     pub target: TargetTriple,
 }
 
+// The hosts that toolchains are built for, which `rustup set default-host`
+// accepts along with those the channel manifests ship `rustc` for.
+pub(crate) static KNOWN_HOSTS: &[&str] = &[
+    "aarch64-apple-darwin",
+    "aarch64-pc-windows-msvc",
+    "aarch64-unknown-linux-gnu",
+    "aarch64-unknown-linux-musl",
+    "arm-unknown-linux-gnueabi",
+    "arm-unknown-linux-gnueabihf",
+    "armv7-unknown-linux-gnueabihf",
+    "i686-pc-windows-gnu",
+    "i686-pc-windows-msvc",
+    "i686-unknown-linux-gnu",
+    "loongarch64-unknown-linux-gnu",
+    "powerpc-unknown-linux-gnu",
+    "powerpc64-unknown-linux-gnu",
+    "powerpc64le-unknown-linux-gnu",
+    "riscv64gc-unknown-linux-gnu",
+    "s390x-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "x86_64-pc-windows-gnu",
+    "x86_64-pc-windows-msvc",
+    "x86_64-unknown-freebsd",
+    "x86_64-unknown-illumos",
+    "x86_64-unknown-linux-gnu",
+    "x86_64-unknown-linux-musl",
+    "x86_64-unknown-netbsd",
+];
+
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct TargetTriple(String);
 
@@ -189,7 +266,19 @@ impl FromStr for ParsedToolchainDesc {
             .unwrap()
         });
 
-        let d = TOOLCHAIN_CHANNEL_RE.captures(desc).map(|c| {
+        // Channel names and targets are all lower case, so `Stable` or
+        // `NIGHTLY` are taken to mean those channels. That is only done
+        // when what follows the channel is then a known target, so that
+        // custom toolchains like `Nightly-Patched` keep their names.
+        let normalized = desc.to_ascii_lowercase();
+        let captures = TOOLCHAIN_CHANNEL_RE.captures(desc).or_else(|| {
+            let c = TOOLCHAIN_CHANNEL_RE.captures(&normalized)?;
+            let known_target = c
+                .get(3)
+                .map_or(true, |t| PartialTargetTriple::new(t.as_str()).is_some());
+            known_target.then_some(c)
+        });
+        let d = captures.map(|c| {
             fn fn_map(s: &str) -> Option<String> {
                 if s.is_empty() {
                     None
@@ -264,6 +353,22 @@ impl TargetTriple {
         Self(name.to_string())
     }
 
+    /// Whether `name` names this same target, either exactly or, as Linux
+    /// distributions with multiarch support write it, without an `unknown`
+    /// or `pc` vendor, like `x86_64-linux-gnu` for `x86_64-unknown-linux-gnu`.
+    pub(crate) fn is_named_by(&self, name: &str) -> bool {
+        if self.0.eq_ignore_ascii_case(name) {
+            return true;
+        }
+        let parts: Vec<_> = self.0.split('-').collect();
+        match parts[..] {
+            [arch, "unknown" | "pc", os, env] => {
+                name.eq_ignore_ascii_case(&format!("{arch}-{os}-{env}"))
+            }
+            _ => false,
+        }
+    }
+
     pub(crate) fn from_build() -> Self {
         if let Some(triple) = option_env!("RUSTUP_OVERRIDE_BUILD_TRIPLE") {
             Self::new(triple)
@@ -295,6 +400,12 @@ impl TargetTriple {
     }
 
     pub(crate) fn from_host() -> Option<Self> {
+        Self::from_host_in(&process())
+    }
+
+    /// Like [`Self::from_host`], but reads `RUSTUP_OVERRIDE_HOST_TRIPLE` from
+    /// `process` instead of the current process.
+    pub(crate) fn from_host_in(process: &Process) -> Option<Self> {
         #[cfg(windows)]
         fn inner() -> Option<TargetTriple> {
             use std::mem;
@@ -429,7 +540,7 @@ impl TargetTriple {
             host_triple.map(TargetTriple::new)
         }
 
-        if let Ok(triple) = process().var("RUSTUP_OVERRIDE_HOST_TRIPLE") {
+        if let Ok(triple) = process.var("RUSTUP_OVERRIDE_HOST_TRIPLE") {
             Some(Self(triple))
         } else {
             inner()
@@ -440,6 +551,10 @@ impl TargetTriple {
         Self::from_host().unwrap_or_else(Self::from_build)
     }
 
+    pub(crate) fn from_host_or_build_in(process: &Process) -> Self {
+        Self::from_host_in(process).unwrap_or_else(Self::from_build)
+    }
+
     pub(crate) fn can_run(&self, other: &TargetTriple) -> Result<bool> {
         // Most trivial shortcut of all
         if self == other {
@@ -611,11 +726,11 @@ impl ToolchainDesc {
 }
 
 impl TryFrom<&ToolchainName> for ToolchainDesc {
-    type Error = DistError;
+    type Error = InvalidName;
 
     fn try_from(value: &ToolchainName) -> std::result::Result<Self, Self::Error> {
         match value {
-            ToolchainName::Custom(n) => Err(DistError::InvalidOfficialName(n.str().into())),
+            ToolchainName::Custom(n) => Err(InvalidName::OfficialName(n.str().into())),
             ToolchainName::Official(n) => Ok(n.clone()),
         }
     }
@@ -653,6 +768,10 @@ impl Profile {
     pub(crate) fn default_name() -> &'static str {
         "default"
     }
+
+    pub(crate) fn all() -> &'static [Self] {
+        &[Self::Minimal, Self::Default, Self::Complete]
+    }
 }
 
 impl Default for Profile {
@@ -783,7 +902,11 @@ fn update_from_dist_(
     let mut toolchain = toolchain.clone();
     let mut fetched = String::new();
     let mut first_err = None;
-    let backtrack = toolchain.channel == "nightly" && toolchain.date.is_none();
+    // A local dist directory only holds the one manifest, so there is nothing to
+    // backtrack to.
+    let backtrack = toolchain.channel == "nightly"
+        && toolchain.date.is_none()
+        && download.local_dist_dir.is_none();
     // We want to limit backtracking if we do not already have a toolchain
     let mut backtrack_limit: Option<i32> = if toolchain.date.is_some() {
         None
@@ -823,6 +946,13 @@ fn update_from_dist_(
         manifestation.load_manifest()?
     };
 
+    // Unless allowed, a channel that now resolves to an older build than the
+    // installed one, as after a release is rolled back, is left as it is
+    let downgrade_guard = match allow_downgrade || force_update {
+        true => None,
+        false => current_manifest.as_ref().map(|m| m.date.clone()),
+    };
+
     loop {
         match try_update_from_dist_(
             download,
@@ -831,6 +961,7 @@ fn update_from_dist_(
             profile,
             prefix,
             force_update,
+            downgrade_guard.as_deref(),
             components,
             targets,
             &mut fetched,
@@ -911,6 +1042,7 @@ fn try_update_from_dist_(
     profile: Option<Profile>,
     prefix: &InstallPrefix,
     force_update: bool,
+    installed_date: Option<&str>,
     components: &[&str],
     targets: &[&str],
     fetched: &mut String,
@@ -938,79 +1070,59 @@ fn try_update_from_dist_(
                 m.get_rust_version().ok(),
             ));
 
-            let profile_components = match profile {
-                Some(profile) => m.get_profile_components(profile, &toolchain.target)?,
-                None => Vec::new(),
-            };
-
-            let mut all_components: HashSet<Component> = profile_components.into_iter().collect();
-
-            let rust_package = m.get_package("rust")?;
-            let rust_target_package = rust_package.get_target(Some(&toolchain.target.clone()))?;
-
-            for component in components {
-                let mut component =
-                    Component::new(component.to_string(), Some(toolchain.target.clone()), false);
-                if let Some(renamed) = m.rename_component(&component) {
-                    component = renamed;
-                }
-                // Look up the newly constructed/renamed component and ensure that
-                // if it's a wildcard component we note such, otherwise we end up
-                // exacerbating the problem we thought we'd fixed with #2087 and #2115
-                if let Some(c) = rust_target_package
-                    .components
-                    .iter()
-                    .find(|c| c.short_name_in_manifest() == component.short_name_in_manifest())
-                {
-                    if c.target.is_none() {
-                        component = component.wildcard();
+            if let Some(installed) = installed_date {
+                let older = match (
+                    date_from_manifest_date(&m.date),
+                    date_from_manifest_date(installed),
+                ) {
+                    (Some(resolved), Some(installed)) => resolved < installed,
+                    _ => false,
+                };
+                if older {
+                    return Err(DistError::Downgrade {
+                        toolchain: toolchain_str,
+                        installed: installed.to_owned(),
+                        resolved: m.date,
                     }
+                    .into());
                 }
-                all_components.insert(component);
-            }
-
-            for target in targets {
-                let triple = TargetTriple::new(target);
-                all_components.insert(Component::new("rust-std".to_string(), Some(triple), false));
             }
 
-            let mut explicit_add_components: Vec<_> = all_components.into_iter().collect();
-            explicit_add_components.sort();
-
-            let changes = Changes {
-                explicit_add_components,
-                remove_components: Vec::new(),
-            };
+            let installed = manifestation.read_config()?.unwrap_or_default().components;
+            let changes = requested_changes(
+                &m,
+                toolchain,
+                profile,
+                force_update,
+                components,
+                targets,
+                &installed,
+                download.notify_handler,
+            )?;
 
             fetched.clone_from(&m.date);
 
-            return match manifestation.update(
-                &m,
-                changes,
-                force_update,
-                &download,
-                &toolchain.manifest_name(),
-                true,
-            ) {
-                Ok(status) => match status {
-                    UpdateStatus::Unchanged => Ok(None),
-                    UpdateStatus::Changed => Ok(Some(hash)),
-                },
-                Err(err) => match err.downcast_ref::<RustupError>() {
-                    Some(RustupError::RequestedComponentsUnavailable {
-                        components,
-                        manifest,
-                        toolchain,
-                    }) => Err(anyhow!(DistError::ToolchainComponentsMissing(
-                        components.to_owned(),
-                        Box::new(manifest.to_owned()),
-                        toolchain.to_owned(),
-                    ))),
-                    Some(_) | None => Err(err),
-                },
+            let status = manifestation
+                .update(
+                    &m,
+                    changes,
+                    force_update,
+                    &download,
+                    &toolchain.manifest_name(),
+                    true,
+                )
+                .map_err(components_missing)?;
+            // What `--download-only` saved has now been installed
+            download.remove_saved_manifest(&toolchain.manifest_v2_url(download.dist_root))?;
+            return match status {
+                UpdateStatus::Unchanged => Ok(None),
+                UpdateStatus::Changed => Ok(Some(hash)),
             };
         }
-        Ok(None) => return Ok(None),
+        Ok(None) => {
+            download.remove_saved_manifest(&toolchain.manifest_v2_url(download.dist_root))?;
+            return Ok(None);
+        }
         Err(any) => {
             enum Cases {
                 DNE,
@@ -1018,7 +1130,10 @@ fn try_update_from_dist_(
                 Other,
             }
             let case = match any.downcast_ref::<RustupError>() {
-                Some(RustupError::ChecksumFailed { .. }) => Cases::CF,
+                // A mismatch in a local dist directory will not go away by waiting
+                Some(RustupError::ChecksumFailed { .. }) if download.local_dist_dir.is_none() => {
+                    Cases::CF
+                }
                 Some(RustupError::DownloadNotExists { .. }) => Cases::DNE,
                 _ => Cases::Other,
             };
@@ -1069,6 +1184,7 @@ fn try_update_from_dist_(
         &manifest,
         update_hash,
         download.tmp_cx,
+        download.ca_bundle,
         &download.notify_handler,
     );
     // inspect, determine what context to add, then process afterwards.
@@ -1090,12 +1206,285 @@ fn try_update_from_dist_(
     }
 }
 
+/// The changes that installing `components` and `targets`, on top of
+/// those of `profile`, from the manifest `m` asks for. The dependencies
+/// this pulls in that are not `installed` yet are reported.
+fn requested_changes(
+    m: &ManifestV2,
+    toolchain: &ToolchainDesc,
+    profile: Option<Profile>,
+    force_update: bool,
+    components: &[&str],
+    targets: &[&str],
+    installed: &[Component],
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> Result<Changes> {
+    let profile_components = match profile {
+        Some(profile) => m.get_profile_components(profile, &toolchain.target)?,
+        None => Vec::new(),
+    };
+
+    let mut all_components: HashSet<Component> = profile_components.into_iter().collect();
+
+    let rust_package = m.get_package("rust")?;
+    let rust_target_package = rust_package.get_target(Some(&toolchain.target.clone()))?;
+
+    let targets: Vec<_> = targets
+        .iter()
+        .map(|t| {
+            if toolchain.target.is_named_by(t) {
+                notify_handler(Notification::TargetIsHost(t, &toolchain.target));
+                toolchain.target.clone()
+            } else {
+                TargetTriple::new(t)
+            }
+        })
+        .collect();
+
+    for name in components {
+        // A fully qualified name such as `rust-std-wasm32-unknown-unknown`
+        // already picks the target.
+        if let Some(c) = rust_target_package
+            .components
+            .iter()
+            .find(|c| c.name_in_manifest() == *name)
+        {
+            all_components.insert(c.clone());
+            continue;
+        }
+
+        let mut component = Component::new(name.to_string(), Some(toolchain.target.clone()), false);
+        if let Some(renamed) = m.rename_component(&component) {
+            component = renamed;
+        }
+        let known: Vec<_> = rust_target_package
+            .components
+            .iter()
+            .filter(|c| c.short_name_in_manifest() == component.short_name_in_manifest())
+            .collect();
+        if known.is_empty() && !force_update {
+            bail!(DistError::UnknownComponent {
+                toolchain: toolchain.to_string(),
+                component: name.to_string(),
+                suggestions: component_suggestions(name, &rust_target_package.components),
+            });
+        }
+        // Look up the newly constructed/renamed component and ensure that
+        // if it's a wildcard component we note such, otherwise we end up
+        // exacerbating the problem we thought we'd fixed with #2087 and #2115
+        if known.iter().any(|c| c.target.is_none()) {
+            all_components.insert(component.wildcard());
+            continue;
+        }
+        // Target specific components are installed for each --target
+        // that provides them, as well as for the host.
+        for target in &targets {
+            if known.iter().any(|c| c.target.as_ref() == Some(target)) {
+                let mut component = component.clone();
+                component.target = Some(target.clone());
+                all_components.insert(component);
+            }
+        }
+        all_components.insert(component);
+    }
+
+    for triple in targets {
+        all_components.insert(Component::new("rust-std".to_string(), Some(triple), false));
+    }
+
+    // Pull in whatever the requested components cannot work without.
+    let requested: Vec<_> = all_components.iter().cloned().collect();
+    let mut dependency_components = Vec::new();
+    for (dep, dependent) in m.dependencies(&requested, &toolchain.target)? {
+        if !installed.contains(&dep) {
+            notify_handler(Notification::AddingDependency(
+                &dep.short_name(m),
+                &dependent.short_name(m),
+            ));
+        }
+        all_components.insert(dep.clone());
+        dependency_components.push(dep);
+    }
+
+    let mut explicit_add_components: Vec<_> = all_components.into_iter().collect();
+    explicit_add_components.sort();
+
+    Ok(Changes {
+        explicit_add_components,
+        remove_components: Vec::new(),
+        reinstall_components: Vec::new(),
+        dependency_components,
+    })
+}
+
+/// Reports an update missing requested components as the toolchain missing
+/// them, which is what the nightly backtracking looks for.
+fn components_missing(err: anyhow::Error) -> anyhow::Error {
+    match err.downcast_ref::<RustupError>() {
+        Some(RustupError::RequestedComponentsUnavailable {
+            components,
+            manifest,
+            toolchain,
+        }) => anyhow!(DistError::ToolchainComponentsMissing(
+            components.to_owned(),
+            Box::new(manifest.to_owned()),
+            toolchain.to_owned(),
+        )),
+        Some(_) | None => err,
+    }
+}
+
+/// What installing or updating a toolchain would download.
+#[derive(Debug)]
+pub(crate) struct InstallPlan {
+    /// The date of the manifest that was resolved.
+    pub date: String,
+    /// The name of each component to download, with the size of its package
+    /// if the server reports one.
+    pub downloads: Vec<(String, Option<u64>)>,
+}
+
+/// Resolves what [`update_from_dist`] would download, without downloading
+/// anything but the channel manifest.
+///
+/// Unlike an actual update this does not go back to earlier nightlies when
+/// components are missing, nor fall back to v1 manifests.
+pub(crate) fn plan_from_dist(
+    download: DownloadCfg<'_>,
+    toolchain: &ToolchainDesc,
+    profile: Option<Profile>,
+    prefix: &InstallPrefix,
+    force_update: bool,
+    components: &[&str],
+    targets: &[&str],
+) -> Result<InstallPlan> {
+    let manifestation = Manifestation::open(prefix.clone(), toolchain.target.clone())?;
+
+    (download.notify_handler)(Notification::DownloadingManifest(&toolchain.to_string()));
+    let Some((m, _)) = dl_v2_manifest(download, None, toolchain)? else {
+        unreachable!("a manifest is always downloaded without an update hash");
+    };
+    (download.notify_handler)(Notification::DownloadedManifest(
+        &m.date,
+        m.get_rust_version().ok(),
+    ));
+
+    let installed = manifestation.read_config()?.unwrap_or_default().components;
+    let changes = requested_changes(
+        &m,
+        toolchain,
+        profile,
+        force_update,
+        components,
+        targets,
+        &installed,
+        download.notify_handler,
+    )?;
+    let downloads = manifestation
+        .plan(
+            &m,
+            changes,
+            force_update,
+            &download,
+            &toolchain.manifest_name(),
+        )
+        .map_err(components_missing)?;
+    Ok(InstallPlan {
+        date: m.date,
+        downloads,
+    })
+}
+
+/// Downloads what installing `toolchain`, or updating it if it is installed
+/// already, needs into the download cache without installing any of it, and
+/// saves the channel manifest there too, so that the install can then be
+/// done offline. Returns how many component packages there are.
+///
+/// Like [`plan_from_dist`] this neither goes back to earlier nightlies nor
+/// falls back to v1 manifests.
+pub(crate) fn download_from_dist(
+    download: DownloadCfg<'_>,
+    toolchain: &ToolchainDesc,
+    profile: Option<Profile>,
+    prefix: &InstallPrefix,
+    force_update: bool,
+    components: &[&str],
+    targets: &[&str],
+) -> Result<usize> {
+    let manifestation = Manifestation::open(prefix.clone(), toolchain.target.clone())?;
+
+    (download.notify_handler)(Notification::DownloadingManifest(&toolchain.to_string()));
+    let Some((manifest_str, _)) = dl_v2_manifest_str(download, None, toolchain)? else {
+        unreachable!("a manifest is always downloaded without an update hash");
+    };
+    let m = ManifestV2::parse(&manifest_str)?;
+    (download.notify_handler)(Notification::DownloadedManifest(
+        &m.date,
+        m.get_rust_version().ok(),
+    ));
+
+    let installed = manifestation.read_config()?.unwrap_or_default().components;
+    let changes = requested_changes(
+        &m,
+        toolchain,
+        profile,
+        force_update,
+        components,
+        targets,
+        &installed,
+        download.notify_handler,
+    )?;
+    let downloaded = manifestation
+        .download_only(
+            &m,
+            changes,
+            force_update,
+            &download,
+            &toolchain.manifest_name(),
+        )
+        .map_err(components_missing)?;
+    download.save_manifest(
+        &toolchain.manifest_v2_url(download.dist_root),
+        &manifest_str,
+    )?;
+    Ok(downloaded)
+}
+
 pub(crate) fn dl_v2_manifest(
     download: DownloadCfg<'_>,
     update_hash: Option<&Path>,
     toolchain: &ToolchainDesc,
 ) -> Result<Option<(ManifestV2, String)>> {
+    let Some((manifest_str, manifest_hash)) = dl_v2_manifest_str(download, update_hash, toolchain)?
+    else {
+        return Ok(None);
+    };
+    Ok(Some((ManifestV2::parse(&manifest_str)?, manifest_hash)))
+}
+
+/// Like [`dl_v2_manifest`], but returns the manifest as it was downloaded.
+/// The one that [`download_from_dist`] saved is used instead if there is
+/// one, when offline or when the manifest can't be downloaded.
+fn dl_v2_manifest_str(
+    download: DownloadCfg<'_>,
+    update_hash: Option<&Path>,
+    toolchain: &ToolchainDesc,
+) -> Result<Option<(String, String)>> {
     let manifest_url = toolchain.manifest_v2_url(download.dist_root);
+    let use_saved = || -> Result<Option<Option<(String, String)>>> {
+        let Some((manifest_str, manifest_hash)) = download.saved_manifest(&manifest_url)? else {
+            return Ok(None);
+        };
+        if download.update_hash_matches(update_hash, &manifest_hash) {
+            return Ok(Some(None));
+        }
+        Ok(Some(Some((manifest_str, manifest_hash))))
+    };
+    if download.offline {
+        if let Some(saved) = use_saved()? {
+            return Ok(saved);
+        }
+    }
     match download.download_and_check(&manifest_url, update_hash, ".toml") {
         Ok(manifest_dl) => {
             // Downloaded ok!
@@ -1104,17 +1493,23 @@ pub(crate) fn dl_v2_manifest(
             } else {
                 return Ok(None);
             };
+            download.check_signature(&manifest_url, &manifest_file)?;
             let manifest_str = utils::read_file("manifest", &manifest_file)?;
-            let manifest = ManifestV2::parse(&manifest_str)?;
 
-            Ok(Some((manifest, manifest_hash)))
+            Ok(Some((manifest_str, manifest_hash)))
         }
         Err(any) => {
             if let Some(RustupError::ChecksumFailed { .. }) = any.downcast_ref::<RustupError>() {
-                // Checksum failed - issue warning to try again later
-                (download.notify_handler)(Notification::ManifestChecksumFailedHack);
+                if download.local_dist_dir.is_none() {
+                    // Checksum failed - issue warning to try again later
+                    (download.notify_handler)(Notification::ManifestChecksumFailedHack);
+                }
+                return Err(any);
+            }
+            match use_saved()? {
+                Some(saved) => Ok(saved),
+                None => Err(any),
             }
-            Err(any)
         }
     }
 }
@@ -1187,6 +1582,13 @@ mod tests {
             ("1.6", ("1.6.0", None, None)),
             ("1.7", ("1.7.0", None, None)),
             ("1.8", ("1.8.0", None, None)),
+            // names are normalized to lower case
+            ("Stable", ("stable", None, None)),
+            ("NIGHTLY-2024-01-01", ("nightly", Some("2024-01-01"), None)),
+            (
+                "Beta-X86_64-Unknown-Linux-Gnu",
+                ("beta", None, Some("x86_64-unknown-linux-gnu")),
+            ),
         ];
 
         for (input, (channel, date, target)) in success_cases {
@@ -1223,6 +1625,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn target_triple_is_named_by() {
+        let linux = TargetTriple::new("x86_64-unknown-linux-gnu");
+        assert!(linux.is_named_by("x86_64-unknown-linux-gnu"));
+        assert!(linux.is_named_by("x86_64-linux-gnu"));
+        assert!(!linux.is_named_by("x86_64-unknown-linux-musl"));
+        assert!(!linux.is_named_by("i686-linux-gnu"));
+
+        let windows = TargetTriple::new("x86_64-pc-windows-msvc");
+        assert!(windows.is_named_by("x86_64-windows-msvc"));
+        assert!(!windows.is_named_by("x86_64-pc-windows-gnu"));
+
+        let wasm = TargetTriple::new("wasm32-unknown-unknown");
+        assert!(!wasm.is_named_by("wasm32-unknown"));
+    }
+
     #[test]
     fn test_tracking_channels() {
         static CASES: &[(&str, bool)] = &[
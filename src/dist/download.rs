@@ -1,23 +1,119 @@
 use std::fs;
+use std::io;
 use std::ops;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, SystemTime};
 
-use anyhow::{anyhow, Context, Result};
-use sha2::{Digest, Sha256};
+use anyhow::{anyhow, bail, Context, Result};
+use download::RateLimiter;
+use sha2::digest::DynDigest;
+use sha2::{Digest, Sha256, Sha512};
 use url::Url;
 
 use crate::dist::notifications::*;
+use crate::dist::signature::{self, PublicKey};
 use crate::dist::temp;
 use crate::errors::*;
 use crate::utils::utils;
 
 const UPDATE_HASH_LEN: usize = 20;
 
+/// How many component downloads run at once unless configured otherwise.
+pub(crate) const DEFAULT_CONCURRENCY: usize = 4;
+
+/// How many times a failed component download is retried unless configured
+/// otherwise.
+pub(crate) const DEFAULT_RETRIES: usize = 3;
+
+/// The delay before the first retry of a download, in milliseconds. Each
+/// further retry waits up to twice as long as the one before.
+pub(crate) const DEFAULT_RETRY_BACKOFF_MS: u64 = 500;
+
+/// The hash algorithms that downloads can be verified with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum HashAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn hasher(self) -> Box<dyn DynDigest> {
+        match self {
+            Self::Sha256 => Box::new(Sha256::new()),
+            Self::Sha512 => Box::new(Sha512::new()),
+        }
+    }
+}
+
+/// The expected hash of a download, as given in a manifest: either
+/// `<algorithm>:<hex digest>`, or a bare hex digest, which is SHA-256.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Checksum<'a> {
+    pub algorithm: HashAlgorithm,
+    pub digest: &'a str,
+}
+
+impl<'a> Checksum<'a> {
+    pub(crate) fn parse(hash: &'a str) -> Result<Self> {
+        let Some((algorithm, digest)) = hash.split_once(':') else {
+            return Ok(Self {
+                algorithm: HashAlgorithm::Sha256,
+                digest: hash,
+            });
+        };
+        let algorithm = match algorithm {
+            "sha256" => HashAlgorithm::Sha256,
+            "sha512" => HashAlgorithm::Sha512,
+            _ => bail!(RustupError::UnsupportedHashAlgorithm(algorithm.to_owned())),
+        };
+        Ok(Self { algorithm, digest })
+    }
+}
+
+fn hex_digest(hasher: Box<dyn DynDigest>) -> String {
+    use std::fmt::Write;
+    hasher.finalize().iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
 #[derive(Copy, Clone)]
 pub struct DownloadCfg<'a> {
     pub dist_root: &'a str,
     pub tmp_cx: &'a temp::Context,
     pub download_dir: &'a PathBuf,
+    /// The number of component downloads to run at once.
+    pub concurrency: usize,
+    /// How many times to retry a component download that failed transiently.
+    pub retries: usize,
+    /// The delay before the first retry, doubled for each one after it.
+    pub retry_backoff: Duration,
+    /// A directory holding the manifest and component tarballs, which are
+    /// then read from there instead of the dist server.
+    pub local_dist_dir: Option<&'a Path>,
+    /// The keys that channel manifests must be signed with, if their
+    /// signatures are verified.
+    pub signature_keys: Option<&'a [PublicKey]>,
+    /// Whether manifests that are read from the local file system may be
+    /// unsigned even when signatures are verified.
+    pub allow_unsigned_local: bool,
+    /// Shared by all downloads, so that together they stay within
+    /// `download.max-rate`.
+    pub rate_limiter: Option<&'a RateLimiter>,
+    /// The bundle of CA certificates that downloads trust, set by
+    /// `download.ca-bundle`.
+    pub ca_bundle: Option<&'a Path>,
+    /// The most bytes that `download_dir` may hold, set by
+    /// `download.cache-size`.
+    pub cache_size: Option<u64>,
+    /// Whether component packages are unpacked as they download instead of
+    /// being saved to `download_dir` first, set by `download.stream-extract`.
+    pub stream_extract: bool,
+    /// Whether downloads from anywhere but the local file system are
+    /// refused, set by `--offline` or `RUSTUP_OFFLINE`.
+    pub offline: bool,
     pub notify_handler: &'a dyn Fn(Notification<'_>),
 }
 
@@ -34,7 +130,8 @@ impl ops::Deref for File {
 }
 
 impl<'a> DownloadCfg<'a> {
-    /// Downloads a file and validates its hash. Resumes interrupted downloads.
+    /// Downloads a file and validates its hash, which may name any supported
+    /// algorithm (see [`Checksum`]). Resumes interrupted downloads.
     /// Partial downloads are stored in `self.download_dir`, keyed by hash. If the
     /// target file already exists, then the hash is checked and it is returned
     /// immediately without re-downloading.
@@ -44,79 +141,74 @@ impl<'a> DownloadCfg<'a> {
             self.download_dir,
             &self.notify_handler,
         )?;
-        let target_file = self.download_dir.join(Path::new(hash));
-
-        if target_file.exists() {
-            let cached_result = file_hash(&target_file, self.notify_handler)?;
-            if hash == cached_result {
-                (self.notify_handler)(Notification::FileAlreadyDownloaded);
-                (self.notify_handler)(Notification::ChecksumValid(url.as_ref()));
-                return Ok(File { path: target_file });
-            } else {
-                (self.notify_handler)(Notification::CachedFileChecksumFailed);
-                fs::remove_file(&target_file).context("cleaning up previous download")?;
-            }
-        }
-
-        let partial_file_path = target_file.with_file_name(
-            target_file
-                .file_name()
-                .map(|s| s.to_str().unwrap_or("_"))
-                .unwrap_or("_")
-                .to_owned()
-                + ".partial",
-        );
-
-        let partial_file_existed = partial_file_path.exists();
+        download_to_cache(
+            self.download_dir,
+            url,
+            hash,
+            None,
+            self.download_options(),
+            self.offline,
+            self.notify_handler,
+        )
+    }
 
-        let mut hasher = Sha256::new();
+    /// The options that each download is made with.
+    pub(crate) fn download_options(&self) -> download::Options<'a> {
+        download::Options {
+            rate_limiter: self.rate_limiter,
+            ca_bundle: self.ca_bundle,
+        }
+    }
 
-        if let Err(e) = utils::download_file_with_resume(
-            url,
-            &partial_file_path,
-            Some(&mut hasher),
-            true,
-            &|n| (self.notify_handler)(n.into()),
-        ) {
-            let err = Err(e);
-            if partial_file_existed {
-                return err.context(RustupError::BrokenPartialFile);
-            } else {
-                return err;
-            }
+    /// Removes the least recently used files from `self.download_dir` until
+    /// it holds no more than `self.cache_size`. The files of `keep`, whether
+    /// complete or partial, are left alone, since they are about to be used.
+    pub(crate) fn trim_cache(&self, keep: &[String]) -> Result<()> {
+        let Some(limit) = self.cache_size else {
+            return Ok(());
+        };
+        let keep = keep
+            .iter()
+            .map(|hash| Ok(Checksum::parse(hash)?.digest))
+            .collect::<Result<Vec<_>>>()?;
+        let entries = match fs::read_dir(self.download_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).context("reading the download cache"),
         };
 
-        let actual_hash = format!("{:x}", hasher.finalize());
-
-        if hash != actual_hash {
-            // Incorrect hash
-            if partial_file_existed {
-                self.clean(&[hash.to_string() + ".partial"])?;
-                Err(anyhow!(RustupError::BrokenPartialFile))
-            } else {
-                Err(RustupError::ChecksumFailed {
-                    url: url.to_string(),
-                    expected: hash.to_string(),
-                    calculated: actual_hash,
-                }
-                .into())
+        let mut total = 0;
+        let mut evictable = Vec::new();
+        for entry in entries {
+            let entry = entry.context("reading the download cache")?;
+            let metadata = entry.metadata().context("reading the download cache")?;
+            if !metadata.is_file() {
+                continue;
             }
-        } else {
-            (self.notify_handler)(Notification::ChecksumValid(url.as_ref()));
+            total += metadata.len();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !keep.contains(&name.strip_suffix(".partial").unwrap_or(&name)) {
+                let used = metadata.modified().context("reading the download cache")?;
+                evictable.push((used, metadata.len(), entry.path()));
+            }
+        }
 
-            utils::rename_file(
-                "downloaded",
-                &partial_file_path,
-                &target_file,
-                self.notify_handler,
-            )?;
-            Ok(File { path: target_file })
+        evictable.sort();
+        for (_, len, path) in evictable {
+            if total <= limit {
+                break;
+            }
+            (self.notify_handler)(Notification::EvictingCachedDownload(&path));
+            fs::remove_file(&path).context("evicting cached download")?;
+            total -= len;
         }
+        Ok(())
     }
 
     pub(crate) fn clean(&self, hashes: &[String]) -> Result<()> {
         for hash in hashes.iter() {
-            let used_file = self.download_dir.join(hash);
+            let used_file = self.download_dir.join(Checksum::parse(hash)?.digest);
             if self.download_dir.join(&used_file).exists() {
                 fs::remove_file(used_file).context("cleaning up cached downloads")?;
             }
@@ -124,11 +216,45 @@ impl<'a> DownloadCfg<'a> {
         Ok(())
     }
 
+    /// Finds the file named like the last segment of `url` in the local dist
+    /// directory, or returns `None` when there is no such directory. If a
+    /// `.sha256` file sits next to it, the file is checked against it.
+    pub(crate) fn local_path(&self, url: &str) -> Result<Option<PathBuf>> {
+        let Some(dir) = self.local_dist_dir else {
+            return Ok(None);
+        };
+        let name = url.rsplit('/').next().unwrap_or(url);
+        let path = dir.join(name);
+        if !utils::is_file(&path) {
+            bail!(RustupError::MissingLocalDistFile {
+                name: name.to_owned(),
+                dir: dir.to_owned(),
+            });
+        }
+
+        let hash_path = dir.join(format!("{name}.sha256"));
+        if utils::is_file(&hash_path) {
+            let contents = utils::read_file("hash", &hash_path)?;
+            let expected = contents.split_whitespace().next().unwrap_or_default();
+            let calculated = file_hash(&path, HashAlgorithm::Sha256, self.notify_handler)?;
+            if expected != calculated {
+                return Err(RustupError::ChecksumFailed {
+                    url: path.display().to_string(),
+                    expected: expected.to_owned(),
+                    calculated,
+                }
+                .into());
+            }
+        }
+        Ok(Some(path))
+    }
+
     fn download_hash(&self, url: &str) -> Result<String> {
         let hash_url = utils::parse_url(&(url.to_owned() + ".sha256"))?;
+        check_online(self.offline, &hash_url)?;
         let hash_file = self.tmp_cx.new_file()?;
 
-        utils::download_file(&hash_url, &hash_file, None, &|n| {
+        utils::download_file(&hash_url, &hash_file, None, self.ca_bundle, &|n| {
             (self.notify_handler)(n.into())
         })?;
 
@@ -136,41 +262,47 @@ impl<'a> DownloadCfg<'a> {
     }
 
     /// Downloads a file, sourcing its hash from the same url with a `.sha256` suffix.
+    /// This is only used for files that are not listed in a manifest; those are
+    /// verified against the manifest's hash by [`Self::download`] instead.
     /// If `update_hash` is present, then that will be compared to the downloaded hash,
     /// and if they match, the download is skipped.
-    /// Verifies the signature found at the same url with a `.asc` suffix, and prints a
-    /// warning when the signature does not verify, or is not found.
+    /// With a local dist directory, the file is read from there instead, and is
+    /// only checked against a `.sha256` file if one is present.
     pub(crate) fn download_and_check(
         &self,
         url_str: &str,
         update_hash: Option<&Path>,
         ext: &str,
     ) -> Result<Option<(temp::File<'a>, String)>> {
-        let hash = self.download_hash(url_str)?;
+        let (url_str, hash) = match self.local_path(url_str)? {
+            Some(path) => (
+                local_file_url(&path)?.to_string(),
+                file_hash(&path, HashAlgorithm::Sha256, self.notify_handler)?,
+            ),
+            None => (url_str.to_owned(), self.download_hash(url_str)?),
+        };
+        let url_str = url_str.as_str();
         let partial_hash: String = hash.chars().take(UPDATE_HASH_LEN).collect();
 
-        if let Some(hash_file) = update_hash {
-            if utils::is_file(hash_file) {
-                if let Ok(contents) = utils::read_file("update hash", hash_file) {
-                    if contents == partial_hash {
-                        // Skip download, update hash matches
-                        return Ok(None);
-                    }
-                } else {
-                    (self.notify_handler)(Notification::CantReadUpdateHash(hash_file));
-                }
-            } else {
-                (self.notify_handler)(Notification::NoUpdateHash(hash_file));
-            }
+        if self.update_hash_matches(update_hash, &partial_hash) {
+            // Skip download, update hash matches
+            return Ok(None);
         }
 
         let url = utils::parse_url(url_str)?;
+        check_online(self.offline, &url)?;
         let file = self.tmp_cx.new_file_with_ext("", ext)?;
 
         let mut hasher = Sha256::new();
-        utils::download_file(&url, &file, Some(&mut hasher), &|n| {
-            (self.notify_handler)(n.into())
-        })?;
+        utils::download_file_with_resume(
+            &url,
+            &file,
+            Some(&mut hasher),
+            false,
+            None,
+            self.download_options(),
+            &|n| (self.notify_handler)(n.into()),
+        )?;
         let actual_hash = format!("{:x}", hasher.finalize());
 
         if hash != actual_hash {
@@ -187,10 +319,283 @@ impl<'a> DownloadCfg<'a> {
 
         Ok(Some((file, partial_hash)))
     }
+
+    /// Whether the hash recorded in `update_hash` is `partial_hash`, in which
+    /// case the file it was taken from has not changed.
+    pub(crate) fn update_hash_matches(
+        &self,
+        update_hash: Option<&Path>,
+        partial_hash: &str,
+    ) -> bool {
+        let Some(hash_file) = update_hash else {
+            return false;
+        };
+        if !utils::is_file(hash_file) {
+            (self.notify_handler)(Notification::NoUpdateHash(hash_file));
+            return false;
+        }
+        match utils::read_file("update hash", hash_file) {
+            Ok(contents) => contents == partial_hash,
+            Err(_) => {
+                (self.notify_handler)(Notification::CantReadUpdateHash(hash_file));
+                false
+            }
+        }
+    }
+
+    /// Keeps the manifest downloaded from `url` in `self.download_dir`, so
+    /// that installing from it later can be done without the network.
+    pub(crate) fn save_manifest(&self, url: &str, manifest: &str) -> Result<()> {
+        utils::ensure_dir_exists(
+            "Download Directory",
+            self.download_dir,
+            &self.notify_handler,
+        )?;
+        utils::write_file("manifest", &self.saved_manifest_path(url), manifest)
+    }
+
+    /// The manifest that [`Self::save_manifest`] kept for `url`, if any, with
+    /// the partial hash that update hashes record, as
+    /// [`Self::download_and_check`] returns it.
+    pub(crate) fn saved_manifest(&self, url: &str) -> Result<Option<(String, String)>> {
+        let path = self.saved_manifest_path(url);
+        if !utils::is_file(&path) {
+            return Ok(None);
+        }
+        let manifest = utils::read_file("manifest", &path)?;
+        let hash = format!("{:x}", Sha256::digest(manifest.as_bytes()));
+        let partial_hash = hash.chars().take(UPDATE_HASH_LEN).collect();
+        (self.notify_handler)(Notification::UsingSavedManifest(url));
+        Ok(Some((manifest, partial_hash)))
+    }
+
+    /// Removes the manifest that [`Self::save_manifest`] kept for `url`, once
+    /// it is no longer needed.
+    pub(crate) fn remove_saved_manifest(&self, url: &str) -> Result<()> {
+        let path = self.saved_manifest_path(url);
+        if utils::is_file(&path) {
+            utils::remove_file("manifest", &path)?;
+        }
+        Ok(())
+    }
+
+    fn saved_manifest_path(&self, url: &str) -> PathBuf {
+        let digest = format!("{:x}", Sha256::digest(url.as_bytes()));
+        self.download_dir.join(format!("{digest}.manifest"))
+    }
+
+    /// Checks `file`, which was downloaded from `url`, against the signature
+    /// at the same url with an `.asc` suffix, or next to it in the local dist
+    /// directory. Does nothing unless signatures are verified.
+    pub(crate) fn check_signature(&self, url: &str, file: &Path) -> Result<()> {
+        let Some(keys) = self.signature_keys else {
+            return Ok(());
+        };
+        let sig_url = format!("{url}.asc");
+        let local = self.local_dist_dir.is_some() || url.starts_with("file://");
+
+        let signature = match self.local_dist_dir {
+            Some(dir) => {
+                let path = dir.join(sig_url.rsplit('/').next().unwrap_or(&sig_url));
+                if utils::is_file(&path) {
+                    Some(utils::read_file("signature", &path)?)
+                } else {
+                    None
+                }
+            }
+            None => {
+                let sig_url = utils::parse_url(&sig_url)?;
+                check_online(self.offline, &sig_url)?;
+                let sig_file = self.tmp_cx.new_file()?;
+                let downloaded =
+                    utils::download_file(&sig_url, &sig_file, None, self.ca_bundle, &|n| {
+                        (self.notify_handler)(n.into())
+                    });
+                match downloaded {
+                    Ok(()) => Some(utils::read_file("signature", &sig_file)?),
+                    Err(e) => match e.downcast_ref::<RustupError>() {
+                        Some(RustupError::DownloadNotExists { .. }) => None,
+                        _ => return Err(e),
+                    },
+                }
+            }
+        };
+
+        let Some(signature) = signature else {
+            if local && self.allow_unsigned_local {
+                (self.notify_handler)(Notification::UnsignedLocalManifest(url));
+                return Ok(());
+            }
+            return Err(RustupError::MissingSignature {
+                url: url.to_owned(),
+            }
+            .into());
+        };
+
+        let data = fs::read(file).with_context(|| RustupError::ReadingFile {
+            name: "manifest",
+            path: file.to_owned(),
+        })?;
+        signature::verify(&data, &signature, keys).map_err(|e| {
+            RustupError::SignatureVerificationFailed {
+                url: url.to_owned(),
+                reason: e.to_string(),
+            }
+        })?;
+        (self.notify_handler)(Notification::SignatureValid(url));
+        Ok(())
+    }
+}
+
+/// The `file://` url of `path`, which is made absolute first.
+pub(crate) fn local_file_url(path: &Path) -> Result<Url> {
+    let path = utils::current_dir()?.join(path);
+    Url::from_file_path(&path).map_err(|()| anyhow!("could not make a url of '{}'", path.display()))
+}
+
+/// Fails if `offline` is set and `url` is not on the local file system, so
+/// that offline mode errors out before a download client is even made.
+pub(crate) fn check_online(offline: bool, url: &Url) -> Result<()> {
+    if offline && url.scheme() != "file" {
+        bail!(RustupError::Offline {
+            url: url.to_string()
+        });
+    }
+    Ok(())
+}
+
+/// Downloads `url` into `download_dir` under the name of its digest, and
+/// validates the hash. See [`DownloadCfg::download`]; this variant does not need a whole
+/// `DownloadCfg`, so it can run off the main thread, and stops early once
+/// `cancel` is set. A file that is already cached is used even if `offline`
+/// is set.
+pub(crate) fn download_to_cache(
+    download_dir: &Path,
+    url: &Url,
+    hash: &str,
+    cancel: Option<&AtomicBool>,
+    options: download::Options<'_>,
+    offline: bool,
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> Result<File> {
+    let checksum = Checksum::parse(hash)?;
+    let target_file = download_dir.join(Path::new(checksum.digest));
+
+    if target_file.exists() {
+        let cached_result = file_hash(&target_file, checksum.algorithm, notify_handler)?;
+        if checksum.digest == cached_result {
+            // Reusing the file counts as using it, for `trim_cache`
+            let _ = fs::File::options()
+                .write(true)
+                .open(&target_file)
+                .and_then(|f| f.set_modified(SystemTime::now()));
+            notify_handler(Notification::FileAlreadyDownloaded);
+            notify_handler(Notification::ChecksumValid(url.as_ref()));
+            return Ok(File { path: target_file });
+        } else {
+            notify_handler(Notification::CachedFileChecksumFailed);
+            fs::remove_file(&target_file).context("cleaning up previous download")?;
+        }
+    }
+
+    let partial_file_path = target_file.with_file_name(
+        target_file
+            .file_name()
+            .map(|s| s.to_str().unwrap_or("_"))
+            .unwrap_or("_")
+            .to_owned()
+            + ".partial",
+    );
+
+    check_online(offline, url)?;
+    let partial_file_existed = partial_file_path.exists();
+
+    let mut hasher = checksum.algorithm.hasher();
+
+    if let Err(e) = utils::download_file_with_resume(
+        url,
+        &partial_file_path,
+        Some(&mut *hasher),
+        true,
+        cancel,
+        options,
+        &|n| notify_handler(n.into()),
+    ) {
+        let err = Err(e);
+        if partial_file_existed {
+            return err.context(RustupError::BrokenPartialFile);
+        } else {
+            return err;
+        }
+    };
+
+    let actual_hash = hex_digest(hasher);
+
+    if checksum.digest != actual_hash {
+        // Incorrect hash
+        if partial_file_existed {
+            fs::remove_file(&partial_file_path).context("cleaning up cached downloads")?;
+            Err(anyhow!(RustupError::BrokenPartialFile))
+        } else {
+            Err(RustupError::ChecksumFailed {
+                url: url.to_string(),
+                expected: checksum.digest.to_string(),
+                calculated: actual_hash,
+            }
+            .into())
+        }
+    } else {
+        notify_handler(Notification::ChecksumValid(url.as_ref()));
+
+        utils::rename_file(
+            "downloaded",
+            &partial_file_path,
+            &target_file,
+            notify_handler,
+        )?;
+        Ok(File { path: target_file })
+    }
+}
+
+/// Downloads `url` without saving it, passing the data to `data_handler` as
+/// it arrives, and validates the hash once the download is complete. See
+/// [`download_to_cache`] for the other arguments.
+pub(crate) fn download_stream(
+    url: &Url,
+    hash: &str,
+    cancel: Option<&AtomicBool>,
+    options: download::Options<'_>,
+    offline: bool,
+    data_handler: &dyn Fn(&[u8]),
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> Result<()> {
+    let checksum = Checksum::parse(hash)?;
+    check_online(offline, url)?;
+
+    let mut hasher = checksum.algorithm.hasher();
+    utils::download_stream(url, &mut *hasher, cancel, options, data_handler, &|n| {
+        notify_handler(n.into())
+    })?;
+    let actual_hash = hex_digest(hasher);
+
+    if checksum.digest != actual_hash {
+        return Err(RustupError::ChecksumFailed {
+            url: url.to_string(),
+            expected: checksum.digest.to_string(),
+            calculated: actual_hash,
+        }
+        .into());
+    }
+    notify_handler(Notification::ChecksumValid(url.as_ref()));
+    Ok(())
 }
 
-fn file_hash(path: &Path, notify_handler: &dyn Fn(Notification<'_>)) -> Result<String> {
-    let mut hasher = Sha256::new();
+fn file_hash(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> Result<String> {
+    let mut hasher = algorithm.hasher();
     let notification_converter = |notification: crate::utils::Notification<'_>| {
         notify_handler(notification.into());
     };
@@ -204,5 +609,5 @@ fn file_hash(path: &Path, notify_handler: &dyn Fn(Notification<'_>)) -> Result<S
         hasher.update(&buf[..n]);
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    Ok(hex_digest(hasher))
 }
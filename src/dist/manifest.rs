@@ -12,7 +12,7 @@
 //!
 //! Docs: <https://forge.rust-lang.org/infra/channel-layout.html>
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
@@ -29,7 +29,6 @@ pub(crate) const SUPPORTED_MANIFEST_VERSIONS: [&str; 1] = ["2"];
 /// Used by the `installed_components` function
 pub(crate) struct ComponentStatus {
     pub component: Component,
-    pub name: String,
     pub installed: bool,
     pub available: bool,
 }
@@ -274,6 +273,16 @@ impl Manifest {
 
         Ok(result)
     }
+    /// The profiles this manifest defines, each with the names of its
+    /// components, in order of size. Empty for an older manifest that
+    /// predates profiles.
+    pub(crate) fn profiles(&self) -> Vec<(Profile, &[String])> {
+        Profile::all()
+            .iter()
+            .filter_map(|p| self.profiles.get(p).map(|c| (*p, &c[..])))
+            .collect()
+    }
+
     pub fn get_profile_components(
         &self,
         profile: Profile,
@@ -356,6 +365,62 @@ impl Manifest {
         })
     }
 
+    /// The components that those in `requested` cannot work without,
+    /// directly or through one another, and that are not requested
+    /// themselves, each with the component that needs it. Dependencies are
+    /// looked up among the components of the `rust` package for `target`,
+    /// and skipped if it has no such component.
+    pub(crate) fn dependencies(
+        &self,
+        requested: &[Component],
+        target: &TargetTriple,
+    ) -> Result<Vec<(Component, Component)>> {
+        let rust_target_package = self.get_package("rust")?.get_target(Some(target))?;
+
+        // Everything is visited once, so that a cycle of dependencies cannot
+        // go round forever
+        let mut seen: HashSet<&Component> = requested.iter().collect();
+        let mut pending: Vec<&Component> = requested.iter().collect();
+        let mut dependencies = Vec::new();
+        while let Some(c) = pending.pop() {
+            for dep in hard_dependencies(c.short_name_in_manifest()) {
+                let Some(dep) = rust_target_package.components.iter().find(|d| {
+                    d.short_name_in_manifest() == dep
+                        && (d.target.is_none() || d.target == c.target)
+                }) else {
+                    continue;
+                };
+                if seen.insert(dep) {
+                    pending.push(dep);
+                    let dep = match dep.target {
+                        Some(_) => dep.clone(),
+                        None => dep.wildcard(),
+                    };
+                    dependencies.push((dep, c.clone()));
+                }
+            }
+        }
+        dependencies.sort();
+        Ok(dependencies)
+    }
+
+    /// The components of `installed` that cannot work without `component`.
+    pub(crate) fn dependents(
+        &self,
+        component: &Component,
+        installed: &[Component],
+    ) -> Vec<Component> {
+        installed
+            .iter()
+            .filter(|c| {
+                hard_dependencies(c.short_name_in_manifest())
+                    .contains(&component.short_name_in_manifest().as_str())
+                    && (component.target.is_none() || component.target == c.target)
+            })
+            .cloned()
+            .collect()
+    }
+
     /// Determine installed components from an installed manifest.
     pub(crate) fn query_components(
         &self,
@@ -396,7 +461,6 @@ impl Manifest {
 
             res.push(ComponentStatus {
                 component: component.clone(),
-                name: component.name(self),
                 installed,
                 available: component_target_pkg.available(),
             });
@@ -408,6 +472,27 @@ impl Manifest {
     }
 }
 
+/// Components which are of no use without the returned ones, by the names
+/// of their packages, which for some tools still have a `-preview` suffix.
+/// The manifest does not record this, so it is kept here.
+fn hard_dependencies(component: &str) -> &'static [&'static str] {
+    match component {
+        "rustc" | "rust-analysis" => &["rust-std"],
+        "cargo"
+        | "clippy"
+        | "clippy-preview"
+        | "rustc-dev"
+        | "rustfmt"
+        | "rustfmt-preview"
+        | "rls"
+        | "rls-preview"
+        | "rust-analyzer"
+        | "rust-analyzer-preview" => &["rustc"],
+        "miri" | "miri-preview" => &["rustc", "rust-src"],
+        _ => &[],
+    }
+}
+
 impl Package {
     pub(crate) fn from_toml(mut table: toml::value::Table, path: &str) -> Result<Self> {
         Ok(Self {
@@ -710,7 +795,7 @@ impl Component {
 #[cfg(test)]
 mod tests {
     use crate::dist::dist::TargetTriple;
-    use crate::dist::manifest::Manifest;
+    use crate::dist::manifest::{Component, Manifest};
     use crate::RustupError;
 
     // Example manifest from https://public.etherpad-mozilla.org/p/Rust-infra-work-week
@@ -756,6 +841,32 @@ mod tests {
         assert_eq!(docs_target_pkg.bins[0].1.url, "example.com");
     }
 
+    #[test]
+    fn dependents_are_found_by_package_name() {
+        let target = TargetTriple::new("x86_64-unknown-linux-gnu");
+        let manifest = Manifest::parse(EXAMPLE).unwrap();
+        let component = |name: &str| Component::new(name.to_owned(), Some(target.clone()), false);
+        let installed = [
+            component("rustc"),
+            component("rust-std"),
+            component("clippy-preview"),
+            component("rust-analysis"),
+            component("rust-docs"),
+        ];
+
+        assert_eq!(
+            manifest.dependents(&component("rustc"), &installed),
+            [component("clippy-preview")]
+        );
+        assert_eq!(
+            manifest.dependents(&component("rust-std"), &installed),
+            [component("rustc"), component("rust-analysis")]
+        );
+        assert!(manifest
+            .dependents(&component("rust-docs"), &installed)
+            .is_empty());
+    }
+
     #[test]
     fn renames() {
         let manifest = Manifest::parse(EXAMPLE2).unwrap();
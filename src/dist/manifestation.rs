@@ -4,26 +4,33 @@
 #[cfg(test)]
 mod tests;
 
-use std::path::Path;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 use anyhow::{anyhow, bail, Context, Result};
-use retry::delay::NoDelay;
+use retry::delay::{jitter, Exponential};
 use retry::{retry, OperationResult};
+use url::Url;
 
-use crate::currentprocess::varsource::VarSource;
+use crate::currentprocess;
 use crate::dist::component::{
-    Components, Package, TarGzPackage, TarXzPackage, TarZStdPackage, Transaction,
+    integrity, Components, DirectoryPackage, Package, TarGzPackage, TarXzPackage, TarZStdPackage,
+    Transaction,
 };
 use crate::dist::config::Config;
 use crate::dist::dist::{Profile, TargetTriple, DEFAULT_DIST_SERVER};
-use crate::dist::download::{DownloadCfg, File};
+use crate::dist::download::{self as dist_download, DownloadCfg, File};
 use crate::dist::manifest::{Component, CompressionKind, Manifest, TargetedPackage};
 use crate::dist::notifications::*;
 use crate::dist::prefix::InstallPrefix;
 use crate::dist::temp;
 use crate::errors::{OperationError, RustupError};
 use crate::process;
-use crate::utils::utils;
+use crate::utils::{utils, Notification as Un};
 
 pub(crate) const DIST_MANIFEST: &str = "multirust-channel-manifest.toml";
 pub(crate) const CONFIG_FILE: &str = "multirust-config.toml";
@@ -38,6 +45,13 @@ pub struct Manifestation {
 pub struct Changes {
     pub explicit_add_components: Vec<Component>,
     pub remove_components: Vec<Component>,
+    /// Installed components to uninstall and install again, even though the
+    /// manifest has not changed, to repair them.
+    pub reinstall_components: Vec<Component>,
+    /// Those of `explicit_add_components` that are only added because
+    /// others need them, and so are recorded as dependencies unless they
+    /// are installed already.
+    pub dependency_components: Vec<Component>,
 }
 
 impl Changes {
@@ -125,81 +139,40 @@ impl Manifestation {
             &download_cfg.notify_handler,
         )?;
 
+        let dependencies = update.dependencies(&changes, &config);
         if update.nothing_changes() {
-            return Ok(UpdateStatus::Unchanged);
-        }
-
-        // Validate that the requested components are available
-        if let Err(e) = update.unavailable_components(new_manifest, toolchain_str) {
-            if !force_update {
-                return Err(e);
-            }
-            if let Ok(RustupError::RequestedComponentsUnavailable { components, .. }) =
-                e.downcast::<RustupError>()
-            {
-                for component in &components {
-                    (download_cfg.notify_handler)(Notification::ForcingUnavailableComponent(
-                        &component.name(new_manifest),
-                    ));
-                }
-                update.drop_components_to_install(&components);
+            // A dependency may still have been asked for by name, which
+            // makes it count as explicitly installed from now on
+            if let Some(config) = config.filter(|c| c.dependencies != dependencies) {
+                let new_config = Config {
+                    dependencies,
+                    ..config
+                };
+                let config_path = prefix.path().join(prefix.rel_manifest_file(CONFIG_FILE));
+                utils::write_file("dist config", &config_path, &new_config.stringify())?;
             }
+            return Ok(UpdateStatus::Unchanged);
         }
 
-        let altered = tmp_cx.dist_server != DEFAULT_DIST_SERVER;
+        update.check_available(new_manifest, toolchain_str, force_update, download_cfg)?;
 
         // Download component packages and validate hashes
-        let mut things_to_install: Vec<(Component, CompressionKind, File)> = Vec::new();
-        let mut things_downloaded: Vec<String> = Vec::new();
-        let components = update.components_urls_and_hashes(new_manifest)?;
-
-        const DEFAULT_MAX_RETRIES: usize = 3;
-        let max_retries: usize = process()
-            .var("RUSTUP_MAX_RETRIES")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(DEFAULT_MAX_RETRIES);
-
-        for (component, format, url, hash) in components {
-            (download_cfg.notify_handler)(Notification::DownloadingComponent(
-                &component.short_name(new_manifest),
-                &self.target_triple,
-                component.target.as_ref(),
-            ));
-            let url = if altered {
-                url.replace(DEFAULT_DIST_SERVER, tmp_cx.dist_server.as_str())
-            } else {
-                url
-            };
-
-            let url_url = utils::parse_url(&url)?;
-
-            let downloaded_file = retry(NoDelay.take(max_retries), || {
-                match download_cfg.download(&url_url, &hash) {
-                    Ok(f) => OperationResult::Ok(f),
-                    Err(e) => {
-                        match e.downcast_ref::<RustupError>() {
-                            Some(RustupError::BrokenPartialFile) => {
-                                (download_cfg.notify_handler)(Notification::RetryingDownload(&url));
-                                return OperationResult::Retry(OperationError(e));
-                            }
-                            Some(RustupError::DownloadingFile { .. }) => {
-                                (download_cfg.notify_handler)(Notification::RetryingDownload(&url));
-                                return OperationResult::Retry(OperationError(e));
-                            }
-                            Some(_) => return OperationResult::Err(OperationError(e)),
-                            None => (),
-                        };
-                        OperationResult::Err(OperationError(e))
-                    }
-                }
-            })
-            .with_context(|| RustupError::ComponentDownloadFailed(component.name(new_manifest)))?;
-
-            things_downloaded.push(hash);
-
-            things_to_install.push((component, format, downloaded_file));
-        }
+        let downloads = update.downloads(new_manifest, download_cfg)?;
+        let things_downloaded: Vec<String> = downloads.iter().map(|d| d.hash.clone()).collect();
+
+        // Make room for this update's downloads, keeping any that an earlier,
+        // interrupted attempt left behind
+        download_cfg.trim_cache(&things_downloaded)?;
+
+        let things_to_install = if download_cfg.stream_extract {
+            // Each package is downloaded when it is installed
+            downloads
+                .into_iter()
+                .map(|d| (d.component.clone(), d.format, PackageSource::Streamed(d)))
+                .collect()
+        } else {
+            self.download_packages(downloads, new_manifest, download_cfg)?
+        };
 
         // Begin transaction
         let mut tx = Transaction::new(prefix.clone(), tmp_cx, download_cfg.notify_handler);
@@ -208,7 +181,9 @@ impl Manifestation {
         // to uninstall it first.
         tx = self.maybe_handle_v2_upgrade(&config, tx)?;
 
-        // Uninstall components
+        // Uninstall components, noting those being reinstalled whose files
+        // rustup has lost track of
+        let mut untracked = Vec::new();
         for component in &update.components_to_uninstall {
             let notification = if implicit_modify {
                 Notification::RemovingOldComponent
@@ -221,16 +196,29 @@ impl Manifestation {
                 component.target.as_ref(),
             ));
 
-            tx = self.uninstall_component(
-                component,
-                new_manifest,
-                tx,
-                &download_cfg.notify_handler,
-            )?;
+            if changes.reinstall_components.contains(component) {
+                let tracked;
+                (tx, tracked) = self.uninstall_damaged_component(
+                    component,
+                    new_manifest,
+                    tx,
+                    &download_cfg.notify_handler,
+                )?;
+                if !tracked {
+                    untracked.push(component.clone());
+                }
+            } else {
+                tx = self.uninstall_component(
+                    component,
+                    new_manifest,
+                    tx,
+                    &download_cfg.notify_handler,
+                )?;
+            }
         }
 
         // Install components
-        for (component, format, installer_file) in things_to_install {
+        for (component, format, source) in things_to_install {
             // For historical reasons, the rust-installer component
             // names are not the same as the dist manifest component
             // names. Some are just the component name some are the
@@ -239,6 +227,13 @@ impl Manifestation {
             let short_pkg_name = component.short_name_in_manifest();
             let short_name = component.short_name(new_manifest);
 
+            if let PackageSource::Streamed(_) = source {
+                (download_cfg.notify_handler)(Notification::DownloadingComponent(
+                    &short_name,
+                    &self.target_triple,
+                    component.target.as_ref(),
+                ));
+            }
             (download_cfg.notify_handler)(Notification::InstallingComponent(
                 &short_name,
                 &self.target_triple,
@@ -248,24 +243,23 @@ impl Manifestation {
             let notification_converter = |notification: crate::utils::Notification<'_>| {
                 (download_cfg.notify_handler)(notification.into());
             };
-            let gz;
-            let xz;
-            let zst;
-            let reader =
-                utils::FileReaderWithProgress::new_file(&installer_file, &notification_converter)?;
-            let package: &dyn Package = match format {
-                CompressionKind::GZip => {
-                    gz = TarGzPackage::new(reader, tmp_cx, Some(&notification_converter))?;
-                    &gz
-                }
-                CompressionKind::XZ => {
-                    xz = TarXzPackage::new(reader, tmp_cx, Some(&notification_converter))?;
-                    &xz
-                }
-                CompressionKind::ZStd => {
-                    zst = TarZStdPackage::new(reader, tmp_cx, Some(&notification_converter))?;
-                    &zst
+            (download_cfg.notify_handler)(Notification::ExtractingComponent(&short_name));
+            let package = match source {
+                PackageSource::Cached(installer_file) => {
+                    let reader = utils::FileReaderWithProgress::new_file(
+                        &installer_file,
+                        &notification_converter,
+                    )?;
+                    unpack(format, reader, tmp_cx, &notification_converter)?
                 }
+                PackageSource::Streamed(d) => download_component(
+                    &d,
+                    new_manifest,
+                    download_cfg.retries,
+                    download_cfg.retry_backoff,
+                    || stream_component(&d, download_cfg, &notification_converter),
+                    |url| (download_cfg.notify_handler)(Notification::RetryingDownload(url)),
+                )?,
             };
 
             // If the package doesn't contain the component that the
@@ -274,7 +268,14 @@ impl Manifestation {
                 return Err(RustupError::CorruptComponent(short_name).into());
             }
 
-            tx = package.install(&self.installation, &pkg_name, Some(short_pkg_name), tx)?;
+            // Without a record of the old files, the best that can be done
+            // is to unpack the component over them
+            let target = if untracked.contains(&component) {
+                self.installation.overwriting()
+            } else {
+                self.installation.clone()
+            };
+            tx = package.install(&target, &pkg_name, Some(short_pkg_name), tx)?;
         }
 
         // Install new distribution manifest
@@ -290,6 +291,7 @@ impl Manifestation {
         // name/target. Needs to be fixed in rust-installer.
         let mut new_config = Config::new();
         new_config.components = update.final_component_list;
+        new_config.dependencies = dependencies;
         let config_str = new_config.stringify();
         let rel_config_path = prefix.rel_manifest_file(CONFIG_FILE);
         let config_path = prefix.path().join(&rel_config_path);
@@ -304,6 +306,73 @@ impl Manifestation {
         Ok(UpdateStatus::Changed)
     }
 
+    /// Downloads what `update` would install for `changes` into the download
+    /// cache, and returns how many packages that is, without installing any
+    /// of them. The packages are kept in the cache even if
+    /// `download.stream-extract` is set.
+    pub(crate) fn download_only(
+        &self,
+        new_manifest: &Manifest,
+        changes: Changes,
+        force_update: bool,
+        download_cfg: &DownloadCfg<'_>,
+        toolchain_str: &str,
+    ) -> Result<usize> {
+        let config = self.read_config()?;
+        let mut update = Update::build_update(
+            self,
+            new_manifest,
+            &changes,
+            &config,
+            &download_cfg.notify_handler,
+        )?;
+        if update.nothing_changes() {
+            return Ok(0);
+        }
+        update.check_available(new_manifest, toolchain_str, force_update, download_cfg)?;
+
+        let downloads = update.downloads(new_manifest, download_cfg)?;
+        let things_downloaded: Vec<String> = downloads.iter().map(|d| d.hash.clone()).collect();
+        download_cfg.trim_cache(&things_downloaded)?;
+        Ok(self
+            .download_packages(downloads, new_manifest, download_cfg)?
+            .len())
+    }
+
+    /// What `update` would download for `changes`, as the name of each
+    /// component with the size of its package if the server reports one.
+    pub(crate) fn plan(
+        &self,
+        new_manifest: &Manifest,
+        changes: Changes,
+        force_update: bool,
+        download_cfg: &DownloadCfg<'_>,
+        toolchain_str: &str,
+    ) -> Result<Vec<(String, Option<u64>)>> {
+        let config = self.read_config()?;
+        let mut update = Update::build_update(
+            self,
+            new_manifest,
+            &changes,
+            &config,
+            &download_cfg.notify_handler,
+        )?;
+        if update.nothing_changes() {
+            return Ok(Vec::new());
+        }
+        update.check_available(new_manifest, toolchain_str, force_update, download_cfg)?;
+
+        update
+            .downloads(new_manifest, download_cfg)?
+            .into_iter()
+            .map(|d| {
+                dist_download::check_online(download_cfg.offline, &d.url)?;
+                let size = utils::download_content_length(&d.url, download_cfg.ca_bundle)?;
+                Ok((d.component.name(new_manifest), size))
+            })
+            .collect()
+    }
+
     pub fn uninstall(
         &self,
         manifest: &Manifest,
@@ -354,6 +423,113 @@ impl Manifestation {
         Ok(tx)
     }
 
+    /// Like [`Self::uninstall_component`], but for a component that is to be
+    /// reinstalled because it is damaged. Returns whether rustup still knew
+    /// which files the component has.
+    fn uninstall_damaged_component<'a>(
+        &self,
+        component: &Component,
+        manifest: &Manifest,
+        tx: Transaction<'a>,
+        notify_handler: &dyn Fn(Notification<'_>),
+    ) -> Result<(Transaction<'a>, bool)> {
+        let name = component.name_in_manifest();
+        let short_name = component.short_name_in_manifest();
+        if let Some(c) = self.installation.find(&name)? {
+            c.uninstall_damaged(tx)
+        } else if let Some(c) = self.installation.find(short_name)? {
+            c.uninstall_damaged(tx)
+        } else {
+            notify_handler(Notification::MissingInstalledComponent(
+                &component.short_name(manifest),
+            ));
+            Ok((tx, false))
+        }
+    }
+
+    /// Install the component `name` from the unpacked rust-installer package
+    /// in `path`. It is tracked like any other component, so it can be
+    /// removed again, but as it is not in the dist config updates leave it be.
+    pub(crate) fn install_local_component(
+        &self,
+        name: &str,
+        path: &Path,
+        tmp_cx: &temp::Context,
+        notify_handler: &dyn Fn(Notification<'_>),
+    ) -> Result<()> {
+        let package = DirectoryPackage::new(path.to_owned(), true)?;
+        if !package.contains(name, None) {
+            bail!(RustupError::ComponentNotInPackage {
+                name: name.to_owned(),
+                path: path.to_owned(),
+            });
+        }
+        if self.installation.find(name)?.is_some() {
+            bail!(RustupError::ComponentAlreadyInstalled(name.to_owned()));
+        }
+
+        notify_handler(Notification::InstallingComponent(
+            name,
+            &self.target_triple,
+            None,
+        ));
+        let tx = Transaction::new(self.installation.prefix(), tmp_cx, notify_handler);
+        let tx = package.install(&self.installation, name, None, tx)?;
+        tx.commit();
+        Ok(())
+    }
+
+    /// Remove a component installed by [`Self::install_local_component`].
+    /// Returns `false` if there is no such component.
+    pub(crate) fn uninstall_local_component(
+        &self,
+        name: &str,
+        tmp_cx: &temp::Context,
+        notify_handler: &dyn Fn(Notification<'_>),
+    ) -> Result<bool> {
+        if !self.local_components()?.iter().any(|c| c == name) {
+            return Ok(false);
+        }
+        let Some(component) = self.installation.find(name)? else {
+            return Ok(false);
+        };
+
+        notify_handler(Notification::RemovingComponent(
+            name,
+            &self.target_triple,
+            None,
+        ));
+        let tx = Transaction::new(self.installation.prefix(), tmp_cx, notify_handler);
+        let tx = component.uninstall(tx)?;
+        tx.commit();
+        Ok(true)
+    }
+
+    /// Check the files of the installed components against the hashes that
+    /// were recorded when they were installed.
+    pub(crate) fn verify(&self) -> Result<integrity::Report> {
+        integrity::verify(&self.installation)
+    }
+
+    /// The names of the installed components that are not from the
+    /// distribution server, sorted.
+    pub(crate) fn local_components(&self) -> Result<Vec<String>> {
+        let config = self.read_config()?.unwrap_or_default();
+        let mut local: Vec<_> =
+            self.installation
+                .list()?
+                .into_iter()
+                .map(|c| c.name().to_owned())
+                .filter(|name| {
+                    !config.components.iter().any(|c| {
+                        c.name_in_manifest() == *name || c.short_name_in_manifest() == name
+                    })
+                })
+                .collect();
+        local.sort();
+        Ok(local)
+    }
+
     // Read the config file. Config files are presently only created
     // for v2 installations.
     pub(crate) fn read_config(&self) -> Result<Option<Config>> {
@@ -386,6 +562,7 @@ impl Manifestation {
         new_manifest: &[String],
         update_hash: Option<&Path>,
         tmp_cx: &temp::Context,
+        ca_bundle: Option<&Path>,
         notify_handler: &dyn Fn(Notification<'_>),
     ) -> Result<Option<String>> {
         // If there's already a v2 installation then something has gone wrong
@@ -415,12 +592,22 @@ impl Manifestation {
             Some(&self.target_triple),
         ));
 
-        use std::path::PathBuf;
         let dld_dir = PathBuf::from("bogus");
         let dlcfg = DownloadCfg {
             dist_root: "bogus",
             download_dir: &dld_dir,
             tmp_cx,
+            concurrency: 1,
+            retries: 0,
+            retry_backoff: Duration::ZERO,
+            local_dist_dir: None,
+            signature_keys: None,
+            allow_unsigned_local: false,
+            rate_limiter: None,
+            ca_bundle,
+            cache_size: None,
+            stream_extract: false,
+            offline: false,
             notify_handler,
         };
 
@@ -488,6 +675,428 @@ impl Manifestation {
 
         Ok(tx)
     }
+
+    /// Downloads the packages of `downloads` into the download cache.
+    fn download_packages(
+        &self,
+        downloads: Vec<ComponentDownload>,
+        new_manifest: &Manifest,
+        download_cfg: &DownloadCfg<'_>,
+    ) -> Result<Vec<(Component, CompressionKind, PackageSource)>> {
+        if download_cfg.concurrency > 1 && downloads.len() > 1 {
+            return self.download_concurrently(downloads, new_manifest, download_cfg);
+        }
+        let mut things_to_install = Vec::with_capacity(downloads.len());
+        for d in downloads {
+            self.notify_downloading(&d, new_manifest, download_cfg.notify_handler);
+            let file = download_component(
+                &d,
+                new_manifest,
+                download_cfg.retries,
+                download_cfg.retry_backoff,
+                || download_cfg.download(&d.url, &d.hash),
+                |url| (download_cfg.notify_handler)(Notification::RetryingDownload(url)),
+            )?;
+            things_to_install.push((d.component, d.format, PackageSource::Cached(file)));
+        }
+        Ok(things_to_install)
+    }
+
+    /// Tells `notify_handler` that the component of `d` is being downloaded.
+    fn notify_downloading(
+        &self,
+        d: &ComponentDownload,
+        new_manifest: &Manifest,
+        notify_handler: &dyn Fn(Notification<'_>),
+    ) {
+        notify_handler(Notification::DownloadingComponent(
+            &d.component.short_name(new_manifest),
+            &self.target_triple,
+            d.component.target.as_ref(),
+        ));
+    }
+
+    /// Download up to `download_cfg.concurrency` components at once, returning
+    /// the files in the same order as `downloads`.
+    ///
+    /// The notification handler is not `Sync`, so the workers send their
+    /// progress back to this thread, which reports it as a single download.
+    /// The first failure stops the remaining downloads and is returned.
+    fn download_concurrently(
+        &self,
+        downloads: Vec<ComponentDownload>,
+        new_manifest: &Manifest,
+        download_cfg: &DownloadCfg<'_>,
+    ) -> Result<Vec<(Component, CompressionKind, PackageSource)>> {
+        let notify_handler = download_cfg.notify_handler;
+        utils::ensure_dir_exists(
+            "Download Directory",
+            download_cfg.download_dir,
+            &notify_handler,
+        )?;
+        for d in &downloads {
+            self.notify_downloading(d, new_manifest, notify_handler);
+        }
+
+        let download_dir = download_cfg.download_dir;
+        let (retries, retry_backoff) = (download_cfg.retries, download_cfg.retry_backoff);
+        let options = download_cfg.download_options();
+        let offline = download_cfg.offline;
+        let next = AtomicUsize::new(0);
+        let cancel = AtomicBool::new(false);
+        let mut files: Vec<Option<File>> = downloads.iter().map(|_| None).collect();
+        let mut first_error = None;
+        let process = process();
+
+        thread::scope(|s| {
+            let (tx, rx) = mpsc::channel();
+            for _ in 0..download_cfg.concurrency.min(downloads.len()) {
+                let tx = tx.clone();
+                let (downloads, next, cancel, process) = (&downloads, &next, &cancel, &process);
+                s.spawn(move || {
+                    currentprocess::with(process.clone(), || loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= downloads.len() || cancel.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let d = &downloads[i];
+                        let notify = |n: Notification<'_>| WorkerEvent::forward(&tx, n);
+                        let result = download_component(
+                            d,
+                            new_manifest,
+                            retries,
+                            retry_backoff,
+                            || {
+                                dist_download::download_to_cache(
+                                    download_dir,
+                                    &d.url,
+                                    &d.hash,
+                                    Some(cancel),
+                                    options,
+                                    offline,
+                                    &notify,
+                                )
+                            },
+                            |url| {
+                                let _ = tx.send(WorkerEvent::Retrying(url.to_owned()));
+                            },
+                        );
+                        let _ = tx.send(WorkerEvent::Done(i, result));
+                    })
+                });
+            }
+            drop(tx);
+
+            let mut content_len = 0;
+            for event in rx {
+                match event.report(notify_handler, &mut content_len) {
+                    Some((i, Ok(file))) => files[i] = Some(file),
+                    Some((_, Err(e))) => {
+                        if first_error.is_none() {
+                            cancel.store(true, Ordering::Relaxed);
+                            first_error = Some(e);
+                        }
+                    }
+                    None => (),
+                }
+            }
+        });
+        notify_handler(Notification::Utils(Un::DownloadFinished));
+
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+        Ok(downloads
+            .into_iter()
+            .zip(files)
+            .map(|(d, file)| {
+                let file = file.expect("download finished");
+                (d.component, d.format, PackageSource::Cached(file))
+            })
+            .collect())
+    }
+}
+
+/// Where the package of a component to install comes from.
+enum PackageSource {
+    /// A file in the download cache.
+    Cached(File),
+    /// The dist server, from which it is unpacked as it downloads.
+    Streamed(ComponentDownload),
+}
+
+/// Unpacks the package that `reader` reads into a temporary directory.
+fn unpack<'a>(
+    format: CompressionKind,
+    reader: impl Read,
+    tmp_cx: &'a temp::Context,
+    notify_handler: &'a dyn Fn(Un<'_>),
+) -> Result<Box<dyn Package + 'a>> {
+    Ok(match format {
+        CompressionKind::GZip => Box::new(TarGzPackage::new(reader, tmp_cx, Some(notify_handler))?),
+        CompressionKind::XZ => Box::new(TarXzPackage::new(reader, tmp_cx, Some(notify_handler))?),
+        CompressionKind::ZStd => {
+            Box::new(TarZStdPackage::new(reader, tmp_cx, Some(notify_handler))?)
+        }
+    })
+}
+
+/// How many chunks of a streamed download may wait to be unpacked before
+/// the download waits in turn.
+const STREAM_BUFFER_CHUNKS: usize = 64;
+
+/// Downloads the package of `d` on another thread and unpacks it on this
+/// one as the data arrives, so that the package is never stored whole.
+///
+/// The unpacked package is only returned once the download is complete and
+/// has matched its hash, so nothing of a corrupt download gets installed.
+fn stream_component<'a>(
+    d: &ComponentDownload,
+    download_cfg: &DownloadCfg<'a>,
+    notify_handler: &'a dyn Fn(Un<'_>),
+) -> Result<Box<dyn Package + 'a>> {
+    let (options, offline) = (download_cfg.download_options(), download_cfg.offline);
+    let cancel = AtomicBool::new(false);
+    let process = process();
+
+    thread::scope(|s| {
+        let (data_tx, data_rx) = mpsc::sync_channel(STREAM_BUFFER_CHUNKS);
+        let (event_tx, event_rx) = mpsc::channel();
+        let (cancel, process) = (&cancel, &process);
+        let worker = s.spawn(move || {
+            currentprocess::with(process.clone(), || {
+                dist_download::download_stream(
+                    &d.url,
+                    &d.hash,
+                    Some(cancel),
+                    options,
+                    offline,
+                    &|data| {
+                        let _ = data_tx.send(data.to_vec());
+                    },
+                    &|n| WorkerEvent::forward(&event_tx, n),
+                )
+            })
+        });
+
+        let mut reader = StreamReader {
+            data: data_rx,
+            events: event_rx,
+            chunk: Vec::new(),
+            pos: 0,
+            content_len: 0,
+            notify_handler: download_cfg.notify_handler,
+        };
+        let package = unpack(d.format, &mut reader, download_cfg.tmp_cx, notify_handler);
+        // The hash covers all of the download, including anything after the
+        // end of the archive
+        let drained = match package {
+            Ok(_) => io::copy(&mut reader, &mut io::sink()).map(drop),
+            Err(_) => {
+                cancel.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+        };
+        // Dropping the data channel lets a download that is waiting to send
+        // data on a full channel see that it has been cancelled
+        let StreamReader {
+            data,
+            events,
+            mut content_len,
+            ..
+        } = reader;
+        drop(data);
+        let downloaded = worker
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        for event in events.try_iter() {
+            event.report(download_cfg.notify_handler, &mut content_len);
+        }
+        (download_cfg.notify_handler)(Notification::Utils(Un::DownloadFinished));
+
+        // A failed download explains a failure to unpack it, so it comes first
+        match downloaded {
+            Err(e)
+                if !matches!(
+                    e.downcast_ref::<download::DownloadError>(),
+                    Some(download::DownloadError::Cancelled)
+                ) =>
+            {
+                Err(e)
+            }
+            _ => {
+                drained.context("failed to extract package")?;
+                package
+            }
+        }
+    })
+}
+
+/// Reads a download that runs on another thread, out of the chunks of data
+/// that it sends, and reports the progress it sends along the way.
+struct StreamReader<'a> {
+    data: mpsc::Receiver<Vec<u8>>,
+    events: mpsc::Receiver<WorkerEvent>,
+    chunk: Vec<u8>,
+    pos: usize,
+    content_len: u64,
+    notify_handler: &'a dyn Fn(Notification<'_>),
+}
+
+impl Read for StreamReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            for event in self.events.try_iter() {
+                event.report(self.notify_handler, &mut self.content_len);
+            }
+            // The download is over once the channel closes
+            let Ok(chunk) = self.data.recv() else {
+                return Ok(0);
+            };
+            self.chunk = chunk;
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A component package to fetch into the download cache.
+struct ComponentDownload {
+    component: Component,
+    format: CompressionKind,
+    url: Url,
+    hash: String,
+}
+
+/// Download a single component, retrying failures that may be transient
+/// up to `retries` times, with an exponential backoff starting at `backoff`.
+fn download_component<T>(
+    d: &ComponentDownload,
+    new_manifest: &Manifest,
+    retries: usize,
+    backoff: Duration,
+    fetch: impl Fn() -> Result<T>,
+    on_retry: impl Fn(&str),
+) -> Result<T> {
+    let backoff = u64::try_from(backoff.as_millis()).unwrap_or(u64::MAX);
+    let delays = Exponential::from_millis_with_factor(backoff, 2.0)
+        .map(jitter)
+        .take(retries);
+    retry(delays, || match fetch() {
+        Ok(f) => OperationResult::Ok(f),
+        Err(e) => {
+            match e.downcast_ref::<download::DownloadError>() {
+                Some(download::DownloadError::LocalFileNotFound(path)) => {
+                    let err = RustupError::ComponentNotFoundOnMirror {
+                        name: d.component.name(new_manifest),
+                        path: path.clone(),
+                    };
+                    return OperationResult::Err(OperationError(e.context(err)));
+                }
+                Some(download::DownloadError::Cancelled) => {
+                    return OperationResult::Err(OperationError(e))
+                }
+                _ => (),
+            }
+            match e.downcast_ref::<RustupError>() {
+                Some(RustupError::BrokenPartialFile) => {
+                    on_retry(d.url.as_str());
+                    return OperationResult::Retry(OperationError(e));
+                }
+                Some(RustupError::DownloadingFile { .. } | RustupError::StreamingFile { .. })
+                    if download::is_transient(&e) =>
+                {
+                    on_retry(d.url.as_str());
+                    return OperationResult::Retry(OperationError(e));
+                }
+                Some(_) => return OperationResult::Err(OperationError(e)),
+                None => (),
+            };
+            OperationResult::Err(OperationError(e))
+        }
+    })
+    .with_context(|| RustupError::ComponentDownloadFailed(d.component.name(new_manifest)))
+}
+
+/// The parts of a download worker's notifications that are passed on to the
+/// notification handler; the rest are dropped.
+enum WorkerEvent {
+    DownloadingFile(Url, PathBuf),
+    UsingCurl,
+    UsingReqwest,
+    ContentLength(u64),
+    Data(usize),
+    ResumingPartialDownload,
+    FileAlreadyDownloaded,
+    CachedFileChecksumFailed,
+    ChecksumValid(String),
+    Retrying(String),
+    Done(usize, Result<File>),
+}
+
+impl WorkerEvent {
+    /// Passes the event on to `notify_handler`, adding the content lengths
+    /// of all downloads up in `content_len`. The index and result of a
+    /// `Done` event are handed back instead.
+    fn report(
+        self,
+        notify_handler: &dyn Fn(Notification<'_>),
+        content_len: &mut u64,
+    ) -> Option<(usize, Result<File>)> {
+        match self {
+            Self::DownloadingFile(url, path) => {
+                notify_handler(Notification::Utils(Un::DownloadingFile(&url, &path)))
+            }
+            Self::UsingCurl => notify_handler(Notification::Utils(Un::UsingCurl)),
+            Self::UsingReqwest => notify_handler(Notification::Utils(Un::UsingReqwest)),
+            Self::ContentLength(len) => {
+                *content_len += len;
+                notify_handler(Notification::Utils(Un::DownloadContentLengthReceived(
+                    *content_len,
+                )));
+            }
+            Self::Data(len) => notify_handler(Notification::Utils(Un::DownloadDataReceived(len))),
+            Self::ResumingPartialDownload => {
+                notify_handler(Notification::Utils(Un::ResumingPartialDownload))
+            }
+            Self::FileAlreadyDownloaded => notify_handler(Notification::FileAlreadyDownloaded),
+            Self::CachedFileChecksumFailed => {
+                notify_handler(Notification::CachedFileChecksumFailed)
+            }
+            Self::ChecksumValid(url) => notify_handler(Notification::ChecksumValid(&url)),
+            Self::Retrying(url) => notify_handler(Notification::RetryingDownload(&url)),
+            Self::Done(i, result) => return Some((i, result)),
+        }
+        None
+    }
+
+    /// Sends the part of `n` that is passed on, if any, to the thread that
+    /// reports it.
+    fn forward(tx: &mpsc::Sender<Self>, n: Notification<'_>) {
+        if let Some(event) = Self::from_notification(n) {
+            let _ = tx.send(event);
+        }
+    }
+
+    fn from_notification(n: Notification<'_>) -> Option<Self> {
+        Some(match n {
+            Notification::Utils(Un::DownloadingFile(url, path)) => {
+                Self::DownloadingFile(url.clone(), path.to_owned())
+            }
+            Notification::Utils(Un::UsingCurl) => Self::UsingCurl,
+            Notification::Utils(Un::UsingReqwest) => Self::UsingReqwest,
+            Notification::Utils(Un::DownloadContentLengthReceived(len)) => Self::ContentLength(len),
+            Notification::Utils(Un::DownloadDataReceived(len)) => Self::Data(len),
+            Notification::Utils(Un::ResumingPartialDownload) => Self::ResumingPartialDownload,
+            Notification::FileAlreadyDownloaded => Self::FileAlreadyDownloaded,
+            Notification::CachedFileChecksumFailed => Self::CachedFileChecksumFailed,
+            Notification::ChecksumValid(url) => Self::ChecksumValid(url.to_owned()),
+            _ => return None,
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -567,6 +1176,9 @@ impl Update {
             for component in &result.final_component_list {
                 if !starting_list.contains(component) {
                     result.components_to_install.push(component.clone());
+                } else if changes.reinstall_components.contains(component) {
+                    result.components_to_uninstall.push(component.clone());
+                    result.components_to_install.push(component.clone());
                 } else if changes.explicit_add_components.contains(component) {
                     notify_handler(Notification::ComponentAlreadyInstalled(
                         &component.description(new_manifest),
@@ -641,6 +1253,29 @@ impl Update {
         }
     }
 
+    /// The components of the final list that count as dependencies: those
+    /// that were already, unless `changes` asks for them by name, and those
+    /// that `changes` newly adds as dependencies.
+    fn dependencies(&self, changes: &Changes, config: &Option<Config>) -> Vec<Component> {
+        let (installed, dependencies) = match config {
+            Some(config) => (&config.components[..], &config.dependencies[..]),
+            None => (&[][..], &[][..]),
+        };
+        self.final_component_list
+            .iter()
+            .filter(|c| {
+                let as_dependency = changes.dependency_components.contains(c);
+                if installed.contains(c) {
+                    dependencies.contains(c)
+                        && (as_dependency || !changes.explicit_add_components.contains(c))
+                } else {
+                    as_dependency
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
     fn nothing_changes(&self) -> bool {
         self.components_to_uninstall.is_empty() && self.components_to_install.is_empty()
     }
@@ -673,6 +1308,67 @@ impl Update {
         Ok(())
     }
 
+    /// Validates that the requested components are available, or with
+    /// `force_update` drops those that are not.
+    fn check_available(
+        &mut self,
+        new_manifest: &Manifest,
+        toolchain_str: &str,
+        force_update: bool,
+        download_cfg: &DownloadCfg<'_>,
+    ) -> Result<()> {
+        let Err(e) = self.unavailable_components(new_manifest, toolchain_str) else {
+            return Ok(());
+        };
+        if !force_update {
+            return Err(e);
+        }
+        if let Ok(RustupError::RequestedComponentsUnavailable { components, .. }) =
+            e.downcast::<RustupError>()
+        {
+            for component in &components {
+                (download_cfg.notify_handler)(Notification::ForcingUnavailableComponent(
+                    &component.name(new_manifest),
+                ));
+            }
+            self.drop_components_to_install(&components);
+        }
+        Ok(())
+    }
+
+    /// The packages to download, from the dist server or local directory in
+    /// use.
+    fn downloads(
+        &self,
+        new_manifest: &Manifest,
+        download_cfg: &DownloadCfg<'_>,
+    ) -> Result<Vec<ComponentDownload>> {
+        let tmp_cx = download_cfg.tmp_cx;
+        let altered = tmp_cx.dist_server != DEFAULT_DIST_SERVER;
+
+        let mut downloads = Vec::new();
+        for (component, format, url, hash) in self.components_urls_and_hashes(new_manifest)? {
+            let url = match download_cfg.local_path(&url)? {
+                Some(path) => dist_download::local_file_url(&path)?,
+                None if altered => utils::parse_url(
+                    &url.replace(DEFAULT_DIST_SERVER, tmp_cx.dist_server.as_str()),
+                )?,
+                None => utils::parse_url(&url)?,
+            };
+            // Reject hashes we cannot verify before downloading anything
+            dist_download::Checksum::parse(&hash).with_context(|| {
+                RustupError::ComponentDownloadFailed(component.name(new_manifest))
+            })?;
+            downloads.push(ComponentDownload {
+                component,
+                format,
+                url,
+                hash,
+            });
+        }
+        Ok(downloads)
+    }
+
     fn drop_components_to_install(&mut self, to_drop: &[Component]) {
         self.components_to_install.retain(|c| !to_drop.contains(c));
         self.final_component_list.retain(|c| !to_drop.contains(c));
@@ -9,8 +9,9 @@ use std::fs;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use url::Url;
 
 use rustup_macros::unit_test as test;
@@ -19,9 +20,11 @@ use crate::{
     currentprocess,
     dist::{
         dist::{Profile, TargetTriple, ToolchainDesc, DEFAULT_DIST_SERVER},
-        download::DownloadCfg,
-        manifest::{Component, Manifest},
-        manifestation::{Changes, Manifestation, UpdateStatus},
+        download::{DownloadCfg, DEFAULT_CONCURRENCY, DEFAULT_RETRIES},
+        manifest::{Component, CompressionKind, Manifest},
+        manifestation::{
+            download_component, Changes, ComponentDownload, Manifestation, UpdateStatus,
+        },
         prefix::InstallPrefix,
         temp, Notification,
     },
@@ -443,7 +446,7 @@ fn update_from_dist(
     // Download the dist manifest and place it into the installation prefix
     let manifest_url = make_manifest_url(dist_server, toolchain)?;
     let manifest_file = tmp_cx.new_file()?;
-    utils::download_file(&manifest_url, &manifest_file, None, &|_| {})?;
+    utils::download_file(&manifest_url, &manifest_file, None, None, &|_| {})?;
     let manifest_str = utils::read_file("manifest", &manifest_file)?;
     let manifest = Manifest::parse(&manifest_str)?;
 
@@ -459,6 +462,8 @@ fn update_from_dist(
     let changes = Changes {
         explicit_add_components: add_components,
         remove_components: remove.to_owned(),
+        reinstall_components: Vec::new(),
+        dependency_components: Vec::new(),
     };
 
     manifestation.update(
@@ -551,6 +556,17 @@ fn setup_from_dist_server(
         dist_root: "phony",
         tmp_cx: &tmp_cx,
         download_dir: &prefix.path().to_owned().join("downloads"),
+        concurrency: DEFAULT_CONCURRENCY,
+        retries: DEFAULT_RETRIES,
+        retry_backoff: Duration::ZERO,
+        local_dist_dir: None,
+        signature_keys: None,
+        allow_unsigned_local: false,
+        rate_limiter: None,
+        ca_bundle: None,
+        cache_size: None,
+        stream_extract: false,
+        offline: false,
         notify_handler: &|event| {
             println!("{event}");
         },
@@ -2122,6 +2138,17 @@ fn reuse_downloaded_file() {
             dist_root: download_cfg.dist_root,
             tmp_cx: download_cfg.tmp_cx,
             download_dir: download_cfg.download_dir,
+            concurrency: download_cfg.concurrency,
+            retries: download_cfg.retries,
+            retry_backoff: download_cfg.retry_backoff,
+            local_dist_dir: download_cfg.local_dist_dir,
+            signature_keys: download_cfg.signature_keys,
+            allow_unsigned_local: download_cfg.allow_unsigned_local,
+            rate_limiter: download_cfg.rate_limiter,
+            ca_bundle: download_cfg.ca_bundle,
+            cache_size: download_cfg.cache_size,
+            stream_extract: download_cfg.stream_extract,
+            offline: download_cfg.offline,
             notify_handler: &|n| {
                 if let Notification::FileAlreadyDownloaded = n {
                     reuse_notification_fired.set(true);
@@ -2189,6 +2216,17 @@ fn checks_files_hashes_before_reuse() {
             dist_root: download_cfg.dist_root,
             tmp_cx: download_cfg.tmp_cx,
             download_dir: download_cfg.download_dir,
+            concurrency: download_cfg.concurrency,
+            retries: download_cfg.retries,
+            retry_backoff: download_cfg.retry_backoff,
+            local_dist_dir: download_cfg.local_dist_dir,
+            signature_keys: download_cfg.signature_keys,
+            allow_unsigned_local: download_cfg.allow_unsigned_local,
+            rate_limiter: download_cfg.rate_limiter,
+            ca_bundle: download_cfg.ca_bundle,
+            cache_size: download_cfg.cache_size,
+            stream_extract: download_cfg.stream_extract,
+            offline: download_cfg.offline,
             notify_handler: &|n| {
                 if let Notification::CachedFileChecksumFailed = n {
                     noticed_bad_checksum.set(true);
@@ -2259,3 +2297,398 @@ fn handle_corrupt_partial_downloads() {
         assert!(utils::path_exists(prefix.path().join("lib/libstd.rlib")));
     });
 }
+
+#[test]
+fn trims_download_cache_but_keeps_partial_downloads_for_update() {
+    setup(None, GZOnly, &|url,
+                          toolchain,
+                          prefix,
+                          download_cfg,
+                          tmp_cx| {
+        let path = url.to_file_path().unwrap();
+        let tarball = path.join("dist/2016-02-02/rustc-nightly-x86_64-apple-darwin.tar.gz");
+        let target_hash = utils::read_file("target hash", &tarball.with_extension("gz.sha256"))
+            .unwrap()[..SHA256_HASH_LEN]
+            .to_owned();
+
+        utils::ensure_dir_exists(
+            "download dir",
+            download_cfg.download_dir,
+            &|_: Notification<'_>| {},
+        )
+        .unwrap();
+        // Half of a download that an interrupted update left behind
+        let tarball = fs::read(tarball).unwrap();
+        let partial = &tarball[..tarball.len() / 2];
+        let partial_path = download_cfg
+            .download_dir
+            .join(format!("{target_hash}.partial"));
+        fs::write(partial_path, partial).unwrap();
+        // And two downloads of earlier updates, of which only the older one
+        // has to go to get back within the limit
+        let older = download_cfg.download_dir.join("older");
+        let newer = download_cfg.download_dir.join("newer");
+        fs::write(&older, [0; 1024]).unwrap();
+        fs::write(&newer, [0; 1024]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&older)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+
+        let resumed = Cell::new(false);
+        let download_cfg = DownloadCfg {
+            cache_size: Some(partial.len() as u64 + 1024),
+            notify_handler: &|n| {
+                if let Notification::Utils(crate::utils::Notification::ResumingPartialDownload) = n
+                {
+                    resumed.set(true);
+                }
+            },
+            ..*download_cfg
+        };
+
+        update_from_dist(
+            url,
+            toolchain,
+            prefix,
+            &[],
+            &[],
+            &download_cfg,
+            tmp_cx,
+            false,
+        )
+        .unwrap();
+
+        assert!(resumed.get());
+        assert!(!older.exists());
+        assert!(newer.exists());
+        assert!(utils::path_exists(prefix.path().join("bin/rustc")));
+    });
+}
+
+#[test]
+fn download_component_retries_only_transient_failures() {
+    setup(None, GZOnly, &|url, toolchain, _, _, tmp_cx| {
+        let manifest_url = make_manifest_url(url, toolchain).unwrap();
+        let manifest_file = tmp_cx.new_file().unwrap();
+        utils::download_file(&manifest_url, &manifest_file, None, None, &|_| {}).unwrap();
+        let manifest_str = utils::read_file("manifest", &manifest_file).unwrap();
+        let manifest = Manifest::parse(&manifest_str).unwrap();
+
+        let d = ComponentDownload {
+            component: Component::new("rustc".to_owned(), Some(toolchain.target.clone()), false),
+            format: CompressionKind::GZip,
+            url: url.clone(),
+            hash: String::new(),
+        };
+        let attempts = Cell::new(0);
+        let fail_with = |status| {
+            attempts.set(0);
+            let result = download_component::<()>(
+                &d,
+                &manifest,
+                2,
+                Duration::ZERO,
+                || {
+                    attempts.set(attempts.get() + 1);
+                    Err(anyhow!(download::DownloadError::HttpStatus(status))).context(
+                        if status == 404 {
+                            RustupError::DownloadNotExists {
+                                url: url.clone(),
+                                path: "rustc".into(),
+                            }
+                        } else {
+                            RustupError::DownloadingFile {
+                                url: url.clone(),
+                                path: "rustc".into(),
+                            }
+                        },
+                    )
+                },
+                |_| {},
+            );
+            assert!(result.is_err());
+            attempts.get()
+        };
+
+        assert_eq!(fail_with(503), 3);
+        assert_eq!(fail_with(404), 1);
+
+        let download_from = |from: &str| {
+            attempts.set(0);
+            let from = Url::parse(from).unwrap();
+            let path = tmp_cx.new_file().unwrap();
+            let result = download_component(
+                &d,
+                &manifest,
+                2,
+                Duration::ZERO,
+                || {
+                    attempts.set(attempts.get() + 1);
+                    utils::download_file(&from, &path, None, None, &|_| {})
+                },
+                |_| {},
+            );
+            assert!(result.is_err());
+            attempts.get()
+        };
+
+        // Nothing is listening on the port once the listener is dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        assert_eq!(download_from(&format!("http://127.0.0.1:{port}/rustc")), 3);
+        assert_eq!(download_from("unsupported://127.0.0.1/rustc"), 1);
+    });
+}
+
+// Replaces the hash of every package in the manifest with `new_hash` of the
+// package file, as a server advertising another hash algorithm would.
+fn rewrite_manifest_hashes(
+    url: &Url,
+    toolchain: &ToolchainDesc,
+    new_hash: &dyn Fn(&Path) -> String,
+) {
+    let manifest_path = make_manifest_url(url, toolchain)
+        .unwrap()
+        .to_file_path()
+        .unwrap();
+    let mut manifest: toml::value::Table =
+        toml::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+    for (_, pkg) in manifest["pkg"].as_table_mut().unwrap().iter_mut() {
+        for (_, target) in pkg["target"].as_table_mut().unwrap().iter_mut() {
+            let target = target.as_table_mut().unwrap();
+            let Some(pkg_url) = target.get("url").and_then(|u| u.as_str()) else {
+                continue;
+            };
+            let pkg_path = Url::parse(pkg_url).unwrap().to_file_path().unwrap();
+            target.insert("hash".to_owned(), toml::Value::String(new_hash(&pkg_path)));
+        }
+    }
+    fs::write(&manifest_path, toml::to_string(&manifest).unwrap()).unwrap();
+}
+
+#[test]
+fn install_verifies_sha512_hashes() {
+    use sha2::{Digest, Sha512};
+
+    setup(None, GZOnly, &|url,
+                          toolchain,
+                          prefix,
+                          download_cfg,
+                          tmp_cx| {
+        rewrite_manifest_hashes(url, toolchain, &|path| {
+            let digest = Sha512::digest(fs::read(path).unwrap());
+            format!("sha512:{digest:x}")
+        });
+
+        update_from_dist(
+            url,
+            toolchain,
+            prefix,
+            &[],
+            &[],
+            download_cfg,
+            tmp_cx,
+            false,
+        )
+        .unwrap();
+
+        assert!(utils::path_exists(prefix.path().join("bin/rustc")));
+        assert!(utils::path_exists(prefix.path().join("lib/libstd.rlib")));
+    });
+}
+
+#[test]
+fn install_rejects_wrong_sha512_hashes() {
+    setup(None, GZOnly, &|url,
+                          toolchain,
+                          prefix,
+                          download_cfg,
+                          tmp_cx| {
+        rewrite_manifest_hashes(url, toolchain, &|_| format!("sha512:{}", "0".repeat(128)));
+
+        let err = update_from_dist(
+            url,
+            toolchain,
+            prefix,
+            &[],
+            &[],
+            download_cfg,
+            tmp_cx,
+            false,
+        )
+        .unwrap_err();
+        assert!(
+            err.chain()
+                .any(|e| e.to_string().starts_with("checksum failed for")),
+            "{err:?}"
+        );
+    });
+}
+
+#[test]
+fn install_rejects_unsupported_hash_algorithms() {
+    setup(None, GZOnly, &|url,
+                          toolchain,
+                          prefix,
+                          download_cfg,
+                          tmp_cx| {
+        rewrite_manifest_hashes(url, toolchain, &|_| {
+            "md5:d41d8cd98f00b204e9800998ecf8427e".to_owned()
+        });
+
+        let err = update_from_dist(
+            url,
+            toolchain,
+            prefix,
+            &[],
+            &[],
+            download_cfg,
+            tmp_cx,
+            false,
+        )
+        .unwrap_err();
+        assert!(
+            err.chain()
+                .any(|e| e.to_string() == "unsupported hash algorithm 'md5'"),
+            "{err:?}"
+        );
+        assert!(!utils::path_exists(prefix.path().join("bin/rustc")));
+    });
+}
+
+#[test]
+fn stream_extract_installs_without_saving_downloads() {
+    setup(None, AddXZ, &|url,
+                         toolchain,
+                         prefix,
+                         download_cfg,
+                         tmp_cx| {
+        let saved = Cell::new(false);
+        let download_cfg = DownloadCfg {
+            stream_extract: true,
+            notify_handler: &|n| {
+                if let Notification::Utils(crate::utils::Notification::DownloadingFile(..)) = n {
+                    saved.set(true);
+                }
+            },
+            ..*download_cfg
+        };
+        update_from_dist(
+            url,
+            toolchain,
+            prefix,
+            &[],
+            &[],
+            &download_cfg,
+            tmp_cx,
+            false,
+        )
+        .unwrap();
+
+        assert!(utils::path_exists(prefix.path().join("bin/rustc")));
+        assert!(utils::path_exists(prefix.path().join("lib/libstd.rlib")));
+        assert!(!saved.get());
+    });
+}
+
+#[test]
+fn stream_extract_rejects_wrong_hashes() {
+    setup(None, GZOnly, &|url,
+                          toolchain,
+                          prefix,
+                          download_cfg,
+                          tmp_cx| {
+        rewrite_manifest_hashes(url, toolchain, &|_| "0".repeat(64));
+        let download_cfg = DownloadCfg {
+            stream_extract: true,
+            ..*download_cfg
+        };
+
+        let err = update_from_dist(
+            url,
+            toolchain,
+            prefix,
+            &[],
+            &[],
+            &download_cfg,
+            tmp_cx,
+            false,
+        )
+        .unwrap_err();
+        assert!(
+            err.chain()
+                .any(|e| e.to_string().starts_with("checksum failed for")),
+            "{err:?}"
+        );
+        assert!(!utils::path_exists(prefix.path().join("bin/rustc")));
+    });
+}
+
+#[test]
+fn stream_extract_stops_downloading_when_unpacking_fails() {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use sha2::{Digest, Sha256};
+
+    setup(None, GZOnly, &|url,
+                          toolchain,
+                          prefix,
+                          download_cfg,
+                          tmp_cx| {
+        // A file, then a header that is not one, then much more than the
+        // stream buffers hold, all stored uncompressed, so that unpacking
+        // fails while the download is waiting for the buffers to empty
+        let data = vec![7; 12 << 20];
+        let mut header = tar::Header::new_gnu();
+        header.set_path("pkg/data").unwrap();
+        header.set_size(4 << 20);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::none());
+        encoder.write_all(header.as_bytes()).unwrap();
+        encoder.write_all(&data[..4 << 20]).unwrap();
+        encoder.write_all(&[0xff; 512]).unwrap();
+        encoder.write_all(&data[4 << 20..]).unwrap();
+        let package = encoder.finish().unwrap();
+        rewrite_manifest_hashes(url, toolchain, &|path| {
+            fs::write(path, &package).unwrap();
+            format!("{:x}", Sha256::digest(&package))
+        });
+        let download_cfg = DownloadCfg {
+            stream_extract: true,
+            // Holding up the unpacking lets the download get ahead of it
+            notify_handler: &|n| {
+                if let Notification::Utils(crate::utils::Notification::DownloadDataReceived(_)) = n
+                {
+                    std::thread::sleep(Duration::from_millis(25));
+                }
+            },
+            ..*download_cfg
+        };
+
+        let err = update_from_dist(
+            url,
+            toolchain,
+            prefix,
+            &[],
+            &[],
+            &download_cfg,
+            tmp_cx,
+            false,
+        )
+        .unwrap_err();
+        assert!(
+            err.chain()
+                .any(|e| e.to_string() == "failed to extract package"),
+            "{err:?}"
+        );
+        assert!(!utils::path_exists(prefix.path().join("bin/rustc")));
+    });
+}
@@ -13,4 +13,5 @@ pub mod manifest;
 pub mod manifestation;
 pub(crate) mod notifications;
 pub mod prefix;
+pub mod signature;
 pub(crate) mod triple;
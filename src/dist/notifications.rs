@@ -25,6 +25,7 @@ pub enum Notification<'a> {
     MissingInstalledComponent(&'a str),
     DownloadingComponent(&'a str, &'a TargetTriple, Option<&'a TargetTriple>),
     InstallingComponent(&'a str, &'a TargetTriple, Option<&'a TargetTriple>),
+    ExtractingComponent(&'a str),
     RemovingComponent(&'a str, &'a TargetTriple, Option<&'a TargetTriple>),
     RemovingOldComponent(&'a str, &'a TargetTriple, Option<&'a TargetTriple>),
     DownloadingManifest(&'a str),
@@ -36,7 +37,17 @@ pub enum Notification<'a> {
     ComponentUnavailable(&'a str, Option<&'a TargetTriple>),
     StrayHash(&'a Path),
     SignatureInvalid(&'a str),
+    SignatureValid(&'a str),
+    UnsignedLocalManifest(&'a str),
+    /// Offline, the manifest at this url that `--download-only` saved is used
+    UsingSavedManifest(&'a str),
     RetryingDownload(&'a str),
+    EvictingCachedDownload(&'a Path),
+    HashingComponentFiles(&'a str),
+    AddingDependency(&'a str, &'a str),
+    RemovingUnusedDependency(&'a str),
+    RemovingNeededComponent(&'a str, &'a [String]),
+    TargetIsHost(&'a str, &'a TargetTriple),
 }
 
 impl<'a> From<crate::utils::Notification<'a>> for Notification<'a> {
@@ -58,8 +69,12 @@ impl<'a> Notification<'a> {
             Temp(n) => n.level(),
             Utils(n) => n.level(),
             ChecksumValid(_)
+            | SignatureValid(_)
             | NoUpdateHash(_)
             | FileAlreadyDownloaded
+            | EvictingCachedDownload(_)
+            | HashingComponentFiles(_)
+            | ExtractingComponent(_)
             | DownloadingLegacyManifest => NotificationLevel::Verbose,
             Extracting(_, _)
             | DownloadingComponent(_, _, _)
@@ -72,6 +87,10 @@ impl<'a> Notification<'a> {
             | DownloadingManifest(_)
             | SkippingNightlyMissingComponent(_, _, _)
             | RetryingDownload(_)
+            | AddingDependency(_, _)
+            | RemovingUnusedDependency(_)
+            | TargetIsHost(_, _)
+            | UsingSavedManifest(_)
             | DownloadedManifest(_, _) => NotificationLevel::Info,
             CantReadUpdateHash(_)
             | ExtensionNotInstalled(_)
@@ -79,6 +98,8 @@ impl<'a> Notification<'a> {
             | CachedFileChecksumFailed
             | ComponentUnavailable(_, _)
             | ForcingUnavailableComponent(_)
+            | UnsignedLocalManifest(_)
+            | RemovingNeededComponent(_, _)
             | StrayHash(_) => NotificationLevel::Warn,
             NonFatalError(_) => NotificationLevel::Error,
             SignatureInvalid(_) => NotificationLevel::Warn,
@@ -123,6 +144,7 @@ impl<'a> Display for Notification<'a> {
                     write!(f, "installing component '{}' for '{}'", c, t.unwrap())
                 }
             }
+            ExtractingComponent(c) => write!(f, "extracting component '{c}'"),
             RemovingComponent(c, h, t) => {
                 if Some(h) == t.as_ref() || t.is_none() {
                     write!(f, "removing component '{c}'")
@@ -181,11 +203,46 @@ impl<'a> Display for Notification<'a> {
                     .collect::<Vec<_>>()
                     .join("', '")
             ),
+            AddingDependency(dep, dependent) => {
+                write!(
+                    f,
+                    "adding component '{dep}', which '{dependent}' depends on"
+                )
+            }
+            RemovingUnusedDependency(dep) => write!(
+                f,
+                "removing component '{dep}', which nothing installed needs any more"
+            ),
+            RemovingNeededComponent(component, dependents) => write!(
+                f,
+                "removing component '{component}', which {} will not work without",
+                dependents
+                    .iter()
+                    .map(|c| format!("'{c}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TargetIsHost(name, host) => write!(
+                f,
+                "target '{name}' is the host of the toolchain, '{host}', whose standard library comes with it"
+            ),
             ForcingUnavailableComponent(component) => {
                 write!(f, "Force-skipping unavailable component '{component}'")
             }
             SignatureInvalid(url) => write!(f, "Signature verification failed for '{url}'"),
+            SignatureValid(url) => write!(f, "signature verified for '{url}'"),
+            UnsignedLocalManifest(url) => write!(
+                f,
+                "'{url}' is not signed, which is allowed as it is a local file"
+            ),
+            UsingSavedManifest(url) => {
+                write!(f, "using the manifest for '{url}' that was downloaded earlier")
+            }
             RetryingDownload(url) => write!(f, "retrying download for '{url}'"),
+            EvictingCachedDownload(path) => {
+                write!(f, "evicting cached download '{}'", path.display())
+            }
+            HashingComponentFiles(c) => write!(f, "hashing the files of component '{c}'"),
         }
     }
 }
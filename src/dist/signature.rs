@@ -0,0 +1,785 @@
+//! Verification of the OpenPGP signatures that the dist server publishes
+//! alongside channel manifests.
+//!
+//! Only as much of OpenPGP as that needs is understood:
+//!
+//! - Version 4 RSA keys. Keys of other kinds are skipped.
+//! - A primary key is trusted because it is listed. A subkey is trusted if
+//!   its primary key has bound it as a key for signing, with a binding made
+//!   with one of the SHA-2 hashes. Only the keys built into rustup, which are
+//!   trusted for being built in, may have bindings made with SHA-1, as Rust's
+//!   own does. The back-signature that the subkey makes in return is not
+//!   checked, as it only matters where the primary key is not trusted
+//!   already.
+//! - A key is dropped if its primary key has revoked it, which a revocation
+//!   made with SHA-1 also does. The reason given for the revocation is not
+//!   looked at.
+//! - A key expires when its latest self-signature or binding says. SHA-1 is
+//!   accepted on the self-signatures of a primary key, which older keys such
+//!   as Rust's own have no other of. A subkey expires with its primary key.
+//! - Version 4 signatures over binary documents, made with one of the SHA-2
+//!   hashes by a key that had not expired by then, and that have not expired
+//!   themselves.
+//!
+//! Anything else, such as third-party certifications, user attributes and
+//! the trust packets of a keyring, is ignored.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::signature::{self, RsaParameters, RsaPublicKeyComponents};
+
+/// The keys that Rust releases are signed with. A new key is added here
+/// ahead of a rotation, so that manifests signed with either key verify
+/// while the old one is phased out.
+static TRUSTED_KEYS: &[&str] = &[include_str!("../rust-key.pgp.ascii")];
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+const TAG_USER_ATTRIBUTE: u8 = 17;
+
+const SIG_TYPE_BINARY: u8 = 0x00;
+const SIG_TYPE_CERTIFICATIONS: std::ops::RangeInclusive<u8> = 0x10..=0x13;
+const SIG_TYPE_SUBKEY_BINDING: u8 = 0x18;
+const SIG_TYPE_DIRECT_KEY: u8 = 0x1f;
+const SIG_TYPE_KEY_REVOCATION: u8 = 0x20;
+const SIG_TYPE_SUBKEY_REVOCATION: u8 = 0x28;
+
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_SIGNATURE_EXPIRY: u8 = 3;
+const SUBPACKET_KEY_EXPIRY: u8 = 9;
+const SUBPACKET_KEY_FLAGS: u8 = 27;
+const KEY_FLAG_SIGN: u8 = 0x02;
+
+const ALGO_RSA: u8 = 1;
+const ALGO_RSA_SIGN_ONLY: u8 = 3;
+
+const HASH_SHA1: u8 = 2;
+
+/// An RSA key, primary or subkey, that signatures may be made with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    n: Vec<u8>,
+    e: Vec<u8>,
+    /// When the key was made, in seconds since the Unix epoch
+    created: u64,
+    /// When the key stops being valid, if it does, in seconds since the Unix
+    /// epoch
+    expires: Option<u64>,
+}
+
+impl PublicKey {
+    /// Whether the key was valid at `time`.
+    fn is_valid_at(&self, time: u64) -> bool {
+        time >= self.created && self.expires.map_or(true, |expires| time < expires)
+    }
+}
+
+/// The keys built into rustup, followed by those in the armored key blocks
+/// of `extra`.
+pub(crate) fn trusted_keys(extra: &[String]) -> Result<Vec<PublicKey>> {
+    let mut keys = Vec::new();
+    for armored in TRUSTED_KEYS {
+        keys.extend(parse_keys(armored, true)?);
+    }
+    for armored in extra {
+        keys.extend(parse_keys(armored, false)?);
+    }
+    Ok(keys)
+}
+
+/// Reads the RSA primary keys out of an armored public key block, along with
+/// the RSA subkeys that they have bound as keys for signing, leaving out
+/// those that have been revoked. Subkeys bound with SHA-1 are only accepted
+/// from the keys that are `built_in`.
+fn parse_keys(armored: &str, built_in: bool) -> Result<Vec<PublicKey>> {
+    let data = dearmor(armored, "PUBLIC KEY BLOCK")?;
+    let now = now();
+    let mut keys = Vec::new();
+    for cert in certs(&packets(&data)?) {
+        keys.extend(cert.keys(built_in, now)?);
+    }
+    if keys.is_empty() {
+        bail!("no unrevoked RSA keys found in key block");
+    }
+    Ok(keys)
+}
+
+/// A primary key packet and the packets that follow it in a key block, up to
+/// the next primary key.
+#[derive(Default)]
+struct Cert<'a> {
+    key: &'a [u8],
+    /// The signatures directly on the primary key, such as its revocation
+    sigs: Vec<&'a [u8]>,
+    /// The user IDs, with their signatures
+    user_ids: Vec<(&'a [u8], Vec<&'a [u8]>)>,
+    /// The subkeys, with their signatures
+    subkeys: Vec<(&'a [u8], Vec<&'a [u8]>)>,
+}
+
+/// Groups `packets` by the primary key that they follow.
+fn certs<'a>(packets: &[(u8, &'a [u8])]) -> Vec<Cert<'a>> {
+    // What the signatures that follow are about
+    enum Target {
+        Key,
+        UserId,
+        Subkey,
+        Other,
+    }
+
+    let mut certs: Vec<Cert<'a>> = Vec::new();
+    let mut target = Target::Other;
+    for &(tag, body) in packets {
+        if tag == TAG_PUBLIC_KEY {
+            certs.push(Cert {
+                key: body,
+                ..Cert::default()
+            });
+            target = Target::Key;
+            continue;
+        }
+        let Some(cert) = certs.last_mut() else {
+            continue;
+        };
+        match tag {
+            TAG_USER_ID => {
+                cert.user_ids.push((body, Vec::new()));
+                target = Target::UserId;
+            }
+            TAG_PUBLIC_SUBKEY => {
+                cert.subkeys.push((body, Vec::new()));
+                target = Target::Subkey;
+            }
+            TAG_USER_ATTRIBUTE => target = Target::Other,
+            TAG_SIGNATURE => match target {
+                Target::Key => cert.sigs.push(body),
+                Target::UserId => cert.user_ids.last_mut().unwrap().1.push(body),
+                Target::Subkey => cert.subkeys.last_mut().unwrap().1.push(body),
+                Target::Other => {}
+            },
+            // Trust packets, and anything else there is no use for
+            _ => {}
+        }
+    }
+    certs
+}
+
+impl<'a> Cert<'a> {
+    /// The primary key and the signing subkeys, if they are RSA keys and
+    /// have not been revoked. Bindings made with SHA-1 are accepted only with
+    /// `sha1_bindings`.
+    fn keys(&self, sha1_bindings: bool, now: u64) -> Result<Vec<PublicKey>> {
+        let Some(mut primary) = rsa_key(self.key)? else {
+            return Ok(Vec::new());
+        };
+        let Some(primary_message) = key_message(self.key) else {
+            return Ok(Vec::new());
+        };
+        let signer = primary.clone();
+        let by_primary = |body: &'a [u8], sig_type: u8, message: &[u8], allow_sha1: bool| {
+            valid_signature(body, sig_type, message, &signer, allow_sha1, now)
+        };
+
+        if self
+            .sigs
+            .iter()
+            .any(|body| by_primary(body, SIG_TYPE_KEY_REVOCATION, &primary_message, true).is_some())
+        {
+            return Ok(Vec::new());
+        }
+
+        // The self-signatures, which are either directly on the key or on
+        // one of its user IDs
+        let direct = self
+            .sigs
+            .iter()
+            .filter_map(|body| by_primary(body, SIG_TYPE_DIRECT_KEY, &primary_message, true));
+        let certifications = self.user_ids.iter().flat_map(|(user_id, sigs)| {
+            let mut message = primary_message.clone();
+            message.push(0xb4);
+            message.extend_from_slice(&(user_id.len() as u32).to_be_bytes());
+            message.extend_from_slice(user_id);
+            sigs.iter()
+                .filter_map(|body| {
+                    SIG_TYPE_CERTIFICATIONS
+                        .clone()
+                        .find_map(|sig_type| by_primary(body, sig_type, &message, true))
+                })
+                .collect::<Vec<_>>()
+        });
+        let self_sig = direct
+            .chain(certifications)
+            .max_by_key(|sig| sig.created().unwrap_or(0));
+        primary.expires = self_sig.and_then(|sig| sig.key_expiry(primary.created));
+
+        let mut keys = vec![primary.clone()];
+        for (subkey_body, sigs) in &self.subkeys {
+            let Some(mut subkey) = rsa_key(subkey_body)? else {
+                continue;
+            };
+            let Some(subkey_message) = key_message(subkey_body) else {
+                continue;
+            };
+            let message = [primary_message.as_slice(), &subkey_message].concat();
+            if sigs
+                .iter()
+                .any(|body| by_primary(body, SIG_TYPE_SUBKEY_REVOCATION, &message, true).is_some())
+            {
+                continue;
+            }
+            let binding = sigs
+                .iter()
+                .filter_map(|body| {
+                    by_primary(body, SIG_TYPE_SUBKEY_BINDING, &message, sha1_bindings)
+                })
+                .max_by_key(|sig| sig.created().unwrap_or(0));
+            let Some(binding) = binding.filter(Signature::allows_signing) else {
+                continue;
+            };
+            subkey.expires = match (primary.expires, binding.key_expiry(subkey.created)) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            keys.push(subkey);
+        }
+        Ok(keys)
+    }
+}
+
+/// The RSA key in the body of a key packet, or `None` if it is another kind
+/// of key.
+fn rsa_key(body: &[u8]) -> Result<Option<PublicKey>> {
+    // version, creation time, algorithm
+    let Some([4, t0, t1, t2, t3, algo]) = body.get(..6) else {
+        return Ok(None);
+    };
+    if *algo != ALGO_RSA && *algo != ALGO_RSA_SIGN_ONLY {
+        return Ok(None);
+    }
+    let mut rest = &body[6..];
+    let n = mpi(&mut rest)?.to_vec();
+    let e = mpi(&mut rest)?.to_vec();
+    Ok(Some(PublicKey {
+        n,
+        e,
+        created: u64::from(u32::from_be_bytes([*t0, *t1, *t2, *t3])),
+        expires: None,
+    }))
+}
+
+/// A key packet as signatures over it hash it, with a header that gives its
+/// length, or `None` if it is too long for one.
+fn key_message(body: &[u8]) -> Option<Vec<u8>> {
+    let len = u16::try_from(body.len()).ok()?;
+    let mut message = vec![0x99];
+    message.extend_from_slice(&len.to_be_bytes());
+    message.extend_from_slice(body);
+    Some(message)
+}
+
+/// The signature packet `body`, if it is a signature of type `sig_type` over
+/// `message` made with `key` that has not expired by `now`. SHA-1 is only
+/// accepted with `allow_sha1`. Signatures that cannot be read are not valid.
+fn valid_signature<'a>(
+    body: &'a [u8],
+    sig_type: u8,
+    message: &[u8],
+    key: &PublicKey,
+    allow_sha1: bool,
+    now: u64,
+) -> Option<Signature<'a>> {
+    let sig = Signature::parse(body).ok()?;
+    if sig.sig_type != sig_type || sig.is_expired(now) {
+        return None;
+    }
+    let params = match sig.hash_algo {
+        HASH_SHA1 if allow_sha1 => &signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+        algo => sha2_params(algo)?,
+    };
+    sig.is_made_by(message, key, params).then_some(sig)
+}
+
+/// The parameters for checking an RSA signature made with the SHA-2 hash
+/// `hash_algo`, if it is one.
+fn sha2_params(hash_algo: u8) -> Option<&'static RsaParameters> {
+    match hash_algo {
+        8 => Some(&signature::RSA_PKCS1_2048_8192_SHA256),
+        9 => Some(&signature::RSA_PKCS1_2048_8192_SHA384),
+        10 => Some(&signature::RSA_PKCS1_2048_8192_SHA512),
+        _ => None,
+    }
+}
+
+/// The current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Checks that `armored_signature` is a signature of `data` made with one of
+/// `keys`.
+pub(crate) fn verify(data: &[u8], armored_signature: &str, keys: &[PublicKey]) -> Result<()> {
+    let raw = dearmor(armored_signature, "SIGNATURE")?;
+    let packets = packets(&raw)?;
+    let body = match packets.as_slice() {
+        [(TAG_SIGNATURE, body)] => *body,
+        _ => bail!("expected a single signature packet"),
+    };
+
+    let sig = Signature::parse(body)?;
+    if sig.sig_type != SIG_TYPE_BINARY {
+        bail!("unsupported signature type {:#04x}", sig.sig_type);
+    }
+    let Some(params) = sha2_params(sig.hash_algo) else {
+        bail!("unsupported hash algorithm {}", sig.hash_algo);
+    };
+    let Some(created) = sig.created() else {
+        bail!("signature has no creation time");
+    };
+    if sig.is_expired(now()) {
+        bail!("signature has expired");
+    }
+    let mut signers = keys.iter().filter(|key| sig.is_made_by(data, key, params));
+    match signers.clone().find(|key| key.is_valid_at(created)) {
+        Some(_) => Ok(()),
+        None if signers.next().is_some() => {
+            bail!("signed by a trusted key that was not valid at the time")
+        }
+        None => bail!("not signed by any trusted key"),
+    }
+}
+
+/// The parts of a version 4 RSA signature packet that checking it needs.
+struct Signature<'a> {
+    sig_type: u8,
+    hash_algo: u8,
+    /// The start of the packet up to and including the hashed subpackets,
+    /// which is hashed along with what is signed.
+    hashed: &'a [u8],
+    value: &'a [u8],
+}
+
+impl<'a> Signature<'a> {
+    fn parse(body: &'a [u8]) -> Result<Self> {
+        let truncated = || anyhow!("truncated signature packet");
+        let header: [u8; 6] = body
+            .get(..6)
+            .and_then(|h| h.try_into().ok())
+            .ok_or_else(truncated)?;
+        let [version, sig_type, algo, hash_algo, len_hi, len_lo] = header;
+        if version != 4 {
+            bail!("unsupported signature version {version}");
+        }
+        if algo != ALGO_RSA && algo != ALGO_RSA_SIGN_ONLY {
+            bail!("unsupported public key algorithm {algo}");
+        }
+
+        let hashed_end = 6 + usize::from(u16::from_be_bytes([len_hi, len_lo]));
+        let hashed = body.get(..hashed_end).ok_or_else(truncated)?;
+        let mut rest = &body[hashed_end..];
+        let unhashed_len = take(&mut rest, 2)?;
+        take(
+            &mut rest,
+            usize::from(u16::from_be_bytes([unhashed_len[0], unhashed_len[1]])),
+        )?;
+        // The first two bytes of the hash, which are only a quick check
+        take(&mut rest, 2)?;
+        let value = mpi(&mut rest)?;
+        Ok(Self {
+            sig_type,
+            hash_algo,
+            hashed,
+            value,
+        })
+    }
+
+    /// The hashed subpackets, as type and body.
+    fn hashed_subpackets(&self) -> Result<Vec<(u8, &'a [u8])>> {
+        let mut data = &self.hashed[6..];
+        let mut subpackets = Vec::new();
+        while !data.is_empty() {
+            let len = match take(&mut data, 1)?[0] {
+                len @ 0..=191 => usize::from(len),
+                first @ 192..=254 => {
+                    (usize::from(first - 192) << 8) + usize::from(take(&mut data, 1)?[0]) + 192
+                }
+                255 => be_len(take(&mut data, 4)?),
+            };
+            let Some((kind, body)) = take(&mut data, len)?.split_first() else {
+                bail!("empty signature subpacket");
+            };
+            // The top bit only marks the subpacket as critical
+            subpackets.push((kind & 0x7f, body));
+        }
+        Ok(subpackets)
+    }
+
+    /// The 4 byte time in the hashed subpacket of the given `kind`, if there
+    /// is one.
+    fn time_subpacket(&self, kind: u8) -> Option<u64> {
+        let subpackets = self.hashed_subpackets().ok()?;
+        let (_, body) = subpackets.iter().find(|(k, _)| *k == kind)?;
+        let time: [u8; 4] = (*body).try_into().ok()?;
+        Some(u64::from(u32::from_be_bytes(time)))
+    }
+
+    /// When the signature was made, in seconds since the Unix epoch.
+    fn created(&self) -> Option<u64> {
+        self.time_subpacket(SUBPACKET_CREATION_TIME)
+    }
+
+    /// Whether the signature has expired by `now`.
+    fn is_expired(&self, now: u64) -> bool {
+        match self.time_subpacket(SUBPACKET_SIGNATURE_EXPIRY) {
+            // Zero means that it does not expire
+            None | Some(0) => false,
+            Some(secs) => self.created().unwrap_or(0) + secs <= now,
+        }
+    }
+
+    /// When the key that this self-signature or binding is about expires, as
+    /// it says, for a key made at `key_created`.
+    fn key_expiry(&self, key_created: u64) -> Option<u64> {
+        match self.time_subpacket(SUBPACKET_KEY_EXPIRY)? {
+            0 => None,
+            secs => Some(key_created + secs),
+        }
+    }
+
+    /// Whether this binding lets the subkey sign.
+    fn allows_signing(&self) -> bool {
+        self.hashed_subpackets().is_ok_and(|subpackets| {
+            subpackets.iter().any(|(kind, flags)| {
+                *kind == SUBPACKET_KEY_FLAGS
+                    && flags.first().is_some_and(|f| f & KEY_FLAG_SIGN != 0)
+            })
+        })
+    }
+
+    /// Whether this is a signature of `data` made with `key`, checked with
+    /// `params`.
+    fn is_made_by(&self, data: &[u8], key: &PublicKey, params: &RsaParameters) -> bool {
+        // The signed data is the document, then the hashed part of the
+        // packet, then a trailer giving its length
+        let mut message = Vec::with_capacity(data.len() + self.hashed.len() + 6);
+        message.extend_from_slice(data);
+        message.extend_from_slice(self.hashed);
+        message.extend_from_slice(&[4, 0xff]);
+        message.extend_from_slice(&(self.hashed.len() as u32).to_be_bytes());
+
+        // Leading zeroes are dropped from the MPI, but not from what the
+        // signature is checked as
+        let mut padded = vec![0; key.n.len().saturating_sub(self.value.len())];
+        padded.extend_from_slice(self.value);
+        let components = RsaPublicKeyComponents {
+            n: &key.n,
+            e: &key.e,
+        };
+        components.verify(params, &message, &padded).is_ok()
+    }
+}
+
+/// Decodes the ASCII armor of a block of the given `kind`, such as
+/// `SIGNATURE`.
+fn dearmor(armored: &str, kind: &str) -> Result<Vec<u8>> {
+    let begin = format!("-----BEGIN PGP {kind}-----");
+    let end = format!("-----END PGP {kind}-----");
+    let mut lines = armored
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|l| *l != begin)
+        .skip(1);
+    // Armor headers run up to the first blank line
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+    }
+    let mut encoded = String::new();
+    let mut ended = false;
+    for line in lines {
+        if line == end {
+            ended = true;
+            break;
+        }
+        // The checksum, which the packets' own checks make redundant
+        if !line.starts_with('=') {
+            encoded.push_str(line);
+        }
+    }
+    if !ended {
+        bail!("no armored PGP {} found", kind.to_lowercase());
+    }
+    STANDARD
+        .decode(encoded)
+        .context("invalid base64 in armored data")
+}
+
+/// Splits `data` into packets, as tag and body.
+fn packets(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>> {
+    let mut packets = Vec::new();
+    while !data.is_empty() {
+        let ctb = take(&mut data, 1)?[0];
+        if ctb & 0x80 == 0 {
+            bail!("invalid packet header");
+        }
+        let (tag, len) = if ctb & 0x40 != 0 {
+            let tag = ctb & 0x3f;
+            let len = match take(&mut data, 1)?[0] {
+                len @ 0..=191 => usize::from(len),
+                first @ 192..=223 => {
+                    (usize::from(first - 192) << 8) + usize::from(take(&mut data, 1)?[0]) + 192
+                }
+                255 => be_len(take(&mut data, 4)?),
+                _ => bail!("partial packet lengths are not supported"),
+            };
+            (tag, len)
+        } else {
+            let tag = (ctb >> 2) & 0x0f;
+            let len = match ctb & 0x03 {
+                0 => be_len(take(&mut data, 1)?),
+                1 => be_len(take(&mut data, 2)?),
+                2 => be_len(take(&mut data, 4)?),
+                _ => data.len(),
+            };
+            (tag, len)
+        };
+        packets.push((tag, take(&mut data, len)?));
+    }
+    Ok(packets)
+}
+
+fn be_len(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |len, byte| (len << 8) | usize::from(*byte))
+}
+
+/// Reads a multiprecision integer, returning its big-endian bytes.
+fn mpi<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let bits = take(data, 2)?;
+    let bits = usize::from(u16::from_be_bytes([bits[0], bits[1]]));
+    take(data, (bits + 7) / 8)
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        bail!("truncated OpenPGP data");
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Ok(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TEST_KEY: &str = include_str!("../../tests/mock/signing-key.pub.asc");
+
+    /// Made with `gpg --detach-sign --armor` and the test key, over
+    /// `hello, world\n`.
+    static GPG_SIGNATURE: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iQEzBAABCAAdFiEEp80hbAh+1hp2gPGqQLOZyJS6WCwFAmrPI60ACgkQQLOZyJS6
+WCxWVAgAi7sPj00raXTK59+6v2kXy5Nw0w/zBf4MPASYe2TdefI/AO9+snMfgH8q
+2Iy7UTD1/3a6AsCoY3GYR3sNoxrYRyjUZDfGIp/v75mzInP5fHdLgzKJn14Y7HQF
+qaHf93PkDL0/Cn/S4Qmh7/JlpTWzxJeYLUe2cZrPUoWSXqrC1GMxOsQT3tXAhxCy
+DKKXOEmQu70TeFe6EI9ujKs88i7mj8fkbugQoUqP9muX/Uukn7VMx96m6r6spBVb
+OC2RGrwONE7k2Lk6ECHnvoZlVyWwf5kT/lHw9qUlb6elMiBEwcxJjh5UvtjF6Sp/
+vT+vXsQsldXbUNn7/gUIVWNCxu8RjA==
+=I5vS
+-----END PGP SIGNATURE-----
+";
+
+    /// The test keys below were made with `gpg --faked-system-time` at the
+    /// start of 2020, and the signatures with them, over `hello, world\n`,
+    /// within an hour after that unless said otherwise.
+    static EXPIRING_KEY: &str = include_str!("../../tests/mock/signing-key-expiring.pub.asc");
+    static REVOKED_KEY: &str = include_str!("../../tests/mock/signing-key-revoked.pub.asc");
+    static SUBKEY_KEY: &str = include_str!("../../tests/mock/signing-key-subkey.pub.asc");
+    static SUBKEY_REVOKED_KEY: &str =
+        include_str!("../../tests/mock/signing-key-subkey-revoked.pub.asc");
+
+    /// Made with the expiring key before it expired.
+    static EARLY_SIGNATURE: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iQEzBAABCAAdFiEErums6ZSWI2n+cDNEFBKdrVvq+L8FAl4L7xAACgkQFBKdrVvq
++L/qcgf/cnjJOMAVkXN7bvQdhqDTDcN1DlLX5ZUHY564goeNczanjryRPSDwg3q2
+8KWg297enJMYJnLBzLVL7Ki8tlTXBj9bl6oaJsKT6rvwDlIJoH9elW8Y8RXLTxJs
+dW3t1Q3j9/DhnNkQsbTMLbrD3+PluhLQSXPZUnNOq7oo846eH8ibDDc7YxwCy9w7
+VicPeRL1zJu1a22YgcAhfeoanhyZ3cHYUp20wQh9Gi3BS81iTkpriNM2wCSuWlVI
+LgtwFt51SNf+sU9hGzd9sTcX8Qh3yYRifeK9QYUGMEZM0A0OgdnyA2k9kxkxuBrC
+nhoP2efdBwuFDPSeUhKsuXp9TcrfAA==
+=D5my
+-----END PGP SIGNATURE-----
+";
+
+    /// Made with the expiring key a day after it expired, which did not stop
+    /// gpg as it had not been told yet.
+    static LATE_SIGNATURE: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iQEzBAABCAAdFiEErums6ZSWI2n+cDNEFBKdrVvq+L8FAl4OhAAACgkQFBKdrVvq
++L80GQf/aBRSqRdn5/HCIhI6gmgGVcrzKvS1ILWeCvPnpMFhSFZZuf9DcRxm+XmL
+ZAWXfOXq+CtAIXJE5WGlxat3PjO3vVIHHaP98T/wugX2boSqPSq0qhLJbD2lzi/F
+m4fZ9olXyps2D97Tr2ccDwm2w+2zFh8MLxdHu0CPPqWjJfB9m5rqjMJleNUOBdBe
+jlupT5Xm/E8XzmhAIFt/GtbinO9xjZQtWriXIKF4NlCgn+bP5hUZgrzARAsNyJ+D
+wtxPIwobB19KdRdyhrYyuiko6xOAx9j97XRsN4GC9EzX0zfH4E07lf158vj+sdVw
+I6NxfYHXzoLhLHWyvR+wRByhuWQ5pg==
+=uB4P
+-----END PGP SIGNATURE-----
+";
+
+    /// Made with the expiring key, and itself expiring after a day.
+    static EXPIRING_SIGNATURE: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iQE5BAABCAAjFiEErums6ZSWI2n+cDNEFBKdrVvq+L8FAl4L/SAFgwABUYAACgkQ
+FBKdrVvq+L+megf8CfGEsE44n3OyYx+82JJpAO6v7mZ4YR4lKBshr/8tSUzHK7nQ
+DdHjpncYPNw3wv5Hyu4Gg3arRrjaeg1v158zo0fY1k6qATHC4MZoNzlKG/8bx6ZY
+lyotD6BPuFNN6P9g3BhIgG+3HFODLSgmazFf1HhUtzomBbOTbd0meku2A83wfEUM
+avWz05EDJHfatP6stHdZoNnpj8EpU00vEtkp5b1fpRL7DlpGLE5a37cX9dX2hCWT
+Rrn7AYv+ejkOrOMu2pQCEWV4ckI/D2YNYZt7UkJ9//LpUNOVcXUGJU80aP5H8A+N
+E64UbpDvdJ/Lf/zKgXIxrjDQU96A7bShZw1FeQ==
+=PzjO
+-----END PGP SIGNATURE-----
+";
+
+    /// Made with the signing subkey of the subkey test key.
+    static SUBKEY_SIGNATURE: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iQEzBAABCAAdFiEELw8o01+SnEfUUQWwbvYuCu1/Ne0FAl4L7xAACgkQbvYuCu1/
+Ne0lmggAhzoipyYyiXG+pMYf6WDqUT4macF0hFFpGI8Svecx5ctbBFqGcS1qbXJE
+eJl87ejHYMvvk+g9jfAnATKRGwxDrseQokeg1jxn0xBTyJE7QjriUILVLjlXydop
+jKr0vZxx5NtXb8XYFctoghdfnfvzGl9enqCIH3Tf64QmtQgePwFCJbOgZdorE59s
+I4fDWwmXM/jUYJb/NIdARQRx1bDanvfnOYhiwa+OIbvm6W+Yt341t6Lx6aNw8bkh
+LJ9Ha/qlOBaMPVwVEfUlhEnC7k7Ho3cpnwGDV36M51iK5qxqtB0JMtbb60Wcc+iP
+LrF6H8uZRAgh5zulS4kCoRs5h0gd8Q==
+=589v
+-----END PGP SIGNATURE-----
+";
+
+    /// Encodes a packet with a five byte length header.
+    fn packet(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0xc0 | tag, 255];
+        packet.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    #[test]
+    fn parses_builtin_keys() {
+        // The primary key and the signing subkey, but not the subkey that is
+        // only for encryption
+        assert_eq!(trusted_keys(&[]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rejects_sha1_bindings_outside_builtin_keys() {
+        // The signing subkey of Rust's key was bound with SHA-1
+        let keys = trusted_keys(&[TRUSTED_KEYS[0].to_owned()]).unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[2], keys[0]);
+    }
+
+    #[test]
+    fn rejects_subkeys_bound_to_another_key() {
+        let ours = dearmor(TEST_KEY, "PUBLIC KEY BLOCK").unwrap();
+        let theirs = dearmor(TRUSTED_KEYS[0], "PUBLIC KEY BLOCK").unwrap();
+        // The test key, followed by the subkeys of the built-in key and the
+        // bindings that the built-in key made for them
+        let mut block = Vec::new();
+        for (tag, body) in packets(&ours).unwrap() {
+            block.extend(packet(tag, body));
+        }
+        for (tag, body) in packets(&theirs)
+            .unwrap()
+            .into_iter()
+            .skip_while(|(tag, _)| *tag != TAG_PUBLIC_SUBKEY)
+        {
+            block.extend(packet(tag, body));
+        }
+        let armored = format!(
+            "-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n{}\n-----END PGP PUBLIC KEY BLOCK-----\n",
+            STANDARD.encode(block)
+        );
+        assert_eq!(
+            parse_keys(&armored, true).unwrap(),
+            parse_keys(TEST_KEY, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn verifies_gpg_signature() {
+        let keys = parse_keys(TEST_KEY, false).unwrap();
+        verify(b"hello, world\n", GPG_SIGNATURE, &keys).unwrap();
+    }
+
+    #[test]
+    fn rejects_changed_data() {
+        let keys = parse_keys(TEST_KEY, false).unwrap();
+        assert!(verify(b"hello, world!\n", GPG_SIGNATURE, &keys).is_err());
+    }
+
+    #[test]
+    fn rejects_untrusted_key() {
+        let keys = trusted_keys(&[]).unwrap();
+        let err = verify(b"hello, world\n", GPG_SIGNATURE, &keys).unwrap_err();
+        assert_eq!(err.to_string(), "not signed by any trusted key");
+    }
+
+    #[test]
+    fn verifies_signature_made_before_key_expired() {
+        let keys = parse_keys(EXPIRING_KEY, false).unwrap();
+        assert!(keys[0].expires.is_some());
+        verify(b"hello, world\n", EARLY_SIGNATURE, &keys).unwrap();
+    }
+
+    #[test]
+    fn rejects_signature_made_after_key_expired() {
+        let keys = parse_keys(EXPIRING_KEY, false).unwrap();
+        let err = verify(b"hello, world\n", LATE_SIGNATURE, &keys).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "signed by a trusted key that was not valid at the time"
+        );
+    }
+
+    #[test]
+    fn rejects_expired_signature() {
+        let keys = parse_keys(EXPIRING_KEY, false).unwrap();
+        let err = verify(b"hello, world\n", EXPIRING_SIGNATURE, &keys).unwrap_err();
+        assert_eq!(err.to_string(), "signature has expired");
+    }
+
+    #[test]
+    fn rejects_revoked_key() {
+        let err = parse_keys(REVOKED_KEY, false).unwrap_err();
+        assert_eq!(err.to_string(), "no unrevoked RSA keys found in key block");
+    }
+
+    #[test]
+    fn verifies_subkey_signature() {
+        let keys = parse_keys(SUBKEY_KEY, false).unwrap();
+        assert_eq!(keys.len(), 2);
+        verify(b"hello, world\n", SUBKEY_SIGNATURE, &keys).unwrap();
+    }
+
+    #[test]
+    fn rejects_revoked_subkey() {
+        // Only the primary key, which is only for certifying, is left
+        let keys = parse_keys(SUBKEY_REVOKED_KEY, false).unwrap();
+        assert_eq!(keys.len(), 1);
+        let err = verify(b"hello, world\n", SUBKEY_SIGNATURE, &keys).unwrap_err();
+        assert_eq!(err.to_string(), "not signed by any trusted key");
+    }
+}
@@ -7,6 +7,20 @@ use crate::{currentprocess::varsource::VarSource, process};
 
 pub const RUST_RECURSION_COUNT_MAX: u32 = 20;
 
+/// Variables that rustup sets itself when running a toolchain binary.
+///
+/// Both loader path variables are listed regardless of platform so that a
+/// toolchain file is accepted or rejected the same way everywhere.
+pub(crate) const RUSTUP_MANAGED_VARS: &[&str] = &[
+    "CARGO_HOME",
+    "DYLD_FALLBACK_LIBRARY_PATH",
+    "LD_LIBRARY_PATH",
+    "PATH",
+    "RUST_RECURSION_COUNT",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+];
+
 pub(crate) fn prepend_path(name: &str, prepend: Vec<PathBuf>, cmd: &mut Command) {
     let old_value = process().var_os(name);
     let parts = if let Some(ref v) = old_value {
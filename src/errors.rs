@@ -2,21 +2,21 @@
 
 use std::ffi::OsString;
 use std::fmt::Debug;
-#[cfg(not(windows))]
 use std::io;
 use std::io::Write;
 use std::path::PathBuf;
+use std::process::ExitStatus;
 
 use thiserror::Error as ThisError;
 use url::Url;
 
 use crate::{
     currentprocess::process,
-    dist::dist::{TargetTriple, ToolchainDesc},
+    dist::dist::{DistError, TargetTriple, ToolchainDesc},
 };
 use crate::{
     dist::manifest::{Component, Manifest},
-    toolchain::names::{PathBasedToolchainName, ToolchainName},
+    toolchain::names::{CustomToolchainName, PathBasedToolchainName, ToolchainName},
 };
 
 /// A type erasing thunk for the retry crate to permit use with anyhow. See <https://github.com/dtolnay/anyhow/issues/149>
@@ -24,18 +24,143 @@ use crate::{
 #[error(transparent)]
 pub struct OperationError(pub anyhow::Error);
 
+/// Why [`active_toolchain`](crate::active_toolchain) failed. Like
+/// [`InstallError`], each kind keeps the error it was told from.
+#[derive(ThisError, Debug)]
+#[non_exhaustive]
+pub enum ActiveToolchainError {
+    /// There is neither an override nor a default toolchain.
+    #[error(transparent)]
+    NotSelected(anyhow::Error),
+    /// Anything else, such as a toolchain file that cannot be parsed.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ActiveToolchainError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast_ref::<RustupError>() {
+            Some(RustupError::ToolchainNotSelected) => Self::NotSelected(err),
+            _ => Self::Other(err),
+        }
+    }
+}
+
+/// Why [`install_toolchain`](crate::install_toolchain) failed, so that a
+/// program embedding it can decide whether to retry or fall back. Each kind
+/// keeps the error it was told from, which displays as the CLI shows it.
+#[derive(ThisError, Debug)]
+#[non_exhaustive]
+pub enum InstallError {
+    /// A download failed, such as by losing the connection or an HTTP error.
+    #[error(transparent)]
+    Network(anyhow::Error),
+    /// There is no release of the toolchain, and so no manifest for it.
+    #[error(transparent)]
+    ManifestNotFound(anyhow::Error),
+    /// The release lacks components that the install needs.
+    #[error(transparent)]
+    ComponentUnavailable(anyhow::Error),
+    /// A download did not have the hash that the manifest gives for it.
+    #[error(transparent)]
+    HashMismatch(anyhow::Error),
+    /// The disk, or the user's quota on it, ran out of space.
+    #[error(transparent)]
+    DiskFull(anyhow::Error),
+    /// A file in the rustup home could not be written.
+    #[error(transparent)]
+    PermissionDenied(anyhow::Error),
+    /// Anything else, such as an invalid toolchain name.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for InstallError {
+    fn from(err: anyhow::Error) -> Self {
+        let kind = install_error_kind(&err);
+        kind(err)
+    }
+}
+
+/// The kind of [`InstallError`] that `err` is, going by the first of the
+/// errors in its chain that tells. Failing to write to the disk while
+/// downloading counts as the disk failing rather than the network.
+fn install_error_kind(err: &anyhow::Error) -> fn(anyhow::Error) -> InstallError {
+    fn find<T: std::error::Error + Send + Sync + 'static>(err: &anyhow::Error) -> Option<&T> {
+        err.downcast_ref::<T>()
+            .or_else(|| err.chain().find_map(|e| e.downcast_ref::<T>()))
+    }
+
+    match find::<RustupError>(err) {
+        Some(RustupError::ChecksumFailed { .. }) => return InstallError::HashMismatch,
+        Some(
+            RustupError::RequestedComponentsUnavailable { .. }
+            | RustupError::ComponentsUnsupported(_),
+        ) => return InstallError::ComponentUnavailable,
+        _ => {}
+    }
+    match find::<DistError>(err) {
+        Some(DistError::ToolchainComponentsMissing(..) | DistError::UnknownComponent { .. }) => {
+            return InstallError::ComponentUnavailable
+        }
+        Some(DistError::MissingReleaseForToolchain(_)) => return InstallError::ManifestNotFound,
+        _ => {}
+    }
+
+    let download = find::<download::DownloadError>(err);
+    let io = match download {
+        Some(download::DownloadError::IoError(e)) => Some(e),
+        _ => find::<io::Error>(err),
+    };
+    if let Some(e) = io {
+        if is_disk_full(e) {
+            return InstallError::DiskFull;
+        }
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            return InstallError::PermissionDenied;
+        }
+    }
+    if download.is_some() {
+        return InstallError::Network;
+    }
+    InstallError::Other
+}
+
+fn is_disk_full(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    let codes = [libc::ENOSPC, libc::EDQUOT];
+    // ERROR_DISK_FULL and ERROR_HANDLE_DISK_FULL
+    #[cfg(windows)]
+    let codes = [112, 39];
+    e.raw_os_error().is_some_and(|code| codes.contains(&code))
+}
+
 #[derive(ThisError, Debug)]
 pub(crate) enum RustupError {
+    #[error("no installed toolchain provides '{0}'")]
+    BinaryNotInAnyToolchain(String),
     #[error("partially downloaded file may have been damaged and was removed, please try again")]
     BrokenPartialFile,
     #[error("component download failed for {0}")]
     ComponentDownloadFailed(String),
+    #[error("component '{0}' is already installed")]
+    ComponentAlreadyInstalled(String),
+    #[error("{}", component_set_not_found_msg(.name, .defined))]
+    ComponentSetNotFound { name: String, defined: Vec<String> },
+    #[error("package in '{}' does not contain component '{name}'", .path.display())]
+    ComponentNotInPackage { name: String, path: PathBuf },
+    #[error("component '{name}' not found on local mirror: '{}' does not exist", .path.display())]
+    ComponentNotFoundOnMirror { name: String, path: PathBuf },
     #[error("failure removing component '{name}', directory does not exist: '{}'", .path.display())]
     ComponentMissingDir { name: String, path: PathBuf },
     #[error("failure removing component '{name}', directory does not exist: '{}'", .path.display())]
     ComponentMissingFile { name: String, path: PathBuf },
     #[error("could not create {name} directory: '{}'", .path.display())]
     CreatingDirectory { name: &'static str, path: PathBuf },
+    #[error("invalid download cache size '{0}': expected a number of bytes, optionally followed by K, M or G")]
+    InvalidDownloadCacheSize(String),
+    #[error("invalid download rate '{0}': expected a number of bytes per second, optionally followed by K, M or G")]
+    InvalidDownloadRate(String),
     #[error("invalid toolchain name: '{0}'")]
     InvalidToolchainName(String),
     #[error("could not create link from '{}' to '{}'", .src.display(), .dest.display())]
@@ -59,14 +184,26 @@ pub(crate) enum RustupError {
     ComponentsUnsupportedV1(String),
     #[error("component manifest for '{0}' is corrupt")]
     CorruptComponent(String),
+    #[error("could not parse settings file '{}'\nhelp: the settings from before they were last changed are in '{}'; copy that file over the settings file to restore them", .path.display(), .backup.display())]
+    CorruptSettings { path: PathBuf, backup: PathBuf },
     #[error("could not download file from '{url}' to '{}'", .path.display())]
     DownloadingFile { url: Url, path: PathBuf },
     #[error("could not download file from '{url}' to '{}'", .path.display())]
     DownloadNotExists { url: Url, path: PathBuf },
+    #[error("could not download file from '{url}'")]
+    StreamingFile { url: Url },
+    #[error("cannot download '{url}' in offline mode")]
+    Offline { url: String },
+    #[error("rustup cannot be updated in offline mode")]
+    OfflineSelfUpdate,
+    #[error("'{name}' not found in local dist directory '{}'", .dir.display())]
+    MissingLocalDistFile { name: String, dir: PathBuf },
     #[error("Missing manifest in toolchain '{}'", .0)]
     MissingManifest(ToolchainDesc),
     #[error("server sent a broken manifest: missing package for component {0}")]
     MissingPackageForComponent(String),
+    #[error("'{url}' is not signed, and signatures are required by `download.verify-signatures`")]
+    MissingSignature { url: String },
     #[error("could not read {name} directory: '{}'", .path.display())]
     ReadingDirectory { name: &'static str, path: PathBuf },
     #[error("could not read {name} file: '{}'", .path.display())]
@@ -81,12 +218,56 @@ pub(crate) enum RustupError {
         manifest: Manifest,
         toolchain: String,
     },
+    #[error("the '{component}' component, which '{}' requires, is not installed for the toolchain '{toolchain}'\nhelp: run `rustup component add --toolchain {toolchain} {component}` to install it", .file.display())]
+    RequiredComponentMissing {
+        component: String,
+        file: PathBuf,
+        toolchain: String,
+    },
+    #[error("the '{component}' component, which '{}' requires, is not available for the toolchain '{toolchain}'\nhelp: use a toolchain that provides it, or remove it from the file's `components`", .file.display())]
+    RequiredComponentUnavailable {
+        component: String,
+        file: PathBuf,
+        toolchain: String,
+    },
+    #[error("the post-install hook for toolchain '{toolchain}' failed ({status})\nhelp: set `post-install-fatal = false` in the `[hooks]` of the settings to only warn about it")]
+    PostInstallHookFailed {
+        toolchain: String,
+        status: ExitStatus,
+    },
     #[error("command failed: '{}'", PathBuf::from(.name).display())]
     RunningCommand { name: OsString },
+    #[error("signature verification failed for '{url}': {reason}")]
+    SignatureVerificationFailed { url: String, reason: String },
+    #[error("target '{target}' is the one cargo builds for here, as set by {configured_by}\nhelp: pass `--force` to remove it anyway")]
+    TargetInUse {
+        target: String,
+        configured_by: String,
+    },
+    #[error("'{}', which the `[tool-overrides]` of the settings run as `{tool}` for toolchain '{toolchain}', does not exist", .path.display())]
+    ToolOverrideNotFound {
+        tool: String,
+        toolchain: String,
+        path: PathBuf,
+    },
+    #[error("toolchain '{0}' is already installed, and is not a link that can be replaced")]
+    ToolchainNotALink(CustomToolchainName),
+    #[error(
+        "{0} toolchain file(s) or directories beneath the roots could not be read, \
+         so no toolchains were removed"
+    )]
+    UnreadableToolchainFiles(usize),
+    #[error(
+        "{count} file(s) of toolchain '{toolchain}' are missing or modified\n\
+         help: run `rustup toolchain uninstall {toolchain}` and install it again"
+    )]
+    ToolchainDamaged { toolchain: String, count: usize },
     #[error("toolchain '{0}' is not installable")]
     ToolchainNotInstallable(String),
     #[error("toolchain '{0}' is not installed")]
     ToolchainNotInstalled(ToolchainName),
+    #[error("toolchain '{0}' does not track a release channel, so it cannot be frozen")]
+    ToolchainNotFreezable(String),
     #[error("path '{0}' not found")]
     PathToolchainNotInstalled(PathBasedToolchainName),
     #[error(
@@ -121,8 +302,28 @@ pub(crate) enum RustupError {
         target: TargetTriple,
         suggestion: Option<String>,
     },
+    #[error("'{triple}' is not a known host triple{}\n\
+    help: use `rustup set default-host --force {triple}` if it is a new host", suggest_message(.suggestion))]
+    UnknownHostTriple {
+        triple: String,
+        suggestion: Option<String>,
+    },
+    #[error("'{0}' is not a rustup version; expected one such as '1.27.0'")]
+    InvalidRustupVersion(String),
+    #[error("rustup {version} is not available from '{update_root}'")]
+    UnknownRustupVersion {
+        version: String,
+        update_root: String,
+    },
+    #[error(
+        "rustup {version} is older than this rustup, {current}\n\
+    help: pass `--allow-downgrade` to install it anyway"
+    )]
+    RustupDowngrade { version: String, current: String },
     #[error("unknown metadata version: '{0}'")]
     UnknownMetadataVersion(String),
+    #[error("unsupported hash algorithm '{0}'")]
+    UnsupportedHashAlgorithm(String),
     #[error("manifest version '{0}' is not supported")]
     UnsupportedVersion(String),
     #[error("could not write {name} file: '{}'", .path.display())]
@@ -199,3 +400,19 @@ fn component_unavailable_msg(cs: &[Component], manifest: &Manifest, toolchain: &
 
     String::from_utf8(buf).unwrap()
 }
+
+fn component_set_not_found_msg(name: &str, defined: &[String]) -> String {
+    let help = match defined {
+        [] => "no component sets are defined in the `[component-sets]` table of the settings"
+            .to_owned(),
+        _ => format!(
+            "the defined component sets are {}",
+            defined
+                .iter()
+                .map(|s| format!("'{s}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    format!("no component set named '{name}'\nhelp: {help}")
+}
@@ -1,12 +1,14 @@
 //! Installation and upgrade of both distribution-managed and local
 //! toolchains
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use crate::{
     config::Cfg,
     dist::{dist, download::DownloadCfg, prefix::InstallPrefix, Notification},
+    env_var,
     errors::RustupError,
     notifications::Notification as RootNotification,
     toolchain::{
@@ -38,7 +40,8 @@ pub(crate) enum InstallMethod<'a> {
     Dist {
         cfg: &'a Cfg,
         desc: &'a dist::ToolchainDesc,
-        profile: dist::Profile,
+        /// `None` installs only the requested components
+        profile: Option<dist::Profile>,
         update_hash: Option<&'a Path>,
         dl_cfg: DownloadCfg<'a>,
         /// --force bool is whether to force an update/install
@@ -96,10 +99,16 @@ impl<'a> InstallMethod<'a> {
         };
 
         // Final check, to ensure we're installed
-        match Toolchain::exists(self.cfg(), &self.local_name())? {
-            true => Ok(status),
-            false => Err(RustupError::ToolchainNotInstallable(self.dest_basename()).into()),
+        if !Toolchain::exists(self.cfg(), &self.local_name())? {
+            return Err(RustupError::ToolchainNotInstallable(self.dest_basename()).into());
         }
+
+        if let (InstallMethod::Dist { .. }, UpdateStatus::Installed | UpdateStatus::Updated(_)) =
+            (self, &status)
+        {
+            run_post_install_hook(self.cfg(), &self.local_name())?;
+        }
+        Ok(status)
     }
 
     fn run(&self, path: &Path, notify_handler: &dyn Fn(Notification<'_>)) -> Result<bool> {
@@ -107,6 +116,8 @@ impl<'a> InstallMethod<'a> {
             // Don't uninstall first for Dist method
             match self {
                 InstallMethod::Dist { .. } => {}
+                // The old link is swapped for the new one in a single step
+                InstallMethod::Link { .. } if utils::is_link(path) => {}
                 _ => {
                     uninstall(path, notify_handler)?;
                 }
@@ -140,7 +151,7 @@ impl<'a> InstallMethod<'a> {
                     *dl_cfg,
                     update_hash.as_deref(),
                     desc,
-                    if *exists { None } else { Some(*profile) },
+                    if *exists { None } else { *profile },
                     prefix,
                     *force_update,
                     *allow_downgrade,
@@ -191,6 +202,55 @@ impl<'a> InstallMethod<'a> {
     }
 }
 
+/// Runs the `post-install` hook of the settings, if there is one, for the
+/// toolchain `name` that was just installed or updated. The hook runs in a
+/// shell with the toolchain's binaries first on `PATH`.
+///
+/// Hooks only ever come from the user's own settings: a `rust-toolchain.toml`
+/// can choose a toolchain to install, but never a command to run.
+fn run_post_install_hook(cfg: &Cfg, name: &LocalToolchainName) -> Result<()> {
+    let Some((command, fatal)) = cfg.get_post_install_hook()? else {
+        return Ok(());
+    };
+    (cfg.notify_handler)(RootNotification::RunningPostInstallHook(
+        &name.to_string(),
+        &command,
+    ));
+
+    let toolchain = Toolchain::new(cfg, name.clone())?;
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(&command);
+    toolchain.set_env(&mut cmd);
+    env_var::prepend_path("PATH", vec![toolchain.path().join("bin")], &mut cmd);
+
+    let result = cmd
+        .status()
+        .with_context(|| format!("could not run the post-install hook `{command}`"))
+        .and_then(|status| match status.success() {
+            true => Ok(()),
+            false => Err(RustupError::PostInstallHookFailed {
+                toolchain: name.to_string(),
+                status,
+            }
+            .into()),
+        });
+    match result {
+        Err(e) if !fatal => {
+            (cfg.notify_handler)(RootNotification::NonFatalError(&e));
+            Ok(())
+        }
+        result => result,
+    }
+}
+
 pub(crate) fn uninstall(path: &Path, notify_handler: &dyn Fn(Notification<'_>)) -> Result<()> {
     utils::remove_dir("install", path, notify_handler)
 }
@@ -10,10 +10,15 @@
 )]
 #![recursion_limit = "1024"]
 
-pub(crate) use crate::config::*;
+pub use crate::config::{
+    active_toolchain, install_toolchain, list_components, ActiveReason, Cfg, ComponentStatus,
+};
 use crate::currentprocess::*;
 pub use crate::errors::*;
 pub(crate) use crate::notifications::*;
+pub use crate::toolchain::names::{
+    CustomToolchainName, LocalToolchainName, PathBasedToolchainName, ToolchainName,
+};
 pub(crate) use crate::utils::toml_utils;
 use anyhow::{anyhow, Result};
 use itertools::{chain, Itertools};
@@ -3,35 +3,78 @@ use std::path::{Path, PathBuf};
 
 use crate::{
     dist::{dist::ToolchainDesc, temp},
-    toolchain::names::ToolchainName,
     utils::notify::NotificationLevel,
 };
 
+/// Receives the [`Notification`]s rustup emits while it works, such as the
+/// data received for a download, the component being downloaded and the
+/// component being installed.
+///
+/// The command line renders them to the terminal; programs that embed rustup
+/// can implement this to present progress their own way.
+pub trait Notifier {
+    fn handle(&self, n: Notification<'_>);
+}
+
+impl<F: Fn(Notification<'_>)> Notifier for F {
+    fn handle(&self, n: Notification<'_>) {
+        self(n)
+    }
+}
+
 #[derive(Debug)]
-pub(crate) enum Notification<'a> {
+pub enum Notification<'a> {
     Install(crate::dist::Notification<'a>),
     Utils(crate::utils::Notification<'a>),
     Temp(temp::Notification<'a>),
 
-    SetDefaultToolchain(Option<&'a ToolchainName>),
+    SetDefaultToolchain(Option<&'a str>),
     SetOverrideToolchain(&'a Path, &'a str),
+    FrozeOverrideToolchain(&'a Path, &'a str),
     SetProfile(&'a str),
     SetSelfUpdate(&'a str),
+    SetDownloadConcurrency(usize),
+    SetDownloadRetries(usize),
+    SetDownloadRetryBackoff(u64),
+    SetDownloadVerifySignatures(bool),
+    SetDisableSelfUpdate(bool),
+    SetDownloadMaxRate(u64),
+    SetDownloadCacheSize(u64),
+    SetDownloadCaBundle(Option<&'a Path>),
+    SetDownloadStreamExtract(bool),
     LookingForToolchain(&'a ToolchainDesc),
     ToolchainDirectory(&'a Path),
     UpdatingToolchain(&'a str),
     InstallingToolchain(&'a str),
     InstalledToolchain(&'a str),
     UsingExistingToolchain(&'a ToolchainDesc),
-    UninstallingToolchain(&'a ToolchainName),
-    UninstalledToolchain(&'a ToolchainName),
+    UninstallingToolchain(&'a str),
+    UninstalledToolchain(&'a str),
     UpdateHashMatches,
+    /// The `post-install` hook is being run for a toolchain
+    RunningPostInstallHook(&'a str, &'a str),
+    /// A toolchain is left as it is because an override froze it at the given
+    /// date for the given path
+    SkippingFrozenToolchain(&'a ToolchainDesc, &'a str, &'a Path),
     UpgradingMetadata(&'a str, &'a str),
     MetadataUpgradeNotNeeded(&'a str),
     ReadMetadataVersion(&'a str),
     NonFatalError(&'a anyhow::Error),
     UpgradeRemovesToolchains,
+    /// An override was recorded under a path that was not canonical
+    MovedOverride(&'a Path, &'a Path),
+    /// A frozen override was left without the override it freezes
+    RemovedStaleFreeze(&'a Path),
+    /// An update hash was left behind by a toolchain that is not installed
+    RemovedOrphanedUpdateHash(&'a str),
     PlainVerboseMessage(&'a str),
+    /// The settings file of `~/.rustup` was moved to the XDG config directory
+    MovedLegacySettings(&'a Path, &'a Path),
+    /// Both `~/.rustup` and the XDG config directory have a settings file
+    IgnoringLegacySettings(&'a Path, &'a Path),
+    /// A directory or toolchain file beneath a `toolchain gc` root could not
+    /// be read
+    UnreadableToolchainFile(&'a Path, &'a anyhow::Error),
     /// Both `rust-toolchain` and `rust-toolchain.toml` exist within a directory
     DuplicateToolchainFile {
         rust_toolchain: &'a Path,
@@ -72,15 +115,34 @@ impl<'a> Notification<'a> {
             | UpdateHashMatches => NotificationLevel::Verbose,
             SetDefaultToolchain(_)
             | SetOverrideToolchain(_, _)
+            | FrozeOverrideToolchain(_, _)
             | SetProfile(_)
             | SetSelfUpdate(_)
+            | SetDownloadConcurrency(_)
+            | SetDownloadRetries(_)
+            | SetDownloadRetryBackoff(_)
+            | SetDownloadVerifySignatures(_)
+            | SetDisableSelfUpdate(_)
+            | SetDownloadMaxRate(_)
+            | SetDownloadCacheSize(_)
+            | SetDownloadCaBundle(_)
+            | SetDownloadStreamExtract(_)
             | UsingExistingToolchain(_)
             | UninstallingToolchain(_)
             | UninstalledToolchain(_)
             | UpgradingMetadata(_, _)
+            | MovedOverride(_, _)
+            | RemovedStaleFreeze(_)
+            | RemovedOrphanedUpdateHash(_)
+            | RunningPostInstallHook(..)
             | MetadataUpgradeNotNeeded(_) => NotificationLevel::Info,
             NonFatalError(_) => NotificationLevel::Error,
-            UpgradeRemovesToolchains | DuplicateToolchainFile { .. } => NotificationLevel::Warn,
+            UpgradeRemovesToolchains
+            | SkippingFrozenToolchain(..)
+            | MovedLegacySettings(..)
+            | IgnoringLegacySettings(..)
+            | UnreadableToolchainFile(..)
+            | DuplicateToolchainFile { .. } => NotificationLevel::Warn,
         }
     }
 }
@@ -100,8 +162,37 @@ impl<'a> Display for Notification<'a> {
                 path.display(),
                 name
             ),
+            FrozeOverrideToolchain(path, date) => write!(
+                f,
+                "override toolchain for '{}' frozen at {}",
+                path.display(),
+                date
+            ),
             SetProfile(name) => write!(f, "profile set to '{name}'"),
             SetSelfUpdate(mode) => write!(f, "auto-self-update mode set to '{mode}'"),
+            SetDownloadConcurrency(n) => write!(f, "download concurrency set to {n}"),
+            SetDownloadRetries(n) => write!(f, "download retries set to {n}"),
+            SetDownloadRetryBackoff(ms) => write!(f, "download retry backoff set to {ms}ms"),
+            SetDownloadVerifySignatures(true) => write!(f, "manifest signatures will be verified"),
+            SetDownloadVerifySignatures(false) => {
+                write!(f, "manifest signatures will not be verified")
+            }
+            SetDisableSelfUpdate(true) => write!(f, "self-update is disabled by policy"),
+            SetDisableSelfUpdate(false) => write!(f, "self-update is no longer disabled"),
+            SetDownloadMaxRate(0) => write!(f, "download rate limit removed"),
+            SetDownloadMaxRate(rate) => write!(f, "download rate limited to {rate} bytes/s"),
+            SetDownloadCacheSize(0) => write!(f, "download cache size limit removed"),
+            SetDownloadCacheSize(size) => write!(f, "download cache limited to {size} bytes"),
+            SetDownloadCaBundle(Some(path)) => {
+                write!(f, "downloads will trust the CA bundle '{}'", path.display())
+            }
+            SetDownloadCaBundle(None) => write!(f, "download CA bundle removed"),
+            SetDownloadStreamExtract(true) => {
+                write!(f, "components will be unpacked as they download")
+            }
+            SetDownloadStreamExtract(false) => {
+                write!(f, "components will be downloaded before they are unpacked")
+            }
             LookingForToolchain(name) => write!(f, "looking for installed toolchain '{name}'"),
             ToolchainDirectory(path) => write!(f, "toolchain directory: '{}'", path.display()),
             UpdatingToolchain(name) => write!(f, "updating existing install for '{name}'"),
@@ -111,6 +202,30 @@ impl<'a> Display for Notification<'a> {
             UninstallingToolchain(name) => write!(f, "uninstalling toolchain '{name}'"),
             UninstalledToolchain(name) => write!(f, "toolchain '{name}' uninstalled"),
             UpdateHashMatches => write!(f, "toolchain is already up to date"),
+            RunningPostInstallHook(name, command) => {
+                write!(f, "running the post-install hook for '{name}': {command}")
+            }
+            MovedLegacySettings(from, to) => write!(
+                f,
+                "moved settings from '{}' to '{}'",
+                from.display(),
+                to.display()
+            ),
+            IgnoringLegacySettings(legacy, settings) => write!(
+                f,
+                "ignoring '{}', as the settings in '{}' are used instead",
+                legacy.display(),
+                settings.display()
+            ),
+            UnreadableToolchainFile(path, e) => {
+                write!(f, "could not read '{}': {e:#}", path.display())
+            }
+            SkippingFrozenToolchain(name, date, path) => write!(
+                f,
+                "not updating '{name}', which the override for '{}' froze at {date}; \
+                 use --force to update it anyway",
+                path.display()
+            ),
             UpgradingMetadata(from_ver, to_ver) => write!(
                 f,
                 "upgrading metadata version from '{from_ver}' to '{to_ver}'"
@@ -118,6 +233,21 @@ impl<'a> Display for Notification<'a> {
             MetadataUpgradeNotNeeded(ver) => {
                 write!(f, "nothing to upgrade: metadata version is already '{ver}'")
             }
+            MovedOverride(from, to) => write!(
+                f,
+                "moved the override for '{}' to its canonical path '{}'",
+                from.display(),
+                to.display()
+            ),
+            RemovedStaleFreeze(path) => write!(
+                f,
+                "removed the freeze for '{}', which has no override",
+                path.display()
+            ),
+            RemovedOrphanedUpdateHash(name) => write!(
+                f,
+                "removed the update hash of '{name}', which is not installed"
+            ),
             ReadMetadataVersion(ver) => write!(f, "read metadata version: '{ver}'"),
             NonFatalError(e) => write!(f, "{e}"),
             UpgradeRemovesToolchains => write!(
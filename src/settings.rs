@@ -1,11 +1,11 @@
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{btree_map::Entry, BTreeMap};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::{Context, Result};
 
-use crate::cli::self_update::SelfUpdateMode;
+use crate::cli::self_update::{PathPosition, SelfUpdateMode};
 use crate::dist::dist::Profile;
 use crate::errors::*;
 use crate::notifications::*;
@@ -29,9 +29,22 @@ impl SettingsFile {
         }
     }
 
+    /// Where the settings are kept as they were before the last write.
+    fn backup_path(&self) -> PathBuf {
+        let mut backup = self.path.as_os_str().to_owned();
+        backup.push(".bak");
+        backup.into()
+    }
+
     fn write_settings(&self) -> Result<()> {
         let s = self.cache.borrow().as_ref().unwrap().clone();
-        utils::write_file("settings", &self.path, &s.stringify())?;
+        // Any settings there were read successfully, so they are good to
+        // back up. Their contents are, rather than a symlink to them
+        if utils::is_file(&self.path) {
+            let old = utils::read_file("settings", &self.path)?;
+            utils::write_file_atomic("settings backup", &self.backup_path(), &old)?;
+        }
+        utils::write_file_atomic("settings", &self.path, &s.stringify())?;
         Ok(())
     }
 
@@ -42,7 +55,19 @@ impl SettingsFile {
             if b.is_none() {
                 *b = Some(if utils::is_file(&self.path) {
                     let content = utils::read_file("settings", &self.path)?;
-                    Settings::parse(&content)?
+                    Settings::parse(&content).map_err(|e| {
+                        let backup = self.backup_path();
+                        let restorable = utils::read_file("settings", &backup)
+                            .and_then(|b| Settings::parse(&b))
+                            .is_ok();
+                        match restorable {
+                            true => e.context(RustupError::CorruptSettings {
+                                path: self.path.clone(),
+                                backup,
+                            }),
+                            false => e,
+                        }
+                    })?
                 } else {
                     needs_save = true;
                     Default::default()
@@ -79,8 +104,67 @@ pub struct Settings {
     pub default_toolchain: Option<String>,
     pub profile: Option<Profile>,
     pub overrides: BTreeMap<String, String>,
+    /// The manifest dates that frozen overrides pin their channel at, keyed
+    /// like `overrides`.
+    pub frozen_overrides: BTreeMap<String, String>,
+    /// The named sets of components that `--component-set` installs on top
+    /// of the profile, from the `[component-sets]` table.
+    pub component_sets: BTreeMap<String, Vec<String>>,
+    /// The binaries that the proxies run in place of a toolchain's own, from
+    /// the `[tool-overrides]` table, keyed by toolchain name.
+    pub tool_overrides: BTreeMap<String, ToolOverrides>,
     pub pgp_keys: Option<String>,
     pub auto_self_update: Option<SelfUpdateMode>,
+    /// When `check-only` self-update mode last looked for a new rustup, in
+    /// seconds since the Unix epoch.
+    pub last_self_update_check: Option<u64>,
+    /// Whether every self-update, explicit or not, is refused.
+    pub disable_self_update: Option<bool>,
+    pub download_concurrency: Option<usize>,
+    pub download_retries: Option<usize>,
+    pub download_retry_backoff_ms: Option<u64>,
+    /// Whether channel manifests must carry a valid signature.
+    pub download_verify_signatures: Option<bool>,
+    /// Whether manifests from `file://` mirrors may lack a signature when
+    /// signatures are verified.
+    pub download_allow_unsigned_local: Option<bool>,
+    /// The most bytes per second that downloads may receive, or zero for no
+    /// limit.
+    pub download_max_rate: Option<u64>,
+    /// The most bytes that the download cache may hold, or zero for no
+    /// limit.
+    pub download_cache_size: Option<u64>,
+    /// The file of PEM certificates that downloads trust instead of the
+    /// system's.
+    pub download_ca_bundle: Option<String>,
+    /// Whether component packages are unpacked as they download, without
+    /// being saved to the download cache.
+    pub download_stream_extract: Option<bool>,
+    /// The command run after a toolchain is installed or updated, from the
+    /// `[hooks]` table.
+    pub hooks_post_install: Option<String>,
+    /// Whether the install fails when the `post-install` hook does, which
+    /// it does unless this is false.
+    pub hooks_post_install_fatal: Option<bool>,
+    pub path_position: Option<PathPosition>,
+}
+
+/// The binaries run in place of a toolchain's own `cargo` and `rustc`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ToolOverrides {
+    pub cargo: Option<String>,
+    pub rustc: Option<String>,
+}
+
+impl ToolOverrides {
+    /// The binary to run in place of `tool`, if it is overridden.
+    pub(crate) fn get(&self, tool: &str) -> Option<&str> {
+        match tool {
+            "cargo" => self.cargo.as_deref(),
+            "rustc" => self.rustc.as_deref(),
+            _ => None,
+        }
+    }
 }
 
 impl Default for Settings {
@@ -91,8 +175,25 @@ impl Default for Settings {
             default_toolchain: None,
             profile: Some(Profile::Default),
             overrides: BTreeMap::new(),
+            frozen_overrides: BTreeMap::new(),
+            component_sets: BTreeMap::new(),
+            tool_overrides: BTreeMap::new(),
             pgp_keys: None,
             auto_self_update: None,
+            last_self_update_check: None,
+            disable_self_update: None,
+            download_concurrency: None,
+            download_retries: None,
+            download_retry_backoff_ms: None,
+            download_verify_signatures: None,
+            download_allow_unsigned_local: None,
+            download_max_rate: None,
+            download_cache_size: None,
+            download_ca_bundle: None,
+            download_stream_extract: None,
+            hooks_post_install: None,
+            hooks_post_install_fatal: None,
+            path_position: None,
         }
     }
 }
@@ -114,6 +215,7 @@ impl Settings {
         notify_handler: &dyn Fn(Notification<'_>),
     ) -> bool {
         let key = Self::path_to_key(path, notify_handler);
+        self.frozen_overrides.remove(&key);
         self.overrides.remove(&key).is_some()
     }
 
@@ -125,9 +227,80 @@ impl Settings {
     ) {
         let key = Self::path_to_key(path, notify_handler);
         notify_handler(Notification::SetOverrideToolchain(path, &toolchain));
+        self.frozen_overrides.remove(&key);
         self.overrides.insert(key, toolchain);
     }
 
+    /// Pins the channel that the override for `path` names to the manifest
+    /// `date` it is currently installed at.
+    pub(crate) fn freeze_override(
+        &mut self,
+        path: &Path,
+        date: String,
+        notify_handler: &dyn Fn(Notification<'_>),
+    ) {
+        let key = Self::path_to_key(path, notify_handler);
+        notify_handler(Notification::FrozeOverrideToolchain(path, &date));
+        self.frozen_overrides.insert(key, date);
+    }
+
+    /// Finds a frozen override for `toolchain`, returning the path it was set
+    /// for and the date it is frozen at.
+    pub(crate) fn frozen_override(&self, toolchain: &str) -> Option<(&str, &str)> {
+        self.frozen_overrides
+            .iter()
+            .find(|(path, _)| self.overrides.get(*path).map(String::as_str) == Some(toolchain))
+            .map(|(path, date)| (path.as_str(), date.as_str()))
+    }
+
+    /// Moves the overrides recorded under a path other than its canonical
+    /// form, as older versions of rustup did, to the canonical one, and drops
+    /// the freezes left without an override. Returns whether anything
+    /// changed.
+    pub(crate) fn normalize_overrides(
+        &mut self,
+        notify_handler: &dyn Fn(Notification<'_>),
+    ) -> bool {
+        let mut changed = false;
+        let keys: Vec<_> = self.overrides.keys().cloned().collect();
+        for key in keys {
+            let canonical = Self::path_to_key(Path::new(&key), notify_handler);
+            if canonical == key {
+                continue;
+            }
+            let toolchain = self
+                .overrides
+                .remove(&key)
+                .expect("override key should exist");
+            let frozen = self.frozen_overrides.remove(&key);
+            notify_handler(Notification::MovedOverride(
+                Path::new(&key),
+                Path::new(&canonical),
+            ));
+            // An override set since for the canonical path is the one in effect
+            if let Entry::Vacant(entry) = self.overrides.entry(canonical.clone()) {
+                entry.insert(toolchain);
+                if let Some(date) = frozen {
+                    self.frozen_overrides.insert(canonical, date);
+                }
+            }
+            changed = true;
+        }
+
+        let stale: Vec<_> = self
+            .frozen_overrides
+            .keys()
+            .filter(|key| !self.overrides.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in stale {
+            notify_handler(Notification::RemovedStaleFreeze(Path::new(&key)));
+            self.frozen_overrides.remove(&key);
+            changed = true;
+        }
+        changed
+    }
+
     pub(crate) fn dir_override(
         &self,
         dir: &Path,
@@ -153,16 +326,69 @@ impl Settings {
         }
         let auto_self_update = get_opt_string(&mut table, "auto_self_update", path)?
             .and_then(|mode| SelfUpdateMode::from_str(mode.as_str()).ok());
+        let path_position = get_opt_string(&mut table, "path_position", path)?
+            .and_then(|p| PathPosition::from_str(p.as_str()).ok());
         let profile = get_opt_string(&mut table, "profile", path)?
             .and_then(|p| Profile::from_str(p.as_str()).ok());
+        let mut download = get_table(&mut table, "download", path)?;
+        let download_path = format!("{path}download.");
+        let download_concurrency = get_opt_integer(&mut download, "concurrency", &download_path)?
+            .and_then(|n| usize::try_from(n).ok())
+            .filter(|n| *n > 0);
+        let download_retries = get_opt_integer(&mut download, "retries", &download_path)?
+            .and_then(|n| usize::try_from(n).ok());
+        let download_retry_backoff_ms =
+            get_opt_integer(&mut download, "retry-backoff-ms", &download_path)?
+                .and_then(|n| u64::try_from(n).ok());
+        let download_verify_signatures =
+            get_opt_bool(&mut download, "verify-signatures", &download_path)?;
+        let download_allow_unsigned_local =
+            get_opt_bool(&mut download, "allow-unsigned-local", &download_path)?;
+        let download_max_rate = get_opt_bytes(
+            &mut download,
+            "max-rate",
+            &download_path,
+            utils::parse_download_rate,
+        )?;
+        let download_cache_size = get_opt_bytes(
+            &mut download,
+            "cache-size",
+            &download_path,
+            utils::parse_download_cache_size,
+        )?;
+        let download_ca_bundle = get_opt_string(&mut download, "ca-bundle", &download_path)?;
+        let download_stream_extract =
+            get_opt_bool(&mut download, "stream-extract", &download_path)?;
+        let mut hooks = get_table(&mut table, "hooks", path)?;
+        let hooks_path = format!("{path}hooks.");
+        let hooks_post_install = get_opt_string(&mut hooks, "post-install", &hooks_path)?;
+        let hooks_post_install_fatal = get_opt_bool(&mut hooks, "post-install-fatal", &hooks_path)?;
         Ok(Self {
             version,
             default_host_triple: get_opt_string(&mut table, "default_host_triple", path)?,
             default_toolchain: get_opt_string(&mut table, "default_toolchain", path)?,
             profile,
-            overrides: Self::table_to_overrides(&mut table, path)?,
+            overrides: Self::table_to_overrides(&mut table, "overrides", path)?,
+            frozen_overrides: Self::table_to_overrides(&mut table, "frozen_overrides", path)?,
+            component_sets: Self::table_to_component_sets(&mut table, "component-sets", path)?,
+            tool_overrides: Self::table_to_tool_overrides(&mut table, "tool-overrides", path)?,
             pgp_keys: get_opt_string(&mut table, "pgp_keys", path)?,
             auto_self_update,
+            last_self_update_check: get_opt_integer(&mut table, "last_self_update_check", path)?
+                .and_then(|t| u64::try_from(t).ok()),
+            disable_self_update: get_opt_bool(&mut table, "disable_self_update", path)?,
+            download_concurrency,
+            download_retries,
+            download_retry_backoff_ms,
+            download_verify_signatures,
+            download_allow_unsigned_local,
+            download_max_rate,
+            download_cache_size,
+            download_ca_bundle,
+            download_stream_extract,
+            hooks_post_install,
+            hooks_post_install_fatal,
+            path_position,
         })
     }
     pub(crate) fn into_toml(self) -> toml::value::Table {
@@ -193,18 +419,126 @@ impl Settings {
             );
         }
 
+        if let Some(v) = self.last_self_update_check {
+            result.insert(
+                "last_self_update_check".to_owned(),
+                toml::Value::Integer(v as i64),
+            );
+        }
+
+        if let Some(v) = self.disable_self_update {
+            result.insert("disable_self_update".to_owned(), toml::Value::Boolean(v));
+        }
+
+        if let Some(v) = self.path_position {
+            result.insert(
+                "path_position".to_owned(),
+                toml::Value::String(v.to_string()),
+            );
+        }
+
+        let mut download = toml::value::Table::new();
+        if let Some(v) = self.download_concurrency {
+            download.insert("concurrency".to_owned(), toml::Value::Integer(v as i64));
+        }
+        if let Some(v) = self.download_retries {
+            download.insert("retries".to_owned(), toml::Value::Integer(v as i64));
+        }
+        if let Some(v) = self.download_retry_backoff_ms {
+            download.insert(
+                "retry-backoff-ms".to_owned(),
+                toml::Value::Integer(v as i64),
+            );
+        }
+        if let Some(v) = self.download_verify_signatures {
+            download.insert("verify-signatures".to_owned(), toml::Value::Boolean(v));
+        }
+        if let Some(v) = self.download_allow_unsigned_local {
+            download.insert("allow-unsigned-local".to_owned(), toml::Value::Boolean(v));
+        }
+        if let Some(v) = self.download_max_rate {
+            download.insert("max-rate".to_owned(), toml::Value::Integer(v as i64));
+        }
+        if let Some(v) = self.download_cache_size {
+            download.insert("cache-size".to_owned(), toml::Value::Integer(v as i64));
+        }
+        if let Some(v) = self.download_ca_bundle {
+            download.insert("ca-bundle".to_owned(), toml::Value::String(v));
+        }
+        if let Some(v) = self.download_stream_extract {
+            download.insert("stream-extract".to_owned(), toml::Value::Boolean(v));
+        }
+        if !download.is_empty() {
+            result.insert("download".to_owned(), toml::Value::Table(download));
+        }
+
+        let mut hooks = toml::value::Table::new();
+        if let Some(v) = self.hooks_post_install {
+            hooks.insert("post-install".to_owned(), toml::Value::String(v));
+        }
+        if let Some(v) = self.hooks_post_install_fatal {
+            hooks.insert("post-install-fatal".to_owned(), toml::Value::Boolean(v));
+        }
+        if !hooks.is_empty() {
+            result.insert("hooks".to_owned(), toml::Value::Table(hooks));
+        }
+
         let overrides = Self::overrides_to_table(self.overrides);
         result.insert("overrides".to_owned(), toml::Value::Table(overrides));
 
+        if !self.frozen_overrides.is_empty() {
+            let frozen_overrides = Self::overrides_to_table(self.frozen_overrides);
+            result.insert(
+                "frozen_overrides".to_owned(),
+                toml::Value::Table(frozen_overrides),
+            );
+        }
+
+        if !self.component_sets.is_empty() {
+            let component_sets = self
+                .component_sets
+                .into_iter()
+                .map(|(name, components)| {
+                    let components = components.into_iter().map(toml::Value::String).collect();
+                    (name, toml::Value::Array(components))
+                })
+                .collect();
+            result.insert(
+                "component-sets".to_owned(),
+                toml::Value::Table(component_sets),
+            );
+        }
+
+        if !self.tool_overrides.is_empty() {
+            let tool_overrides = self
+                .tool_overrides
+                .into_iter()
+                .map(|(toolchain, tools)| {
+                    let mut table = toml::value::Table::new();
+                    for (tool, path) in [("cargo", tools.cargo), ("rustc", tools.rustc)] {
+                        if let Some(path) = path {
+                            table.insert(tool.to_owned(), toml::Value::String(path));
+                        }
+                    }
+                    (toolchain, toml::Value::Table(table))
+                })
+                .collect();
+            result.insert(
+                "tool-overrides".to_owned(),
+                toml::Value::Table(tool_overrides),
+            );
+        }
+
         result
     }
 
     fn table_to_overrides(
         table: &mut toml::value::Table,
+        key: &str,
         path: &str,
     ) -> Result<BTreeMap<String, String>> {
         let mut result = BTreeMap::new();
-        let pkg_table = get_table(table, "overrides", path)?;
+        let pkg_table = get_table(table, key, path)?;
 
         for (k, v) in pkg_table {
             if let toml::Value::String(t) = v {
@@ -215,6 +549,47 @@ impl Settings {
         Ok(result)
     }
 
+    fn table_to_component_sets(
+        table: &mut toml::value::Table,
+        key: &str,
+        path: &str,
+    ) -> Result<BTreeMap<String, Vec<String>>> {
+        let mut sets = get_table(table, key, path)?;
+        let sets_path = format!("{path}{key}.");
+        let names: Vec<_> = sets.keys().cloned().collect();
+        names
+            .into_iter()
+            .map(|name| {
+                let components = get_string_array(&mut sets, &name, &sets_path)?;
+                Ok((name, components))
+            })
+            .collect()
+    }
+
+    /// Only `cargo` and `rustc` are read from each toolchain's table, as no
+    /// other tool can be overridden.
+    fn table_to_tool_overrides(
+        table: &mut toml::value::Table,
+        key: &str,
+        path: &str,
+    ) -> Result<BTreeMap<String, ToolOverrides>> {
+        let mut toolchains = get_table(table, key, path)?;
+        let toolchains_path = format!("{path}{key}.");
+        let names: Vec<_> = toolchains.keys().cloned().collect();
+        names
+            .into_iter()
+            .map(|name| {
+                let mut tools = get_table(&mut toolchains, &name, &toolchains_path)?;
+                let tools_path = format!("{toolchains_path}{name}.");
+                let overrides = ToolOverrides {
+                    cargo: get_opt_string(&mut tools, "cargo", &tools_path)?,
+                    rustc: get_opt_string(&mut tools, "rustc", &tools_path)?,
+                };
+                Ok((name, overrides))
+            })
+            .collect()
+    }
+
     fn overrides_to_table(overrides: BTreeMap<String, String>) -> toml::value::Table {
         let mut result = toml::value::Table::new();
         for (k, v) in overrides {
@@ -223,3 +598,21 @@ impl Settings {
         result
     }
 }
+
+/// Reads a number of bytes, given either as an integer or as a string with
+/// a suffix such as `2M`, which `parse` understands.
+fn get_opt_bytes(
+    table: &mut toml::value::Table,
+    key: &str,
+    path: &str,
+    parse: fn(&str) -> Result<u64>,
+) -> Result<Option<u64>> {
+    match table.get(key) {
+        Some(toml::Value::Integer(_)) => {
+            Ok(get_opt_integer(table, key, path)?.and_then(|n| u64::try_from(n).ok()))
+        }
+        _ => get_opt_string(table, key, path)?
+            .map(|bytes| parse(&bytes))
+            .transpose(),
+    }
+}
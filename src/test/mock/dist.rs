@@ -7,7 +7,10 @@ use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use once_cell::sync::Lazy;
+use ring::rand::SystemRandom;
+use ring::signature::{self, RsaKeyPair};
 use sha2::{Digest, Sha256};
 use url::Url;
 
@@ -441,7 +444,10 @@ impl MockDistServer {
         let hash_path = self.path.join(format!("{manifest_name}.toml.sha256"));
         create_hash(&manifest_path, &hash_path);
 
-        // Also copy the manifest and hash into the archive folder
+        let sig_path = self.path.join(format!("{manifest_name}.toml.asc"));
+        create_signature(&manifest_path, &sig_path);
+
+        // Also copy the manifest, hash and signature into the archive folder
         let archive_manifest_name = format!("dist/{}/channel-rust-{}", channel.date, channel.name);
         let archive_manifest_path = self.path.join(format!("{archive_manifest_name}.toml"));
         hard_link(&manifest_path, archive_manifest_path).unwrap();
@@ -450,6 +456,9 @@ impl MockDistServer {
             .path
             .join(format!("{archive_manifest_name}.toml.sha256"));
         hard_link(hash_path, archive_hash_path).unwrap();
+
+        let archive_sig_path = self.path.join(format!("{archive_manifest_name}.toml.asc"));
+        hard_link(sig_path, archive_sig_path).unwrap();
     }
 }
 
@@ -512,6 +521,62 @@ pub fn create_hash(src: &Path, dst: &Path) -> String {
     hex
 }
 
+/// The key that mock manifests are signed with, whose public half is in
+/// `tests/mock/signing-key.pub.asc`.
+static SIGNING_KEY: Lazy<RsaKeyPair> = Lazy::new(|| {
+    RsaKeyPair::from_pkcs8(include_bytes!("../../../tests/mock/signing-key.pk8")).unwrap()
+});
+const SIGNING_KEY_ID: [u8; 8] = [0x40, 0xb3, 0x99, 0xc8, 0x94, 0xba, 0x58, 0x2c];
+/// When mock signatures claim to have been made, so that they don't change
+/// from one run to the next.
+const SIGNATURE_TIME: u32 = 1_791_960_000;
+
+/// Writes an armored OpenPGP signature of `src` to `dst`, as `gpg
+/// --detach-sign --armor` would.
+pub fn create_signature(src: &Path, dst: &Path) {
+    // A version 4 signature of a binary document, made by RSA over SHA-256,
+    // with the creation time as its one hashed subpacket
+    let mut body = vec![4, 0x00, 1, 8, 0, 6, 5, 2];
+    body.extend(SIGNATURE_TIME.to_be_bytes());
+
+    let mut message = fs::read(src).unwrap();
+    message.extend(&body);
+    message.extend([4, 0xff]);
+    message.extend((body.len() as u32).to_be_bytes());
+    let mut signature = vec![0; SIGNING_KEY.public().modulus_len()];
+    SIGNING_KEY
+        .sign(
+            &signature::RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            &message,
+            &mut signature,
+        )
+        .unwrap();
+
+    // The issuer, unhashed
+    body.extend([0, 10, 9, 16]);
+    body.extend(SIGNING_KEY_ID);
+    body.extend(&Sha256::digest(&message)[..2]);
+    let signature = &signature[signature.iter().take_while(|b| **b == 0).count()..];
+    let bits = signature.len() * 8 - signature[0].leading_zeros() as usize;
+    body.extend((bits as u16).to_be_bytes());
+    body.extend(signature);
+
+    // A new format signature packet, with a two-octet length
+    let len = body.len() - 192;
+    let mut packet = vec![0xc2, (len >> 8) as u8 + 192, len as u8];
+    packet.extend(body);
+
+    let encoded = STANDARD.encode(packet);
+    let mut armored = String::from("-----BEGIN PGP SIGNATURE-----\n\n");
+    for line in encoded.as_bytes().chunks(64) {
+        armored.push_str(std::str::from_utf8(line).unwrap());
+        armored.push('\n');
+    }
+    armored.push_str("-----END PGP SIGNATURE-----\n");
+    write_file(dst, &armored);
+}
+
 pub fn write_file(dst: &Path, contents: &str) {
     drop(fs::remove_file(dst));
     File::create(dst)
@@ -85,6 +85,20 @@ fn main() {
             let mut out = io::stderr();
             writeln!(out, "{}", std::env::var("PATH").unwrap()).unwrap();
         }
+        Some("--echo-env") => {
+            let mut out = io::stderr();
+            for name in args {
+                writeln!(out, "{}", std::env::var(name).unwrap()).unwrap();
+            }
+        }
+        Some("--exit") => {
+            let code = args.next().unwrap().to_str().unwrap().parse().unwrap();
+            std::process::exit(code);
+        }
+        Some("--sleep") => {
+            let secs = args.next().unwrap().to_str().unwrap().parse().unwrap();
+            std::thread::sleep(std::time::Duration::from_secs(secs));
+        }
         arg => panic!("bad mock proxy commandline: {:?}", arg),
     }
 }
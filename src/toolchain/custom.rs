@@ -1,9 +1,14 @@
 use std::{
     env::consts::EXE_SUFFIX,
+    fs,
     path::{Path, PathBuf},
 };
 
-use crate::{config::Cfg, install::InstallMethod, utils::utils};
+use crate::{
+    config::{Cfg, ComponentStatus},
+    install::InstallMethod,
+    utils::utils,
+};
 
 use super::{names::CustomToolchainName, toolchain::InstalledPath};
 
@@ -44,4 +49,54 @@ impl CustomToolchain {
     pub(crate) fn installed_paths(path: &Path) -> anyhow::Result<Vec<InstalledPath<'_>>> {
         Ok(vec![InstalledPath::Dir { path }])
     }
+
+    /// The components of the toolchain at `path`, going by the files it
+    /// contains, as there is no manifest to tell. Such components are all
+    /// installed, and none are available to add.
+    pub(crate) fn component_statuses(path: &Path) -> Vec<ComponentStatus> {
+        let status = |name: &str, target: Option<String>| ComponentStatus {
+            name: name.to_owned(),
+            target,
+            installed: true,
+            available: false,
+        };
+        let has_bin = |name: &str| path.join(format!("bin/{name}{EXE_SUFFIX}")).is_file();
+
+        let mut statuses = Vec::new();
+        for (name, bins) in PROBED_BINARIES {
+            if bins.iter().any(|bin| has_bin(bin)) {
+                statuses.push(status(name, None));
+            }
+        }
+        if path.join("lib/rustlib/src/rust").is_dir() {
+            statuses.push(status("rust-src", None));
+        }
+        if path.join("share/doc/rust/html").is_dir() {
+            statuses.push(status("rust-docs", None));
+        }
+
+        // A standard library for each target in the sysroot
+        let mut targets = fs::read_dir(path.join("lib/rustlib"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join("lib").is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect::<Vec<_>>();
+        targets.sort();
+        statuses.extend(targets.into_iter().map(|t| status("rust-std", Some(t))));
+
+        statuses.sort_by_key(ComponentStatus::full_name);
+        statuses
+    }
 }
+
+/// The components that are told apart by their binaries, with the binaries
+/// that any of which give the component away.
+const PROBED_BINARIES: &[(&str, &[&str])] = &[
+    ("cargo", &["cargo"]),
+    ("clippy", &["cargo-clippy", "clippy-driver"]),
+    ("rust-analyzer", &["rust-analyzer"]),
+    ("rustc", &["rustc"]),
+    ("rustfmt", &["rustfmt", "cargo-fmt"]),
+];
@@ -1,5 +1,6 @@
 use std::{
-    convert::Infallible, env::consts::EXE_SUFFIX, ffi::OsStr, fs, path::Path, process::Command,
+    cell::Cell, convert::Infallible, env::consts::EXE_SUFFIX, ffi::OsStr, fs, path::Path,
+    process::Command,
 };
 
 use anyhow::{anyhow, Context};
@@ -8,8 +9,9 @@ use crate::{
     component_for_bin,
     config::Cfg,
     dist::{
+        component::integrity,
         config::Config,
-        dist::{Profile, ToolchainDesc},
+        dist::{self, InstallPlan, Profile, ToolchainDesc},
         manifest::{Component, ComponentStatus, Manifest},
         manifestation::{Changes, Manifestation},
         prefix::InstallPrefix,
@@ -45,7 +47,13 @@ impl<'a> DistributableToolchain<'a> {
         &self.desc
     }
 
-    pub(crate) fn add_component(&self, mut component: Component) -> anyhow::Result<()> {
+    /// Install `component`, or with `reinstall`, remove it first if it is
+    /// already installed, to repair its files.
+    pub(crate) fn add_component(
+        &self,
+        mut component: Component,
+        reinstall: bool,
+    ) -> anyhow::Result<()> {
         // TODO: take multiple components?
         let manifestation = self.get_manifestation()?;
         let manifest = self.get_manifest()?;
@@ -94,15 +102,32 @@ impl<'a> DistributableToolchain<'a> {
             }
         }
 
-        let changes = Changes {
-            explicit_add_components: vec![component],
-            remove_components: vec![],
-        };
-
         let notify_handler =
             &|n: crate::dist::Notification<'_>| (self.cfg.notify_handler)(n.into());
         let download_cfg = self.cfg.download_cfg(&notify_handler);
 
+        // Along with whatever it cannot work without
+        let installed = manifestation.read_config()?.unwrap_or_default().components;
+        let mut explicit_add_components = vec![component.clone()];
+        let mut dependency_components = vec![];
+        for (dep, dependent) in manifest.dependencies(&[component.clone()], &self.desc.target)? {
+            if !installed.contains(&dep) {
+                notify_handler(crate::dist::Notification::AddingDependency(
+                    &dep.short_name(&manifest),
+                    &dependent.short_name(&manifest),
+                ));
+                explicit_add_components.push(dep.clone());
+                dependency_components.push(dep);
+            }
+        }
+
+        let changes = Changes {
+            reinstall_components: if reinstall { vec![component] } else { vec![] },
+            explicit_add_components,
+            remove_components: vec![],
+            dependency_components,
+        };
+
         manifestation.update(
             &manifest,
             changes,
@@ -115,6 +140,32 @@ impl<'a> DistributableToolchain<'a> {
         Ok(())
     }
 
+    /// What adding `components` would download, as the name of each one
+    /// with the size of its package if the server reports one.
+    pub(crate) fn plan_components(
+        &self,
+        components: Vec<Component>,
+    ) -> anyhow::Result<Vec<(String, Option<u64>)>> {
+        let manifestation = self.get_manifestation()?;
+        let manifest = self.get_manifest()?;
+        let notify_handler =
+            &|n: crate::dist::Notification<'_>| (self.cfg.notify_handler)(n.into());
+        let download_cfg = self.cfg.download_cfg(&notify_handler);
+        let changes = Changes {
+            explicit_add_components: components,
+            remove_components: vec![],
+            reinstall_components: vec![],
+            dependency_components: vec![],
+        };
+        manifestation.plan(
+            &manifest,
+            changes,
+            false,
+            &download_cfg,
+            &self.desc.manifest_name(),
+        )
+    }
+
     pub(crate) fn components(&self) -> anyhow::Result<Vec<ComponentStatus>> {
         let manifestation = self.get_manifestation()?;
         let config = manifestation.read_config()?.unwrap_or_default();
@@ -122,6 +173,32 @@ impl<'a> DistributableToolchain<'a> {
         manifest.query_components(self.desc(), &config)
     }
 
+    /// The components of this toolchain, including those added from a local
+    /// package, which come last.
+    pub(crate) fn component_statuses(&self) -> anyhow::Result<Vec<crate::config::ComponentStatus>> {
+        let manifestation = self.get_manifestation()?;
+        let manifest = self.get_manifest()?;
+        let mut statuses: Vec<_> = self
+            .components()?
+            .into_iter()
+            .map(|status| crate::config::ComponentStatus {
+                name: status.component.short_name(&manifest),
+                target: status.component.target.as_ref().map(ToString::to_string),
+                installed: status.installed,
+                available: status.available,
+            })
+            .collect();
+        statuses.extend(manifestation.local_components()?.into_iter().map(|name| {
+            crate::config::ComponentStatus {
+                name,
+                target: None,
+                installed: true,
+                available: false,
+            }
+        }));
+        Ok(statuses)
+    }
+
     /// Are all the components installed in this distribution
     pub(crate) fn components_exist(
         &self,
@@ -324,7 +401,7 @@ impl<'a> DistributableToolchain<'a> {
         desc: &'_ ToolchainDesc,
         components: &[&str],
         targets: &[&str],
-        profile: Profile,
+        profile: Option<Profile>,
         force: bool,
     ) -> anyhow::Result<(UpdateStatus, DistributableToolchain<'a>)> {
         let hash_path = cfg.get_hash_file(desc, true)?;
@@ -347,17 +424,74 @@ impl<'a> DistributableToolchain<'a> {
         Ok((status, Self::new(cfg, desc.clone())?))
     }
 
+    /// What installing `desc`, or updating it if it is installed already,
+    /// would download.
+    pub(crate) fn plan(
+        cfg: &'a Cfg,
+        desc: &'_ ToolchainDesc,
+        components: &[&str],
+        targets: &[&str],
+        profile: Option<Profile>,
+        force: bool,
+    ) -> anyhow::Result<InstallPlan> {
+        let name = desc.into();
+        let profile = if cfg.toolchain_installed(&name)? {
+            None
+        } else {
+            profile
+        };
+        let prefix = InstallPrefix::from(cfg.toolchain_path(&(&name).into()));
+        dist::plan_from_dist(
+            cfg.download_cfg(&|n| (cfg.notify_handler)(n.into())),
+            desc,
+            profile,
+            &prefix,
+            force,
+            components,
+            targets,
+        )
+    }
+
+    /// Downloads what installing `desc`, or updating it if it is installed
+    /// already, needs into the download cache without installing it, and
+    /// returns how many component packages that is.
+    pub(crate) fn download_only(
+        cfg: &'a Cfg,
+        desc: &'_ ToolchainDesc,
+        components: &[&str],
+        targets: &[&str],
+        profile: Option<Profile>,
+        force: bool,
+    ) -> anyhow::Result<usize> {
+        let name = desc.into();
+        let profile = if cfg.toolchain_installed(&name)? {
+            None
+        } else {
+            profile
+        };
+        let prefix = InstallPrefix::from(cfg.toolchain_path(&(&name).into()));
+        dist::download_from_dist(
+            cfg.download_cfg(&|n| (cfg.notify_handler)(n.into())),
+            desc,
+            profile,
+            &prefix,
+            force,
+            components,
+            targets,
+        )
+    }
+
     #[cfg_attr(feature = "otel", tracing::instrument(err, skip_all))]
     pub fn install_if_not_installed(
         cfg: &'a Cfg,
         desc: &'a ToolchainDesc,
     ) -> anyhow::Result<UpdateStatus> {
         (cfg.notify_handler)(Notification::LookingForToolchain(desc));
-        if Toolchain::exists(cfg, &desc.into())? {
+        if cfg.toolchain_installed(&desc.into())? {
             (cfg.notify_handler)(Notification::UsingExistingToolchain(desc));
             Ok(UpdateStatus::Unchanged)
         } else {
-            Ok(Self::install(cfg, desc, &[], &[], cfg.get_profile()?, false)?.0)
+            Ok(Self::install(cfg, desc, &[], &[], Some(cfg.get_profile()?), false)?.0)
         }
     }
 
@@ -402,7 +536,7 @@ impl<'a> DistributableToolchain<'a> {
         InstallMethod::Dist {
             cfg: self.cfg,
             desc: &self.desc,
-            profile,
+            profile: Some(profile),
             update_hash,
             dl_cfg: self
                 .cfg
@@ -452,7 +586,43 @@ impl<'a> DistributableToolchain<'a> {
         }
     }
 
-    pub(crate) fn remove_component(&self, mut component: Component) -> anyhow::Result<()> {
+    /// Install the component `name` from an unpacked rust-installer package
+    /// that is not on any dist server.
+    pub(crate) fn add_local_component(&self, name: &str, path: &Path) -> anyhow::Result<()> {
+        let notify_handler =
+            &|n: crate::dist::Notification<'_>| (self.cfg.notify_handler)(n.into());
+        self.get_manifestation()?.install_local_component(
+            name,
+            path,
+            &self.cfg.tmp_cx,
+            notify_handler,
+        )
+    }
+
+    /// Remove a component added with [`Self::add_local_component`], returning
+    /// `false` if `name` is not one.
+    pub(crate) fn remove_local_component(&self, name: &str) -> anyhow::Result<bool> {
+        let notify_handler =
+            &|n: crate::dist::Notification<'_>| (self.cfg.notify_handler)(n.into());
+        self.get_manifestation()?
+            .uninstall_local_component(name, &self.cfg.tmp_cx, notify_handler)
+    }
+
+    /// Check that the files of the installed components are as they were
+    /// when installed.
+    pub(crate) fn verify(&self) -> anyhow::Result<integrity::Report> {
+        self.get_manifestation()?.verify()
+    }
+
+    /// Removes `component`, warning if another installed component depends
+    /// on it. With `purge_unused_deps`, the
+    /// components that were only installed as dependencies and that nothing
+    /// left needs are removed with it.
+    pub(crate) fn remove_component(
+        &self,
+        mut component: Component,
+        purge_unused_deps: bool,
+    ) -> anyhow::Result<()> {
         // TODO: take multiple components?
         let manifestation = self.get_manifestation()?;
         let config = manifestation.read_config()?.unwrap_or_default();
@@ -492,13 +662,55 @@ impl<'a> DistributableToolchain<'a> {
             }
         }
 
+        let notify_handler =
+            &|n: crate::dist::Notification<'_>| (self.cfg.notify_handler)(n.into());
+
+        let dependents = manifest.dependents(&component, &config.components);
+        if !dependents.is_empty() {
+            let dependents: Vec<_> = dependents.iter().map(|c| c.short_name(&manifest)).collect();
+            notify_handler(crate::dist::Notification::RemovingNeededComponent(
+                &component.short_name(&manifest),
+                &dependents,
+            ));
+        }
+
+        let mut remove_components = vec![component];
+        if purge_unused_deps {
+            // Removing one can leave what it needed unneeded in turn
+            loop {
+                let remaining: Vec<_> = config
+                    .components
+                    .iter()
+                    .filter(|c| !remove_components.contains(c))
+                    .cloned()
+                    .collect();
+                let unused: Vec<_> = config
+                    .dependencies
+                    .iter()
+                    .filter(|d| {
+                        remaining.contains(d) && manifest.dependents(d, &remaining).is_empty()
+                    })
+                    .cloned()
+                    .collect();
+                if unused.is_empty() {
+                    break;
+                }
+                for dep in unused {
+                    notify_handler(crate::dist::Notification::RemovingUnusedDependency(
+                        &dep.short_name(&manifest),
+                    ));
+                    remove_components.push(dep);
+                }
+            }
+        }
+
         let changes = Changes {
             explicit_add_components: vec![],
-            remove_components: vec![component],
+            remove_components,
+            reinstall_components: vec![],
+            dependency_components: vec![],
         };
 
-        let notify_handler =
-            &|n: crate::dist::Notification<'_>| (self.cfg.notify_handler)(n.into());
         let download_cfg = self.cfg.download_cfg(&notify_handler);
 
         manifestation.update(
@@ -513,16 +725,30 @@ impl<'a> DistributableToolchain<'a> {
         Ok(())
     }
 
-    pub fn show_dist_version(&self) -> anyhow::Result<Option<String>> {
+    /// The version a newer manifest would update this toolchain to, if there
+    /// is one, and whether that comes from a manifest that `--download-only`
+    /// saved earlier, because the current one could not be fetched, and so
+    /// may be out of date.
+    pub fn show_dist_version(&self) -> anyhow::Result<(Option<String>, bool)> {
         let update_hash = self.cfg.get_hash_file(&self.desc, false)?;
-        let notify_handler =
-            &|n: crate::dist::Notification<'_>| (self.cfg.notify_handler)(n.into());
+        let stale = Cell::new(false);
+        let notify_handler = &|n: crate::dist::Notification<'_>| {
+            if let crate::dist::Notification::UsingSavedManifest(_) = n {
+                stale.set(true);
+            }
+            (self.cfg.notify_handler)(n.into())
+        };
         let download_cfg = self.cfg.download_cfg(&notify_handler);
 
-        match crate::dist::dist::dl_v2_manifest(download_cfg, Some(&update_hash), &self.desc)? {
-            Some((manifest, _)) => Ok(Some(manifest.get_rust_version()?.to_string())),
-            None => Ok(None),
-        }
+        let version = match crate::dist::dist::dl_v2_manifest(
+            download_cfg,
+            Some(&update_hash),
+            &self.desc,
+        )? {
+            Some((manifest, _)) => Some(manifest.get_rust_version()?.to_string()),
+            None => None,
+        };
+        Ok((version, stale.get()))
     }
 
     pub fn show_version(&self) -> anyhow::Result<Option<String>> {
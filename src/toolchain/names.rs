@@ -42,6 +42,7 @@
 //! fallible ones otherwise.
 
 use std::{
+    env::consts::EXE_SUFFIX,
     fmt::Display,
     ops::Deref,
     path::{Path, PathBuf},
@@ -63,7 +64,9 @@ pub enum InvalidName {
     PathToolchain(String),
     #[error("relative path toolchain '{0}'")]
     PathToolchainRelative(String),
-    #[error("invalid toolchain: the path '{0}' has no bin/ directory")]
+    #[error(
+        "invalid toolchain: the path '{0}' has no bin/rustc, so it is not a toolchain directory"
+    )]
     ToolchainPath(String),
     #[error("invalid toolchain name '{0}'")]
     ToolchainName(String),
@@ -276,7 +279,7 @@ impl Display for MaybeOfficialToolchainName {
 /// like setting overrides, or that depend on configuration, like calculating
 /// the toolchain directory.
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
-pub(crate) enum ToolchainName {
+pub enum ToolchainName {
     Custom(CustomToolchainName),
     Official(ToolchainDesc),
 }
@@ -387,7 +390,7 @@ pub(crate) fn resolvable_local_toolchainame_parser(
 /// variable, because that can take unresolved toolchain values that are not
 /// invalid for referring to an installed toolchain.
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
-pub(crate) enum LocalToolchainName {
+pub enum LocalToolchainName {
     Named(ToolchainName),
     Path(PathBasedToolchainName),
 }
@@ -429,7 +432,7 @@ impl Display for LocalToolchainName {
 /// A custom toolchain name, but not an official toolchain name
 /// (e.g. my-custom-toolchain)
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
-pub(crate) struct CustomToolchainName(String);
+pub struct CustomToolchainName(String);
 
 impl CustomToolchainName {
     pub fn str(&self) -> &str {
@@ -471,7 +474,7 @@ pub(crate) fn custom_toolchain_name_parser(
 /// code execution in a rust dir, so as a partial mitigation is limited to
 /// absolute paths.
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
-pub(crate) struct PathBasedToolchainName(PathBuf, String);
+pub struct PathBasedToolchainName(PathBuf, String);
 
 impl Display for PathBasedToolchainName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -490,11 +493,15 @@ impl TryFrom<&Path> for PathBasedToolchainName {
         {
             Err(InvalidName::PathToolchain(as_str))
         } else {
-            // Perform minimal validation; there should at least be a `bin/` that might
-            // contain things for us to run.
+            // Perform minimal validation; there should at least be a compiler
+            // in `bin/` for us to run.
             if !value.is_absolute() {
                 Err(InvalidName::PathToolchainRelative(as_str))
-            } else if !value.join("bin").is_dir() {
+            } else if !value
+                .join("bin")
+                .join(format!("rustc{EXE_SUFFIX}"))
+                .is_file()
+            {
                 Err(InvalidName::ToolchainPath(as_str))
             } else {
                 Ok(PathBasedToolchainName(value.into(), as_str))
@@ -694,4 +701,27 @@ mod tests {
 
         assert_eq!(expected, v);
     }
+
+    #[test]
+    fn test_channel_names_are_case_insensitive() {
+        let name = ResolvableToolchainName::try_from("Stable-X86_64-Unknown-Linux-Gnu").unwrap();
+        assert_eq!(name.to_string(), "stable-x86_64-unknown-linux-gnu");
+        assert!(CustomToolchainName::try_from("NIGHTLY").is_err());
+
+        // Custom names keep their case
+        let name = ResolvableToolchainName::try_from("MyToolchain").unwrap();
+        assert_eq!(name.to_string(), "MyToolchain");
+
+        // Even when they start with a channel name
+        for custom in ["Nightly-Patched", "Stable-Local"] {
+            let name = ToolchainName::try_from(custom).unwrap();
+            assert!(matches!(&name, ToolchainName::Custom(_)), "{name:?}");
+            assert_eq!(name.to_string(), custom);
+            let name = ResolvableToolchainName::try_from(custom).unwrap();
+            assert!(
+                matches!(&name, ResolvableToolchainName::Custom(_)),
+                "{name:?}"
+            );
+        }
+    }
 }
@@ -14,8 +14,9 @@ use fs_at::OpenOptions;
 use wait_timeout::ChildExt;
 
 use crate::{
-    config::Cfg,
+    config::{Cfg, ComponentStatus},
     currentprocess::{process, varsource::VarSource},
+    dist::dist::TargetTriple,
     env_var, install,
     notifications::Notification,
     utils::{raw::open_dir_following_links, utils},
@@ -84,6 +85,15 @@ impl<'a> Toolchain<'a> {
         &self.path
     }
 
+    /// The components of the toolchain, as its manifest lists them if it is
+    /// from a dist server, or else as its files give them away.
+    pub(crate) fn component_statuses(&self) -> anyhow::Result<Vec<ComponentStatus>> {
+        match DistributableToolchain::try_from(self) {
+            Ok(distributable) => distributable.component_statuses(),
+            Err(_) => Ok(CustomToolchain::component_statuses(&self.path)),
+        }
+    }
+
     /// The path to a binary within the toolchain, without regard for cargo-fallback logic
     pub fn binary_file(&self, name: &str) -> PathBuf {
         let mut path = self.path.clone();
@@ -92,6 +102,57 @@ impl<'a> Toolchain<'a> {
         path
     }
 
+    /// The path to a binary that the toolchain ships for `target` alone,
+    /// such as `rust-lld`, rather than in its `bin` directory.
+    pub(crate) fn target_binary_file(&self, target: &TargetTriple, name: &str) -> PathBuf {
+        let mut path = self.path.join("lib/rustlib");
+        path.push(&**target);
+        path.push("bin");
+        path.push(name.to_owned() + env::consts::EXE_SUFFIX);
+        path
+    }
+
+    /// The path to the binary `name` that would be used to build for
+    /// `target`, or for the host if none is given: the one shipped for
+    /// `target`, then the host's, such as `rust-lld`, then the one in `bin`.
+    /// If there is none the first of those is returned.
+    pub(crate) fn find_binary_file(
+        &self,
+        target: Option<&TargetTriple>,
+        name: &str,
+    ) -> anyhow::Result<PathBuf> {
+        let host = self.host_triple()?;
+        let mut candidates = Vec::new();
+        if let Some(target) = target.filter(|&target| *target != host) {
+            candidates.push(self.target_binary_file(target, name));
+        }
+        match target {
+            Some(_) => {
+                candidates.push(self.target_binary_file(&host, name));
+                candidates.push(self.binary_file(name));
+            }
+            // Without a target, what's in `bin` is what runs
+            None => {
+                candidates.push(self.binary_file(name));
+                candidates.push(self.target_binary_file(&host, name));
+            }
+        }
+        Ok(candidates
+            .iter()
+            .find(|path| utils::is_file(path))
+            .unwrap_or(&candidates[0])
+            .clone())
+    }
+
+    /// The target this toolchain runs on: the one in its name if it is from
+    /// a release channel, or else the default host.
+    pub(crate) fn host_triple(&self) -> anyhow::Result<TargetTriple> {
+        match &self.name {
+            LocalToolchainName::Named(ToolchainName::Official(desc)) => Ok(desc.target.clone()),
+            _ => self.cfg.get_default_host_triple(),
+        }
+    }
+
     /// Not intended to be public, but more code golf required to get it hidden.
     /// pub because of create_fallback_command
     pub fn set_env(&self, cmd: &mut Command) {
@@ -102,14 +163,18 @@ impl<'a> Toolchain<'a> {
         // versions of Cargo did. Rustup and Cargo should be in sync now (both
         // using the same `home` crate), but this is retained to ensure cargo
         // and rustup agree in older versions.
-        if let Ok(cargo_home) = utils::cargo_home() {
-            cmd.env("CARGO_HOME", &cargo_home);
+        if let Some(cargo_home) = &self.cfg.cargo_home {
+            cmd.env("CARGO_HOME", cargo_home);
         }
 
         env_var::inc("RUST_RECURSION_COUNT", cmd);
 
         cmd.env("RUSTUP_TOOLCHAIN", format!("{}", self.name));
-        cmd.env("RUSTUP_HOME", &self.cfg().rustup_dir);
+        // Under the XDG layout the settings are not in the rustup home, so
+        // commands run from here have to find both the same way rustup did
+        if utils::xdg_dirs().is_none() {
+            cmd.env("RUSTUP_HOME", &self.cfg().rustup_dir);
+        }
     }
 
     /// Apply the appropriate LD path for a command being run from a toolchain.
@@ -158,7 +223,7 @@ impl<'a> Toolchain<'a> {
         // proxy bins don't exist. We'll just be running whatever happens to
         // be on the PATH.
         let mut path_entries = vec![];
-        if let Ok(cargo_home) = utils::cargo_home() {
+        if let Some(cargo_home) = &self.cfg.cargo_home {
             path_entries.push(cargo_home.join("bin"));
         }
 
@@ -301,8 +366,8 @@ impl<'a> Toolchain<'a> {
         Ok(doc_dir)
     }
 
-    pub fn open_docs(&self, relative: &str) -> anyhow::Result<()> {
-        utils::open_browser(&self.doc_path(relative)?)
+    pub fn open_docs(&self, relative: &str, browser: Option<&str>) -> anyhow::Result<()> {
+        utils::open_browser(&self.doc_path(relative)?, browser)
     }
 
     /// Remove the toolchain from disk
@@ -316,7 +381,7 @@ impl<'a> Toolchain<'a> {
         };
         match Self::exists(cfg, &(&name).into())? {
             true => {
-                (cfg.notify_handler)(Notification::UninstallingToolchain(&name));
+                (cfg.notify_handler)(Notification::UninstallingToolchain(&name.to_string()));
                 let installed_paths = match &name {
                     ToolchainName::Custom(_) => CustomToolchain::installed_paths(&path),
                     ToolchainName::Official(desc) => {
@@ -337,7 +402,7 @@ impl<'a> Toolchain<'a> {
             false => {
                 // Might be a dangling symlink
                 if path.is_symlink() {
-                    (cfg.notify_handler)(Notification::UninstallingToolchain(&name));
+                    (cfg.notify_handler)(Notification::UninstallingToolchain(&name.to_string()));
                     fs::remove_dir_all(&path)?;
                 } else {
                     info!("no toolchain installed for '{name}'");
@@ -346,7 +411,7 @@ impl<'a> Toolchain<'a> {
         }
 
         if !path.is_symlink() && !path.exists() {
-            (cfg.notify_handler)(Notification::UninstalledToolchain(&name));
+            (cfg.notify_handler)(Notification::UninstalledToolchain(&name.to_string()));
         }
         Ok(())
     }
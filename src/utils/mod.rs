@@ -6,5 +6,6 @@ pub(crate) mod units;
 #[allow(clippy::module_inception)]
 pub mod utils;
 
-pub(crate) use crate::utils::notifications::Notification;
+pub use crate::utils::notifications::Notification;
+pub use crate::utils::units::Unit;
 pub(crate) mod notify;
@@ -15,8 +15,8 @@ pub enum Notification<'a> {
     DownloadingFile(&'a Url, &'a Path),
     /// Received the Content-Length of the to-be downloaded data.
     DownloadContentLengthReceived(u64),
-    /// Received some data.
-    DownloadDataReceived(&'a [u8]),
+    /// Received some data, of this many bytes.
+    DownloadDataReceived(usize),
     /// Download has finished.
     DownloadFinished,
     /// The things we're tracking that are not counted in bytes.
@@ -93,7 +93,7 @@ impl<'a> Display for Notification<'a> {
             ),
             DownloadingFile(url, _) => write!(f, "downloading file from: '{url}'"),
             DownloadContentLengthReceived(len) => write!(f, "download size is: '{len}'"),
-            DownloadDataReceived(data) => write!(f, "received some data of size {}", data.len()),
+            DownloadDataReceived(len) => write!(f, "received some data of size {len}"),
             DownloadPushUnit(_) => Ok(()),
             DownloadPopUnit => Ok(()),
             DownloadFinished => write!(f, "download finished"),
@@ -30,6 +30,11 @@ pub fn is_file<P: AsRef<Path>>(path: P) -> bool {
     fs::metadata(path).ok().as_ref().map(fs::Metadata::is_file) == Some(true)
 }
 
+/// Whether `path` is a symlink or, on Windows, a directory junction.
+pub(crate) fn is_link<P: AsRef<Path>>(path: P) -> bool {
+    fs::read_link(path).is_ok()
+}
+
 #[cfg(windows)]
 pub fn open_dir_following_links(p: &Path) -> std::io::Result<File> {
     use std::fs::OpenOptions;
@@ -87,6 +92,48 @@ pub fn write_file(path: &Path, contents: &str) -> io::Result<()> {
     Ok(())
 }
 
+/// Writes `contents` to a temporary file next to `path` and renames it into
+/// place, so that `path` holds either the old contents or all of the new
+/// ones, even if rustup is killed or the machine loses power meanwhile.
+///
+/// If `path` is a symlink, the file it leads to is replaced instead, and
+/// the new file gets the permissions of the one it replaces.
+pub(crate) fn write_file_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let path = match fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_owned(),
+        Err(e) => return Err(e),
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".rustup-tmp");
+    // A new file gets the same permissions that `write_file` would give it,
+    // rather than the private ones of a temporary file
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    let mut tmp = builder.tempfile_in(dir)?;
+    match fs::metadata(&path) {
+        Ok(metadata) => tmp.as_file().set_permissions(metadata.permissions())?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    // The contents are synced to disk before the rename, and the temporary
+    // file is removed if anything fails before it is renamed
+    tmp.write_all(contents.as_bytes())?;
+    tmp.as_file().sync_data()?;
+    tmp.persist(&path).map_err(|e| e.error)?;
+
+    // And so is the rename, which on Unix lives in the directory
+    #[cfg(unix)]
+    fs::File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
 pub(crate) fn filter_file<F: FnMut(&str) -> bool>(
     src: &Path,
     dest: &Path,
@@ -140,6 +187,22 @@ pub fn symlink_dir(src: &Path, dest: &Path) -> io::Result<()> {
         std::os::unix::fs::symlink(src, dest)
     }
 
+    // An existing link is replaced by renaming the new one over it, so that
+    // anything resolving `dest` meanwhile finds either the old directory or
+    // the new one. Windows can't rename over a junction.
+    #[cfg(not(windows))]
+    if fs::symlink_metadata(dest).map_or(false, |m| m.file_type().is_symlink()) {
+        let mut tmp = dest.as_os_str().to_owned();
+        tmp.push(".rustup-tmp");
+        let tmp = Path::new(&tmp);
+        let _ = fs::remove_file(tmp);
+        symlink_dir_inner(src, tmp)?;
+        return fs::rename(tmp, dest).map_err(|e| {
+            let _ = fs::remove_file(tmp);
+            e
+        });
+    }
+
     let _ = remove_dir(dest);
     symlink_dir_inner(src, dest)
 }
@@ -35,6 +35,22 @@ pub(crate) fn get_opt_string(
     }
 }
 
+pub(crate) fn get_opt_integer(
+    table: &mut toml::value::Table,
+    key: &str,
+    path: &str,
+) -> Result<Option<i64>> {
+    if let Ok(v) = get_value(table, key, path) {
+        if let toml::Value::Integer(i) = v {
+            Ok(Some(i))
+        } else {
+            Err(ExpectedType("integer", path.to_owned() + key).into())
+        }
+    } else {
+        Ok(None)
+    }
+}
+
 pub(crate) fn get_bool(table: &mut toml::value::Table, key: &str, path: &str) -> Result<bool> {
     get_value(table, key, path).and_then(|v| {
         if let toml::Value::Boolean(b) = v {
@@ -45,6 +61,22 @@ pub(crate) fn get_bool(table: &mut toml::value::Table, key: &str, path: &str) ->
     })
 }
 
+pub(crate) fn get_opt_bool(
+    table: &mut toml::value::Table,
+    key: &str,
+    path: &str,
+) -> Result<Option<bool>> {
+    if let Ok(v) = get_value(table, key, path) {
+        if let toml::Value::Boolean(b) = v {
+            Ok(Some(b))
+        } else {
+            Err(ExpectedType("bool", path.to_owned() + key).into())
+        }
+    } else {
+        Ok(None)
+    }
+}
+
 pub(crate) fn get_table(
     table: &mut toml::value::Table,
     key: &str,
@@ -76,3 +108,17 @@ pub(crate) fn get_array(
         Ok(toml::value::Array::new())
     }
 }
+
+pub(crate) fn get_string_array(
+    table: &mut toml::value::Table,
+    key: &str,
+    path: &str,
+) -> Result<Vec<String>> {
+    get_array(table, key, path)?
+        .into_iter()
+        .map(|v| match v {
+            toml::Value::String(s) => Ok(s),
+            _ => Err(ExpectedType("array of strings", path.to_owned() + key).into()),
+        })
+        .collect()
+}
@@ -2,15 +2,16 @@ use std::env;
 use std::fs::{self, File};
 use std::io::{self, BufReader, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::{anyhow, bail, Context, Result};
 use home::env as home;
 use retry::delay::{jitter, Fibonacci};
 use retry::{retry, OperationResult};
-use sha2::Sha256;
+use sha2::digest::DynDigest;
 use url::Url;
 
-use crate::currentprocess::{cwdsource::CurrentDirSource, varsource::VarSource};
+use crate::currentprocess::{cwdsource::CurrentDirSource, varsource::VarSource, Process};
 use crate::errors::*;
 use crate::utils::notifications::Notification;
 use crate::utils::raw;
@@ -18,7 +19,7 @@ use crate::{home_process, process};
 
 #[cfg(not(windows))]
 pub(crate) use crate::utils::utils::raw::find_cmd;
-pub(crate) use crate::utils::utils::raw::{if_not_empty, is_directory};
+pub(crate) use crate::utils::utils::raw::{if_not_empty, is_directory, is_link};
 
 pub use crate::utils::utils::raw::{is_file, path_exists};
 
@@ -55,6 +56,13 @@ pub fn write_file(name: &'static str, path: &Path, contents: &str) -> Result<()>
     })
 }
 
+pub(crate) fn write_file_atomic(name: &'static str, path: &Path, contents: &str) -> Result<()> {
+    raw::write_file_atomic(path, contents).with_context(|| RustupError::WritingFile {
+        name,
+        path: PathBuf::from(path),
+    })
+}
+
 pub(crate) fn append_file(name: &'static str, path: &Path, line: &str) -> Result<()> {
     raw::append_file(path, line).with_context(|| RustupError::WritingFile {
         name,
@@ -134,31 +142,48 @@ where
 pub fn download_file(
     url: &Url,
     path: &Path,
-    hasher: Option<&mut Sha256>,
+    hasher: Option<&mut dyn DynDigest>,
+    ca_bundle: Option<&Path>,
     notify_handler: &dyn Fn(Notification<'_>),
 ) -> Result<()> {
-    download_file_with_resume(url, path, hasher, false, &notify_handler)
+    let options = download::Options {
+        ca_bundle,
+        ..download::Options::default()
+    };
+    download_file_with_resume(url, path, hasher, false, None, options, &notify_handler)
 }
 
 pub(crate) fn download_file_with_resume(
     url: &Url,
     path: &Path,
-    hasher: Option<&mut Sha256>,
+    hasher: Option<&mut dyn DynDigest>,
     resume_from_partial: bool,
+    cancel: Option<&AtomicBool>,
+    options: download::Options<'_>,
     notify_handler: &dyn Fn(Notification<'_>),
 ) -> Result<()> {
     use download::DownloadError as DEK;
-    match download_file_(url, path, hasher, resume_from_partial, notify_handler) {
+    match download_file_(
+        url,
+        path,
+        hasher,
+        resume_from_partial,
+        cancel,
+        options,
+        notify_handler,
+    ) {
         Ok(_) => Ok(()),
         Err(e) => {
-            if e.downcast_ref::<std::io::Error>().is_some() {
+            if e.downcast_ref::<std::io::Error>().is_some() && !download::is_transient(&e) {
                 return Err(e);
             }
             let is_client_error = match e.downcast_ref::<DEK>() {
                 // Specifically treat the bad partial range error as not our
                 // fault in case it was something odd which happened.
                 Some(DEK::HttpStatus(416)) => false,
-                Some(DEK::HttpStatus(400..=499)) | Some(DEK::FileNotFound) => true,
+                Some(DEK::HttpStatus(400..=499))
+                | Some(DEK::FileNotFound)
+                | Some(DEK::LocalFileNotFound(_)) => true,
                 _ => false,
             };
             Err(e).with_context(|| {
@@ -181,13 +206,14 @@ pub(crate) fn download_file_with_resume(
 fn download_file_(
     url: &Url,
     path: &Path,
-    hasher: Option<&mut Sha256>,
+    hasher: Option<&mut dyn DynDigest>,
     resume_from_partial: bool,
+    cancel: Option<&AtomicBool>,
+    options: download::Options<'_>,
     notify_handler: &dyn Fn(Notification<'_>),
 ) -> Result<()> {
     use download::download_to_path_with_backend;
-    use download::{Backend, Event, TlsBackend};
-    use sha2::Digest;
+    use download::Event;
     use std::cell::RefCell;
 
     notify_handler(Notification::DownloadingFile(url, path));
@@ -196,34 +222,111 @@ fn download_file_(
 
     // This callback will write the download to disk and optionally
     // hash the contents, then forward the notification up the stack
-    let callback: &dyn Fn(Event<'_>) -> download::Result<()> = &|msg| {
-        if let Event::DownloadDataReceived(data) = msg {
-            if let Some(h) = hasher.borrow_mut().as_mut() {
-                h.update(data);
+    let callback: &dyn Fn(Event<'_>) -> download::Result<()> =
+        &|msg| handle_download_event(msg, &hasher, cancel, notify_handler);
+
+    // Download the file
+    let (backend, notification) = download_backend();
+    notify_handler(notification);
+    let res = download_to_path_with_backend(
+        backend,
+        url,
+        path,
+        resume_from_partial,
+        Some(callback),
+        options,
+    );
+
+    notify_handler(Notification::DownloadFinished);
+
+    res
+}
+
+/// Downloads `url` without saving it, passing the data to `data_handler` as
+/// it arrives.
+pub(crate) fn download_stream(
+    url: &Url,
+    hasher: &mut dyn DynDigest,
+    cancel: Option<&AtomicBool>,
+    options: download::Options<'_>,
+    data_handler: &dyn Fn(&[u8]),
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> Result<()> {
+    use std::cell::RefCell;
+
+    let hasher = RefCell::new(Some(hasher));
+    let (backend, notification) = download_backend();
+    notify_handler(notification);
+    let res = download::download_to_callback_with_backend(
+        backend,
+        url,
+        &|msg| {
+            handle_download_event(msg, &hasher, cancel, notify_handler)?;
+            if let download::Event::DownloadDataReceived(data) = msg {
+                data_handler(data);
             }
+            Ok(())
+        },
+        options,
+    );
+    notify_handler(Notification::DownloadFinished);
+
+    res.with_context(|| RustupError::StreamingFile { url: url.clone() })
+}
+
+/// Hashes the data of a download, if there is a hasher, and passes the
+/// event on as a notification. Fails once `cancel` is set, which stops the
+/// download.
+fn handle_download_event(
+    msg: download::Event<'_>,
+    hasher: &std::cell::RefCell<Option<&mut dyn DynDigest>>,
+    cancel: Option<&AtomicBool>,
+    notify_handler: &dyn Fn(Notification<'_>),
+) -> download::Result<()> {
+    use download::Event;
+
+    if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+        return Err(download::DownloadError::Cancelled.into());
+    }
+    if let Event::DownloadDataReceived(data) = msg {
+        if let Some(h) = hasher.borrow_mut().as_mut() {
+            h.update(data);
         }
+    }
 
-        match msg {
-            Event::DownloadContentLengthReceived(len) => {
-                notify_handler(Notification::DownloadContentLengthReceived(len));
-            }
-            Event::DownloadDataReceived(data) => {
-                notify_handler(Notification::DownloadDataReceived(data));
-            }
-            Event::ResumingPartialDownload => {
-                notify_handler(Notification::ResumingPartialDownload);
-            }
+    match msg {
+        Event::DownloadContentLengthReceived(len) => {
+            notify_handler(Notification::DownloadContentLengthReceived(len));
+        }
+        Event::DownloadDataReceived(data) => {
+            notify_handler(Notification::DownloadDataReceived(data.len()));
+        }
+        Event::ResumingPartialDownload => {
+            notify_handler(Notification::ResumingPartialDownload);
         }
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// The length of the file at `url`, or `None` if the server does not say,
+/// without downloading it.
+pub(crate) fn download_content_length(url: &Url, ca_bundle: Option<&Path>) -> Result<Option<u64>> {
+    let options = download::Options {
+        ca_bundle,
+        ..download::Options::default()
     };
+    download::content_length_with_backend(download_backend().0, url, options)
+        .with_context(|| format!("could not get the size of '{url}'"))
+}
 
-    // Download the file
+fn download_backend() -> (download::Backend, Notification<'static>) {
+    use download::{Backend, TlsBackend};
 
     // Keep the curl env var around for a bit
     let use_curl_backend = process().var_os("RUSTUP_USE_CURL").is_some();
     let use_rustls = process().var_os("RUSTUP_USE_RUSTLS").is_some();
-    let (backend, notification) = if use_curl_backend {
+    if use_curl_backend {
         (Backend::Curl, Notification::UsingCurl)
     } else {
         let tls_backend = if use_rustls {
@@ -239,20 +342,36 @@ fn download_file_(
             }
         };
         (Backend::Reqwest(tls_backend), Notification::UsingReqwest)
-    };
-    notify_handler(notification);
-    let res =
-        download_to_path_with_backend(backend, url, path, resume_from_partial, Some(callback));
-
-    notify_handler(Notification::DownloadFinished);
-
-    res
+    }
 }
 
 pub(crate) fn parse_url(url: &str) -> Result<Url> {
     Url::parse(url).with_context(|| format!("failed to parse url: {url}"))
 }
 
+/// Parses a rate in bytes per second, such as `500K` or `2M`. The suffixes
+/// are multiples of 1024, and may be in either case.
+pub(crate) fn parse_download_rate(rate: &str) -> Result<u64> {
+    Ok(parse_bytes(rate).ok_or_else(|| RustupError::InvalidDownloadRate(rate.to_owned()))?)
+}
+
+/// Parses a size in bytes, with the same suffixes as
+/// [`parse_download_rate`].
+pub(crate) fn parse_download_cache_size(size: &str) -> Result<u64> {
+    Ok(parse_bytes(size).ok_or_else(|| RustupError::InvalidDownloadCacheSize(size.to_owned()))?)
+}
+
+fn parse_bytes(bytes: &str) -> Option<u64> {
+    let trimmed = bytes.trim();
+    let (number, multiplier) = match trimmed.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&trimmed[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&trimmed[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&trimmed[..i], 1 << 30),
+        _ => (trimmed, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
 pub(crate) fn assert_is_file(path: &Path) -> Result<()> {
     if !is_file(path) {
         Err(anyhow!(format!("not a file: '{}'", path.display())))
@@ -423,8 +542,48 @@ pub(crate) fn read_dir(name: &'static str, path: &Path) -> Result<fs::ReadDir> {
     })
 }
 
-pub(crate) fn open_browser(path: &Path) -> Result<()> {
-    opener::open_browser(path).context("couldn't open browser")
+/// Opens `path` with `browser` if one is given, else with the first of the
+/// space-separated commands in `BROWSER` that can be started, and otherwise
+/// leaves it to `opener`, which also knows about `BROWSER` and WSL.
+///
+/// `browser` is split on whitespace into a program and its arguments, with no
+/// support for quoting. The browser is left running in the background.
+pub(crate) fn open_browser(path: &Path, browser: Option<&str>) -> Result<()> {
+    if let Some(browser) = browser {
+        let mut words = browser.split_whitespace();
+        let program = words.next().unwrap_or_default();
+        return spawn_browser(program, words, path)
+            .with_context(|| format!("couldn't open browser with '{browser}'"));
+    }
+
+    let browsers: Vec<String> = process()
+        .var("BROWSER")
+        .map(|var| var.split_whitespace().map(str::to_owned).collect())
+        .unwrap_or_default();
+    if browsers.len() <= 1 {
+        return opener::open_browser(path).context("couldn't open browser");
+    }
+    for browser in &browsers {
+        if spawn_browser(browser, std::iter::empty(), path).is_ok() {
+            return Ok(());
+        }
+    }
+    let tried: Vec<_> = browsers.iter().map(|b| format!("'{b}'")).collect();
+    Err(anyhow!("couldn't open browser with {}", tried.join(", ")))
+}
+
+fn spawn_browser<'a>(
+    program: &str,
+    args: impl Iterator<Item = &'a str>,
+    path: &Path,
+) -> io::Result<()> {
+    std::process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .map(|_| ())
 }
 
 #[cfg(not(windows))]
@@ -447,6 +606,22 @@ pub fn file_size(path: &Path) -> Result<u64> {
         .len())
 }
 
+/// The total length of the files beneath `path`. Links inside it are not
+/// followed, so that nothing is counted twice.
+pub(crate) fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
 pub(crate) fn make_executable(path: &Path) -> Result<()> {
     #[allow(clippy::unnecessary_wraps)]
     #[cfg(windows)]
@@ -502,7 +677,7 @@ pub(crate) fn cargo_home() -> Result<PathBuf> {
     home::cargo_home_with_env(&home_process()).context("failed to determine cargo home")
 }
 
-// Creates a ~/.rustup folder
+// Creates a ~/.rustup folder, or its XDG counterpart
 pub(crate) fn create_rustup_home() -> Result<()> {
     // If RUSTUP_HOME is set then don't make any assumptions about where it's
     // ok to put ~/.rustup
@@ -510,12 +685,68 @@ pub(crate) fn create_rustup_home() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(dirs) = xdg_dirs() {
+        fs::create_dir_all(&dirs.data)
+            .with_context(|| format!("unable to create '{}'", dirs.data.display()))?;
+        return Ok(());
+    }
+
     let home = rustup_home_in_user_dir()?;
     fs::create_dir_all(home).context("unable to create ~/.rustup")?;
 
     Ok(())
 }
 
+/// Where rustup keeps its files under the XDG Base Directory layout.
+#[derive(Debug)]
+pub(crate) struct XdgDirs {
+    /// Toolchains, downloads and the like: `$XDG_DATA_HOME/rustup`, or an
+    /// existing `~/.rustup` with toolchains in it while that does not exist.
+    pub data: PathBuf,
+    /// The settings file: `$XDG_CONFIG_HOME/rustup`.
+    pub config: PathBuf,
+}
+
+/// The XDG directories rustup uses, if it follows that layout: on Unix, when
+/// `RUSTUP_HOME` is not set, and either `RUSTUP_USE_XDG` is or the XDG settings
+/// file already exists.
+pub(crate) fn xdg_dirs() -> Option<XdgDirs> {
+    xdg_dirs_of(&process())
+}
+
+/// As [`xdg_dirs`], but going by the environment of `process`.
+pub(crate) fn xdg_dirs_of(process: &Process) -> Option<XdgDirs> {
+    if cfg!(windows) || process.var_os("RUSTUP_HOME").is_some_and(|h| !h.is_empty()) {
+        return None;
+    }
+    let home = home::home_dir_with_env(process)?;
+    // Relative paths are invalid in these variables, and must be ignored
+    let base_dir = |var: &str, default: &str| {
+        process
+            .var_os(var)
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .unwrap_or_else(|| home.join(default))
+    };
+
+    let config = base_dir("XDG_CONFIG_HOME", ".config").join("rustup");
+    let opted_in = process
+        .var_os("RUSTUP_USE_XDG")
+        .is_some_and(|v| !v.is_empty() && v != "0");
+    if !opted_in && !is_file(config.join("settings.toml")) {
+        return None;
+    }
+
+    let data = base_dir("XDG_DATA_HOME", ".local/share").join("rustup");
+    let legacy = home.join(".rustup");
+    let data = if !data.exists() && is_directory(legacy.join("toolchains")) {
+        legacy
+    } else {
+        data
+    };
+    Some(XdgDirs { data, config })
+}
+
 fn dot_dir(name: &str) -> Option<PathBuf> {
     home_dir().map(|p| p.join(name))
 }
@@ -526,6 +757,9 @@ fn rustup_home_in_user_dir() -> Result<PathBuf> {
 }
 
 pub(crate) fn rustup_home() -> Result<PathBuf> {
+    if let Some(dirs) = xdg_dirs() {
+        return Ok(dirs.data);
+    }
     home::rustup_home_with_env(&home_process()).context("failed to determine rustup home dir")
 }
 
@@ -664,7 +898,7 @@ impl<'a> io::Read for FileReaderWithProgress<'a> {
             Ok(nbytes) => {
                 self.nbytes += nbytes as u64;
                 if nbytes != 0 {
-                    (self.notify_handler)(Notification::DownloadDataReceived(&buf[0..nbytes]));
+                    (self.notify_handler)(Notification::DownloadDataReceived(nbytes));
                 }
                 if (nbytes == 0) || (self.flen == self.nbytes) {
                     (self.notify_handler)(Notification::DownloadFinished);
@@ -715,6 +949,20 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn parses_download_rates() {
+        assert_eq!(parse_download_rate("0").unwrap(), 0);
+        assert_eq!(parse_download_rate("1500").unwrap(), 1500);
+        assert_eq!(parse_download_rate("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_download_rate("2M").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_download_rate("1G").unwrap(), 1024 * 1024 * 1024);
+        for invalid in ["", "M", "2.5M", "-1", "2MB", "99999999999G"] {
+            assert!(parse_download_rate(invalid).is_err(), "{invalid}");
+        }
+        assert_eq!(parse_download_cache_size("4G").unwrap(), 4 << 30);
+        assert!(parse_download_cache_size("4GB").is_err());
+    }
+
     #[test]
     fn test_remove_file() {
         let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
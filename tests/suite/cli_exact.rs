@@ -177,6 +177,25 @@ nightly-{0} - Up to date : 1.3.0 (hash-nightly-2)
     })
 }
 
+#[test]
+fn check_updates_older_channel_is_not_an_update() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "toolchain", "add", "stable", "nightly"]);
+        });
+        config.with_scenario(Scenario::ArchivesV2_2015_01_01, &|config| {
+            config.expect_stdout_ok(
+                &["rustup", "check"],
+                for_host!(
+                    r"stable-{0} - Up to date : 1.1.0 (hash-stable-1.1.0)
+nightly-{0} - Up to date : 1.3.0 (hash-nightly-2)
+"
+                ),
+            );
+        })
+    })
+}
+
 #[test]
 fn check_updates_some() {
     test(&|config| {
@@ -197,6 +216,50 @@ nightly-{0} - Update available : 1.2.0 (hash-nightly-1) -> 1.3.0 (hash-nightly-2
     })
 }
 
+#[test]
+fn check_updates_json() {
+    test(&|config| {
+        config.with_scenario(Scenario::ArchivesV2_2015_01_01, &|config| {
+            config.expect_ok(&["rustup", "toolchain", "add", "stable", "nightly"]);
+        });
+        with_update_server(config, "2.0.0", &|config| {
+            config.with_scenario(Scenario::SimpleV2, &|config| {
+                config.expect_ok(&["rustup", "update", "nightly"]);
+                let out = config.run("rustup", ["check", "--json"], &[]);
+                assert!(out.ok, "{}", out.stderr);
+                let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+                assert_eq!(
+                    json,
+                    serde_json::json!({
+                        "schema": 1,
+                        "toolchains": [
+                            {
+                                "name": for_host!("stable-{0}"),
+                                "current_version": "1.0.0 (hash-stable-1.0.0)",
+                                "latest_version": "1.1.0 (hash-stable-1.1.0)",
+                                "update_available": true,
+                                "stale": false,
+                            },
+                            {
+                                "name": for_host!("nightly-{0}"),
+                                "current_version": "1.3.0 (hash-nightly-2)",
+                                "latest_version": "1.3.0 (hash-nightly-2)",
+                                "update_available": false,
+                                "stale": false,
+                            },
+                        ],
+                        "rustup": {
+                            "current_version": env!("CARGO_PKG_VERSION"),
+                            "latest_version": "2.0.0",
+                            "update_available": true,
+                        },
+                    })
+                );
+            })
+        })
+    })
+}
+
 #[test]
 fn check_updates_self() {
     let test_version = "2.0.0";
@@ -450,6 +513,14 @@ fn remove_override_nonexistent() {
                 // FIXME TempDir seems to succumb to difficulties removing dirs on windows
                 let _ = rustup::utils::raw::remove_dir(&path);
                 assert!(!path.exists());
+                config.expect_ok_ex(
+                    &["rustup", "override", keyword, "--nonexistent", "--dry-run"],
+                    r"",
+                    &format!(
+                        "info: would remove override toolchain for '{}'\n",
+                        path.display()
+                    ),
+                );
                 config.expect_ok_ex(
                     &["rustup", "override", keyword, "--nonexistent"],
                     r"",
@@ -528,6 +599,60 @@ fn list_overrides_with_nonexistent() {
     });
 }
 
+#[test]
+fn list_overrides_json() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let trip = this_host_triple();
+            config.expect_ok_ex(&["rustup", "override", "list", "--json"], "[]\n", r"");
+
+            let cwd = std::fs::canonicalize(config.current_dir()).unwrap();
+            config.expect_ok(&["rustup", "override", "add", "nightly"]);
+
+            let nonexistent_path = {
+                let dir = tempfile::Builder::new()
+                    .prefix("rustup-test")
+                    .tempdir()
+                    .unwrap();
+                config.change_dir(dir.path(), &|config| {
+                    config.expect_ok(&["rustup", "override", "add", "stable"]);
+                });
+                std::fs::canonicalize(dir.path()).unwrap()
+            };
+            let _ = rustup::utils::raw::remove_dir(&nonexistent_path);
+            assert!(!nonexistent_path.exists());
+
+            let display = |path: &std::path::Path| {
+                let mut path = format!("{}", path.display());
+                if cfg!(windows) {
+                    path.drain(..4);
+                }
+                path
+            };
+            let entry = |path: &std::path::Path, toolchain: &str, exists: bool| {
+                format!(
+                    r#"{{"path":{},"toolchain":"{toolchain}-{trip}","exists":{exists}}}"#,
+                    serde_json::to_string(&display(path)).unwrap()
+                )
+            };
+            let mut expected = vec![
+                (display(&cwd), entry(&cwd, "nightly", true)),
+                (
+                    display(&nonexistent_path),
+                    entry(&nonexistent_path, "stable", false),
+                ),
+            ];
+            expected.sort();
+            let expected: Vec<_> = expected.into_iter().map(|(_, e)| e).collect();
+            config.expect_ok_ex(
+                &["rustup", "override", "list", "--json"],
+                &format!("[{}]\n", expected.join(",")),
+                r"",
+            );
+        })
+    });
+}
+
 #[test]
 fn update_no_manifest() {
     test(&|config| {
@@ -278,6 +278,95 @@ fn rustup_run_searches_path() {
     });
 }
 
+#[test]
+fn rustup_run_with_env() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stderr_ok(
+            &[
+                "rustup",
+                "run",
+                "--env",
+                "RUSTFLAGS=-C opt-level=3",
+                "--env",
+                "EMPTY=",
+                "nightly",
+                "rustc",
+                "--echo-env",
+                "RUSTFLAGS",
+                "EMPTY",
+            ],
+            "-C opt-level=3\n\n",
+        );
+        config.expect_err(
+            &["rustup", "run", "--env", "RUSTFLAGS", "nightly", "rustc"],
+            "invalid environment variable 'RUSTFLAGS': expected KEY=VALUE",
+        );
+        config.expect_err(
+            &["rustup", "run", "--env", "PATH=/bin", "nightly", "rustc"],
+            "environment variable 'PATH' is set by rustup and cannot be overridden",
+        );
+        config.expect_err(
+            &[
+                "rustup",
+                "run",
+                "--env",
+                "RUSTUP_TOOLCHAIN=stable",
+                "nightly",
+                "rustc",
+            ],
+            "environment variable 'RUSTUP_TOOLCHAIN' is set by rustup",
+        );
+    });
+}
+
+#[test]
+fn rustup_run_quiet() {
+    setup(&|config| {
+        // Installing the toolchain is the kind of chatter to hide
+        let run = ["run", "--install", "nightly", "rustc", "--version"];
+        let out = config.run("rustup", ["--quiet"].iter().chain(&run), &[]);
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.contains("hash-nightly-2"));
+        assert!(out.stderr.contains("info: "), "{}", out.stderr);
+
+        config.expect_ok(&["rustup", "toolchain", "uninstall", "nightly"]);
+        let out = config.run(
+            "rustup",
+            [
+                "run",
+                "--quiet",
+                "--install",
+                "nightly",
+                "rustc",
+                "--version",
+            ],
+            &[],
+        );
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.contains("hash-nightly-2"));
+        assert_eq!(out.stderr, "");
+
+        config.expect_ok(&["rustup", "toolchain", "uninstall", "nightly"]);
+        let out = config.run("rustup", run, &[("RUSTUP_QUIET", "1")]);
+        assert!(out.ok, "{}", out.stderr);
+        assert_eq!(out.stderr, "");
+
+        let mut cmd = clitools::cmd(
+            config,
+            "rustup",
+            ["run", "--quiet", "nightly", "rustc", "--exit", "3"],
+        );
+        clitools::env(config, &mut cmd);
+        assert_eq!(cmd.status().unwrap().code(), Some(3));
+
+        config.expect_err(
+            &["rustup", "run", "--quiet", "stable", "rustc"],
+            for_host!("toolchain 'stable-{0}' is not installed"),
+        );
+    });
+}
+
 #[test]
 fn rustup_doesnt_prepend_path_unnecessarily() {
     setup(&|config| {
@@ -464,6 +553,37 @@ fn rustup_run_install() {
     });
 }
 
+#[test]
+fn rustup_run_install_with_profile() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "install", "stable"]);
+        config.expect_ok(&[
+            "rustup",
+            "run",
+            "--install",
+            "--profile",
+            "minimal",
+            "nightly",
+            "rustc",
+            "--version",
+        ]);
+        config.expect_stdout_ok(
+            &["rustup", "component", "list", "--toolchain", "nightly"],
+            for_host!("rustc-{0} (installed)"),
+        );
+        config.expect_not_stdout_ok(
+            &["rustup", "component", "list", "--toolchain", "nightly"],
+            for_host!("cargo-{0} (installed)"),
+        );
+
+        // The profile is only for what gets installed
+        config.expect_err(
+            &["rustup", "run", "--profile", "minimal", "nightly", "rustc"],
+            "--install",
+        );
+    });
+}
+
 #[test]
 fn toolchains_are_resolved_early() {
     setup(&|config| {
@@ -899,6 +1019,49 @@ fn completion_cargo_unsupported_shell() {
     });
 }
 
+#[test]
+fn completion_rustup_dynamic() {
+    setup(&|config| {
+        for shell in ["bash", "fish", "zsh"] {
+            config.expect_stdout_ok(
+                &["rustup", "completions", shell, "--dynamic"],
+                "rustup internal complete",
+            );
+        }
+        config.expect_not_stdout_ok(
+            &["rustup", "completions", "zsh", "--dynamic"],
+            "compdef _rustup rustup",
+        );
+        config.expect_err(
+            &["rustup", "completions", "powershell", "--dynamic"],
+            "error: rustup does not currently support dynamic completions for powershell",
+        );
+    });
+}
+
+#[test]
+fn internal_complete_values() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_ok(&["rustup", "toolchain", "install", "stable"]);
+        for toolchain in [for_host!("nightly-{0}\n"), for_host!("stable-{0}\n")] {
+            config.expect_stdout_ok(&["rustup", "internal", "complete", "toolchain"], toolchain);
+        }
+        config.expect_stdout_ok(
+            &["rustup", "internal", "complete", "target"],
+            clitools::CROSS_ARCH1,
+        );
+        config.expect_stdout_ok(
+            &["rustup", "internal", "complete", "component"],
+            for_host!("rust-std-{0}\n"),
+        );
+        config.expect_not_stdout_ok(
+            &["rustup", "internal", "complete", "component"],
+            "(installed)",
+        );
+    });
+}
+
 #[test]
 fn add_remove_component() {
     setup(&|config| {
@@ -968,6 +1131,160 @@ fn which_asking_uninstalled_toolchain() {
     });
 }
 
+#[test]
+fn which_target_binary() {
+    setup(&|config| {
+        let host = this_host_triple();
+        let dir = tempfile::tempdir().unwrap();
+        let binaries = [
+            "bin/rustc".to_owned(),
+            format!("lib/rustlib/{host}/bin/rust-lld"),
+            "lib/rustlib/wasm32-unknown-unknown/bin/rust-lld".to_owned(),
+        ];
+        for binary in binaries {
+            let path = dir.path().join(binary + EXE_SUFFIX);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let path = dir.path().to_string_lossy();
+        config.expect_ok(&["rustup", "toolchain", "link", "custom", &path]);
+
+        let lld = |target: &str| {
+            Path::new("lib/rustlib")
+                .join(target)
+                .join("bin")
+                .join(format!("rust-lld{EXE_SUFFIX}"))
+                .display()
+                .to_string()
+        };
+        config.expect_stdout_ok(
+            &["rustup", "which", "--toolchain=custom", "rust-lld"],
+            &lld(&host),
+        );
+        config.expect_stdout_ok(
+            &[
+                "rustup",
+                "which",
+                "--toolchain=custom",
+                "--target=wasm32-unknown-unknown",
+                "rust-lld",
+            ],
+            &lld("wasm32-unknown-unknown"),
+        );
+        // Targets without their own binary use the host's, then `bin`
+        config.expect_stdout_ok(
+            &[
+                "rustup",
+                "which",
+                "--toolchain=custom",
+                "--target=aarch64-unknown-none",
+                "rust-lld",
+            ],
+            &lld(&host),
+        );
+        config.expect_stdout_ok(
+            &[
+                "rustup",
+                "which",
+                "--toolchain=custom",
+                "--target=wasm32-unknown-unknown",
+                "rustc",
+            ],
+            &Path::new("bin")
+                .join(format!("rustc{EXE_SUFFIX}"))
+                .display()
+                .to_string(),
+        );
+        config.expect_err(
+            &[
+                "rustup",
+                "which",
+                "--toolchain=custom",
+                "--target=wasm32-unknown-unknown",
+                "cargo",
+            ],
+            "not a file",
+        );
+    });
+}
+
+#[test]
+fn which_all() {
+    setup(&|config| {
+        for name in ["custom-1", "custom-2"] {
+            let path = config.customdir.join(name);
+            let path = path.to_string_lossy();
+            config.expect_ok(&["rustup", "toolchain", "link", name, &path]);
+        }
+        config.expect_ok(&["rustup", "default", "custom-1"]);
+
+        let out = config.run("rustup", ["which", "rustc", "--all"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        let lines: Vec<_> = out.stdout.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", out.stdout);
+        assert!(
+            lines[0].ends_with(&format!("rustc{EXE_SUFFIX} (custom-1) (active)")),
+            "{}",
+            out.stdout
+        );
+        assert!(
+            lines[1].ends_with(&format!("rustc{EXE_SUFFIX} (custom-2)")),
+            "{}",
+            out.stdout
+        );
+
+        config.expect_err(
+            &["rustup", "which", "no-such-binary", "--all"],
+            "no installed toolchain provides 'no-such-binary'",
+        );
+    });
+}
+
+#[test]
+fn toolchain_link_replaces_link() {
+    setup(&|config| {
+        let path_1 = config.customdir.join("custom-1");
+        let path_2 = config.customdir.join("custom-2");
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "link",
+            "custom",
+            &path_1.to_string_lossy(),
+        ]);
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "link",
+            "custom",
+            &path_2.to_string_lossy(),
+        ]);
+        let link = config.rustupdir.join("toolchains").join("custom");
+        assert_eq!(std::fs::read_link(link).unwrap(), path_2);
+    });
+}
+
+#[test]
+fn toolchain_link_keeps_installed_toolchain() {
+    setup(&|config| {
+        let installed = config.rustupdir.join("toolchains").join("custom");
+        std::fs::create_dir_all(installed.join("lib")).unwrap();
+        std::fs::create_dir_all(installed.join("bin")).unwrap();
+        std::fs::write(installed.join("bin").join(format!("rustc{EXE_SUFFIX}")), "").unwrap();
+        config.expect_err(
+            &[
+                "rustup",
+                "toolchain",
+                "link",
+                "custom",
+                &config.customdir.join("custom-2").to_string_lossy(),
+            ],
+            "toolchain 'custom' is already installed, and is not a link that can be replaced",
+        );
+        assert!(installed.join("lib").is_dir());
+    });
+}
+
 #[test]
 fn override_by_toolchain_on_the_command_line() {
     setup(&|config| {
@@ -1054,3 +1371,101 @@ fn deprecated_interfaces() {
         );
     })
 }
+
+#[cfg(unix)]
+#[test]
+fn xdg_layout_keeps_files_under_xdg_dirs() {
+    setup(&|config| {
+        let data_home = config.homedir.join("data");
+        let config_home = config.homedir.join("config");
+        let xdg_env = [
+            ("RUSTUP_HOME", ""),
+            ("XDG_DATA_HOME", data_home.to_str().unwrap()),
+            ("XDG_CONFIG_HOME", config_home.to_str().unwrap()),
+        ];
+        let mut opt_in_env = xdg_env.to_vec();
+        opt_in_env.push(("RUSTUP_USE_XDG", "1"));
+
+        let out = config.run("rustup", ["default", "nightly"], &opt_in_env);
+        assert!(out.ok, "{}", out.stderr);
+        assert!(config_home.join("rustup/settings.toml").exists());
+        assert!(data_home
+            .join("rustup/toolchains")
+            .join(format!("nightly-{}", this_host_triple()))
+            .exists());
+
+        // The settings file keeps rustup on the XDG layout without opting in again
+        let out = config.run("rustc", ["--version"], &xdg_env);
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.contains("hash-nightly-2"));
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn xdg_layout_migrates_legacy_settings() {
+    setup(&|config| {
+        let legacy_home = config.homedir.join(".rustup");
+        let out = config.run(
+            "rustup",
+            ["default", "nightly"],
+            &[("RUSTUP_HOME", legacy_home.to_str().unwrap())],
+        );
+        assert!(out.ok, "{}", out.stderr);
+
+        let config_home = config.homedir.join("config");
+        let xdg_env = [
+            ("RUSTUP_HOME", ""),
+            ("RUSTUP_USE_XDG", "1"),
+            ("XDG_DATA_HOME", ""),
+            ("XDG_CONFIG_HOME", config_home.to_str().unwrap()),
+        ];
+        let out = config.run("rustc", ["--version"], &xdg_env);
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stderr.contains("moved settings from"));
+        assert!(out.stdout.contains("hash-nightly-2"));
+        assert!(!legacy_home.join("settings.toml").exists());
+        assert!(config_home.join("rustup/settings.toml").exists());
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn no_exec_proxy_exits_as_the_child_does() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        for no_exec in ["0", "1"] {
+            let mut cmd = clitools::cmd(config, "rustc", ["--exit", "3"]);
+            clitools::env(config, &mut cmd);
+            cmd.env("RUSTUP_NO_EXEC", no_exec);
+            let status = cmd.status().unwrap();
+            assert_eq!(status.code(), Some(3), "RUSTUP_NO_EXEC={no_exec}");
+        }
+
+        let out = config.run("rustc", ["--version"], &[("RUSTUP_NO_EXEC", "1")]);
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.contains("hash-nightly-2"));
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn no_exec_proxy_forwards_sigterm() {
+    use std::time::{Duration, Instant};
+
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let mut cmd = clitools::cmd(config, "rustc", ["--sleep", "60"]);
+        clitools::env(config, &mut cmd);
+        cmd.env("RUSTUP_NO_EXEC", "1");
+        let started = Instant::now();
+        let mut proxy = cmd.spawn().unwrap();
+        // Time for the proxy to start its child
+        std::thread::sleep(Duration::from_secs(2));
+        unsafe { libc::kill(proxy.id() as i32, libc::SIGTERM) };
+
+        let status = proxy.wait().unwrap();
+        assert_eq!(status.code(), Some(128 + libc::SIGTERM));
+        assert!(started.elapsed() < Duration::from_secs(60));
+    });
+}
@@ -418,6 +418,37 @@ mod windows {
         });
     }
 
+    #[test]
+    /// `--path-position append` puts the cargo bin directory after the existing entries.
+    fn install_appends_to_path() {
+        clitools::test(Scenario::Empty, &|config| {
+            with_saved_path(&mut || {
+                let cfg_path = config.cargodir.join("bin").display().to_string();
+                let get_path_ = || get_path().unwrap().unwrap().to_string();
+
+                config.expect_ok(&[
+                    "rustup-init",
+                    "-y",
+                    "--default-toolchain",
+                    "none",
+                    "--path-position",
+                    "append",
+                ]);
+                assert!(
+                    get_path_()
+                        .trim_matches('"')
+                        .ends_with(cfg_path.trim_matches('"')),
+                    "`{}` not at the end of `{}`",
+                    cfg_path,
+                    get_path_()
+                );
+
+                config.expect_ok(&["rustup", "self", "uninstall", "-y"]);
+                assert!(!get_path_().contains(&cfg_path));
+            })
+        });
+    }
+
     #[test]
     /// Smoke test for end-to-end code connectivity of the installer path mgmt on windows.
     fn install_uninstall_affect_path_with_non_unicode() {
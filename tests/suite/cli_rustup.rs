@@ -1,7 +1,9 @@
 //! Test cases for new rustup UI
 
 use std::fs;
+use std::io::Write;
 use std::path::{PathBuf, MAIN_SEPARATOR};
+use std::process::Stdio;
 use std::{env::consts::EXE_SUFFIX, path::Path};
 
 use rustup::for_host;
@@ -1050,6 +1052,91 @@ fn override_set_unset_with_path() {
     });
 }
 
+#[test]
+fn default_with_path_sets_override() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let cwd = fs::canonicalize(config.current_dir()).unwrap();
+            let mut cwd_str = cwd.to_str().unwrap();
+
+            if cfg!(windows) {
+                cwd_str = &cwd_str[4..];
+            }
+
+            config.expect_ok(&["rustup", "default", "stable"]);
+            let emptydir = tempfile::tempdir().unwrap();
+            config.change_dir(emptydir.path(), &|config| {
+                config.expect_ok(&["rustup", "default", "nightly", "--path", cwd_str]);
+                config.expect_stdout_ok(&["rustup", "default"], "stable");
+                config.expect_err(
+                    &["rustup", "default", "none", "--path", cwd_str],
+                    "a directory cannot be given no toolchain",
+                );
+            });
+            config.expect_ok_ex(
+                &["rustup", "override", "list"],
+                &format!("{}\tnightly-{}\n", cwd_str, this_host_triple()),
+                r"",
+            );
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        })
+    });
+}
+
+#[test]
+fn override_set_channel_freeze() {
+    test(&|config| {
+        config.with_scenario(Scenario::ArchivesV2_2015_01_01, &|config| {
+            config.expect_stderr_ok(
+                &["rustup", "override", "set", "--channel-freeze", "stable"],
+                "frozen at 2015-01-01",
+            );
+        });
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let frozen = for_host!("warning: not updating 'stable-{0}', which the override for");
+            config.expect_stderr_ok(&["rustup", "update", "stable"], frozen);
+            config.expect_stderr_ok(&["rustup", "update"], frozen);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.0.0");
+
+            config.expect_ok(&["rustup", "update", "stable", "--force"]);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+        })
+    });
+}
+
+#[test]
+fn override_set_channel_freeze_lifted_by_plain_override() {
+    test(&|config| {
+        config.with_scenario(Scenario::ArchivesV2_2015_01_01, &|config| {
+            config.expect_ok(&["rustup", "override", "set", "--channel-freeze", "stable"]);
+            config.expect_ok(&["rustup", "override", "set", "stable"]);
+        });
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_not_stderr_ok(&["rustup", "update", "stable"], "not updating");
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+        })
+    });
+}
+
+#[test]
+fn override_set_channel_freeze_needs_channel() {
+    test(&|config| {
+        config.with_scenario(Scenario::ArchivesV2, &|config| {
+            config.expect_err(
+                &[
+                    "rustup",
+                    "override",
+                    "set",
+                    "--channel-freeze",
+                    "nightly-2015-01-01",
+                ],
+                "does not track a release channel, so it cannot be frozen",
+            );
+            config.expect_ok_ex(&["rustup", "override", "list"], "no overrides\n", r"");
+        })
+    });
+}
+
 #[test]
 fn show_toolchain_env() {
     test(&|config| {
@@ -1090,6 +1177,78 @@ fn show_toolchain_env_not_installed() {
     });
 }
 
+#[test]
+fn show_json() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "nightly"]);
+            config.expect_ok(&["rustup", "update", "stable"]);
+            let out = config.run("rustup", ["show", "--json"], &[]);
+            assert!(out.ok, "{}", out.stderr);
+            let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+
+            assert_eq!(json["schema"], 1);
+            assert_eq!(json["default_host"], this_host_triple());
+            assert_eq!(json["rustup_home"], config.rustupdir.to_string());
+            assert_eq!(
+                json["active_toolchain"],
+                serde_json::json!({
+                    "name": for_host!("nightly-{}"),
+                    "reason": "default",
+                    "installed": true,
+                    "targets": [this_host_triple()],
+                })
+            );
+            let installed = json["installed_toolchains"].as_array().unwrap();
+            assert_eq!(installed.len(), 2, "{installed:?}");
+            for toolchain in installed {
+                let nightly = toolchain["name"] == *for_host!("nightly-{}");
+                assert_eq!(toolchain["default"], nightly);
+                let components = toolchain["components"].as_array().unwrap();
+                assert!(
+                    components.contains(&for_host!("rustc-{}").as_str().into()),
+                    "{components:?}"
+                );
+            }
+        })
+    });
+}
+
+#[test]
+fn show_json_does_not_install_the_active_toolchain() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let cwd = config.current_dir();
+            let toolchain_file = cwd.join("rust-toolchain");
+            raw::write_file(&toolchain_file, "nightly").unwrap();
+            let out = config.run("rustup", ["show", "--json"], &[]);
+            assert!(out.ok, "{}", out.stderr);
+            let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+            assert_eq!(
+                json["active_toolchain"],
+                serde_json::json!({
+                    "name": for_host!("nightly-{}"),
+                    "reason": format!("overridden by '{}'", toolchain_file.display()),
+                    "installed": false,
+                    "targets": [],
+                })
+            );
+            assert_eq!(json["installed_toolchains"], serde_json::json!([]));
+        })
+    });
+}
+
+#[test]
+fn show_json_without_active_toolchain() {
+    test(&|config| {
+        let out = config.run("rustup", ["show", "--json"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+        assert!(json["active_toolchain"].is_null());
+        assert_eq!(json["installed_toolchains"], serde_json::json!([]));
+    });
+}
+
 #[test]
 fn show_active_toolchain() {
     test(&|config| {
@@ -1202,6 +1361,59 @@ fn show_profile() {
     });
 }
 
+#[test]
+fn show_profile_json() {
+    test(&|config| {
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        config.expect_stdout_ok(
+            &["rustup", "show", "profile", "--json"],
+            r#"{"profile":"minimal"}"#,
+        );
+    });
+}
+
+#[test]
+fn show_overrides() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_stdout_ok(&["rustup", "show", "overrides"], "no overrides");
+            config.expect_ok(&["rustup", "override", "set", "nightly"]);
+            let listed = config.run("rustup", ["override", "list", "--json"], &[]);
+            let shown = config.run("rustup", ["show", "overrides", "--json"], &[]);
+            assert!(shown.ok, "{}", shown.stderr);
+            assert_eq!(shown.stdout, listed.stdout);
+            assert!(
+                shown.stdout.contains(r#""exists":true"#),
+                "{}",
+                shown.stdout
+            );
+            config.expect_stdout_ok(&["rustup", "show", "overrides"], "nightly");
+        })
+    });
+}
+
+#[test]
+fn set_profile_list() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_stdout_ok(
+                &["rustup", "set", "profile", "--list"],
+                "minimal\ndefault\ncomplete\n",
+            );
+            config.expect_ok(&["rustup", "default", "nightly"]);
+            config.expect_stdout_ok(
+                &["rustup", "set", "profile", "--list"],
+                "minimal: rustc\ndefault: rustc, cargo, rust-std, rust-docs\ncomplete: rustc, cargo, rust-std, rust-docs, rls\n",
+            );
+            config.expect_err(
+                &["rustup", "set", "profile", "minmal"],
+                "[possible values: minimal, default, complete]",
+            );
+            config.expect_stdout_ok(&["rustup", "show", "profile"], "default");
+        })
+    });
+}
+
 // #846
 #[test]
 fn set_default_host() {
@@ -1233,6 +1445,29 @@ fn set_default_host_invalid_triple_valid_partial() {
     });
 }
 
+#[test]
+fn set_default_host_unknown_triple() {
+    test(&|config| {
+        config.expect_err(
+            &["rustup", "set", "default-host", "x86_64-unknown-linux-gnux32"],
+            "error: 'x86_64-unknown-linux-gnux32' is not a known host triple; \
+             did you mean 'x86_64-unknown-linux-gnu'?\n\
+             help: use `rustup set default-host --force x86_64-unknown-linux-gnux32` if it is a new host",
+        );
+        config.expect_ok(&[
+            "rustup",
+            "set",
+            "default-host",
+            "--force",
+            "x86_64-unknown-linux-gnux32",
+        ]);
+        config.expect_stdout_ok(
+            &["rustup", "show"],
+            "Default host: x86_64-unknown-linux-gnux32",
+        );
+    });
+}
+
 // #422
 #[test]
 fn update_doesnt_update_non_tracking_channels() {
@@ -1267,50 +1502,438 @@ fn toolchain_install_is_like_update() {
 }
 
 #[test]
-fn toolchain_install_is_like_update_quiet() {
+fn toolchain_install_is_like_update_quiet() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "--quiet", "toolchain", "install", "nightly"]);
+            config.expect_stdout_ok(
+                &["rustup", "run", "nightly", "rustc", "--version"],
+                "hash-nightly-2",
+            );
+        })
+    });
+}
+
+#[test]
+fn toolchain_install_is_like_update_except_that_bare_install_is_an_error() {
+    test(&|config| {
+        config.expect_err(
+            &["rustup", "toolchain", "install"],
+            "arguments were not provided",
+        );
+    });
+}
+
+#[test]
+fn toolchain_update_is_like_update() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "toolchain", "update", "nightly"]);
+            config.expect_stdout_ok(
+                &["rustup", "run", "nightly", "rustc", "--version"],
+                "hash-nightly-2",
+            );
+        })
+    });
+}
+
+#[test]
+fn corrupt_settings_point_to_backup() {
+    test(&|config| {
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        config.expect_ok(&["rustup", "set", "profile", "complete"]);
+        let settings = config.rustupdir.join("settings.toml");
+        let backup = config.rustupdir.join("settings.toml.bak");
+        assert!(fs::read_to_string(&backup).unwrap().contains("minimal"));
+        let leftovers = fs::read_dir(&config.rustupdir.rustupdir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(".rustup-tmp"))
+            .collect::<Vec<_>>();
+        assert!(leftovers.is_empty(), "{leftovers:?}");
+
+        fs::write(&settings, "profile = \"comp").unwrap();
+        config.expect_err(
+            &["rustup", "show", "profile"],
+            "copy that file over the settings file to restore them",
+        );
+        fs::copy(&backup, &settings).unwrap();
+        config.expect_stdout_ok(&["rustup", "show", "profile"], "minimal");
+    })
+}
+
+#[test]
+fn upgrade_data_migrates_once() {
+    test(&|config| {
+        config.expect_stderr_ok(
+            &["rustup", "self", "upgrade-data"],
+            "nothing to upgrade: metadata version is already '12'",
+        );
+
+        let sub = config.current_dir().join("sub");
+        fs::create_dir(&sub).unwrap();
+        let uncanonical = sub.join("..");
+        fs::write(
+            config.rustupdir.join("settings.toml"),
+            format!(
+                "version = \"12\"\n\
+                 [overrides]\n\
+                 '{}' = 'nightly'\n\
+                 [frozen_overrides]\n\
+                 '/gone' = '2015-01-01'\n",
+                uncanonical.display()
+            ),
+        )
+        .unwrap();
+        let hashes = config.rustupdir.join("update-hashes");
+        fs::create_dir_all(&hashes).unwrap();
+        fs::write(hashes.join("nightly-gone"), "hash").unwrap();
+
+        let out = config.run("rustup", ["self", "upgrade-data"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        assert!(
+            out.stderr.contains("moved the override for"),
+            "{}",
+            out.stderr
+        );
+        assert!(
+            out.stderr.contains("removed the freeze for '/gone'"),
+            "{}",
+            out.stderr
+        );
+        assert!(out
+            .stderr
+            .contains("removed the update hash of 'nightly-gone', which is not installed"));
+        assert!(!hashes.join("nightly-gone").exists());
+        config.expect_not_stdout_ok(&["rustup", "override", "list"], "sub");
+
+        config.expect_stderr_ok(&["rustup", "self", "upgrade-data"], "nothing to upgrade");
+    })
+}
+
+#[test]
+fn default_unset() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "nightly"]);
+        });
+        config.expect_stderr_ok(
+            &["rustup", "default", "--unset"],
+            "info: default toolchain unset",
+        );
+        let settings = fs::read_to_string(config.rustupdir.join("settings.toml")).unwrap();
+        assert!(!settings.contains("default_toolchain"), "{settings}");
+        config.expect_err(&["rustup", "default"], "no default toolchain configured");
+        config.expect_err(
+            &["rustc", "--version"],
+            "rustup could not choose a version of rustc to run",
+        );
+        config.expect_err(
+            &["rustup", "default", "--unset", "nightly"],
+            "cannot be used with",
+        );
+    })
+}
+
+fn run_with_stdin(config: &Config, args: &[&str], input: &str) -> clitools::SanitizedOutput {
+    let mut cmd = clitools::cmd(config, args[0], &args[1..]);
+    clitools::env(config, &mut cmd);
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    let mut child = cmd.spawn().unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    clitools::SanitizedOutput {
+        ok: out.status.success(),
+        stdout: String::from_utf8(out.stdout).unwrap(),
+        stderr: String::from_utf8(out.stderr).unwrap(),
+    }
+}
+
+#[test]
+fn corrupt_settings_can_be_restored_from_backup() {
+    test(&|config| {
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        config.expect_ok(&["rustup", "set", "profile", "complete"]);
+        fs::write(config.rustupdir.join("settings.toml"), "profile = \"comp").unwrap();
+
+        let out = run_with_stdin(config, &["rustup", "show", "profile"], "n\n");
+        assert!(!out.ok);
+        assert!(out.stderr.contains("copy that file over the settings file"));
+
+        let out = run_with_stdin(config, &["rustup", "show", "profile"], "y\n");
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.ends_with("minimal\n"), "{}", out.stdout);
+        assert!(
+            out.stderr.contains("info: restored the settings from"),
+            "{}",
+            out.stderr
+        );
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn settings_write_keeps_symlink_and_mode() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    test(&|config| {
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        let settings = config.rustupdir.join("settings.toml");
+        let target = config.rustupdir.join("dotfiles-settings.toml");
+        fs::rename(&settings, &target).unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+        symlink(&target, &settings).unwrap();
+
+        config.expect_ok(&["rustup", "set", "profile", "complete"]);
+        assert!(fs::symlink_metadata(&settings)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::read_to_string(&target).unwrap().contains("complete"));
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    })
+}
+
+#[test]
+fn default_from_stdin() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let out = run_with_stdin(config, &["rustup", "default", "-"], "  nightly  \n");
+            assert!(out.ok, "{}", out.stderr);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+
+            let out = run_with_stdin(config, &["rustup", "default", "-"], "\n");
+            assert!(!out.ok);
+            assert!(
+                out.stderr.contains("no toolchain name was given on stdin"),
+                "{}",
+                out.stderr
+            );
+
+            let out = run_with_stdin(config, &["rustup", "default", "-"], "");
+            assert!(!out.ok);
+            assert!(
+                out.stderr
+                    .contains("unable to read a toolchain name from stdin"),
+                "{}",
+                out.stderr
+            );
+
+            let out = run_with_stdin(config, &["rustup", "default", "-"], "nightly/x\n");
+            assert!(!out.ok);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        });
+    })
+}
+
+#[test]
+fn toolchain_uninstall_is_like_uninstall() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        });
+        config.expect_ok(&["rustup", "default", "none"]);
+        config.expect_ok(&["rustup", "uninstall", "nightly"]);
+        config.expect_not_stdout_ok(&["rustup", "show"], for_host!("'nightly-{}'"));
+    });
+}
+
+#[test]
+fn toolchain_gc_removes_unreferenced_toolchains() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+            config.expect_ok(&["rustup", "toolchain", "install", "beta", "nightly"]);
+        });
+        let project = config.current_dir().join("project");
+        fs::create_dir_all(&project).unwrap();
+        raw::write_file(
+            &project.join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"beta\"",
+        )
+        .unwrap();
+        let root = config.current_dir();
+        let root = root.to_str().unwrap();
+
+        config.expect_stdout_ok(
+            &["rustup", "toolchain", "gc", "--root", root, "--dry-run"],
+            for_host!("nightly-{0}"),
+        );
+        config.expect_not_stdout_ok(
+            &["rustup", "toolchain", "gc", "--root", root, "--dry-run"],
+            for_host!("beta-{0}"),
+        );
+        config.expect_ok(&["rustup", "toolchain", "gc", "--root", root, "-y"]);
+        config.expect_ok_ex(
+            &["rustup", "toolchain", "list"],
+            for_host!("stable-{0} (default)\nbeta-{0}\n"),
+            "",
+        );
+    });
+}
+
+#[test]
+fn toolchain_gc_removes_nothing_if_toolchain_files_are_malformed() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+            config.expect_ok(&["rustup", "toolchain", "install", "beta", "nightly"]);
+        });
+        let project = config.current_dir().join("project");
+        let broken = config.current_dir().join("broken");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(&broken).unwrap();
+        raw::write_file(
+            &project.join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"beta\"",
+        )
+        .unwrap();
+        raw::write_file(&broken.join("rust-toolchain.toml"), "[toolchain").unwrap();
+        let root = config.current_dir();
+        let root = root.to_str().unwrap();
+
+        config.expect_err(
+            &["rustup", "toolchain", "gc", "--root", root, "-y"],
+            &format!(
+                "warning: could not read '{}'",
+                broken.join("rust-toolchain.toml").display()
+            ),
+        );
+        config.expect_err(
+            &["rustup", "toolchain", "gc", "--root", root, "-y"],
+            "error: 1 toolchain file(s) or directories beneath the roots could not be read, \
+             so no toolchains were removed",
+        );
+        config.expect_ok_ex(
+            &["rustup", "toolchain", "list"],
+            for_host!("stable-{0} (default)\nbeta-{0}\nnightly-{0}\n"),
+            "",
+        );
+    });
+}
+
+#[test]
+fn toolchain_gc_keep_last() {
+    test(&|config| {
+        config.with_scenario(Scenario::ArchivesV2_2015_01_01, &|config| {
+            config.expect_ok(&["rustup", "toolchain", "install", "nightly-2015-01-01"]);
+        });
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+            config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        });
+        config.expect_ok_ex(
+            &["rustup", "toolchain", "gc", "--keep-last", "1", "--dry-run"],
+            for_host!("nightly-2015-01-01-{0}\n"),
+            "",
+        );
+        config.expect_ok_ex(
+            &["rustup", "toolchain", "gc", "--keep-last", "2", "--dry-run"],
+            "",
+            "info: no unreferenced toolchains to uninstall\n",
+        );
+    });
+}
+
+#[test]
+fn toolchain_verify() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        });
+        let toolchain_path = config
+            .rustupdir
+            .join("toolchains")
+            .join(format!("nightly-{}", this_host_triple()));
+
+        config.expect_stderr_ok(
+            &["rustup", "toolchain", "verify", "nightly"],
+            "0 of which had to be hashed",
+        );
+
+        raw::write_file(
+            &toolchain_path.join(format!("bin/rustc{EXE_SUFFIX}")),
+            "modified",
+        )
+        .unwrap();
+        fs::remove_file(toolchain_path.join(format!("bin/cargo{EXE_SUFFIX}"))).unwrap();
+        config.expect_err_ex(
+            &["rustup", "toolchain", "verify", "nightly"],
+            "",
+            &format!(
+                r"warning: 'bin/cargo{1}' of component 'cargo-{0}' is missing
+warning: 'bin/rustc{1}' of component 'rustc-{0}' has been modified
+error: 2 file(s) of toolchain 'nightly-{0}' are missing or modified
+help: run `rustup toolchain uninstall nightly-{0}` and install it again
+",
+                this_host_triple(),
+                EXE_SUFFIX
+            ),
+        );
+    });
+}
+
+#[test]
+fn toolchain_verify_checks_against_hashes_from_install() {
     test(&|config| {
         config.with_scenario(Scenario::SimpleV2, &|config| {
-            config.expect_ok(&["rustup", "--quiet", "toolchain", "install", "nightly"]);
-            config.expect_stdout_ok(
-                &["rustup", "run", "nightly", "rustc", "--version"],
-                "hash-nightly-2",
+            config.expect_stderr_ok(
+                &["rustup", "--verbose", "toolchain", "install", "nightly"],
+                "hashing the files of component 'rustc",
             );
-        })
-    });
-}
+        });
+        let toolchain_path = config
+            .rustupdir
+            .join("toolchains")
+            .join(format!("nightly-{}", this_host_triple()));
 
-#[test]
-fn toolchain_install_is_like_update_except_that_bare_install_is_an_error() {
-    test(&|config| {
+        // Damage from before the first check is found all the same
+        raw::write_file(
+            &toolchain_path.join(format!("bin/rustc{EXE_SUFFIX}")),
+            "modified",
+        )
+        .unwrap();
         config.expect_err(
-            &["rustup", "toolchain", "install"],
-            "arguments were not provided",
+            &["rustup", "toolchain", "verify", "nightly"],
+            &format!("'bin/rustc{EXE_SUFFIX}' of component 'rustc-"),
         );
-    });
-}
 
-#[test]
-fn toolchain_update_is_like_update() {
-    test(&|config| {
-        config.with_scenario(Scenario::SimpleV2, &|config| {
-            config.expect_ok(&["rustup", "toolchain", "update", "nightly"]);
-            config.expect_stdout_ok(
-                &["rustup", "run", "nightly", "rustc", "--version"],
-                "hash-nightly-2",
-            );
-        })
+        fs::remove_file(toolchain_path.join("lib/rustlib/integrity")).unwrap();
+        config.expect_ok_ex(
+            &["rustup", "toolchain", "verify", "nightly"],
+            "",
+            &format!(
+                r"warning: component 'cargo-{0}' cannot be checked, as no hashes were recorded when it was installed
+warning: component 'rust-docs-{0}' cannot be checked, as no hashes were recorded when it was installed
+warning: component 'rust-std-{0}' cannot be checked, as no hashes were recorded when it was installed
+warning: component 'rustc-{0}' cannot be checked, as no hashes were recorded when it was installed
+info: to check them, run `rustup toolchain uninstall nightly-{0}` and install it again
+info: checked 0 files of 0 components, 0 of which had to be hashed
+",
+                this_host_triple()
+            ),
+        );
     });
 }
 
 #[test]
-fn toolchain_uninstall_is_like_uninstall() {
+fn toolchain_verify_custom_toolchain() {
     test(&|config| {
-        config.with_scenario(Scenario::SimpleV2, &|config| {
-            config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
-        });
-        config.expect_ok(&["rustup", "default", "none"]);
-        config.expect_ok(&["rustup", "uninstall", "nightly"]);
-        config.expect_not_stdout_ok(&["rustup", "show"], for_host!("'nightly-{}'"));
+        let path = config.customdir.join("custom-1");
+        let path = path.to_string_lossy();
+        config.expect_ok(&["rustup", "toolchain", "link", "custom", &path]);
+        config.expect_err(
+            &["rustup", "toolchain", "verify", "custom"],
+            "custom' does not support components",
+        );
     });
 }
 
@@ -1337,6 +1960,26 @@ fn proxy_toolchain_shorthand() {
     });
 }
 
+#[test]
+fn channel_names_are_case_insensitive() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "Stable"]);
+            config.expect_ok(&["rustup", "toolchain", "install", "NIGHTLY"]);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+            config.expect_stdout_ok(&["rustc", "+Nightly", "--version"], "hash-nightly-2");
+
+            config.expect_ok(&["rustup", "override", "set", "Nightly"]);
+            let settings = fs::read_to_string(config.rustupdir.join("settings.toml")).unwrap();
+            assert!(
+                settings.contains(&format!("\"nightly-{}\"", this_host_triple())),
+                "{settings}"
+            );
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        })
+    });
+}
+
 #[test]
 fn add_component() {
     test(&|config| {
@@ -1397,6 +2040,71 @@ fn add_remove_multiple_components() {
     });
 }
 
+#[test]
+fn add_remove_component_from_path() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let package = config.current_dir().join("vendored");
+            fs::create_dir_all(package.join("vendored-tool/bin")).unwrap();
+            fs::write(package.join("rust-installer-version"), "3\n").unwrap();
+            fs::write(package.join("components"), "vendored-tool\n").unwrap();
+            fs::write(
+                package.join("vendored-tool/manifest.in"),
+                "file:bin/vendored-tool\n",
+            )
+            .unwrap();
+            fs::write(package.join("vendored-tool/bin/vendored-tool"), "").unwrap();
+            let package = package.to_str().unwrap();
+
+            config.expect_ok(&["rustup", "default", "stable"]);
+            config.expect_err(
+                &[
+                    "rustup",
+                    "component",
+                    "add",
+                    "other-tool",
+                    "--path",
+                    package,
+                ],
+                "does not contain component 'other-tool'",
+            );
+            config.expect_ok(&[
+                "rustup",
+                "component",
+                "add",
+                "vendored-tool",
+                "--path",
+                package,
+            ]);
+            let path = format!("toolchains/stable-{}/bin/vendored-tool", this_host_triple());
+            assert!(config.rustupdir.has(&path));
+            config.expect_err(
+                &[
+                    "rustup",
+                    "component",
+                    "add",
+                    "vendored-tool",
+                    "--path",
+                    package,
+                ],
+                "component 'vendored-tool' is already installed",
+            );
+            config.expect_stdout_ok(
+                &["rustup", "component", "list"],
+                "vendored-tool (installed)",
+            );
+            config.expect_stdout_ok(
+                &["rustup", "component", "list", "--installed"],
+                "vendored-tool",
+            );
+
+            config.expect_ok(&["rustup", "component", "remove", "vendored-tool"]);
+            assert!(!config.rustupdir.has(&path));
+            config.expect_not_stdout_ok(&["rustup", "component", "list"], "vendored-tool");
+        })
+    });
+}
+
 #[test]
 fn file_override() {
     test(&|config| {
@@ -1438,6 +2146,28 @@ fn env_override_path() {
     });
 }
 
+#[test]
+fn env_override_path_without_rustc() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+
+            let toolchain_path = config.current_dir().join("not-a-toolchain");
+            fs::create_dir_all(toolchain_path.join("bin")).unwrap();
+
+            let out = config.run(
+                "rustc",
+                ["--version"],
+                &[("RUSTUP_TOOLCHAIN", toolchain_path.to_str().unwrap())],
+            );
+            assert!(!out.ok);
+            assert!(out
+                .stderr
+                .contains("has no bin/rustc, so it is not a toolchain directory"));
+        })
+    });
+}
+
 #[test]
 fn plus_override_relpath_is_not_supported() {
     test(&|config| {
@@ -1877,6 +2607,66 @@ components = [ "rust-bongo" ]
     });
 }
 
+#[test]
+fn file_override_toml_format_required_component_unavailable() {
+    test(&|config| {
+        config.with_scenario(Scenario::UnavailableRls, &|config| {
+            config.expect_ok(&["rustup", "toolchain", "install", "nightly-2015-01-01"]);
+
+            let cwd = config.current_dir();
+            let toolchain_file = cwd.join("rust-toolchain.toml");
+            raw::write_file(
+                &toolchain_file,
+                r#"
+[toolchain]
+channel = "nightly-2015-01-01"
+components = [ "rls" ]
+"#,
+            )
+            .unwrap();
+
+            config.expect_err(
+                &["rls", "--version"],
+                &format!(
+                    "the 'rls' component, which '{}' requires, is not available for the toolchain '{}'",
+                    toolchain_file.display(),
+                    for_host!("nightly-2015-01-01-{}"),
+                ),
+            );
+        })
+    });
+}
+
+#[test]
+fn file_override_toml_format_required_component_custom() {
+    test(&|config| {
+        let path = config.customdir.join("custom-1");
+        let path = path.to_string_lossy();
+        config.expect_ok(&["rustup", "toolchain", "link", "custom", &path]);
+
+        let cwd = config.current_dir();
+        let toolchain_file = cwd.join("rust-toolchain.toml");
+        raw::write_file(
+            &toolchain_file,
+            r#"
+[toolchain]
+channel = "custom"
+components = [ "rls" ]
+"#,
+        )
+        .unwrap();
+
+        config.expect_err(
+            &["rls", "--version"],
+            &format!(
+                "the 'rls' component, which '{}' requires, is not available for the toolchain 'custom'",
+                toolchain_file.display(),
+            ),
+        );
+        config.expect_ok(&["rustc", "--version"]);
+    });
+}
+
 #[test]
 fn file_override_toml_format_specify_profile() {
     test(&|config| {
@@ -2104,6 +2894,26 @@ fn docs_with_path() {
     });
 }
 
+#[test]
+fn docs_list() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "nightly"]);
+            config.expect_stdout_ok(
+                &["rustup", "doc", "--list"],
+                "core                \tThe Rust Core Library\n",
+            );
+            config.expect_stdout_ok(
+                &["rustup", "doc", "--list", "--json"],
+                r#"[{"name":"core","title":"The Rust Core Library","path":""#,
+            );
+            // Only the pages the toolchain has are listed
+            config.expect_not_stdout_ok(&["rustup", "doc", "--list"], "book");
+            config.expect_err(&["rustup", "doc", "--json"], "--list");
+        })
+    });
+}
+
 #[test]
 fn docs_topical_with_path() {
     test(&|config| {
@@ -2128,6 +2938,107 @@ fn docs_topical_with_path() {
     });
 }
 
+#[test]
+fn docs_open_in_browser_that_fails() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+            let path = format!("share{MAIN_SEPARATOR}doc{MAIN_SEPARATOR}rust{MAIN_SEPARATOR}html");
+
+            let out = config.run(
+                "rustup",
+                ["doc", "--open-in", "no-such-browser"],
+                &[("BROWSER", "true")],
+            );
+            assert!(out.ok);
+            assert!(out
+                .stderr
+                .contains("couldn't open browser with 'no-such-browser'"));
+            assert!(out.stdout.contains(&path));
+
+            let out = config.run(
+                "rustup",
+                ["doc"],
+                &[("BROWSER", "no-such-browser another-browser")],
+            );
+            assert!(out.ok);
+            assert!(out
+                .stderr
+                .contains("couldn't open browser with 'no-such-browser', 'another-browser'"));
+            assert!(out.stdout.contains(&path));
+        })
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn docs_open_with_browser_from_env() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+            let out = config.run("rustup", ["doc"], &[("BROWSER", "no-such-browser true")]);
+            assert!(out.ok);
+            assert!(!out.stderr.contains("couldn't open browser"));
+            assert!(out.stdout.is_empty());
+        })
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn docs_open_in_browser_with_arguments() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+            let out = config.run("rustup", ["doc", "--open-in", "true --new-window"], &[]);
+            assert!(out.ok);
+            assert!(!out.stderr.contains("couldn't open browser"));
+            assert!(out.stdout.is_empty());
+        })
+    });
+}
+
+#[test]
+fn offline_mode_fails_fast() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+
+            let dist_server = ("RUSTUP_DIST_SERVER", "https://example.invalid");
+            let out = config.run(
+                "rustup",
+                ["--offline", "toolchain", "install", "nightly"],
+                &[dist_server],
+            );
+            assert!(!out.ok);
+            assert!(out.stderr.contains(
+                "cannot download 'https://example.invalid/dist/channel-rust-nightly.toml.sha256' in offline mode"
+            ));
+            let out = config.run(
+                "rustup",
+                ["update", "stable"],
+                &[dist_server, ("RUSTUP_OFFLINE", "1")],
+            );
+            assert!(out.stderr.contains("in offline mode"));
+
+            // What is already installed needs no network
+            for args in [
+                &["show"][..],
+                &["which", "rustc"],
+                &["run", "stable", "rustc", "--version"],
+                &["component", "list", "--installed"],
+                &["target", "list", "--installed"],
+            ] {
+                let out = config.run("rustup", args, &[dist_server, ("RUSTUP_OFFLINE", "1")]);
+                assert!(out.ok, "{args:?}: {}", out.stderr);
+            }
+
+            // A mirror on the local file system can still be used
+            config.expect_ok(&["rustup", "--offline", "toolchain", "install", "nightly"]);
+        })
+    });
+}
+
 #[test]
 fn docs_missing() {
     test(&|config| {
@@ -2362,6 +3273,50 @@ fn only_toml_in_rust_toolchain_toml() {
     });
 }
 
+/// Checks that the `[env]` section of a `rust-toolchain.toml` is passed on to
+/// proxied commands
+#[test]
+fn rust_toolchain_toml_env() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let cwd = config.current_dir();
+            let toolchain_file = cwd.join("rust-toolchain.toml");
+            raw::write_file(
+                &toolchain_file,
+                "[toolchain]\nchannel = \"nightly\"\n[env]\nRUSTUP_TEST_FROM_TOOLCHAIN_FILE = \"hello\"",
+            )
+            .unwrap();
+
+            config.expect_stderr_ok(
+                &["rustc", "--echo-env", "RUSTUP_TEST_FROM_TOOLCHAIN_FILE"],
+                "hello",
+            );
+        })
+    });
+}
+
+/// Ensures that a `rust-toolchain.toml` cannot override variables that rustup
+/// sets itself
+#[test]
+fn rust_toolchain_toml_env_rejects_rustup_vars() {
+    test(&|config| {
+        config.with_scenario(Scenario::SimpleV2, &|config| {
+            let cwd = config.current_dir();
+            let toolchain_file = cwd.join("rust-toolchain.toml");
+            raw::write_file(
+                &toolchain_file,
+                "[toolchain]\nchannel = \"nightly\"\n[env]\nRUSTUP_TOOLCHAIN = \"stable\"",
+            )
+            .unwrap();
+
+            config.expect_err(
+                &["rustc", "--version"],
+                "environment variable 'RUSTUP_TOOLCHAIN' is set by rustup",
+            );
+        })
+    });
+}
+
 /// Checks that a warning occurs if both `rust-toolchain` and `rust-toolchain.toml` files exist
 #[test]
 fn warn_on_duplicate_rust_toolchain_file() {
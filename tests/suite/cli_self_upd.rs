@@ -3,7 +3,8 @@
 use std::env;
 use std::env::consts::EXE_SUFFIX;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use remove_dir_all::remove_dir_all;
@@ -396,6 +397,84 @@ fn update_updates_rustup_bin() {
     });
 }
 
+/// Publishes the rustup-init of the mock server as `version` as well, with a
+/// hash file next to it.
+fn publish_version(self_dist: &Path, version: &str) -> PathBuf {
+    let trip = this_host_triple();
+    let src = self_dist.join(format!(
+        "archive/{TEST_VERSION}/{trip}/rustup-init{EXE_SUFFIX}"
+    ));
+    let dir = self_dist.join(format!("archive/{version}/{trip}"));
+    fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join(format!("rustup-init{EXE_SUFFIX}"));
+    fs::copy(src, &exe).unwrap();
+    let hash = format!("{}  rustup-init{EXE_SUFFIX}\n", calc_hash(&exe));
+    fs::write(dir.join(format!("rustup-init{EXE_SUFFIX}.sha256")), hash).unwrap();
+    exe
+}
+
+#[test]
+fn update_to_pinned_version() {
+    update_setup(&|config, self_dist| {
+        config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+        publish_version(self_dist, "99.0.0");
+
+        let bin = config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+        let before_hash = calc_hash(&bin);
+        config.expect_ok(&["rustup", "self", "update", "--version", "99.0.0"]);
+        assert_ne!(before_hash, calc_hash(&bin));
+    });
+}
+
+#[test]
+fn update_to_pinned_version_is_checked() {
+    update_setup(&|config, self_dist| {
+        config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+        let bin = config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+        let before_hash = calc_hash(&bin);
+
+        config.expect_err(
+            &["rustup", "self", "update", "--version", "1.x"],
+            "error: '1.x' is not a rustup version; expected one such as '1.27.0'",
+        );
+        config.expect_err(
+            &["rustup", "self", "update", "--version", "98.0.0"],
+            "error: rustup 98.0.0 is not available from",
+        );
+
+        // Older releases are refused unless asked for
+        publish_version(self_dist, "1.0.0");
+        config.expect_err(
+            &["rustup", "self", "update", "--version", "1.0.0"],
+            &format!(
+                "error: rustup 1.0.0 is older than this rustup, {}",
+                env!("CARGO_PKG_VERSION")
+            ),
+        );
+        assert_eq!(before_hash, calc_hash(&bin));
+
+        // A release that does not match its hash is not installed
+        let exe = publish_version(self_dist, "99.0.0");
+        let mut file = fs::OpenOptions::new().append(true).open(exe).unwrap();
+        writeln!(file, "tampered").unwrap();
+        config.expect_err(
+            &["rustup", "self", "update", "--version", "99.0.0"],
+            "checksum failed",
+        );
+        assert_eq!(before_hash, calc_hash(&bin));
+
+        config.expect_ok(&[
+            "rustup",
+            "self",
+            "update",
+            "--version",
+            "1.0.0",
+            "--allow-downgrade",
+        ]);
+        assert_ne!(before_hash, calc_hash(&bin));
+    });
+}
+
 #[test]
 fn update_bad_schema() {
     update_setup(&|config, self_dist| {
@@ -424,6 +503,83 @@ fn update_no_change() {
     });
 }
 
+#[test]
+fn update_check_reports_available_update() {
+    let version = env!("CARGO_PKG_VERSION");
+    let newer_version = "99.0.0";
+    self_update_setup(
+        &|config, _| {
+            config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+
+            let bin = config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+            let before_hash = calc_hash(&bin);
+
+            let out = config.run_subprocess("rustup", ["self", "update", "--check"], &[]);
+            assert_eq!(out.status, Some(100));
+            assert!(String::from_utf8(out.stdout)
+                .unwrap()
+                .contains(&format!("Update available : {version} -> {newer_version}")));
+
+            let setup = config.cargodir.join(format!("bin/rustup-init{EXE_SUFFIX}"));
+            assert!(!setup.exists());
+            assert_eq!(before_hash, calc_hash(&bin));
+        },
+        newer_version,
+    );
+}
+
+#[test]
+fn update_check_does_not_report_older_version() {
+    let version = env!("CARGO_PKG_VERSION");
+    // `TEST_VERSION` is older than the running rustup
+    update_setup(&|config, _| {
+        config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+        let out = config.run_subprocess("rustup", ["self", "update", "--check"], &[]);
+        assert_eq!(out.status, Some(0));
+        assert!(String::from_utf8(out.stdout)
+            .unwrap()
+            .contains(&format!("Up to date : {version}")));
+    });
+}
+
+#[test]
+fn check_only_mode_does_not_check_outside_a_terminal() {
+    let version = env!("CARGO_PKG_VERSION");
+    update_setup(&|config, _| {
+        config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+        let notice = format!("is available (you have {version})");
+
+        config.expect_not_stderr_ok(
+            &["rustup", "set", "auto-self-update", "check-only"],
+            &notice,
+        );
+        let settings_path = config.rustupdir.join("settings.toml");
+        let settings = fs::read_to_string(&settings_path).unwrap();
+        assert!(!settings.contains("last_self_update_check"), "{settings}");
+
+        // Output that is not read by a person is not held up by the check
+        config.expect_not_stderr_ok(&["rustup", "show"], &notice);
+        config.expect_not_stderr_ok(&["rustc", "--version"], &notice);
+        assert_eq!(fs::read_to_string(&settings_path).unwrap(), settings);
+
+        let setup = config.cargodir.join(format!("bin/rustup-init{EXE_SUFFIX}"));
+        assert!(!setup.exists());
+    });
+}
+
+#[test]
+fn update_check_no_change() {
+    let version = env!("CARGO_PKG_VERSION");
+    update_setup(&|config, self_dist| {
+        config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+        output_release_file(self_dist, "1", version);
+        config.expect_stdout_ok(
+            &["rustup", "self", "update", "--check"],
+            &format!("Up to date : {version}"),
+        );
+    });
+}
+
 #[test]
 fn rustup_self_updates_trivial() {
     update_setup(&|config, _| {
@@ -474,6 +630,92 @@ fn rustup_no_self_update_with_specified_toolchain() {
     })
 }
 
+#[test]
+fn self_update_disabled_by_policy() {
+    update_setup(&|config, _| {
+        config.expect_ok(&["rustup", "set", "auto-self-update", "enable"]);
+        config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+        config.expect_ok_ex(
+            &["rustup", "set", "disable-self-update", "true"],
+            "",
+            "info: self-update is disabled by policy\n",
+        );
+
+        let bin = config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+        let before_hash = calc_hash(&bin);
+
+        config.expect_err(
+            &["rustup", "self", "update"],
+            "error: self-update is disabled by policy",
+        );
+        config.expect_stderr_ok(
+            &["rustup", "self", "update", "--check"],
+            "info: self-update is disabled by policy",
+        );
+        config.expect_ok(&["rustup", "update"]);
+
+        // The variable disables it as well, even with the setting lifted
+        config.expect_ok(&["rustup", "set", "disable-self-update", "false"]);
+        let out = config.run(
+            "rustup",
+            ["self", "update"],
+            &[("RUSTUP_DISABLE_SELF_UPDATE", "1")],
+        );
+        assert!(!out.ok);
+        assert!(out
+            .stderr
+            .contains("error: self-update is disabled by policy"));
+
+        let after_hash = calc_hash(&bin);
+        assert_eq!(before_hash, after_hash);
+    })
+}
+
+#[test]
+fn self_update_offline() {
+    update_setup(&|config, _| {
+        config.expect_ok(&["rustup-init", "-y", "--no-modify-path"]);
+        config.expect_err(
+            &["rustup", "--offline", "self", "update"],
+            "error: rustup cannot be updated in offline mode",
+        );
+        config.expect_err(
+            &["rustup", "--offline", "self", "update", "--check"],
+            "error: rustup cannot be updated in offline mode",
+        );
+        config.expect_not_stderr_ok(
+            &["rustup", "--offline", "update"],
+            "checking for self-update",
+        );
+    })
+}
+
+#[test]
+fn self_check_reports_broken_installation() {
+    setup_empty_installed(&|config| {
+        let bin = config.cargodir.join("bin");
+        let path = env::join_paths([&bin, &config.exedir]).unwrap();
+        let path = path.to_str().unwrap();
+
+        let out = config.run("rustup", ["self", "check"], &[("PATH", path)]);
+        assert!(out.ok);
+        assert!(out.stderr.contains("all point at rustup"));
+        assert!(out.stderr.contains("is in PATH"));
+
+        let out = config.run("rustup", ["self", "check"], &[]);
+        assert!(!out.ok);
+        assert!(out.stderr.contains("is not in PATH"));
+
+        fs::remove_file(bin.join(format!("cargo{EXE_SUFFIX}"))).unwrap();
+        let out = config.run("rustup", ["self", "check"], &[("PATH", path)]);
+        assert!(!out.ok);
+        assert!(out.stderr.contains("is missing"));
+        assert!(out
+            .stderr
+            .contains("run `rustup self update` to restore the proxies"));
+    });
+}
+
 #[test]
 fn rustup_self_update_exact() {
     update_setup(&|config, _| {
@@ -1,11 +1,15 @@
 //! Test cases of the rustup command, using v2 manifests, mostly
 //! derived from multirust/test-v2.sh
 
+use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
+use std::sync::{Arc, Mutex};
 
+use rustup::currentprocess::{self, TestProcess};
 use rustup::dist::dist::TargetTriple;
 use rustup::for_host;
+use rustup::notifications::{Notification, Notifier};
 use rustup::test::mock::clitools::{self, set_current_dist_date, Config, Scenario};
 use rustup::test::this_host_triple;
 use rustup_macros::integration_test as test;
@@ -48,6 +52,130 @@ fn install_toolchain_from_channel() {
     });
 }
 
+#[test]
+fn install_toolchain_from_component_tarballs() {
+    use rustup::dist::manifest::Manifest;
+
+    setup(&|config| {
+        // Move the nightly's archive directory, which holds its manifest and
+        // tarballs, out of the dist server
+        let dist = config.distdir.as_ref().unwrap().join("dist");
+        let manifest_str = fs::read_to_string(dist.join("channel-rust-nightly.toml")).unwrap();
+        let date = Manifest::parse(&manifest_str).unwrap().date;
+        let tarballs = config.distdir.as_ref().unwrap().join("tarballs");
+        fs::rename(dist.join(&date), &tarballs).unwrap();
+
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--component-tarballs",
+            &tarballs.to_string_lossy(),
+        ]);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+
+        // The toolchain keeps tracking the dist server
+        fs::rename(&tarballs, dist.join(&date)).unwrap();
+        config.expect_ok(&["rustup", "component", "add", "rust-src"]);
+        config.expect_stdout_ok(&["rustup", "component", "list", "--installed"], "rust-src");
+        config.expect_ok(&["rustup", "component", "remove", "rust-src"]);
+    });
+}
+
+#[test]
+fn install_toolchain_from_component_tarballs_checks_files() {
+    setup(&|config| {
+        let dist = config.distdir.as_ref().unwrap().join("dist");
+        let tarballs = config.distdir.as_ref().unwrap().join("tarballs");
+        fs::create_dir(&tarballs).unwrap();
+        let tarballs_arg = tarballs.to_string_lossy();
+        let args = [
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--component-tarballs",
+            &tarballs_arg,
+        ];
+        config.expect_err(
+            &args,
+            "'channel-rust-nightly.toml' not found in local dist directory",
+        );
+
+        fs::copy(
+            dist.join("channel-rust-nightly.toml"),
+            tarballs.join("channel-rust-nightly.toml"),
+        )
+        .unwrap();
+        fs::write(
+            tarballs.join("channel-rust-nightly.toml.sha256"),
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        config.expect_err(&args, "checksum failed");
+    });
+}
+
+#[test]
+fn install_verifies_manifest_signatures() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "set", "download-verify-signatures", "true"]);
+        config.expect_stderr_ok(
+            &["rustup", "--verbose", "toolchain", "install", "nightly"],
+            "signature verified for",
+        );
+    });
+}
+
+#[test]
+fn install_rejects_manifest_signed_by_untrusted_key() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "set", "download-verify-signatures", "true"]);
+        // Only the built-in keys are trusted without the mock signing key
+        let out = config.run(
+            "rustup",
+            ["toolchain", "install", "nightly"],
+            &[("RUSTUP_PGP_KEY", "")],
+        );
+        assert!(!out.ok);
+        assert!(out.stderr.contains("not signed by any trusted key"));
+
+        config.expect_ok(&["rustup", "set", "download-verify-signatures", "false"]);
+        let out = config.run(
+            "rustup",
+            ["toolchain", "install", "nightly"],
+            &[("RUSTUP_PGP_KEY", "")],
+        );
+        assert!(out.ok);
+    });
+}
+
+#[test]
+fn install_rejects_unsigned_local_manifest_unless_allowed() {
+    setup(&|config| {
+        let dist = config.distdir.as_ref().unwrap().join("dist");
+        fs::remove_file(dist.join("channel-rust-nightly.toml.asc")).unwrap();
+        config.expect_ok(&["rustup", "set", "download-verify-signatures", "true"]);
+        config.expect_err(
+            &["rustup", "toolchain", "install", "nightly"],
+            "channel-rust-nightly.toml' is not signed, and signatures are required",
+        );
+
+        let settings = config.rustupdir.join("settings.toml");
+        let contents = fs::read_to_string(&settings).unwrap().replace(
+            "verify-signatures = true",
+            "verify-signatures = true\nallow-unsigned-local = true",
+        );
+        fs::write(&settings, contents).unwrap();
+        config.expect_stderr_ok(
+            &["rustup", "toolchain", "install", "nightly"],
+            "is not signed, which is allowed as it is a local file",
+        );
+    });
+}
+
 #[test]
 fn install_toolchain_from_archive() {
     clitools::test(Scenario::ArchivesV2, &|config| {
@@ -111,6 +239,52 @@ fn default_existing_toolchain() {
     });
 }
 
+#[test]
+fn default_existing_toolchain_offline() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "update", "nightly"]);
+        let out = config.run(
+            "rustup",
+            ["default", "nightly"],
+            &[("RUSTUP_DIST_SERVER", "file:///no-such-dist-server")],
+        );
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out
+            .stderr
+            .contains(for_host!("using existing install for 'nightly-{0}'")));
+    });
+}
+
+#[test]
+fn toolchain_installed_is_false_for_links_to_removed_toolchains() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-2");
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "link",
+            "gone",
+            &path.to_string_lossy(),
+        ]);
+        fs::remove_dir_all(&path).unwrap();
+        // Still listed, so that it can be found and uninstalled
+        config.expect_stdout_ok(&["rustup", "toolchain", "list"], "gone");
+
+        let process = TestProcess::new(config.current_dir(), &["rustup"], HashMap::new(), "");
+        currentprocess::with(TestProcess::default().into(), || {
+            let cfg = rustup::Cfg::open(
+                config.rustupdir.rustupdir.clone(),
+                Some(config.cargodir.clone()),
+                process.into(),
+                Arc::new(|_| {}),
+            )
+            .unwrap();
+            let name = "gone".parse::<rustup::ToolchainName>().unwrap();
+            assert!(!cfg.toolchain_installed(&name).unwrap());
+        });
+    });
+}
+
 #[test]
 fn update_channel() {
     clitools::test(Scenario::ArchivesV2, &|config| {
@@ -154,6 +328,37 @@ fn list_toolchains() {
     });
 }
 
+#[test]
+fn list_toolchains_usage_shows_date_and_size() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        let out = config.run("rustup", ["toolchain", "list", "-v"], &[]);
+        assert!(out.ok);
+        let line = out.stdout.lines().next().unwrap();
+        let fields: Vec<_> = line.split('\t').collect();
+        assert_eq!(fields.len(), 2, "{line}");
+        assert!(fields[1].ends_with(&*for_host!("nightly-{0}")), "{line}");
+
+        let out = config.run("rustup", ["toolchain", "list", "-v", "--usage"], &[]);
+        assert!(out.ok);
+        let line = out.stdout.lines().next().unwrap();
+        let fields: Vec<_> = line.split('\t').collect();
+        assert_eq!(fields.len(), 4, "{line}");
+        assert_eq!(fields[0], for_host!("nightly-{0} (default)"));
+        assert!(fields[1].ends_with(&*for_host!("nightly-{0}")), "{line}");
+        assert!(
+            chrono::NaiveDate::parse_from_str(fields[2], "%Y-%m-%d").is_ok(),
+            "{line}"
+        );
+        assert!(fields[3].ends_with('B') && fields[3] != "0 B", "{line}");
+
+        let out = config.run("rustup", ["toolchain", "list", "--usage"], &[]);
+        assert!(out.ok);
+        let line = out.stdout.lines().next().unwrap();
+        assert_eq!(line.split('\t').count(), 3, "{line}");
+    });
+}
+
 #[test]
 fn list_toolchains_with_bogus_file() {
     // #520
@@ -185,6 +390,46 @@ fn remove_toolchain() {
     });
 }
 
+#[test]
+fn remove_toolchain_with_overrides() {
+    setup(&|config| {
+        let dir1 = config.customdir.join("project-1");
+        let dir2 = config.customdir.join("project-2");
+        fs::create_dir_all(&dir1).unwrap();
+        fs::create_dir_all(&dir2).unwrap();
+        let (dir1_str, dir2_str) = (dir1.to_str().unwrap(), dir2.to_str().unwrap());
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly", "stable"]);
+        config.expect_ok(&["rustup", "override", "set", "nightly", "--path", dir1_str]);
+        config.expect_ok(&["rustup", "override", "set", "stable", "--path", dir2_str]);
+
+        let out = config.run("rustup", ["toolchain", "remove", "nightly"], &[]);
+        assert!(out.ok);
+        assert!(out.stderr.contains(&format!(
+            "warning: the override for '{}' is still '{}', which is no longer installed",
+            dir1.canonicalize().unwrap().display(),
+            for_host!("nightly-{0}")
+        )));
+        assert!(!out
+            .stderr
+            .contains(&*dir2.canonicalize().unwrap().to_string_lossy()));
+        config.expect_stdout_ok(&["rustup", "override", "list"], "project-1");
+
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        config.expect_stderr_ok(
+            &[
+                "rustup",
+                "toolchain",
+                "remove",
+                "nightly",
+                "--prune-overrides",
+            ],
+            "info: override toolchain for '",
+        );
+        config.expect_not_stdout_ok(&["rustup", "override", "list"], "project-1");
+        config.expect_stdout_ok(&["rustup", "override", "list"], "project-2");
+    });
+}
+
 // Issue #2873
 #[test]
 fn remove_toolchain_ignore_trailing_slash() {
@@ -320,150 +565,612 @@ fn bad_sha_on_installer() {
 }
 
 #[test]
-fn install_override_toolchain_from_channel() {
+fn missing_installer_on_local_mirror() {
     setup(&|config| {
-        config.expect_ok(&["rustup", "override", "add", "nightly"]);
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
-        config.expect_ok(&["rustup", "override", "add", "beta"]);
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
-        config.expect_ok(&["rustup", "override", "add", "stable"]);
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
-    });
-}
-
-#[test]
-fn install_override_toolchain_from_archive() {
-    clitools::test(Scenario::ArchivesV2, &|config| {
-        config.expect_ok(&["rustup", "override", "add", "nightly-2015-01-01"]);
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1");
-        config.expect_ok(&["rustup", "override", "add", "beta-2015-01-01"]);
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.1.0");
-        config.expect_ok(&["rustup", "override", "add", "stable-2015-01-01"]);
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.0.0");
+        let dir = config.distdir.as_ref().unwrap().join("dist/2015-01-02");
+        for file in fs::read_dir(dir).unwrap() {
+            let path = file.unwrap().path();
+            let filename = path.file_name().unwrap().to_string_lossy();
+            if filename.starts_with("rustc-nightly-") {
+                fs::remove_file(&path).unwrap();
+            }
+        }
+        config.expect_err(
+            &["rustup", "default", "nightly"],
+            for_host!("component 'rustc-{0}' not found on local mirror"),
+        );
     });
 }
 
 #[test]
-fn install_override_toolchain_from_version() {
+fn missing_installer_with_sequential_downloads() {
     setup(&|config| {
-        config.expect_ok(&["rustup", "override", "add", "1.1.0"]);
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+        let dir = config.distdir.as_ref().unwrap().join("dist/2015-01-02");
+        for file in fs::read_dir(dir).unwrap() {
+            let path = file.unwrap().path();
+            let filename = path.file_name().unwrap().to_string_lossy();
+            if filename.starts_with("cargo-nightly-") {
+                fs::remove_file(&path).unwrap();
+            }
+        }
+        config.expect_stderr_ok(
+            &["rustup", "set", "download-concurrency", "1"],
+            "info: download concurrency set to 1",
+        );
+        config.expect_err(
+            &["rustup", "default", "nightly"],
+            for_host!("component 'cargo-{0}' not found on local mirror"),
+        );
     });
 }
 
 #[test]
-fn override_overrides_default() {
+fn list_components_matches_component_list() {
     setup(&|config| {
-        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
         config.expect_ok(&["rustup", "default", "nightly"]);
-        config.change_dir(tempdir.path(), &|config| {
-            config.expect_ok(&["rustup", "override", "add", "beta"]);
-            config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
-        });
+        config.expect_ok(&["rustup", "component", "add", "rust-src"]);
+
+        let mut vars = HashMap::new();
+        config.rustupdir.apply(&mut vars);
+        let process = TestProcess::new(config.current_dir(), &["rustup"], vars, "");
+        let components = currentprocess::with(process.into(), || {
+            rustup::list_components(&config.current_dir())
+        })
+        .unwrap();
+
+        let rust_src = components.iter().find(|c| c.name == "rust-src").unwrap();
+        assert!(rust_src.installed && rust_src.available);
+        let rust_std = components
+            .iter()
+            .find(|c| c.name == "rust-std" && c.target.as_deref() == Some(&this_host_triple()))
+            .unwrap();
+        assert!(rust_std.installed);
+
+        let installed: Vec<_> = components
+            .iter()
+            .filter(|c| c.installed)
+            .map(|c| c.full_name() + "\n")
+            .collect();
+        config.expect_ok_ex(
+            &["rustup", "component", "list", "--installed"],
+            &installed.concat(),
+            "",
+        );
     });
 }
 
 #[test]
-fn multiple_overrides() {
+fn list_components_filters() {
     setup(&|config| {
-        let tempdir1 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
-        let tempdir2 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
-
         config.expect_ok(&["rustup", "default", "nightly"]);
-        config.change_dir(tempdir1.path(), &|config| {
-            config.expect_ok(&["rustup", "override", "add", "beta"]);
-        });
-        config.change_dir(tempdir2.path(), &|config| {
-            config.expect_ok(&["rustup", "override", "add", "stable"]);
-        });
+        let cross_std = format!("rust-std-{}", clitools::CROSS_ARCH1);
 
-        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        config.expect_stdout_ok(
+            &["rustup", "component", "list", "--available"],
+            "rust-src\n",
+        );
+        config.expect_not_stdout_ok(&["rustup", "component", "list", "--available"], "cargo");
 
-        config.change_dir(tempdir1.path(), &|config| {
-            config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
-        });
-        config.change_dir(tempdir2.path(), &|config| {
-            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
-        });
+        let both = ["rustup", "component", "list", "--installed", "--available"];
+        config.expect_stdout_ok(&both, "cargo-");
+        config.expect_not_stdout_ok(&both, " (installed)");
+        config.expect_stdout_ok(&both, "rust-src\n");
+
+        let for_cross = [
+            "rustup",
+            "component",
+            "list",
+            "--available",
+            "--target",
+            clitools::CROSS_ARCH1,
+        ];
+        config.expect_stdout_ok(&for_cross, &cross_std);
+        config.expect_stdout_ok(&for_cross, "rust-src\n");
+        config.expect_not_stdout_ok(&for_cross, &format!("rust-std-{}", clitools::CROSS_ARCH2));
+
+        config.expect_ok_ex(
+            &[
+                "rustup",
+                "component",
+                "list",
+                "--installed",
+                "--target",
+                clitools::CROSS_ARCH1,
+            ],
+            "",
+            "",
+        );
     });
 }
 
-// #316
 #[test]
-#[cfg(windows)]
-fn override_windows_root() {
+fn install_toolchain_reports_progress_to_notifier() {
     setup(&|config| {
-        use std::path::{Component, PathBuf};
+        let installing = Arc::new(Mutex::new(Vec::new()));
+        let downloaded = Arc::new(Mutex::new(0));
+        let recorder = installing.clone();
+        let counter = downloaded.clone();
+        let notifier: Arc<dyn Notifier> = Arc::new(move |n: Notification<'_>| match n {
+            Notification::Install(rustup::dist::Notification::InstallingComponent(name, ..)) => {
+                recorder.lock().unwrap().push(name.to_owned());
+            }
+            Notification::Install(rustup::dist::Notification::Utils(
+                rustup::utils::Notification::DownloadDataReceived(len),
+            )) => *counter.lock().unwrap() += len,
+            _ => {}
+        });
 
-        let cwd = config.current_dir();
-        let prefix = cwd.components().next().unwrap();
-        let prefix = match prefix {
-            Component::Prefix(p) => p,
-            _ => panic!(),
-        };
+        let mut vars = HashMap::new();
+        config.env(&mut vars);
+        let process = TestProcess::new(config.current_dir(), &["rustup"], vars, "");
+        currentprocess::with(process.into(), || {
+            rustup::install_toolchain("nightly", notifier)
+        })
+        .unwrap();
 
-        // This value is probably "C:"
-        // Really sketchy to be messing with C:\ in a test...
-        let prefix = prefix.as_os_str().to_str().unwrap();
-        let prefix = format!("{prefix}\\");
-        config.change_dir(&PathBuf::from(&prefix), &|config| {
-            config.expect_ok(&["rustup", "default", "stable"]);
-            config.expect_ok(&["rustup", "override", "add", "nightly"]);
-            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
-            config.expect_ok(&["rustup", "override", "remove"]);
-            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
-        });
+        assert!(installing.lock().unwrap().iter().any(|c| c == "rustc"));
+        assert!(*downloaded.lock().unwrap() > 0);
+        config.expect_stdout_ok(&["rustup", "toolchain", "list"], "nightly");
     });
 }
 
 #[test]
-fn change_override() {
+fn install_toolchain_returns_typed_errors() {
     setup(&|config| {
-        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
-        config.change_dir(tempdir.path(), &|config| {
-            config.expect_ok(&["rustup", "override", "add", "nightly"]);
-            config.expect_ok(&["rustup", "override", "add", "beta"]);
-            config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
-        });
+        let mut vars = HashMap::new();
+        config.env(&mut vars);
+        let process = TestProcess::new(config.current_dir(), &["rustup"], vars, "");
+        let err = currentprocess::with(process.into(), || {
+            rustup::install_toolchain("nightly-2099-01-01", Arc::new(|_: Notification<'_>| {}))
+        })
+        .unwrap_err();
+        assert!(
+            matches!(err, rustup::InstallError::ManifestNotFound(_)),
+            "{err:?}"
+        );
+        assert!(err.to_string().contains("no release found"), "{err}");
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let err = rustup::InstallError::from(anyhow::Error::from(denied));
+        assert!(
+            matches!(err, rustup::InstallError::PermissionDenied(_)),
+            "{err:?}"
+        );
     });
 }
 
 #[test]
-fn remove_override_no_default() {
+fn download_concurrency_is_saved() {
     setup(&|config| {
-        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
-        config.change_dir(tempdir.path(), &|config| {
-            config.expect_ok(&["rustup", "override", "add", "nightly"]);
-            config.expect_ok(&["rustup", "override", "remove"]);
-            config.expect_err(
-                &["rustc"],
-                "rustup could not choose a version of rustc to run",
-            );
-        });
+        config.expect_ok(&["rustup", "set", "download-concurrency", "2"]);
+        let settings = fs::read_to_string(config.rustupdir.join("settings.toml")).unwrap();
+        assert!(
+            settings.contains("[download]\nconcurrency = 2\n"),
+            "{settings}"
+        );
+
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_executable("rustc");
+        config.expect_component_executable("cargo");
+
+        config.expect_err(
+            &["rustup", "set", "download-concurrency", "0"],
+            "0 is not in 1..=65535",
+        );
     });
 }
 
 #[test]
-fn remove_override_with_default() {
+fn download_retry_settings_are_saved() {
     setup(&|config| {
-        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
-        config.change_dir(tempdir.path(), &|config| {
-            config.expect_ok(&["rustup", "default", "nightly"]);
-            config.expect_ok(&["rustup", "override", "add", "beta"]);
-            config.expect_ok(&["rustup", "override", "remove"]);
-            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
-        });
+        config.expect_ok(&["rustup", "set", "download-retries", "5"]);
+        config.expect_ok(&["rustup", "set", "download-retry-backoff-ms", "0"]);
+        let settings = fs::read_to_string(config.rustupdir.join("settings.toml")).unwrap();
+        assert!(
+            settings.contains("[download]\nretries = 5\nretry-backoff-ms = 0\n"),
+            "{settings}"
+        );
+
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_executable("rustc");
     });
 }
 
 #[test]
-fn remove_override_with_multiple_overrides() {
+fn download_max_rate_is_saved() {
     setup(&|config| {
-        let tempdir1 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
-        let tempdir2 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
-        config.expect_ok(&["rustup", "default", "nightly"]);
-        config.change_dir(tempdir1.path(), &|config| {
-            config.expect_ok(&["rustup", "override", "add", "beta"]);
+        config.expect_stderr_ok(
+            &["rustup", "set", "download-max-rate", "2M"],
+            "info: download rate limited to 2097152 bytes/s",
+        );
+        let settings_path = config.rustupdir.join("settings.toml");
+        let settings = fs::read_to_string(&settings_path).unwrap();
+        assert!(
+            settings.contains("[download]\nmax-rate = 2097152\n"),
+            "{settings}"
+        );
+
+        // The setting may also be written by hand with a suffix
+        let settings = settings.replace("max-rate = 2097152", "max-rate = \"500K\"");
+        fs::write(&settings_path, settings).unwrap();
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_executable("rustc");
+
+        config.expect_err(
+            &["rustup", "set", "download-max-rate", "2MB"],
+            "invalid download rate '2MB'",
+        );
+        config.expect_stderr_ok(
+            &["rustup", "set", "download-max-rate", "0"],
+            "info: download rate limit removed",
+        );
+    });
+}
+
+#[test]
+fn download_cache_size_is_saved() {
+    setup(&|config| {
+        config.expect_stderr_ok(
+            &["rustup", "set", "download-cache-size", "4G"],
+            "info: download cache limited to 4294967296 bytes",
+        );
+        let settings = fs::read_to_string(config.rustupdir.join("settings.toml")).unwrap();
+        assert!(
+            settings.contains("[download]\ncache-size = 4294967296\n"),
+            "{settings}"
+        );
+
+        config.expect_err(
+            &["rustup", "set", "download-cache-size", "4GB"],
+            "invalid download cache size '4GB'",
+        );
+        config.expect_stderr_ok(
+            &["rustup", "set", "download-cache-size", "0"],
+            "info: download cache size limit removed",
+        );
+    });
+}
+
+#[test]
+fn download_stream_extract_is_saved() {
+    setup(&|config| {
+        config.expect_stderr_ok(
+            &["rustup", "set", "download-stream-extract", "true"],
+            "info: components will be unpacked as they download",
+        );
+        let settings = fs::read_to_string(config.rustupdir.join("settings.toml")).unwrap();
+        assert!(
+            settings.contains("[download]\nstream-extract = true\n"),
+            "{settings}"
+        );
+
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_executable("rustc");
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn post_install_hook_runs_with_the_new_toolchain() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        let out = config.customdir.join("hook.out");
+        let settings = config.rustupdir.join("settings.toml");
+        let mut contents = fs::read_to_string(&settings).unwrap();
+        contents.push_str(&format!(
+            "\n[hooks]\npost-install = \"echo $RUSTUP_TOOLCHAIN > '{0}' && rustc --version >> '{0}'\"\n",
+            out.display()
+        ));
+        fs::write(&settings, contents).unwrap();
+
+        config.expect_stderr_ok(
+            &["rustup", "toolchain", "install", "nightly"],
+            for_host!("info: running the post-install hook for 'nightly-{0}'"),
+        );
+        let ran = fs::read_to_string(&out).unwrap();
+        assert!(ran.contains(for_host!("nightly-{0}\n")), "{ran}");
+        assert!(ran.contains("hash-nightly-2"), "{ran}");
+
+        // Nothing is installed or updated, so the hook is not run
+        fs::remove_file(&out).unwrap();
+        config.expect_ok(&["rustup", "update", "nightly"]);
+        assert!(!out.exists());
+    });
+}
+
+#[test]
+fn failing_post_install_hook_fails_the_install() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        let settings = config.rustupdir.join("settings.toml");
+        let contents = fs::read_to_string(&settings).unwrap();
+        fs::write(
+            &settings,
+            format!("{contents}\n[hooks]\npost-install = \"exit 3\"\n"),
+        )
+        .unwrap();
+        config.expect_err(
+            &["rustup", "toolchain", "install", "nightly"],
+            for_host!("the post-install hook for toolchain 'nightly-{0}' failed"),
+        );
+
+        fs::write(
+            &settings,
+            format!("{contents}\n[hooks]\npost-install = \"exit 3\"\npost-install-fatal = false\n"),
+        )
+        .unwrap();
+        config.expect_stderr_ok(
+            &["rustup", "toolchain", "install", "beta"],
+            for_host!("the post-install hook for toolchain 'beta-{0}' failed"),
+        );
+        config.expect_ok(&["rustup", "default", "beta"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
+    });
+}
+
+#[test]
+fn download_ca_bundle_is_saved_and_checked() {
+    setup(&|config| {
+        let bundle = config.customdir.join("ca-bundle.pem");
+        config.expect_err(
+            &[
+                "rustup",
+                "set",
+                "download-ca-bundle",
+                bundle.to_str().unwrap(),
+            ],
+            &format!("could not use the CA bundle '{}'", bundle.display()),
+        );
+        fs::write(&bundle, "not a certificate\n").unwrap();
+        config.expect_err(
+            &[
+                "rustup",
+                "set",
+                "download-ca-bundle",
+                bundle.to_str().unwrap(),
+            ],
+            "it contains no PEM certificates",
+        );
+
+        fs::write(
+            &bundle,
+            "-----BEGIN CERTIFICATE-----\n\
+             MIIBiDCCAS+gAwIBAgIURfXyDtosz1x0UfiBB32ymA0BdXgwCgYIKoZIzj0EAwIw\n\
+             GTEXMBUGA1UEAwwOcnVzdHVwIHRlc3QgQ0EwIBcNMjYxMDE0MTIzNjQ3WhgPMjEy\n\
+             NjA5MjAxMjM2NDdaMBkxFzAVBgNVBAMMDnJ1c3R1cCB0ZXN0IENBMFkwEwYHKoZI\n\
+             zj0CAQYIKoZIzj0DAQcDQgAEFmjX7PKKbVeo+Gz4TCHwI6zPjAqW1E10W/NTSy1v\n\
+             f2Vq8HHhkQcJfwOKrIpb/XIpycxLP0DEJDMfP8ZCGFOulKNTMFEwHQYDVR0OBBYE\n\
+             FC/XcOuhdWzFWcrwYX8XsGphrHoKMB8GA1UdIwQYMBaAFC/XcOuhdWzFWcrwYX8X\n\
+             sGphrHoKMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgX8fyfvd7\n\
+             WMm1S0m+7jC6GaUvG5bvGz7N8zxrE/wriTICIEz7jYqSd2mK5PLu8DfPSOgMg5g9\n\
+             2mQGLjt4g6ayEJ8R\n\
+             -----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        config.expect_stderr_ok(
+            &[
+                "rustup",
+                "set",
+                "download-ca-bundle",
+                bundle.to_str().unwrap(),
+            ],
+            &format!(
+                "info: downloads will trust the CA bundle '{}'",
+                bundle.display()
+            ),
+        );
+        let settings = fs::read_to_string(config.rustupdir.join("settings.toml")).unwrap();
+        assert!(settings.contains("ca-bundle = "), "{settings}");
+        // Local mirrors are read without TLS
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+
+        // A bundle that has since gone is reported as such, rather than as a
+        // failed TLS handshake
+        fs::remove_file(&bundle).unwrap();
+        let out = config.run(
+            "rustup",
+            ["toolchain", "install", "stable"],
+            &[("RUSTUP_DIST_SERVER", "https://example.invalid")],
+        );
+        assert!(!out.ok);
+        assert!(
+            out.stderr.contains(&format!(
+                "could not use the CA bundle '{}'",
+                bundle.display()
+            )),
+            "{}",
+            out.stderr
+        );
+
+        config.expect_stderr_ok(
+            &["rustup", "set", "download-ca-bundle", "none"],
+            "info: download CA bundle removed",
+        );
+    });
+}
+
+#[test]
+fn download_cache_size_evicts_old_downloads() {
+    setup(&|config| {
+        let downloads = config.rustupdir.join("downloads");
+        fs::create_dir_all(&downloads).unwrap();
+        let stale = downloads.join("stale");
+        fs::write(&stale, "stale").unwrap();
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        assert!(stale.exists());
+
+        config.expect_ok(&["rustup", "set", "download-cache-size", "1"]);
+        config.expect_ok(&["rustup", "toolchain", "install", "stable"]);
+        assert!(!stale.exists());
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn local_mirror_with_relative_symlinks() {
+    setup(&|config| {
+        let dist = config.distdir.as_ref().unwrap().join("dist");
+        for name in [
+            "channel-rust-nightly.toml",
+            "channel-rust-nightly.toml.sha256",
+        ] {
+            fs::remove_file(dist.join(name)).unwrap();
+            std::os::unix::fs::symlink(
+                std::path::Path::new("2015-01-02").join(name),
+                dist.join(name),
+            )
+            .unwrap();
+        }
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+    });
+}
+
+#[test]
+fn install_override_toolchain_from_channel() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "override", "add", "nightly"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        config.expect_ok(&["rustup", "override", "add", "beta"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
+        config.expect_ok(&["rustup", "override", "add", "stable"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+    });
+}
+
+#[test]
+fn install_override_toolchain_from_archive() {
+    clitools::test(Scenario::ArchivesV2, &|config| {
+        config.expect_ok(&["rustup", "override", "add", "nightly-2015-01-01"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1");
+        config.expect_ok(&["rustup", "override", "add", "beta-2015-01-01"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.1.0");
+        config.expect_ok(&["rustup", "override", "add", "stable-2015-01-01"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.0.0");
+    });
+}
+
+#[test]
+fn install_override_toolchain_from_version() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "override", "add", "1.1.0"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+    });
+}
+
+#[test]
+fn override_overrides_default() {
+    setup(&|config| {
+        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.change_dir(tempdir.path(), &|config| {
+            config.expect_ok(&["rustup", "override", "add", "beta"]);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
+        });
+    });
+}
+
+#[test]
+fn multiple_overrides() {
+    setup(&|config| {
+        let tempdir1 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        let tempdir2 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.change_dir(tempdir1.path(), &|config| {
+            config.expect_ok(&["rustup", "override", "add", "beta"]);
+        });
+        config.change_dir(tempdir2.path(), &|config| {
+            config.expect_ok(&["rustup", "override", "add", "stable"]);
+        });
+
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+
+        config.change_dir(tempdir1.path(), &|config| {
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
+        });
+        config.change_dir(tempdir2.path(), &|config| {
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+        });
+    });
+}
+
+// #316
+#[test]
+#[cfg(windows)]
+fn override_windows_root() {
+    setup(&|config| {
+        use std::path::{Component, PathBuf};
+
+        let cwd = config.current_dir();
+        let prefix = cwd.components().next().unwrap();
+        let prefix = match prefix {
+            Component::Prefix(p) => p,
+            _ => panic!(),
+        };
+
+        // This value is probably "C:"
+        // Really sketchy to be messing with C:\ in a test...
+        let prefix = prefix.as_os_str().to_str().unwrap();
+        let prefix = format!("{prefix}\\");
+        config.change_dir(&PathBuf::from(&prefix), &|config| {
+            config.expect_ok(&["rustup", "default", "stable"]);
+            config.expect_ok(&["rustup", "override", "add", "nightly"]);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+            config.expect_ok(&["rustup", "override", "remove"]);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-stable-1.1.0");
+        });
+    });
+}
+
+#[test]
+fn change_override() {
+    setup(&|config| {
+        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        config.change_dir(tempdir.path(), &|config| {
+            config.expect_ok(&["rustup", "override", "add", "nightly"]);
+            config.expect_ok(&["rustup", "override", "add", "beta"]);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-beta-1.2.0");
+        });
+    });
+}
+
+#[test]
+fn remove_override_no_default() {
+    setup(&|config| {
+        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        config.change_dir(tempdir.path(), &|config| {
+            config.expect_ok(&["rustup", "override", "add", "nightly"]);
+            config.expect_ok(&["rustup", "override", "remove"]);
+            config.expect_err(
+                &["rustc"],
+                "rustup could not choose a version of rustc to run",
+            );
+        });
+    });
+}
+
+#[test]
+fn remove_override_with_default() {
+    setup(&|config| {
+        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        config.change_dir(tempdir.path(), &|config| {
+            config.expect_ok(&["rustup", "default", "nightly"]);
+            config.expect_ok(&["rustup", "override", "add", "beta"]);
+            config.expect_ok(&["rustup", "override", "remove"]);
+            config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        });
+    });
+}
+
+#[test]
+fn remove_override_with_multiple_overrides() {
+    setup(&|config| {
+        let tempdir1 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        let tempdir2 = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.change_dir(tempdir1.path(), &|config| {
+            config.expect_ok(&["rustup", "override", "add", "beta"]);
         });
         config.change_dir(tempdir2.path(), &|config| {
             config.expect_ok(&["rustup", "override", "add", "stable"]);
@@ -499,6 +1206,54 @@ fn update_on_channel_when_date_has_changed() {
     });
 }
 
+#[test]
+fn update_refuses_downgrade() {
+    clitools::test(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-02");
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        // The newer build has been rolled back
+        set_current_dist_date(config, "2015-01-01");
+        config.expect_err(
+            &["rustup", "update", "nightly"],
+            "would be downgraded from the build of 2015-01-02 to the older one of 2015-01-01",
+        );
+        config.expect_stderr_ok(
+            &["rustup", "update"],
+            "would be downgraded from the build of 2015-01-02",
+        );
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+
+        config.expect_ok(&["rustup", "update", "nightly", "--allow-downgrade"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1");
+    });
+}
+
+#[test]
+fn update_forced_downgrade() {
+    clitools::test(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-02");
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        set_current_dist_date(config, "2015-01-01");
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly", "--force"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1");
+    });
+}
+
+#[test]
+fn install_reports_extracting_components() {
+    setup(&|config| {
+        config.expect_stderr_ok(
+            &["rustup", "-v", "toolchain", "install", "nightly"],
+            "verbose: extracting component 'rustc'",
+        );
+        config.expect_ok(&["rustup", "--no-progress", "toolchain", "install", "beta"]);
+        config.expect_stdout_ok(
+            &["rustup", "run", "beta", "rustc", "--version"],
+            "hash-beta",
+        );
+    });
+}
+
 #[test]
 fn run_command() {
     setup(&|config| {
@@ -586,35 +1341,160 @@ fn list_targets_v1_toolchain() {
 }
 
 #[test]
-fn list_targets_custom_toolchain() {
+fn list_components_custom_toolchain() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-1");
+        let exe = std::env::consts::EXE_SUFFIX;
+        fs::write(path.join(format!("bin/cargo-clippy{exe}")), "").unwrap();
+        fs::create_dir_all(path.join("lib/rustlib/src/rust")).unwrap();
+        fs::create_dir_all(path.join(for_host!("lib/rustlib/{}/lib"))).unwrap();
+        let path = path.to_string_lossy();
+        config.expect_ok(&["rustup", "toolchain", "link", "default-from-path", &path]);
+        config.expect_ok(&["rustup", "default", "default-from-path"]);
+        config.expect_stdout_ok(
+            &["rustup", "component", "list", "--installed"],
+            for_host!("clippy\nrust-src\nrust-std-{}\nrustc\n"),
+        );
+        let out = config.run("rustup", ["component", "list", "--available"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        assert_eq!(out.stdout, "");
+    });
+}
+
+#[test]
+fn list_targets_custom_toolchain() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-1");
+        let path = path.to_string_lossy();
+        config.expect_ok(&["rustup", "toolchain", "link", "default-from-path", &path]);
+        config.expect_ok(&["rustup", "default", "default-from-path"]);
+        config.expect_err(
+            &["rustup", "target", "list"],
+            "toolchain 'default-from-path' does not support components",
+        );
+    });
+}
+
+#[test]
+fn list_targets() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stdout_ok(&["rustup", "target", "list"], clitools::CROSS_ARCH1);
+        config.expect_stdout_ok(&["rustup", "target", "list"], clitools::CROSS_ARCH2);
+    });
+}
+
+#[test]
+fn list_installed_targets() {
+    setup(&|config| {
+        let trip = this_host_triple();
+
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stdout_ok(&["rustup", "target", "list", "--installed"], &trip);
+    });
+}
+
+#[test]
+fn list_installed_targets_of_all_toolchains() {
     setup(&|config| {
+        let trip = this_host_triple();
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_ok(&["rustup", "target", "add", clitools::CROSS_ARCH1]);
+        config.expect_ok(&["rustup", "toolchain", "install", "stable"]);
         let path = config.customdir.join("custom-1");
-        let path = path.to_string_lossy();
-        config.expect_ok(&["rustup", "toolchain", "link", "default-from-path", &path]);
-        config.expect_ok(&["rustup", "default", "default-from-path"]);
-        config.expect_err(
-            &["rustup", "target", "list"],
-            "toolchain 'default-from-path' does not support components",
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "link",
+            "custom",
+            &path.to_string_lossy(),
+        ]);
+
+        let out = config.run("rustup", ["target", "list", "--all-toolchains"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        let (first, second) = if clitools::CROSS_ARCH1 < trip.as_str() {
+            (clitools::CROSS_ARCH1, trip.as_str())
+        } else {
+            (trip.as_str(), clitools::CROSS_ARCH1)
+        };
+        assert_eq!(
+            out.stdout,
+            format!("stable-{trip}\n  {trip}\nnightly-{trip}\n  {first}\n  {second}\n")
+        );
+
+        let out = config.run(
+            "rustup",
+            ["target", "list", "--all-toolchains", "--json"],
+            &[],
+        );
+        assert!(out.ok, "{}", out.stderr);
+        let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                { "toolchain": format!("stable-{trip}"), "targets": [trip] },
+                { "toolchain": format!("nightly-{trip}"), "targets": [first, second] },
+            ])
         );
     });
 }
 
 #[test]
-fn list_targets() {
+fn list_installed_targets_of_all_toolchains_skips_broken_ones() {
     setup(&|config| {
-        config.expect_ok(&["rustup", "default", "nightly"]);
-        config.expect_stdout_ok(&["rustup", "target", "list"], clitools::CROSS_ARCH1);
-        config.expect_stdout_ok(&["rustup", "target", "list"], clitools::CROSS_ARCH2);
+        let trip = this_host_triple();
+        config.expect_ok(&["rustup", "toolchain", "install", "stable", "nightly"]);
+        let manifest = config
+            .rustupdir
+            .join("toolchains")
+            .join(format!("stable-{trip}"))
+            .join("lib/rustlib/multirust-channel-manifest.toml");
+        fs::remove_file(manifest).unwrap();
+
+        let out = config.run("rustup", ["target", "list", "--all-toolchains"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        assert_eq!(out.stdout, format!("nightly-{trip}\n  {trip}\n"));
+        assert!(out.stderr.contains(&format!(
+            "warning: could not list the targets of 'stable-{trip}'"
+        )));
+
+        let out = config.run(
+            "rustup",
+            ["target", "list", "--all-toolchains", "--json"],
+            &[],
+        );
+        assert!(out.ok, "{}", out.stderr);
+        let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+        assert_eq!(json[0]["toolchain"], format!("stable-{trip}"));
+        assert_eq!(json[0]["targets"], serde_json::json!([]));
+        assert!(json[0]["error"].is_string());
+        assert_eq!(
+            json[1],
+            serde_json::json!({ "toolchain": format!("nightly-{trip}"), "targets": [trip] })
+        );
     });
 }
 
 #[test]
-fn list_installed_targets() {
+fn list_targets_json() {
     setup(&|config| {
         let trip = this_host_triple();
-
         config.expect_ok(&["rustup", "default", "nightly"]);
-        config.expect_stdout_ok(&["rustup", "target", "list", "--installed"], &trip);
+
+        let out = config.run("rustup", ["target", "list", "--installed", "--json"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{ "target": trip, "installed": true }])
+        );
+
+        let out = config.run("rustup", ["target", "list", "--json"], &[]);
+        let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+        assert!(json
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!({ "target": clitools::CROSS_ARCH1, "installed": false })));
     });
 }
 
@@ -688,6 +1568,118 @@ fn add_all_targets_fail() {
     });
 }
 
+#[test]
+fn add_all_targets_with_flag() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_ok(&["rustup", "target", "add", "--all", "--yes"]);
+        for arch in [clitools::CROSS_ARCH1, clitools::CROSS_ARCH2] {
+            let path = format!(
+                "toolchains/nightly-{}/lib/rustlib/{arch}/lib/libstd.rlib",
+                this_host_triple(),
+            );
+            assert!(config.rustupdir.has(path));
+        }
+        config.expect_stderr_ok(
+            &["rustup", "target", "add", "--all"],
+            "every available target is installed",
+        );
+    });
+}
+
+#[test]
+fn add_all_targets_asks_first() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let out = config.run("rustup", ["target", "add", "--all"], &[]);
+        assert!(!out.ok);
+        assert!(out.stdout.contains(for_host!(
+            "2 targets will be installed for 'nightly-{0}', downloading "
+        )));
+        assert!(out
+            .stderr
+            .contains("unable to read from stdin for confirmation"));
+        let path = format!(
+            "toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+            this_host_triple(),
+            clitools::CROSS_ARCH1
+        );
+        assert!(!config.rustupdir.has(path));
+    });
+}
+
+#[test]
+fn add_all_targets_keeps_going() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let dir = config.distdir.as_ref().unwrap().join("dist/2015-01-02");
+        let broken = format!("rust-std-nightly-{}", clitools::CROSS_ARCH1);
+        for file in fs::read_dir(dir).unwrap() {
+            let path = file.unwrap().path();
+            if path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with(&broken)
+            {
+                fs::remove_file(&path).unwrap();
+            }
+        }
+        config.expect_err(
+            &["rustup", "target", "add", "--all", "--yes"],
+            &format!(
+                "these targets could not be installed: {}",
+                clitools::CROSS_ARCH1
+            ),
+        );
+        let path = format!(
+            "toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+            this_host_triple(),
+            clitools::CROSS_ARCH2
+        );
+        assert!(config.rustupdir.has(path));
+    });
+}
+
+#[test]
+fn add_targets_from_toolchain() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "toolchain", "add", "stable"]);
+        config.expect_ok(&[
+            "rustup",
+            "target",
+            "add",
+            "--toolchain",
+            "stable",
+            clitools::CROSS_ARCH1,
+            clitools::CROSS_ARCH2,
+        ]);
+        make_component_unavailable(config, "rust-std", clitools::CROSS_ARCH2);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+
+        config.expect_stderr_ok(
+            &["rustup", "target", "add", "--from-toolchain", "stable"],
+            &format!(
+                "warning: skipping target '{}', which is not available for 'nightly-{}'",
+                clitools::CROSS_ARCH2,
+                this_host_triple(),
+            ),
+        );
+        let path = format!(
+            "toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+            this_host_triple(),
+            clitools::CROSS_ARCH1
+        );
+        assert!(config.rustupdir.has(path));
+        let path = format!(
+            "toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+            this_host_triple(),
+            clitools::CROSS_ARCH2
+        );
+        assert!(!config.rustupdir.has(path));
+    });
+}
+
 #[test]
 fn add_target_by_component_add() {
     setup(&|config| {
@@ -832,6 +1824,59 @@ fn add_target_host() {
     });
 }
 
+#[test]
+fn add_target_host_is_a_no_op() {
+    setup(&|config| {
+        let trip = this_host_triple();
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let out = config.run("rustup", ["target", "add", &trip], &[]);
+        assert!(out.ok);
+        assert!(out.stderr.contains(&format!(
+            "info: target '{trip}' is the host of the toolchain, '{trip}', whose standard library comes with it"
+        )));
+        assert!(!out.stderr.contains("downloading component"));
+    });
+}
+
+#[test]
+fn add_target_host_by_multiarch_name() {
+    let trip = this_host_triple();
+    let parts: Vec<_> = trip.split('-').collect();
+    let [arch, "unknown" | "pc", os, env] = parts[..] else {
+        return;
+    };
+    let multiarch = format!("{arch}-{os}-{env}");
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stderr_ok(
+            &["rustup", "target", "add", &multiarch],
+            &format!("target '{multiarch}' is the host of the toolchain, '{trip}'"),
+        );
+        config.expect_stderr_ok(
+            &[
+                "rustup",
+                "toolchain",
+                "install",
+                "beta",
+                "--target",
+                &multiarch,
+            ],
+            &format!("target '{multiarch}' is the host of the toolchain, '{trip}'"),
+        );
+        config.expect_stdout_ok(
+            &[
+                "rustup",
+                "target",
+                "list",
+                "--toolchain",
+                "beta",
+                "--installed",
+            ],
+            &trip,
+        );
+    });
+}
+
 #[test]
 fn remove_target() {
     setup(&|config| {
@@ -859,6 +1904,68 @@ fn remove_target() {
     });
 }
 
+#[test]
+fn remove_target_cargo_builds_for() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_ok(&["rustup", "target", "add", clitools::CROSS_ARCH1]);
+        let libstd = format!(
+            "toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+            this_host_triple(),
+            clitools::CROSS_ARCH1
+        );
+
+        let project = config.current_dir().join("project");
+        fs::create_dir_all(project.join(".cargo")).unwrap();
+        let cargo_config = project.join(".cargo").join("config.toml");
+        fs::write(
+            &cargo_config,
+            format!("[build]\ntarget = \"{}\"\n", clitools::CROSS_ARCH1),
+        )
+        .unwrap();
+
+        fs::write(
+            project.join("Cargo.toml"),
+            "[package]\nname = \"project\"\n",
+        )
+        .unwrap();
+        config.change_dir(&project.join(".cargo"), &|config| {
+            config.expect_err(
+                &["rustup", "target", "remove", clitools::CROSS_ARCH1],
+                &format!(
+                    "target '{}' is the one cargo builds for here, as set by '{}'\nhelp: pass `--force` to remove it anyway",
+                    clitools::CROSS_ARCH1,
+                    cargo_config.display()
+                ),
+            );
+            assert!(config.rustupdir.has(&libstd));
+
+            let out = config.run(
+                "rustup",
+                ["target", "remove", "bogus"],
+                &[("CARGO_BUILD_TARGET", "bogus")],
+            );
+            assert!(!out.ok);
+            assert!(
+                out.stderr
+                    .contains("target 'bogus' is the one cargo builds for here, as set by `CARGO_BUILD_TARGET`"),
+                "{}",
+                out.stderr
+            );
+
+            config.expect_stderr_ok(
+                &["rustup", "target", "remove", "--force", clitools::CROSS_ARCH1],
+                &format!(
+                    "warning: cargo is configured to build for '{}' here by '{}'",
+                    clitools::CROSS_ARCH1,
+                    cargo_config.display()
+                ),
+            );
+            assert!(!config.rustupdir.has(&libstd));
+        });
+    });
+}
+
 #[test]
 fn remove_target_not_installed() {
     setup(&|config| {
@@ -1056,6 +2163,67 @@ fn update_unavailable_std() {
     });
 }
 
+#[test]
+fn update_keep_going_tries_every_toolchain() {
+    setup(&|config| {
+        make_component_unavailable(config, "rust-std", &this_host_triple());
+        config.expect_err(
+            &["rustup", "update", "nightly", "stable", "--no-self-update"],
+            "is unavailable for download for channel 'nightly'",
+        );
+        config.expect_ok_ex(
+            &["rustup", "toolchain", "list"],
+            "no installed toolchains\n",
+            "",
+        );
+
+        let out = config.run(
+            "rustup",
+            [
+                "update",
+                "nightly",
+                "stable",
+                "--no-self-update",
+                "--keep-going",
+            ],
+            &[],
+        );
+        assert!(!out.ok);
+        assert!(out
+            .stderr
+            .contains("is unavailable for download for channel 'nightly'"));
+        assert!(out.stdout.contains(for_host!("nightly-{0} update failed")));
+        assert!(out.stdout.contains(for_host!("stable-{0} installed")));
+        config.expect_stdout_ok(&["rustup", "toolchain", "list"], "stable");
+    });
+}
+
+#[test]
+fn update_all_keep_going_fails_if_a_toolchain_does() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "toolchain", "install", "stable", "nightly"]);
+        make_component_unavailable(config, "rust-std", &this_host_triple());
+
+        // Either way every toolchain is tried, nightly failing before stable
+        let out = config.run("rustup", ["update", "--no-self-update"], &[]);
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.contains(for_host!("nightly-{0} update failed")));
+        assert!(out.stdout.contains(for_host!("stable-{0} unchanged")));
+
+        let out = config.run(
+            "rustup",
+            ["update", "--no-self-update", "--keep-going"],
+            &[],
+        );
+        assert!(!out.ok);
+        assert!(out
+            .stderr
+            .contains("is unavailable for download for channel 'nightly'"));
+        assert!(out.stdout.contains(for_host!("nightly-{0} update failed")));
+        assert!(out.stdout.contains(for_host!("stable-{0} unchanged")));
+    });
+}
+
 #[test]
 fn add_missing_component() {
     setup(&|config| {
@@ -1124,27 +2292,158 @@ fn update_unavailable_force() {
                 "component 'rls' for target '{0}' is unavailable for download for channel 'nightly'"
             ),
         );
-        config.expect_ok(&["rustup", "update", "nightly", "--force"]);
+        config.expect_ok(&["rustup", "update", "nightly", "--force"]);
+    });
+}
+
+#[test]
+fn add_component_suggest_best_match() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_err(
+            &["rustup", "component", "add", "rsl"],
+            "did you mean 'rls'?",
+        );
+        config.expect_err(
+            &["rustup", "component", "add", "rsl-preview"],
+            "did you mean 'rls-preview'?",
+        );
+        config.expect_err(
+            &["rustup", "component", "add", "rustd"],
+            "did you mean 'rustc'?",
+        );
+        config.expect_not_stderr_err(&["rustup", "component", "add", "potato"], "did you mean");
+    });
+}
+
+#[test]
+fn add_component_reinstall_repairs_it() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let toolchain_path = config
+            .rustupdir
+            .join("toolchains")
+            .join(for_host!("nightly-{0}"));
+        fs::remove_file(toolchain_path.join(format!("bin/cargo{}", std::env::consts::EXE_SUFFIX)))
+            .unwrap();
+        config.expect_stderr_ok(&["rustup", "component", "add", "cargo"], "is up to date");
+        config.expect_component_not_executable("cargo");
+        config.expect_ok(&["rustup", "component", "add", "cargo", "--reinstall"]);
+        config.expect_component_executable("cargo");
+    });
+}
+
+#[test]
+fn add_component_reinstall_without_component_manifest() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let toolchain_path = config
+            .rustupdir
+            .join("toolchains")
+            .join(for_host!("nightly-{0}"));
+        let cargo = toolchain_path.join(format!("bin/cargo{}", std::env::consts::EXE_SUFFIX));
+
+        // The files are still there, but rustup no longer knows which they are
+        fs::remove_file(toolchain_path.join(for_host!("lib/rustlib/manifest-cargo-{0}"))).unwrap();
+        fs::write(&cargo, "corrupted").unwrap();
+        config.expect_ok(&["rustup", "component", "add", "cargo", "--reinstall"]);
+        config.expect_component_executable("cargo");
+
+        // The component is tracked properly again
+        config.expect_ok(&["rustup", "component", "remove", "cargo"]);
+        assert!(!cargo.exists());
+    });
+}
+
+#[test]
+fn add_component_adds_dependencies() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_ok(&["rustup", "component", "remove", "cargo"]);
+        config.expect_ok(&["rustup", "component", "remove", "rustc"]);
+        config.expect_component_not_executable("rustc");
+
+        config.expect_stderr_ok(
+            &["rustup", "component", "add", "cargo"],
+            "adding component 'rustc', which 'cargo' depends on",
+        );
+        config.expect_component_executable("cargo");
+        config.expect_component_executable("rustc");
+    });
+}
+
+#[test]
+fn remove_component_purges_unused_dependencies() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_ok(&["rustup", "component", "remove", "cargo"]);
+        config.expect_ok(&["rustup", "component", "remove", "rustc"]);
+        config.expect_ok(&["rustup", "component", "add", "cargo"]);
+
+        config.expect_stderr_ok(
+            &[
+                "rustup",
+                "component",
+                "remove",
+                "cargo",
+                "--purge-unused-deps",
+            ],
+            "removing component 'rustc', which nothing installed needs any more",
+        );
+        config.expect_component_not_executable("cargo");
+        config.expect_component_not_executable("rustc");
+        // Only what was installed as a dependency goes
+        config.expect_stdout_ok(
+            &["rustup", "component", "list", "--installed"],
+            for_host!("rust-std-{0}"),
+        );
     });
 }
 
 #[test]
-fn add_component_suggest_best_match() {
+fn remove_component_keeps_explicit_dependencies() {
     setup(&|config| {
+        // Those of the profile were installed explicitly
         config.expect_ok(&["rustup", "default", "nightly"]);
-        config.expect_err(
-            &["rustup", "component", "add", "rsl"],
-            "did you mean 'rls'?",
+        config.expect_not_stderr_ok(
+            &[
+                "rustup",
+                "component",
+                "remove",
+                "cargo",
+                "--purge-unused-deps",
+            ],
+            "which nothing installed needs",
         );
-        config.expect_err(
-            &["rustup", "component", "add", "rsl-preview"],
-            "did you mean 'rls-preview'?",
+        config.expect_component_executable("rustc");
+
+        // Asking for a dependency by name makes it explicit
+        config.expect_ok(&["rustup", "component", "remove", "rustc"]);
+        config.expect_ok(&["rustup", "component", "add", "cargo"]);
+        config.expect_ok(&["rustup", "component", "add", "rustc"]);
+        config.expect_not_stderr_ok(
+            &[
+                "rustup",
+                "component",
+                "remove",
+                "cargo",
+                "--purge-unused-deps",
+            ],
+            "which nothing installed needs",
         );
-        config.expect_err(
-            &["rustup", "component", "add", "rustd"],
-            "did you mean 'rustc'?",
+        config.expect_component_executable("rustc");
+    });
+}
+
+#[test]
+fn remove_component_needed_by_another_warns() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stderr_ok(
+            &["rustup", "component", "remove", "rustc"],
+            "warning: removing component 'rustc', which 'cargo' will not work without",
         );
-        config.expect_not_stderr_err(&["rustup", "component", "add", "potato"], "did you mean");
+        config.expect_component_not_executable("rustc");
     });
 }
 
@@ -1248,6 +2547,100 @@ fn install_with_components() {
     go(&["-c", "rust-src,rust-analysis"]);
 }
 
+#[test]
+fn install_with_component_set() {
+    setup(&|config| {
+        config.expect_err(
+            &[
+                "rustup",
+                "toolchain",
+                "install",
+                "nightly",
+                "--component-set",
+                "dev",
+            ],
+            "no component sets are defined in the `[component-sets]` table of the settings",
+        );
+
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        let settings = config.rustupdir.join("settings.toml");
+        let mut contents = fs::read_to_string(&settings).unwrap();
+        contents.push_str("\n[component-sets]\ndev = [\"rust-src\", \"rust-analysis\"]\n");
+        fs::write(&settings, contents).unwrap();
+
+        config.expect_err(
+            &[
+                "rustup",
+                "toolchain",
+                "install",
+                "nightly",
+                "--component-set",
+                "ci",
+            ],
+            "no component set named 'ci'\nhelp: the defined component sets are 'dev'",
+        );
+
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--component-set",
+            "dev",
+        ]);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let installed = &["rustup", "component", "list", "--installed"];
+        config.expect_stdout_ok(installed, "rust-src");
+        config.expect_stdout_ok(installed, for_host!("rust-analysis-{0}"));
+        // The set is installed on top of the profile
+        config.expect_component_executable("rustc");
+        config.expect_not_stdout_ok(installed, "rust-docs");
+    })
+}
+
+#[test]
+fn tool_override_replaces_cargo_of_toolchain() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "toolchain", "install", "stable", "nightly"]);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        let stable_cargo = config
+            .rustupdir
+            .join("toolchains")
+            .join(for_host!("stable-{0}"))
+            .join("bin")
+            .join(format!("cargo{}", std::env::consts::EXE_SUFFIX));
+        let settings = config.rustupdir.join("settings.toml");
+        let contents = fs::read_to_string(&settings).unwrap();
+        fs::write(
+            &settings,
+            format!(
+                "{contents}\n[tool-overrides.nightly]\ncargo = '{}'\n",
+                stable_cargo.display()
+            ),
+        )
+        .unwrap();
+
+        config.expect_stdout_ok(&["cargo", "--version"], "hash-stable-1.1.0");
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        config.expect_stdout_ok(&["cargo", "+stable", "--version"], "hash-stable-1.1.0");
+        // What rustup reports is still the toolchain's own
+        config.expect_stdout_ok(&["rustup", "which", "cargo"], for_host!("nightly-{0}"));
+
+        fs::write(
+            &settings,
+            format!("{contents}\n[tool-overrides.nightly]\ncargo = '/no/such/cargo'\n"),
+        )
+        .unwrap();
+        config.expect_err(
+            &["cargo", "--version"],
+            &format!(
+                "'/no/such/cargo', which the `[tool-overrides]` of the settings run as `cargo` for toolchain '{}', does not exist",
+                for_host!("nightly-{0}")
+            ),
+        );
+    })
+}
+
 #[test]
 fn install_with_targets() {
     fn go(comp_args: &[&str]) {
@@ -1274,6 +2667,103 @@ fn install_with_targets() {
     ]);
 }
 
+#[test]
+fn install_with_only_components_ignores_profile() {
+    setup(&|config| {
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--only-components",
+            "-c",
+            "rustc",
+        ]);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_executable("rustc");
+        config.expect_component_not_executable("cargo");
+        // rustc cannot link anything without the standard library
+        config.expect_stdout_ok(
+            &["rustup", "component", "list", "--installed"],
+            for_host!("rust-std-{0}"),
+        );
+        config.expect_not_stdout_ok(&["rustup", "component", "list", "--installed"], "rust-docs");
+    })
+}
+
+#[test]
+fn install_with_components_adds_to_profile() {
+    setup(&|config| {
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "-c",
+            "rust-src",
+        ]);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_executable("rustc");
+        config.expect_component_executable("cargo");
+        config.expect_stdout_ok(&["rustup", "component", "list"], "rust-src (installed)");
+    })
+}
+
+#[test]
+fn install_with_components_and_explicit_profile() {
+    setup(&|config| {
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--profile",
+            "minimal",
+            "-c",
+            "rust-src",
+        ]);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_executable("rustc");
+        config.expect_stdout_ok(&["rustup", "component", "list"], "rust-src (installed)");
+    })
+}
+
+#[test]
+fn install_with_only_components_honors_targets() {
+    setup(&|config| {
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--only-components",
+            "-c",
+            "rustc",
+            "-t",
+            clitools::CROSS_ARCH1,
+        ]);
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_component_not_executable("cargo");
+        config.expect_stdout_ok(
+            &["rustup", "target", "list", "--installed"],
+            clitools::CROSS_ARCH1,
+        );
+    })
+}
+
+#[test]
+fn install_with_unknown_component() {
+    setup(&|config| {
+        config.expect_err(
+            &["rustup", "toolchain", "install", "nightly", "-c", "rust-sr"],
+            for_host!(
+                "toolchain 'nightly-{0}' does not contain component 'rust-sr'; did you mean one of 'rust-src', 'rust-std' or 'rustc'?"
+            ),
+        );
+        config.expect_stdout_ok(&["rustup", "toolchain", "list"], "no installed toolchains");
+    })
+}
+
 #[test]
 fn install_with_component_and_target() {
     setup(&|config| {
@@ -1436,3 +2926,179 @@ fn regression_2601() {
         );
     });
 }
+
+#[test]
+fn install_dry_run_prints_the_plan() {
+    setup(&|config| {
+        let out = config.run(
+            "rustup",
+            [
+                "toolchain",
+                "install",
+                "nightly",
+                "--profile",
+                "minimal",
+                "-c",
+                "rust-src",
+                "-t",
+                clitools::CROSS_ARCH1,
+                "--dry-run",
+            ],
+            &[],
+        );
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.contains(for_host!(
+            "toolchain 'nightly-{0}' (2015-01-02) would download:"
+        )));
+        assert!(out.stdout.contains("  rust-src  "));
+        assert!(out
+            .stdout
+            .contains(&format!("  rust-std-{}  ", clitools::CROSS_ARCH1)));
+        assert!(!out.stdout.contains("rust-docs"));
+        assert!(out.stdout.contains("total: "));
+        config.expect_stdout_ok(&["rustup", "toolchain", "list"], "no installed toolchains");
+    });
+}
+
+#[test]
+fn install_download_only_then_offline() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "set", "profile", "minimal"]);
+        config.expect_stderr_ok(
+            &[
+                "rustup",
+                "toolchain",
+                "install",
+                "nightly",
+                "--download-only",
+            ],
+            for_host!("info: downloaded 2 components of 'nightly-{0}'"),
+        );
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "stable",
+            "--download-only",
+        ]);
+        config.expect_stdout_ok(&["rustup", "toolchain", "list"], "no installed toolchains");
+        assert!(!config.rustupdir.has(for_host!("toolchains/nightly-{0}")));
+        let downloads = config.rustupdir.join("downloads");
+        let saved_manifests = || {
+            fs::read_dir(&downloads)
+                .unwrap()
+                .filter(|e| e.as_ref().unwrap().path().extension() == Some("manifest".as_ref()))
+                .count()
+        };
+        assert_eq!(saved_manifests(), 2);
+
+        // Everything now comes from the download cache, whether offline or
+        // because the dist server can't be reached
+        fs::remove_dir_all(config.test_dist_dir.path().join("dist")).unwrap();
+        config.expect_stderr_ok(
+            &["rustup", "--offline", "toolchain", "install", "nightly"],
+            "that was downloaded earlier",
+        );
+        config.expect_stderr_ok(
+            &["rustup", "toolchain", "install", "stable"],
+            "that was downloaded earlier",
+        );
+        config.expect_ok(&["rustup", "default", "nightly"]);
+        config.expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2");
+        assert_eq!(saved_manifests(), 0);
+    });
+}
+
+#[test]
+fn check_json_when_nothing_can_be_fetched() {
+    setup_complex(&|config| {
+        set_current_dist_date(config, "2015-01-01");
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        set_current_dist_date(config, "2015-01-02");
+        config.expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--download-only",
+        ]);
+        fs::remove_dir_all(config.test_dist_dir.path().join("dist")).unwrap();
+
+        let no_server = config.current_dir().join("no-update-server");
+        let no_server = format!("file://{}", no_server.display());
+        let out = config.run(
+            "rustup",
+            ["check", "--json"],
+            &[("RUSTUP_UPDATE_ROOT", &no_server)],
+        );
+        assert!(out.ok, "{}", out.stderr);
+        let json: serde_json::Value = serde_json::from_str(&out.stdout).unwrap();
+        assert_eq!(
+            json["toolchains"],
+            serde_json::json!([{
+                "name": for_host!("nightly-{0}"),
+                "current_version": "1.2.0 (hash-nightly-1)",
+                "latest_version": "1.3.0 (hash-nightly-2)",
+                "update_available": true,
+                "stale": true,
+            }])
+        );
+        assert_eq!(json["rustup"], serde_json::Value::Null);
+        assert!(json["error"].is_string());
+    });
+}
+
+#[test]
+fn install_download_only_of_installed_toolchain() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        config.expect_stderr_ok(
+            &[
+                "rustup",
+                "toolchain",
+                "install",
+                "nightly",
+                "--download-only",
+            ],
+            for_host!("info: toolchain 'nightly-{0}' has nothing to download"),
+        );
+        config.expect_err(
+            &[
+                "rustup",
+                "toolchain",
+                "install",
+                "nightly",
+                "--download-only",
+                "--dry-run",
+            ],
+            "cannot be used with",
+        );
+    });
+}
+
+#[test]
+fn install_dry_run_of_installed_toolchain() {
+    setup(&|config| {
+        config.expect_ok(&["rustup", "toolchain", "install", "nightly"]);
+        config.expect_stdout_ok(
+            &["rustup", "toolchain", "install", "nightly", "--dry-run"],
+            for_host!("toolchain 'nightly-{0}' (2015-01-02) is up to date"),
+        );
+        let out = config.run(
+            "rustup",
+            [
+                "toolchain",
+                "install",
+                "nightly",
+                "-c",
+                "rust-src",
+                "--dry-run",
+            ],
+            &[],
+        );
+        assert!(out.ok, "{}", out.stderr);
+        assert!(out.stdout.contains("  rust-src  "));
+        assert!(!out.stdout.contains("cargo"));
+        config.expect_not_stdout_ok(&["rustup", "component", "list"], "rust-src (installed)");
+    });
+}